@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use tracing::{debug, info};
+
+use crate::config;
+
+/// image 命令中引用资源清单条目的前缀：`"src_type": "path", "src": "asset:logo"`
+pub const ASSET_PREFIX: &str = "asset:";
+
+/// 可作为资源的图片扩展名
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "webp"];
+/// 扫描子目录的最大深度
+const MAX_DEPTH: usize = 4;
+/// 写进生成上下文的条目上限，避免大目录撑爆提示词
+const MAX_LISTED: usize = 100;
+
+/// 资源目录中的一张图片
+#[derive(Debug, Clone)]
+pub struct Asset {
+    /// 相对资源目录、去掉扩展名的路径，如 `logo`、`icons/home`
+    pub name: String,
+    /// 相对资源目录的文件路径
+    pub file: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 资源目录的清单，首次使用时扫描一次
+pub fn manifest() -> &'static [Asset] {
+    static MANIFEST: OnceLock<Vec<Asset>> = OnceLock::new();
+    MANIFEST.get_or_init(|| {
+        let root = &config::get().images.assets_dir;
+        let mut assets = Vec::new();
+        scan(Path::new(root), "", 0, &mut assets);
+        assets.sort_by(|a, b| a.name.cmp(&b.name));
+        info!("found {} image asset(s) in {}", assets.len(), root);
+        assets
+    })
+}
+
+pub fn find(name: &str) -> Option<&'static Asset> {
+    manifest().iter().find(|asset| asset.name == name)
+}
+
+/// 给生成提示词的资源清单：名称与像素尺寸
+pub fn describe() -> String {
+    let assets = manifest();
+    if assets.is_empty() {
+        return "none".to_string();
+    }
+    let mut listed: Vec<String> =
+        assets.iter().take(MAX_LISTED).map(|a| format!("{} ({}x{})", a.name, a.width, a.height)).collect();
+    if assets.len() > MAX_LISTED {
+        listed.push(format!("... and {} more", assets.len() - MAX_LISTED));
+    }
+    listed.join(", ")
+}
+
+fn scan(dir: &Path, prefix: &str, depth: usize, assets: &mut Vec<Asset>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with('.') {
+            continue;
+        }
+        let relative = format!("{}{}", prefix, file_name);
+        let path = entry.path();
+        // 不跟随符号链接进入子目录；指向资源目录外的文件在加载时仍会被拒绝
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if depth < MAX_DEPTH {
+                scan(&path, &format!("{}/", relative), depth + 1, assets);
+            }
+            continue;
+        }
+        let Some(extension) = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()) else {
+            continue;
+        };
+        if !IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            continue;
+        }
+        match image::image_dimensions(&path) {
+            Ok((width, height)) => {
+                let name = relative[..relative.len() - extension.len() - 1].to_string();
+                assets.push(Asset { name, file: relative, width, height });
+            }
+            Err(e) => debug!("skipping asset {}: {}", relative, e),
+        }
+    }
+}
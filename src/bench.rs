@@ -0,0 +1,173 @@
+//! `bench` 子命令：反复渲染几组合成的 render 信封，报告吞吐与耗时分位数，
+//! 用来衡量 `x11/renderer.rs` 的性能回归。
+
+use std::error::Error;
+use std::io::Cursor;
+use std::time::Instant;
+use base64::{Engine as _, engine::general_purpose};
+use image::{ImageBuffer, ImageFormat, Rgba};
+
+use crate::dsl::builder::Render;
+use crate::dsl::model::{Command, RenderEnvelope};
+use crate::x11::{backend, raster_cache, renderer};
+
+const BENCH_USAGE: &str = "usage: x11-gui-bridge bench [--iterations <n>] [--suite <text|polygon|image|mixed|drafts>]";
+const DEFAULT_ITERATIONS: usize = 50;
+const WIDTH: u32 = 1024;
+const HEIGHT: u32 = 768;
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut iterations = DEFAULT_ITERATIONS;
+    let mut only: Option<String> = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--iterations" | "-n" => iterations = rest.next().ok_or(BENCH_USAGE)?.parse().map_err(|_| BENCH_USAGE)?,
+            "--suite" => only = Some(rest.next().ok_or(BENCH_USAGE)?.clone()),
+            _ => return Err(BENCH_USAGE.into()),
+        }
+    }
+    if iterations == 0 {
+        return Err(BENCH_USAGE.into());
+    }
+
+    let (primary, emoji) = backend::load_fonts();
+    if primary.is_none() {
+        println!("note: no font found, text commands are skipped (set X11_GUI_FONT)");
+    }
+    println!("{} iterations per suite, {}x{} frames", iterations, WIDTH, HEIGHT);
+    println!("{:<10} {:>8} {:>9} {:>9} {:>9} {:>9} {:>10}", "suite", "commands", "p50 ms", "p90 ms", "p99 ms", "max ms", "frames/s");
+
+    for (name, render) in suites()? {
+        if only.as_deref().is_some_and(|o| o != name) {
+            continue;
+        }
+        // 预热一次，排除首次加载、分配的开销
+        renderer::render_to_buffer(&render, primary.as_ref(), emoji.as_ref())?;
+        let mut samples = Vec::with_capacity(iterations);
+        let started = Instant::now();
+        for _ in 0..iterations {
+            let frame = Instant::now();
+            renderer::render_to_buffer(&render, primary.as_ref(), emoji.as_ref())?;
+            samples.push(frame.elapsed().as_secs_f64() * 1000.0);
+        }
+        report(name, render.commands.len(), samples, started.elapsed().as_secs_f64());
+    }
+
+    // 模拟评估循环：每份草稿在上一份的基础上改一条文本，经离屏渲染缓存只重画变化的区域
+    if only.as_deref().is_none_or(|o| o == "drafts") {
+        let mut draft = text_heavy();
+        raster_cache::render(&draft, primary.as_ref(), emoji.as_ref())?;
+        let mut samples = Vec::with_capacity(iterations);
+        let started = Instant::now();
+        for i in 0..iterations {
+            let index = 1 + i % (draft.commands.len() - 1);
+            if let Command::Text { text, .. } = &mut draft.commands[index] {
+                text.push('*');
+            }
+            let frame = Instant::now();
+            raster_cache::render(&draft, primary.as_ref(), emoji.as_ref())?;
+            samples.push(frame.elapsed().as_secs_f64() * 1000.0);
+        }
+        report("drafts", draft.commands.len(), samples, started.elapsed().as_secs_f64());
+    }
+    Ok(())
+}
+
+fn report(name: &str, commands: usize, mut samples: Vec<f64>, total: f64) {
+    samples.sort_by(|a, b| a.total_cmp(b));
+    println!(
+        "{:<10} {:>8} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>10.1}",
+        name,
+        commands,
+        percentile(&samples, 0.50),
+        percentile(&samples, 0.90),
+        percentile(&samples, 0.99),
+        samples.last().copied().unwrap_or_default(),
+        samples.len() as f64 / total,
+    );
+}
+
+/// 最近秩法，`sorted` 已升序
+fn percentile(sorted: &[f64], q: f64) -> f64 {
+    let rank = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+/// 固定种子的线性同余生成器，保证每次运行的合成场景一致
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: u32) -> u32 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % bound as u64) as u32
+    }
+
+    fn color(&mut self) -> String {
+        format!("#{:06X}", self.next(0x1000000))
+    }
+}
+
+fn envelope(title: &str) -> Render {
+    Render::window(WIDTH, HEIGHT).title(title).seq(1).clear("#F4F6FA")
+}
+
+fn suites() -> Result<Vec<(&'static str, RenderEnvelope)>, Box<dyn Error>> {
+    let text = text_heavy();
+    let polygon = polygon_heavy();
+    let image = image_heavy()?;
+    let mut mixed = text.commands[1..60].to_vec();
+    mixed.extend_from_slice(&polygon.commands[1..60]);
+    mixed.extend_from_slice(&image.commands[1..5]);
+    Ok(vec![
+        ("text", text),
+        ("polygon", polygon),
+        ("image", image),
+        ("mixed", envelope("mixed").commands(mixed).build()),
+    ])
+}
+
+/// 网格排列的大量短文本，覆盖字形光栅化与混合
+fn text_heavy() -> RenderEnvelope {
+    let mut rng = Lcg(1);
+    let mut render = envelope("text");
+    for row in 0..24 {
+        for col in 0..6 {
+            render = render.text(12 + col * 168, 8 + row * 31, format!("Item {}-{} 标签", row, col), &rng.color());
+            if (row + col) % 3 == 0 {
+                render = render.bg("#FFFFFF");
+            }
+        }
+    }
+    render.build()
+}
+
+/// 随机多边形、折线、圆与圆角矩形，覆盖扫描线填充与描边
+fn polygon_heavy() -> RenderEnvelope {
+    let mut rng = Lcg(2);
+    let mut render = envelope("polygon");
+    for _ in 0..80 {
+        let points: Vec<(i32, i32)> = (0..6).map(|_| (rng.next(WIDTH) as i32, rng.next(HEIGHT) as i32)).collect();
+        render = render.polygon(&points, &rng.color()).stroke(&rng.color(), 2);
+        let points: Vec<(i32, i32)> = (0..8).map(|_| (rng.next(WIDTH) as i32, rng.next(HEIGHT) as i32)).collect();
+        render = render.polyline(&points, &rng.color(), 3);
+        let (cx, cy, r) = (rng.next(WIDTH) as i32, rng.next(HEIGHT) as i32, 10 + rng.next(60));
+        render = render.circle(cx, cy, r, &rng.color());
+        let (x, y, w, h) = (rng.next(WIDTH) as i32, rng.next(HEIGHT) as i32, 40 + rng.next(200), 30 + rng.next(120));
+        render = render.round_rect(x, y, w, h, 12, &rng.color()).stroke("#1F2937", 1);
+    }
+    render.build()
+}
+
+/// base64 内嵌图片的解码、缩放与合成
+fn image_heavy() -> Result<RenderEnvelope, Box<dyn Error>> {
+    let gradient = ImageBuffer::from_fn(256, 256, |x, y| Rgba([x as u8, y as u8, 128, 255]));
+    let mut png = Cursor::new(Vec::new());
+    gradient.write_to(&mut png, ImageFormat::Png)?;
+    let src = general_purpose::STANDARD.encode(png.into_inner());
+    let mut render = envelope("image");
+    for i in 0..12 {
+        render = render.image_base64((i % 4) * 256, (i / 4) * 256, 240, 240, src.clone());
+    }
+    Ok(render.build())
+}
@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::process::Command;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt, EventMask, GrabMode, GrabStatus, ImageFormat, ImageOrder};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+use crate::images;
+
+/// 截取到的图片，像素为 BGRA
+pub struct Captured {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// 运行配置的截取工具（如 `fswebcam --png -1 -`、`maim -s`），它把一张图片写到标准输出
+pub fn run_tool(command: &[String]) -> Result<Captured, Box<dyn Error>> {
+    let (program, args) = command.split_first().ok_or("capture_command is empty")?;
+    let output = Command::new(program).args(args).output().map_err(|e| format!("{}: {}", program, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} exited with {}: {}", program, output.status, stderr.trim()).into());
+    }
+    let (width, height, pixels) = images::decode_bgra(&output.stdout).map_err(|e| format!("{} did not output an image: {}", program, e))?;
+    Ok(Captured { width, height, pixels })
+}
+
+/// X cursor 字体中十字准星的字形号
+const CROSSHAIR_GLYPH: u16 = 34;
+
+/// 用 X `GetImage` 截取屏幕（根窗口）上的一块区域；`region` 为 None 时截整个屏幕，超出屏幕的部分裁掉
+pub fn grab_screen(region: Option<(i32, i32, u32, u32)>) -> Result<Captured, Box<dyn Error>> {
+    let (conn, screen_num) = x11rb::connect(None)?;
+    grab_root(&conn, screen_num, region)
+}
+
+/// 截取另一个程序的窗口：按它当前在屏幕上的位置截取根窗口的这块区域，
+/// 因此得到的是用户实际看到的样子（被遮住的部分是上层窗口）
+pub fn grab_window(window: u32) -> Result<Captured, Box<dyn Error>> {
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let geometry = conn.get_geometry(window)?.reply().map_err(|e| format!("window 0x{:x} is gone: {}", window, e))?;
+    let origin = conn.translate_coordinates(window, root, 0, 0)?.reply()?;
+    let region = (origin.dst_x.into(), origin.dst_y.into(), geometry.width.into(), geometry.height.into());
+    grab_root(&conn, screen_num, Some(region))
+}
+
+/// 让用户点选一个窗口：抓住指针并显示十字准星，返回点中的顶层窗口（含窗口管理器的边框）
+pub fn pick_window() -> Result<u32, Box<dyn Error>> {
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let font = conn.generate_id()?;
+    conn.open_font(font, b"cursor")?;
+    let cursor = conn.generate_id()?;
+    conn.create_glyph_cursor(cursor, font, font, CROSSHAIR_GLYPH, CROSSHAIR_GLYPH + 1, 0, 0, 0, 0xffff, 0xffff, 0xffff)?;
+    conn.close_font(font)?;
+    let grab = conn
+        .grab_pointer(false, root, EventMask::BUTTON_PRESS, GrabMode::ASYNC, GrabMode::ASYNC, x11rb::NONE, cursor, x11rb::CURRENT_TIME)?
+        .reply()?;
+    if grab.status != GrabStatus::SUCCESS {
+        conn.free_cursor(cursor)?;
+        return Err("could not grab the pointer; another program is holding it".into());
+    }
+    let picked = loop {
+        if let Event::ButtonPress(ev) = conn.wait_for_event()? {
+            break ev.child;
+        }
+    };
+    conn.ungrab_pointer(x11rb::CURRENT_TIME)?;
+    conn.free_cursor(cursor)?;
+    conn.flush()?;
+    if picked == x11rb::NONE {
+        return Err("clicked on the desktop, not on a window".into());
+    }
+    Ok(picked)
+}
+
+fn grab_root(conn: &RustConnection, screen_num: usize, region: Option<(i32, i32, u32, u32)>) -> Result<Captured, Box<dyn Error>> {
+    let screen = &conn.setup().roots[screen_num];
+    let (root_w, root_h) = (screen.width_in_pixels as i32, screen.height_in_pixels as i32);
+    let (x, y, w, h) = region.unwrap_or((0, 0, root_w as u32, root_h as u32));
+    let (left, top) = (x.clamp(0, root_w), y.clamp(0, root_h));
+    let (right, bottom) = ((x + w as i32).clamp(0, root_w), (y + h as i32).clamp(0, root_h));
+    if right <= left || bottom <= top {
+        return Err(format!("region {}x{}+{}+{} is outside the {}x{} screen", w, h, x, y, root_w, root_h).into());
+    }
+    let bits_per_pixel = conn
+        .setup()
+        .pixmap_formats
+        .iter()
+        .find(|f| f.depth == screen.root_depth)
+        .map(|f| f.bits_per_pixel)
+        .unwrap_or(0);
+    if bits_per_pixel != 32 {
+        return Err(format!("screen capture needs a 32 bpp visual, the screen uses {} bpp", bits_per_pixel).into());
+    }
+    let (width, height) = ((right - left) as usize, (bottom - top) as usize);
+    let reply = conn
+        .get_image(ImageFormat::Z_PIXMAP, screen.root, left as i16, top as i16, width as u16, height as u16, !0)?
+        .reply()?;
+    if reply.data.len() < width * height * 4 {
+        return Err("GetImage returned a short buffer".into());
+    }
+    let mut pixels = reply.data;
+    // 大端服务器返回的像素是 [A, R, G, B]，翻转成 BGRA
+    if conn.setup().image_byte_order == ImageOrder::MSB_FIRST {
+        pixels.chunks_exact_mut(4).for_each(|px| px.reverse());
+    }
+    Ok(Captured { width, height, pixels })
+}
+
+/// 解析 `/capture` 的区域参数：`x y w h` 或 `x,y,w,h`；为空时返回 None（整个屏幕）
+pub fn parse_region(arg: &str) -> Result<Option<(i32, i32, u32, u32)>, String> {
+    if arg.trim().is_empty() {
+        return Ok(None);
+    }
+    let numbers: Vec<i64> = arg
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<i64>().map_err(|_| format!("not a number: {}", s)))
+        .collect::<Result<_, _>>()?;
+    match numbers[..] {
+        [x, y, w, h] if w > 0 && h > 0 => Ok(Some((x as i32, y as i32, w as u32, h as u32))),
+        _ => Err("expected a region as: x y width height".to_string()),
+    }
+}
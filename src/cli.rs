@@ -0,0 +1,405 @@
+//! 不经过 LLM 的命令行子命令，用于手写 DSL、维护示例信封库、调试提示词和排查模型输出。
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use schemars::schema_for;
+use serde_json::Value;
+
+use crate::config;
+use crate::display::{error_screen, Surface, UiEvent};
+use crate::dsl::model::{Command, RenderEnvelope, WindowSpec};
+use crate::dsl::validator;
+use crate::dsl::{parser, version};
+use crate::images;
+use crate::recorder;
+use crate::x11::backend::X11Backend;
+use crate::x11::renderer;
+
+/// 预览窗口中退出的按键：Escape、q
+const QUIT_KEYS: &[u32] = &[0xFF1B, 0x71];
+
+const PREVIEW_USAGE: &str = "usage: x11-gui-bridge preview <file.json|file.yaml> [--out <file.png> | --watch]";
+
+/// `--watch` 检查文件修改时间的间隔
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// `preview file.json [--out file.png | --watch]`：解析、校验并显示 render 信封；
+/// 指定 `--out` 时只导出 PNG，不打开窗口；`--watch` 时文件一变就重新渲染，
+/// 解析或校验失败则显示错误画面，修好后自动恢复
+pub fn preview(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut file = None;
+    let mut out = None;
+    let mut watch = false;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--out" | "-o" => out = Some(rest.next().ok_or(PREVIEW_USAGE)?),
+            "--watch" | "-w" => watch = true,
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(PREVIEW_USAGE.into()),
+        }
+    }
+    let file = file.ok_or(PREVIEW_USAGE)?;
+    if watch && out.is_some() {
+        return Err(PREVIEW_USAGE.into());
+    }
+    // 读取 agd.json，使主题调色板等设置与运行时一致
+    config::init()?;
+
+    if let Some(out) = out {
+        recorder::save_png(&load_render(file)?, Path::new(out))?;
+        println!("wrote {}", out);
+        return Ok(());
+    }
+
+    let mut render = if watch { load_or_error_screen(file, None) } else { load_render(file)? };
+    let mut modified = modified_time(file);
+    let window = X11Backend::connect(
+        render.window.width as u16,
+        render.window.height as u16,
+        &render.window.title,
+        renderer::window_background(&render),
+    )?;
+    window.present(&render)?;
+    println!("{} {} (Esc or q to quit)", if watch { "watching" } else { "previewing" }, file);
+    let mut last_check = Instant::now();
+    loop {
+        while let Some(event) = window.poll_event()? {
+            match event {
+                UiEvent::Expose => window.present(&render)?,
+                UiEvent::Click(click) => match clickable_at(&render, click.x, click.y) {
+                    Some(id) => println!("click: {} ({}, {})", id, click.x, click.y),
+                    None => println!("click: ({}, {})", click.x, click.y),
+                },
+                UiEvent::Key(key) if QUIT_KEYS.contains(&key.keysym) => return Ok(()),
+                UiEvent::Key(key) => {
+                    if let Some(shortcut) = render.shortcuts.iter().find(|s| {
+                        validator::shortcut_chord(&s.keys).is_some_and(|c| c.matches(key.keysym, key.ctrl, key.alt, key.shift))
+                    }) {
+                        println!("shortcut: {} -> {}", shortcut.keys, shortcut.target);
+                    } else if let Some(id) = access_key_target(&render, key.keysym).filter(|_| !key.ctrl && !key.alt) {
+                        println!("access key: {}", id);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if watch && last_check.elapsed() >= WATCH_INTERVAL {
+            last_check = Instant::now();
+            let current = modified_time(file);
+            if current != modified {
+                modified = current;
+                render = load_or_error_screen(file, Some(&render.window));
+                window.present(&render)?;
+            }
+        }
+        thread::sleep(Duration::from_millis(16));
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// 监视模式下加载失败不退出，改为显示错误画面（沿用上一帧的窗口尺寸）
+fn load_or_error_screen(path: &str, window: Option<&WindowSpec>) -> RenderEnvelope {
+    match load_render(path) {
+        Ok(render) => {
+            println!("rendered {}", path);
+            render
+        }
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            let fallback = WindowSpec { width: 800, height: 600, title: path.to_string(), background: None };
+            error_screen(&e.to_string(), window.unwrap_or(&fallback))
+        }
+    }
+}
+
+/// lint 报告的一条问题：字段路径与说明；`warning` 的问题（布局检查）不计入失败
+struct Issue {
+    path: String,
+    message: String,
+    warning: bool,
+}
+
+const LINT_USAGE: &str = "usage: x11-gui-bridge lint <file.json|file.yaml>...";
+
+/// `lint file.json...`：报告每个文件的全部问题（字段路径 + 说明 + 修改建议），
+/// 而不是像运行时那样只报第一个错误；有问题时以非零状态退出
+pub fn lint(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        return Err(LINT_USAGE.into());
+    }
+    config::init()?;
+    let mut total = 0;
+    for file in args {
+        let issues = match fs::read_to_string(file) {
+            Ok(raw) => lint_source(&raw),
+            Err(e) => vec![Issue { path: String::new(), message: e.to_string(), warning: false }],
+        };
+        for issue in &issues {
+            let mut location = if issue.path.is_empty() { file.clone() } else { format!("{}: {}", file, issue.path) };
+            if issue.warning {
+                location.push_str(": warning");
+            }
+            match suggestion(&issue.message) {
+                Some(hint) => println!("{}: {} (hint: {})", location, issue.message, hint),
+                None => println!("{}: {}", location, issue.message),
+            }
+        }
+        let failures = issues.iter().filter(|i| !i.warning).count();
+        if failures == 0 {
+            println!("{}: ok", file);
+        }
+        total += failures;
+    }
+    if total > 0 {
+        return Err(format!("{} problem(s) found", total).into());
+    }
+    Ok(())
+}
+
+/// JSON/YAML 语法 -> 逐条命令反序列化（定位到 `commands[i]`）-> 未知字段 -> 语义校验
+fn lint_source(raw: &str) -> Vec<Issue> {
+    let issue = |path: &str, message: String| Issue { path: path.to_string(), message, warning: false };
+    let value: Value = match parser::yaml_body(raw) {
+        Some(yaml) => match serde_yaml::from_str(yaml) {
+            Ok(value) => value,
+            Err(e) => return vec![issue("", format!("invalid YAML: {}", e))],
+        },
+        None => match serde_json::from_str(raw) {
+            Ok(value) => value,
+            Err(e) => {
+                let mut message = format!("invalid JSON: {}", e);
+                if parser::parse_render(raw).is_ok() {
+                    message.push_str(" (the runtime repair would accept it)");
+                }
+                return vec![issue("", message)];
+            }
+        },
+    };
+    // 旧版本按运行时的方式升级后再检查
+    let value = match version::upgrade(value) {
+        Ok(value) => value,
+        Err(e) => return vec![issue("version", e.to_string())],
+    };
+
+    let mut issues = Vec::new();
+    let mut structural_ok = true;
+    let commands = value.get("commands").and_then(Value::as_array).cloned().unwrap_or_default();
+    let mut envelope = value.clone();
+    envelope["commands"] = Value::Array(Vec::new());
+    let schema = serde_json::to_value(schema_for!(RenderEnvelope)).unwrap_or_default();
+    let definitions = &schema["definitions"];
+    match serde_json::from_value::<RenderEnvelope>(envelope.clone()) {
+        Ok(parsed) => unknown_keys(&envelope, &serde_json::to_value(parsed).unwrap_or_default(), &schema, definitions, "", &mut issues),
+        Err(e) => {
+            structural_ok = false;
+            issues.push(issue("", e.to_string()));
+        }
+    }
+    let mut typed = Vec::new();
+    for (i, command) in commands.iter().enumerate() {
+        let path = format!("commands[{}]", i);
+        match serde_json::from_value::<Command>(command.clone()) {
+            Ok(parsed) => {
+                let known = serde_json::to_value(&parsed).unwrap_or_default();
+                unknown_keys(command, &known, &definitions["Command"], definitions, &path, &mut issues);
+                typed.push(parsed);
+            }
+            Err(e) => {
+                structural_ok = false;
+                issues.push(issue(&path, e.to_string()));
+            }
+        }
+    }
+    // 有命令无法反序列化时，语义校验（缺 clear、重复 id 等）的结果没有意义
+    if structural_ok {
+        if let Ok(mut render) = serde_json::from_value::<RenderEnvelope>(envelope) {
+            render.commands = typed;
+            parser::prepare(&mut render);
+            let strict = config::get().strict_bounds;
+            let errors = validator::check_render(&render).into_iter().map(|e| (e, false));
+            let findings = validator::check_bounds(&render).into_iter().map(|e| (e, !strict));
+            // 对比度、文字溢出、可点击区域重叠按估算判断，总是警告
+            let contrast = validator::check_contrast(&render)
+                .into_iter()
+                .chain(validator::check_overflow(&render))
+                .chain(validator::check_clickables(&render))
+                .map(|e| (e, true));
+            issues.extend(errors.chain(findings).chain(contrast).map(|(e, warning)| Issue {
+                path: e.path(),
+                message: format!("{} [{}]", e.message(), e.code()),
+                warning,
+            }));
+        }
+    }
+    issues
+}
+
+/// serde 会静默忽略未知字段；按 `model.rs` 推导的 Schema 列出各对象声明的字段，找出拼错或多余的字段。
+/// 序列化时省略了 null 与默认值，所以字段表不能只取反序列化再序列化的结果；
+/// 那份结果（`known`）仍然算进来，本地填写、不在 Schema 里的字段（终端内容、提示条位置等）有值时照常接受
+fn unknown_keys(raw: &Value, known: &Value, schema: &Value, definitions: &Value, path: &str, issues: &mut Vec<Issue>) {
+    let schema = resolve_schema(schema, definitions, raw);
+    match raw {
+        Value::Object(raw) => {
+            let declared = schema.get("properties").and_then(Value::as_object);
+            let fields: Vec<&String> = declared.into_iter().chain(known.as_object()).flat_map(|m| m.keys()).collect();
+            for (key, value) in raw {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                if fields.contains(&key) {
+                    let field_schema = declared.and_then(|d| d.get(key)).unwrap_or(&Value::Null);
+                    unknown_keys(value, &known[key.as_str()], field_schema, definitions, &child, issues);
+                    continue;
+                }
+                let closest = fields.iter().filter(|k| edit_distance(k, key) <= 2).min_by_key(|k| edit_distance(k, key));
+                let message = match closest {
+                    Some(k) => format!("unknown field `{}`, did you mean `{}`?", key, k),
+                    None => format!("unknown field `{}` is ignored", key),
+                };
+                issues.push(Issue { path: child, message, warning: false });
+            }
+        }
+        Value::Array(raw) => {
+            let items = schema.get("items").unwrap_or(&Value::Null);
+            for (i, r) in raw.iter().enumerate() {
+                unknown_keys(r, &known[i], items, definitions, &format!("{}[{}]", path, i), issues);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 展开 `$ref`；可空字段（`anyOf: [T, null]`）取非 null 的一支，`Command` 这样的 tag 枚举（`oneOf`）按 `cmd` 取对应的变体
+fn resolve_schema<'a>(schema: &'a Value, definitions: &'a Value, raw: &Value) -> &'a Value {
+    if let Some(name) = schema.get("$ref").and_then(Value::as_str).and_then(|r| r.strip_prefix("#/definitions/")) {
+        return resolve_schema(&definitions[name], definitions, raw);
+    }
+    for key in ["anyOf", "allOf"] {
+        if let Some(branch) = schema.get(key).and_then(Value::as_array).and_then(|b| b.iter().find(|s| s["type"] != "null")) {
+            return resolve_schema(branch, definitions, raw);
+        }
+    }
+    if let Some(variants) = schema.get("oneOf").and_then(Value::as_array) {
+        let tag = &raw["cmd"];
+        if let Some(variant) = variants.iter().find(|v| v["properties"]["cmd"]["enum"].as_array().is_some_and(|e| e.contains(tag))) {
+            return resolve_schema(variant, definitions, raw);
+        }
+    }
+    schema
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = (prev + usize::from(ca != *cb)).min(row[j] + 1).min(row[j + 1] + 1);
+            prev = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// 常见问题的修改建议
+fn suggestion(message: &str) -> Option<&'static str> {
+    const HINTS: &[(&str, &str)] = &[
+        ("must be #RRGGBB", "use a 6-digit hex color such as \"#1F2937\" or a token such as \"@text\""),
+        ("is required", "add the field; it has no default"),
+        ("must be positive", "use a value of at least 1"),
+        ("must have fill or stroke", "set \"fill\" and/or \"stroke\""),
+        ("clickable rect requires id", "give the rect a unique \"id\""),
+        ("duplicate id", "ids of rects must be unique"),
+        ("duplicate timer id", "ids of timers must be unique"),
+        ("is declared twice", "each key chord can trigger only one target; remove or change one of them"),
+        ("does not name a clickable rect", "point \"target\" at the id of a clickable rect"),
+        ("is already used", "pick a different access key; each key can activate only one button"),
+        ("role button requires", "use \"button\" only on clickable rects; put it on the rect, not the label text"),
+        ("single letter or digit", "use one letter or digit from the button label, e.g. \"s\" for Save"),
+        ("timer.every_ms must be at least", "use a longer interval; each tick triggers a generation"),
+        ("data_source.every_ms must be at least", "use a longer interval; each tick reads the source again"),
+        ("duplicate data source id", "ids of data sources must be unique"),
+        ("does not name a data source", "declare the source in \"data_sources\" or fix the id before the first /"),
+        ("only one dialog per screen", "ask one question at a time; show the next dialog on the following screen"),
+        ("must include clear", "start commands with {\"cmd\": \"clear\", \"color\": \"#FFFFFF\"}"),
+        ("unsupported version", "set \"version\": \"AGD/0.2\" (older AGD/0.1 files are upgraded automatically)"),
+        ("unsupported type", "set \"type\": \"render\""),
+        ("unknown variant", "check \"cmd\" against the command list in prompts/system.txt"),
+        ("requires clickable", "set \"clickable\": true"),
+        ("entirely outside", "move it inside the window or remove it"),
+        ("baseline outside", "move the text up/left so it fits in the window"),
+        ("the limit of", "reduce it or raise the corresponding value in \"limits\" in agd.json"),
+        ("has contrast", "use a darker/lighter text color or a theme token such as \"@text\" / \"@on_primary\""),
+        ("runs past the right edge", "shorten the text, wrap it with \\n, or move it left"),
+        ("overflows its container", "widen the container or shorten the text"),
+        ("clicks in the overlap", "separate the clickable rects so they do not overlap"),
+        ("exceeds the shape size", "use a thinner stroke or a larger shape"),
+        ("must be M|L|Z", "segment cmd is one of \"M\", \"L\", \"Z\""),
+        ("must be path|base64|url", "set \"src_type\" to \"path\", \"base64\" or \"url\""),
+        ("unknown asset", "use a name from the asset manifest (files in images.assets_dir, without extension)"),
+        ("assets directory", "use a file name relative to the assets directory (images.assets_dir), or embed the image as base64"),
+        ("images.allowed_domains", "use an allowed image host, embed the image as base64, or add the host to images.allowed_domains"),
+    ];
+    HINTS.iter().find(|(pattern, _)| message.contains(pattern)).map(|(_, hint)| *hint)
+}
+
+/// 读取文件并走与模型输出相同的解析、校验流程
+fn load_render(path: &str) -> Result<RenderEnvelope, Box<dyn Error>> {
+    let raw = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let (render, dropped) = parser::parse_render_with(&raw, parser::ParseMode::from_config())?;
+    for reason in dropped {
+        eprintln!("{}: dropped {}", path, reason);
+    }
+    validator::validate_render(&render)?;
+    images::prefetch(&render);
+    Ok(render)
+}
+
+/// 最上层（最后绘制）的可点击 rect
+fn access_key_target(render: &RenderEnvelope, keysym: u32) -> Option<&str> {
+    let key = char::from_u32(keysym)?.to_ascii_lowercase();
+    render.commands.iter().find_map(|c| match c {
+        Command::Rect { id: Some(id), clickable: true, access_key: Some(access_key), .. }
+            if validator::access_key_char(access_key) == Some(key) =>
+        {
+            Some(id.as_str())
+        }
+        _ => None,
+    })
+}
+
+fn clickable_at(render: &RenderEnvelope, x: i32, y: i32) -> Option<&str> {
+    render.commands.iter().rev().find_map(|c| match c {
+        Command::Rect { id: Some(id), x: rx, y: ry, w, h, clickable: true, .. }
+            if x >= *rx && y >= *ry && x < rx + *w as i32 && y < ry + *h as i32 =>
+        {
+            Some(id.as_str())
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lint_accepts_null_fields_and_flags_typos() {
+        let issues = lint_source(
+            r##"{"version":"AGD/0.2","type":"render","seq":1,"screen":null,"window":{"width":100,"height":100,"title":"t","background":null},"commands":[
+                {"cmd":"clear","color":"#FFFFFF"},
+                {"cmd":"rect","id":null,"x":0,"y":0,"w":5,"h":5,"fill":"#000000","stroke":null,"clickable":false,"strok":"#000000"},
+                {"cmd":"polyline","points":[{"x":1,"y":2},{"x":3,"y":4,"yy":4}],"color":null,"width":null}]}"##,
+        );
+        let unknown: Vec<&str> = issues.iter().filter(|i| i.message.starts_with("unknown field")).map(|i| i.path.as_str()).collect();
+        assert_eq!(unknown, ["commands[1].strok", "commands[2].points[1].yy"]);
+    }
+}
@@ -0,0 +1,75 @@
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use crate::images;
+
+/// 外部工具读剪贴板的最长等待：剪贴板归本窗口所有时，工具要等编排器线程应答，这里不能一直阻塞
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 剪贴板内容：文字，或解码成 BGRA 像素的图片
+pub enum Clipboard {
+    Text(String),
+    Image { width: usize, height: usize, pixels: Vec<u8> },
+}
+
+/// 读取 X11 剪贴板（CLIPBOARD）：提供 image/png 时取图片，否则取文字；为空或没有 xclip / xsel 时返回 None
+pub fn read() -> Option<Clipboard> {
+    if let Some((width, height, pixels)) = read_image() {
+        return Some(Clipboard::Image { width, height, pixels });
+    }
+    read_text().filter(|text| !text.trim().is_empty()).map(Clipboard::Text)
+}
+
+/// 通过 xclip / xsel 读取剪贴板文字
+pub fn read_text() -> Option<String> {
+    let attempts: [(&str, &[&str]); 2] = [
+        ("xclip", &["-selection", "clipboard", "-o"]),
+        ("xsel", &["--clipboard", "--output"]),
+    ];
+    attempts.iter().find_map(|(program, args)| run(program, args).map(|out| String::from_utf8_lossy(&out).into_owned()))
+}
+
+/// 剪贴板里的 PNG 图片（只有 xclip 能按类型读取）
+fn read_image() -> Option<(usize, usize, Vec<u8>)> {
+    let targets = run("xclip", &["-selection", "clipboard", "-t", "TARGETS", "-o"])?;
+    if !String::from_utf8_lossy(&targets).lines().any(|t| t.trim() == "image/png") {
+        return None;
+    }
+    let png = run("xclip", &["-selection", "clipboard", "-t", "image/png", "-o"])?;
+    match images::decode_bgra(&png) {
+        Ok(image) => Some(image),
+        Err(e) => {
+            warn!("clipboard image could not be decoded: {}", e);
+            None
+        }
+    }
+}
+
+/// 运行工具并收集标准输出；启动失败、退出码非零或超时都返回 None
+fn run(program: &str, args: &[&str]) -> Option<Vec<u8>> {
+    let mut child = Command::new(program).args(args).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().ok()?;
+    let mut stdout = child.stdout.take()?;
+    let reader = thread::spawn(move || {
+        let mut out = Vec::new();
+        stdout.read_to_end(&mut out).map(|_| out)
+    });
+    let deadline = Instant::now() + READ_TIMEOUT;
+    let status = loop {
+        match child.try_wait().ok()? {
+            Some(status) => break status,
+            None if Instant::now() >= deadline => {
+                warn!("{} did not answer within {:?}", program, READ_TIMEOUT);
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            None => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    let out = reader.join().ok()?.ok()?;
+    status.success().then_some(out)
+}
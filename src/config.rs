@@ -0,0 +1,904 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{OnceLock, RwLock};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::info;
+
+use crate::state::router::Intent;
+
+const DEFAULT_CONFIG_PATH: &str = "agd.json";
+/// 配置文件所在目录下存放配置档案的子目录
+const PROFILES_DIR: &str = "profiles";
+
+/// 运行时配置：先读配置文件（`AGD_CONFIG` 指定路径，默认 `agd.json`，不存在则全用默认值），
+/// 叠加选中的配置档案，再用环境变量覆盖。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// openai | anthropic | mock | replay
+    pub provider: String,
+    pub openai: ProviderConfig,
+    pub anthropic: ProviderConfig,
+    /// 主 provider 失败后依次尝试的降级链
+    pub fallback: Vec<FallbackConfig>,
+    pub cache: CacheConfig,
+    pub evaluate: EvaluateConfig,
+    pub retry: RetryConfig,
+    pub http: HttpConfig,
+    pub router: RouterConfig,
+    pub tools: ToolsConfig,
+    pub logging: LoggingConfig,
+    pub theme: ThemeConfig,
+    pub limits: LimitsConfig,
+    pub images: ImagesConfig,
+    pub run: RunConfig,
+    pub scripts: ScriptsConfig,
+    pub data: DataConfig,
+    pub model_screenshot: ModelScreenshotConfig,
+    /// 配置后在该地址（如 `127.0.0.1:9464`）提供 Prometheus `/metrics`
+    pub metrics_addr: Option<String>,
+    pub remote: RemoteConfig,
+    /// 把会话画面录制为动画（`.gif`，或借助 ffmpeg 的 `.webm`）
+    pub screen_record: Option<String>,
+    /// `/screenshot` 与 F12 / Print 键保存截图的目录
+    pub screenshot_dir: String,
+    /// `/capture` 不带区域时运行的截取工具（程序与参数），它把一张图片写到标准输出，
+    /// 如 `["fswebcam", "--no-banner", "--png", "-1", "-"]`；为空时截取整个屏幕
+    pub capture_command: Vec<String>,
+    /// 在每帧右上角叠加指标 HUD（渲染耗时、LLM 耗时、评估迭代、累计 token 与花费）
+    pub hud: bool,
+    /// HUD 估算累计花费用的单价：模型名 -> 每百万 token 的美元价格，
+    /// 如 `{"gpt-5.2": {"input": 1.75, "output": 14.0}}`；没有单价的模型只计 token 数
+    pub prices: BTreeMap<String, ModelPrice>,
+    /// `link` 动作打开浏览器前是否先显示确认卡片
+    pub confirm_links: bool,
+    /// 提交表单时把 `mask` 输入框（密码）的值替换为 `<redacted>`，真实内容不进入发给模型的事件与调试日志
+    pub redact_masked_inputs: bool,
+    /// 本地绘制的界面文字（错误画面、确认卡片、状态条、HUD）的语言：`auto`（跟随语言区域）、`en` 或 `zh`
+    pub language: String,
+    /// 窗口呈现方式：`cpu`（默认，put_image 提交像素）或 `gl`（EGL + OpenGL ES，图片在 GPU 上缩放合成，
+    /// 需要以 `--features gl` 编译；初始化失败时回退到 cpu）
+    pub presenter: String,
+    /// 几何图元的光栅化器：`skia`（默认，抗锯齿，需要以 `--features skia` 编译，否则等同 builtin）或 `builtin`
+    pub rasterizer: String,
+    /// 无输入多少秒后向模型发送 `idle` 事件（有输入时发送 `resume`），用于展台的屏保/摘要界面；None 表示关闭
+    pub idle_timeout_secs: Option<u64>,
+    /// 布局检查（命令整个在窗口外、文本基线出界、描边过宽）的发现按校验错误处理，而不只是提示评估模型
+    pub strict_bounds: bool,
+    /// `strict`：任何一条命令有问题都拒绝整个信封；`lenient`：丢弃有问题的命令（记录警告），保留其余画面
+    pub parse_mode: String,
+    /// 机器模式：stdin/stdout 上收发 JSON-RPC，替代交互式 `>>` 提示符
+    pub rpc: bool,
+    /// 守护进程模式：在该地址提供 HTTP 控制接口，按需创建/销毁多个独立会话
+    pub daemon_addr: Option<String>,
+    /// DSL 解析/校验失败时交回模型修复的最大次数
+    pub max_repair_attempts: u32,
+    /// 提示词模板目录
+    pub prompt_dir: String,
+    /// 提示词集合名：优先读取 `<prompt_dir>/<prompt_set>/`，缺失的文件回退到 `<prompt_dir>/`
+    pub prompt_set: Option<String>,
+    /// 配置档案名：`profiles/<名称>.json`（与配置文件同一目录）中的字段覆盖配置文件，
+    /// 用于成套切换提示词、人设、主题、模型与评估策略；`--profile` 与 `AGD_PROFILE` 优先
+    pub profile: Option<String>,
+    /// 人设：追加在系统提示词末尾的额外指令，通常写在配置档案里
+    pub persona: Option<String>,
+    /// 事件触发的生成请求附带当前画面截图
+    pub attach_screenshot: bool,
+    /// 大于 1 时并发生成 N 份草稿并由评估模型择优，替代串行的评估-修正循环
+    pub best_of: u32,
+    /// mock provider 读取预置 render 信封的目录
+    pub mock_dir: String,
+    /// 把所有 LLM 请求/响应追加录制到该 JSONL 文件
+    pub record_to: Option<String>,
+    /// replay provider 回放的转录文件
+    pub replay_from: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ProviderConfig {
+    /// 完整的接口地址（可指向 Azure、OpenRouter 或自建代理）
+    pub base_url: String,
+    /// 读取 API Key 的环境变量名
+    pub api_key_env: String,
+    pub generate_model: String,
+    pub evaluate_model: String,
+    /// 按模式覆盖的采样参数，未设置时使用各 provider 的内置默认值
+    pub params: ModeParams,
+}
+
+/// 降级链中的一项：以 `provider` 对应的配置段为基础，覆盖其中设置了的字段。
+/// 例如本地 Ollama：`{ "provider": "openai", "base_url": "http://localhost:11434/v1/responses", "api_key_env": "", "generate_model": "qwen3" }`
+#[derive(Debug, Clone, Deserialize)]
+pub struct FallbackConfig {
+    pub provider: String,
+    pub base_url: Option<String>,
+    pub api_key_env: Option<String>,
+    pub generate_model: Option<String>,
+    pub evaluate_model: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ModeParams {
+    pub generate: ModelParams,
+    pub evaluate: ModelParams,
+    pub repair: ModelParams,
+    pub select: ModelParams,
+}
+
+/// 单个模式的请求参数。`reasoning_effort` / `verbosity` 设为空字符串时不发送该字段
+/// （用于不支持推理参数的模型）；Anthropic 只使用 `temperature` 与 `max_output_tokens`。
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ModelParams {
+    pub reasoning_effort: Option<String>,
+    pub verbosity: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_output_tokens: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub dir: String,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { enabled: false, dir: "llm_cache".to_string() }
+    }
+}
+
+/// 视觉评估循环策略
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EvaluateConfig {
+    /// 关闭后生成的草稿通过校验即直接显示
+    pub enabled: bool,
+    pub max_iterations: u32,
+    /// 评估模型给出的 confidence 达到该阈值时即接受当前草稿，即使它没有标记 is_final
+    pub confidence_threshold: Option<f32>,
+    /// 点击等事件触发的更新跳过视觉评估
+    pub skip_for_events: bool,
+    /// 调用评估模型前先做确定性的本地检查（出界、文字溢出、可点击区域重叠、对比度）：
+    /// 对比度不足在本地直接修正，其余问题先让模型按检查结果修改，检查通过后才做视觉评估
+    pub local_checks: bool,
+    /// 整个评估循环的墙钟预算（秒）；超时后直接显示目前最好的草稿并加警告横幅，None 表示不限
+    pub budget_secs: Option<u64>,
+}
+
+impl Default for EvaluateConfig {
+    fn default() -> Self {
+        Self { enabled: true, max_iterations: 4, confidence_threshold: None, skip_for_events: false, local_checks: true, budget_secs: Some(30) }
+    }
+}
+
+/// WebSocket 远程显示
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// 监听地址，如 `0.0.0.0:9300`；未设置时不开启
+    pub listen: Option<String>,
+    /// png（整帧图片）| commands（render 信封 JSON）
+    pub format: String,
+    /// 不连接 X11，只通过远程客户端显示
+    pub headless: bool,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self { listen: None, format: "png".to_string(), headless: false }
+    }
+}
+
+/// 主题调色板：render 中的 `@token` 颜色按这里解析
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// `auto`（跟随桌面配色偏好）、`light` 或 `dark`
+    pub mode: String,
+    /// token 名 -> `#RRGGBB`，浅色模式下覆盖或补充内置调色板
+    pub palette: BTreeMap<String, String>,
+    /// 同上，深色模式使用
+    pub dark_palette: BTreeMap<String, String>,
+    /// 高对比度（强制配色）模式：token 改用内置高对比度调色板（忽略自定义调色板），
+    /// 渲染时把对比度不足的文字改成黑或白
+    pub high_contrast: bool,
+    /// 高对比度模式下文字与背景的最低对比度（WCAG 对比度，AAA 级为 7）
+    pub min_contrast: f32,
+    /// 减少动态效果：不播放元素的出现 / 消失动画，新画面直接显示
+    pub reduced_motion: bool,
+    pub sounds: SoundConfig,
+}
+
+/// 交互提示音（默认关闭）：点击、生成失败、新画面生成完成时响铃，
+/// 适合需要听到点击确认的自助终端。X11 窗口用键盘响铃（XBell）播放，JSON-RPC 嵌入方收到 `sound` 通知自行播放
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SoundConfig {
+    pub enabled: bool,
+    pub click: ToneConfig,
+    pub error: ToneConfig,
+    pub complete: ToneConfig,
+}
+
+impl Default for SoundConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            click: ToneConfig { volume: 30, pitch_hz: 1200, duration_ms: 20 },
+            error: ToneConfig { volume: 80, pitch_hz: 300, duration_ms: 200 },
+            complete: ToneConfig { volume: 50, pitch_hz: 880, duration_ms: 80 },
+        }
+    }
+}
+
+/// 一次响铃：音量为相对系统响铃音量的百分比（0 表示该事件静音）
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ToneConfig {
+    pub volume: u8,
+    pub pitch_hz: u16,
+    pub duration_ms: u16,
+}
+
+impl Default for ToneConfig {
+    fn default() -> Self {
+        Self { volume: 50, pitch_hz: 880, duration_ms: 80 }
+    }
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            mode: "auto".to_string(),
+            palette: BTreeMap::new(),
+            dark_palette: BTreeMap::new(),
+            high_contrast: false,
+            min_contrast: 7.0,
+            reduced_motion: false,
+            sounds: SoundConfig::default(),
+        }
+    }
+}
+
+/// 一个模型每百万 token 的美元价格
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+/// 发给模型的截图（评估草稿、当前画面、择优候选）：缩放比例与编码格式。
+/// 缩得太小时细小文字对评估模型不可读，会多花几轮迭代
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModelScreenshotConfig {
+    /// 相对窗口尺寸的缩放比例，(0, 1]
+    pub scale: f32,
+    /// jpeg | png（无损，文字更清晰，体积更大）
+    pub format: String,
+    /// JPEG 质量 1-100
+    pub quality: u8,
+    /// 事件只改动了一小块画面时，评估额外附带变化区域的原尺寸截图
+    pub region_crops: bool,
+}
+
+impl Default for ModelScreenshotConfig {
+    fn default() -> Self {
+        Self { scale: 0.3, format: "jpeg".to_string(), quality: 75, region_crops: true }
+    }
+}
+
+impl ModelScreenshotConfig {
+    pub fn is_png(&self) -> bool {
+        self.format.eq_ignore_ascii_case("png")
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        if self.is_png() { "image/png" } else { "image/jpeg" }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        if self.is_png() { "png" } else { "jpg" }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if !(self.scale > 0.0 && self.scale <= 1.0) {
+            return Err(format!("model_screenshot.scale must be in (0, 1], got {}", self.scale));
+        }
+        if !(1..=100).contains(&self.quality) {
+            return Err(format!("model_screenshot.quality must be 1-100, got {}", self.quality));
+        }
+        match self.format.to_ascii_lowercase().as_str() {
+            "jpeg" | "jpg" | "png" => Ok(()),
+            // image 0.24 的 WebP 编码依赖 libwebp（webp-encoder 特性），本构建没有启用
+            "webp" => Err("model_screenshot.format webp is not supported by this build; use jpeg or png".to_string()),
+            other => Err(format!("unknown model_screenshot.format {}; use jpeg or png", other)),
+        }
+    }
+}
+
+/// image 命令的图片来源：本地资源目录，以及 `src_type: "url"` 的下载设置（白名单为空时不允许远程图片）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ImagesConfig {
+    /// `src_type: "path"` 的根目录：路径相对于它解析，不能用 `..` 或绝对路径读取目录外的文件
+    pub assets_dir: String,
+    /// 允许的域名（同时匹配其子域名），如 `["upload.wikimedia.org", "*.githubusercontent.com"]`
+    pub allowed_domains: Vec<String>,
+    /// 单张图片下载的字节数上限
+    pub max_bytes: usize,
+    pub timeout_secs: u64,
+}
+
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        Self { assets_dir: "assets".to_string(), allowed_domains: Vec::new(), max_bytes: 5 * 1024 * 1024, timeout_secs: 10 }
+    }
+}
+
+/// `run` 动作可执行的本地命令。白名单为空（默认）时不允许任何命令
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RunConfig {
+    /// 名称 -> 命令行（程序与参数，不经过 shell），如 `{"disk_usage": ["df", "-h"]}`
+    pub commands: BTreeMap<String, Vec<String>>,
+    /// 加在命令行前面的沙箱程序，如 `["bwrap", "--ro-bind", "/", "/", "--dev", "/dev", "--unshare-all", "--die-with-parent", "--"]`
+    pub sandbox: Vec<String>,
+    /// 命令的工作目录；省略时沿用进程的当前目录
+    pub working_dir: Option<String>,
+    pub timeout_secs: u64,
+    /// 标准输出与标准错误各自保留的字节数上限
+    pub max_output_bytes: usize,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self { commands: BTreeMap::new(), sandbox: Vec::new(), working_dir: None, timeout_secs: 10, max_output_bytes: 8192 }
+    }
+}
+
+/// 本地事件脚本（需要 `scripting` 特性）。脚本先于模型处理匹配的事件：校验表单、计算派生值、调用本地接口，
+/// 可以直接给出新界面而不请求模型；配合配置档案即可按会话使用不同的脚本
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScriptsConfig {
+    /// Rhai 脚本文件，按顺序加载；相对路径相对于当前目录
+    pub files: Vec<String>,
+    /// 单次处理函数可执行的操作数上限，防止脚本死循环卡住工作线程
+    pub max_operations: u64,
+    /// `http_get` / `http_post` 允许访问的主机（`*.example.com` 含子域名）；为空时脚本不能发起请求
+    pub http_hosts: Vec<String>,
+}
+
+impl Default for ScriptsConfig {
+    fn default() -> Self {
+        Self { files: Vec::new(), max_operations: 1_000_000, http_hosts: vec!["localhost".to_string(), "127.0.0.1".to_string()] }
+    }
+}
+
+/// `data_sources` 声明的数据来源：界面显示期间由窗口本地轮询，取到的值直接填进绑定的文本与进度条，不请求模型
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DataConfig {
+    /// `url` 来源允许访问的主机（`*.example.com` 含子域名）；为空时不允许 URL 来源
+    pub hosts: Vec<String>,
+    /// `file` 来源的根目录：路径相对于它解析，不能读取目录外的文件
+    pub dir: String,
+    /// 单次读取的字节数上限
+    pub max_bytes: usize,
+    pub timeout_secs: u64,
+}
+
+impl Default for DataConfig {
+    fn default() -> Self {
+        Self {
+            hosts: vec!["localhost".to_string(), "127.0.0.1".to_string()],
+            dir: "data".to_string(),
+            max_bytes: 64 * 1024,
+            timeout_secs: 5,
+        }
+    }
+}
+
+/// render 信封的规模上限，由 `validate_render` 强制，防止失控的模型输出分配超大缓冲区或拖慢渲染
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    pub max_commands: usize,
+    /// 单条 polyline / polygon 的点数、单条 path 的段数
+    pub max_points: usize,
+    /// 单张 base64 内嵌图片的编码后字节数
+    pub max_image_bytes: usize,
+    pub max_window_width: u32,
+    pub max_window_height: u32,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self { max_commands: 2000, max_points: 1000, max_image_bytes: 8 * 1024 * 1024, max_window_width: 4096, max_window_height: 4096 }
+    }
+}
+
+/// 日志输出：终端始终输出到 stderr，配置 `dir` 后额外写入按周期轮转的文件
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// 默认级别（EnvFilter 语法），`AGD_LOG` 环境变量优先
+    pub level: String,
+    pub dir: Option<String>,
+    /// minutely | hourly | daily | never
+    pub rotation: String,
+    /// 文件日志使用 JSON 格式
+    pub json: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self { level: "info".to_string(), dir: None, rotation: "daily".to_string(), json: false }
+    }
+}
+
+/// 生成过程中允许模型调用的运行时查询工具（仅 OpenAI Responses API）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ToolsConfig {
+    pub enabled: bool,
+    /// 单次生成中最多几轮工具调用，用尽后强制模型直接输出
+    pub max_rounds: u32,
+    /// 剪贴板可能含敏感信息，需显式开启
+    pub allow_clipboard: bool,
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self { enabled: false, max_rounds: 4, allow_clipboard: false }
+    }
+}
+
+/// 本地意图路由：命中规则的点击不调用模型
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RouterConfig {
+    pub enabled: bool,
+    /// 自定义规则，优先于内置规则
+    pub rules: Vec<RouteRule>,
+    /// 启用按 id 命名约定（`close`、`*_cancel`、`checkbox_*`、`tab_*` 等）的内置规则
+    pub use_builtin_rules: bool,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self { enabled: false, rules: Vec::new(), use_builtin_rules: true }
+    }
+}
+
+/// `target` 为点击目标 id，支持首尾 `*` 通配
+#[derive(Debug, Clone, Deserialize)]
+pub struct RouteRule {
+    pub target: String,
+    pub intent: Intent,
+}
+
+/// LLM HTTP 客户端的网络设置（企业代理、私有 CA、双向 TLS）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// 代理地址，如 `http://proxy.corp:3128`；未设置时沿用 `HTTPS_PROXY` 等环境变量
+    pub proxy: Option<String>,
+    /// 逗号分隔的直连主机列表
+    pub no_proxy: Option<String>,
+    /// 额外信任的 CA 证书（PEM，可包含多张）
+    pub ca_bundle: Option<String>,
+    /// 客户端证书与 PKCS#8 私钥（PEM），需同时设置
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            no_proxy: None,
+            ca_bundle: None,
+            client_cert: None,
+            client_key: None,
+            timeout_secs: 120,
+            connect_timeout_secs: 10,
+        }
+    }
+}
+
+/// HTTP 重试策略（指数退避 + 抖动）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// 5xx / 网络错误的最大重试次数
+    pub max_attempts: u32,
+    /// 429 限流的最大重试次数，与服务端错误分开计数
+    pub max_rate_limit_retries: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub jitter: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self { max_attempts: 3, max_rate_limit_retries: 5, base_delay_ms: 1000, max_delay_ms: 30_000, jitter: true }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            provider: "openai".to_string(),
+            openai: ProviderConfig {
+                base_url: "https://api.openai.com/v1/responses".to_string(),
+                api_key_env: "OPENAI_API_KEY".to_string(),
+                generate_model: "gpt-5.2".to_string(),
+                evaluate_model: "gpt-5-mini-2025-08-07".to_string(),
+                params: ModeParams::default(),
+            },
+            anthropic: ProviderConfig {
+                base_url: "https://api.anthropic.com/v1/messages".to_string(),
+                api_key_env: "ANTHROPIC_API_KEY".to_string(),
+                generate_model: "claude-sonnet-4-5".to_string(),
+                evaluate_model: "claude-haiku-4-5".to_string(),
+                params: ModeParams::default(),
+            },
+            fallback: Vec::new(),
+            cache: CacheConfig::default(),
+            evaluate: EvaluateConfig::default(),
+            retry: RetryConfig::default(),
+            http: HttpConfig::default(),
+            router: RouterConfig::default(),
+            tools: ToolsConfig::default(),
+            logging: LoggingConfig::default(),
+            theme: ThemeConfig::default(),
+            limits: LimitsConfig::default(),
+            images: ImagesConfig::default(),
+            run: RunConfig::default(),
+            scripts: ScriptsConfig::default(),
+            data: DataConfig::default(),
+            model_screenshot: ModelScreenshotConfig::default(),
+            metrics_addr: None,
+            remote: RemoteConfig::default(),
+            screen_record: None,
+            screenshot_dir: "screenshots".to_string(),
+            capture_command: Vec::new(),
+            hud: false,
+            prices: BTreeMap::new(),
+            confirm_links: true,
+            redact_masked_inputs: true,
+            language: "auto".to_string(),
+            presenter: "cpu".to_string(),
+            rasterizer: "skia".to_string(),
+            idle_timeout_secs: None,
+            strict_bounds: false,
+            parse_mode: "strict".to_string(),
+            rpc: false,
+            daemon_addr: None,
+            max_repair_attempts: 2,
+            prompt_dir: "prompts".to_string(),
+            prompt_set: None,
+            profile: None,
+            persona: None,
+            attach_screenshot: true,
+            best_of: 1,
+            mock_dir: "mock".to_string(),
+            record_to: None,
+            replay_from: None,
+        }
+    }
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Config::default().openai
+    }
+}
+
+impl ProviderConfig {
+    /// `api_key_env` 为空表示不需要鉴权（本地模型服务），返回空字符串
+    pub fn api_key(&self) -> Result<String, Box<dyn Error>> {
+        if self.api_key_env.is_empty() {
+            return Ok(String::new());
+        }
+        std::env::var(&self.api_key_env).map_err(|_| format!("{} is not set", self.api_key_env).into())
+    }
+}
+
+impl Config {
+    /// 按命令行、`AGD_PROFILE`、配置文件的 `profile` 依次决定配置档案后加载
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let profile = PROFILE_OVERRIDE.get().cloned().or_else(|| std::env::var("AGD_PROFILE").ok().filter(|p| !p.is_empty()));
+        Self::load_profile(profile.as_deref())
+    }
+
+    /// 加载配置文件并叠加指定的配置档案；`None` 时使用配置文件里的 `profile`（没有则不叠加）
+    pub fn load_profile(profile: Option<&str>) -> Result<Self, Box<dyn Error>> {
+        let path = config_path();
+        let mut value = match std::fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str(&raw).map_err(|e| format!("invalid config {path}: {e}"))?,
+            Err(_) => Value::Object(Default::default()),
+        };
+        let profile = profile.map(str::to_string).or_else(|| value.get("profile").and_then(Value::as_str).map(str::to_string));
+        if let Some(name) = &profile {
+            check_profile_name(name)?;
+            let file = profiles_dir().join(format!("{name}.json"));
+            let raw = std::fs::read_to_string(&file).map_err(|e| format!("cannot read profile {}: {e}", file.display()))?;
+            let overlay: Value = serde_json::from_str(&raw).map_err(|e| format!("invalid profile {}: {e}", file.display()))?;
+            merge(&mut value, overlay);
+            value["profile"] = Value::String(name.clone());
+        }
+        let mut config: Config = serde_json::from_value(value).map_err(|e| format!("invalid config {path}: {e}"))?;
+        config.apply_env();
+        config.model_screenshot.check().map_err(|e| format!("invalid config {path}: {e}"))?;
+        Ok(config)
+    }
+
+    pub fn fallback_provider_config(&self, entry: &FallbackConfig) -> ProviderConfig {
+        let mut config = match entry.provider.as_str() {
+            "anthropic" | "claude" => self.anthropic.clone(),
+            _ => self.openai.clone(),
+        };
+        if let Some(v) = &entry.base_url {
+            config.base_url = v.clone();
+        }
+        if let Some(v) = &entry.api_key_env {
+            config.api_key_env = v.clone();
+        }
+        if let Some(v) = &entry.generate_model {
+            config.generate_model = v.clone();
+        }
+        if let Some(v) = &entry.evaluate_model {
+            config.evaluate_model = v.clone();
+        }
+        config
+    }
+
+    /// 环境变量覆盖，作用于当前选中的 provider
+    fn apply_env(&mut self) {
+        if let Ok(v) = std::env::var("AGD_PROVIDER") {
+            self.provider = v.trim().to_ascii_lowercase();
+        }
+        if let Ok(v) = std::env::var("AGD_RECORD") {
+            self.record_to = Some(v);
+        }
+        if let Ok(v) = std::env::var("AGD_REPLAY") {
+            self.provider = "replay".to_string();
+            self.replay_from = Some(v);
+        }
+        let active = match self.provider.as_str() {
+            "anthropic" | "claude" => &mut self.anthropic,
+            _ => &mut self.openai,
+        };
+        if let Ok(v) = std::env::var("AGD_BASE_URL") {
+            active.base_url = v;
+        }
+        if let Ok(v) = std::env::var("AGD_GENERATE_MODEL") {
+            active.generate_model = v;
+        }
+        if let Ok(v) = std::env::var("AGD_EVALUATE_MODEL") {
+            active.evaluate_model = v;
+        }
+        if let Ok(v) = std::env::var("AGD_PROMPT_SET") {
+            self.prompt_set = Some(v);
+        }
+        if let Ok(v) = std::env::var("AGD_MOCK_DIR") {
+            self.mock_dir = v;
+        }
+        if let Some(n) = std::env::var("AGD_BEST_OF").ok().and_then(|v| v.parse().ok()) {
+            self.best_of = n;
+        }
+        if let Ok(v) = std::env::var("AGD_EVALUATE") {
+            self.evaluate.enabled = v != "0";
+        }
+        if let Some(n) = std::env::var("AGD_MAX_ITERATIONS").ok().and_then(|v| v.parse().ok()) {
+            self.evaluate.max_iterations = n;
+        }
+        if let Some(scale) = std::env::var("AGD_SCREENSHOT_SCALE").ok().and_then(|v| v.parse().ok()) {
+            self.model_screenshot.scale = scale;
+        }
+        if let Ok(v) = std::env::var("AGD_SCREENSHOT_FORMAT") {
+            self.model_screenshot.format = v;
+        }
+        if let Some(secs) = std::env::var("AGD_EVALUATE_BUDGET").ok().and_then(|v| v.parse::<u64>().ok()) {
+            self.evaluate.budget_secs = Some(secs).filter(|s| *s > 0);
+        }
+        if let Ok(v) = std::env::var("AGD_ROUTER") {
+            self.router.enabled = v == "1";
+        }
+        if let Ok(v) = std::env::var("AGD_TOOLS") {
+            self.tools.enabled = v == "1";
+        }
+        if let Ok(v) = std::env::var("AGD_METRICS_ADDR") {
+            self.metrics_addr = Some(v);
+        }
+        if let Ok(v) = std::env::var("AGD_PROXY") {
+            self.http.proxy = Some(v);
+        }
+        if let Ok(v) = std::env::var("AGD_CA_BUNDLE") {
+            self.http.ca_bundle = Some(v);
+        }
+        if let Ok(v) = std::env::var("AGD_REMOTE_ADDR") {
+            self.remote.listen = Some(v);
+        }
+        if let Ok(v) = std::env::var("AGD_HEADLESS") {
+            self.remote.headless = v == "1";
+        }
+        if let Ok(v) = std::env::var("AGD_SCREEN_RECORD") {
+            self.screen_record = Some(v);
+        }
+        if let Ok(v) = std::env::var("AGD_SCREENSHOT_DIR") {
+            self.screenshot_dir = v;
+        }
+        if let Ok(v) = std::env::var("AGD_CAPTURE_COMMAND") {
+            self.capture_command = v.split_whitespace().map(str::to_string).collect();
+        }
+        if let Ok(v) = std::env::var("AGD_THEME") {
+            self.theme.mode = v.trim().to_ascii_lowercase();
+        }
+        if let Ok(v) = std::env::var("AGD_HIGH_CONTRAST") {
+            self.theme.high_contrast = v == "1";
+        }
+        if let Ok(v) = std::env::var("AGD_REDUCED_MOTION") {
+            self.theme.reduced_motion = v == "1";
+        }
+        if let Ok(v) = std::env::var("AGD_SOUNDS") {
+            self.theme.sounds.enabled = v == "1";
+        }
+        if let Some(secs) = std::env::var("AGD_IDLE_TIMEOUT").ok().and_then(|v| v.parse().ok()) {
+            self.idle_timeout_secs = Some(secs).filter(|s| *s > 0);
+        }
+        if let Ok(v) = std::env::var("AGD_PARSE_MODE") {
+            self.parse_mode = v.trim().to_ascii_lowercase();
+        }
+        if let Ok(v) = std::env::var("AGD_ASSETS_DIR") {
+            self.images.assets_dir = v;
+        }
+        if let Ok(v) = std::env::var("AGD_IMAGE_DOMAINS") {
+            self.images.allowed_domains = v.split(',').map(str::trim).filter(|d| !d.is_empty()).map(String::from).collect();
+        }
+        if let Ok(v) = std::env::var("AGD_SCRIPTS") {
+            self.scripts.files = v.split(',').map(str::trim).filter(|f| !f.is_empty()).map(String::from).collect();
+        }
+        if let Ok(v) = std::env::var("AGD_DATA_HOSTS") {
+            self.data.hosts = v.split(',').map(str::trim).filter(|h| !h.is_empty()).map(String::from).collect();
+        }
+        if let Ok(v) = std::env::var("AGD_DATA_DIR") {
+            self.data.dir = v;
+        }
+        if let Ok(v) = std::env::var("AGD_STRICT_BOUNDS") {
+            self.strict_bounds = v == "1";
+        }
+        if let Ok(v) = std::env::var("AGD_HUD") {
+            self.hud = v == "1";
+        }
+        if let Ok(v) = std::env::var("AGD_CONFIRM_LINKS") {
+            self.confirm_links = v != "0";
+        }
+        if let Ok(v) = std::env::var("AGD_REDACT_MASKED_INPUTS") {
+            self.redact_masked_inputs = v != "0";
+        }
+        if let Ok(v) = std::env::var("AGD_LANGUAGE") {
+            self.language = v;
+        }
+        if let Ok(v) = std::env::var("AGD_PRESENTER") {
+            self.presenter = v;
+        }
+        if let Ok(v) = std::env::var("AGD_RASTERIZER") {
+            self.rasterizer = v;
+        }
+        if let Ok(v) = std::env::var("AGD_RPC") {
+            self.rpc = v == "1";
+        }
+        if let Ok(v) = std::env::var("AGD_DAEMON_ADDR") {
+            self.daemon_addr = Some(v);
+        }
+        if let Ok(v) = std::env::var("AGD_CACHE") {
+            self.cache.enabled = v == "1";
+        }
+    }
+}
+
+/// 当前生效的配置。切换配置档案时换成新的一份；旧的不释放（切换是少见的手动操作），
+/// 这样取到的 `&'static Config` 在整个进程中一直有效
+static CONFIG: RwLock<Option<&'static Config>> = RwLock::new(None);
+/// 每次切换配置档案加一，依赖配置的缓存（调色板等）据此失效
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static PROFILE_OVERRIDE: OnceLock<String> = OnceLock::new();
+
+/// 启动时调用：加载配置，配置文件无效时直接报错
+pub fn init() -> Result<&'static Config, Box<dyn Error>> {
+    let config = Config::load()?;
+    Ok(install(config))
+}
+
+/// 全局配置；未调用 `init` 时退回到默认值 + 环境变量
+pub fn get() -> &'static Config {
+    if let Some(config) = *CONFIG.read().unwrap() {
+        return config;
+    }
+    CONFIG.write().unwrap().get_or_insert_with(|| {
+        let mut config = Config::default();
+        config.apply_env();
+        Box::leak(Box::new(config))
+    })
+}
+
+/// 换上新的配置（启动，或 `/profile` 切换配置档案）
+pub fn install(config: Config) -> &'static Config {
+    let config: &'static Config = Box::leak(Box::new(config));
+    *CONFIG.write().unwrap() = Some(config);
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    if let Some(profile) = &config.profile {
+        info!("Using profile {}", profile);
+    }
+    config
+}
+
+/// 配置被替换的次数
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// 命令行 `--profile <名称>`，优先于 `AGD_PROFILE` 与配置文件；须在 `init` 之前调用
+pub fn set_profile(name: &str) -> Result<(), String> {
+    check_profile_name(name)?;
+    let _ = PROFILE_OVERRIDE.set(name.to_string());
+    Ok(())
+}
+
+/// 档案名就是 `profiles/` 下的文件名，不能带路径
+fn check_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("invalid profile name: {:?}", name));
+    }
+    Ok(())
+}
+
+/// 可用的配置档案名（`profiles/` 下的 `.json` 文件），按名称排序
+pub fn list_profiles() -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(profiles_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+fn config_path() -> String {
+    std::env::var("AGD_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string())
+}
+
+/// 配置文件所在目录下的 `profiles/`
+fn profiles_dir() -> PathBuf {
+    let path = config_path();
+    Path::new(&path).parent().unwrap_or(Path::new("")).join(PROFILES_DIR)
+}
+
+/// 把配置档案逐层合并进配置：对象按键递归合并，其余值直接替换
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::display::UiEvent;
+use crate::orchestrator;
+
+/// 控制接口请求体的上限，防止异常客户端占满内存
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+struct Session {
+    /// 创建会话时的初始请求，便于列表中辨认
+    prompt: String,
+    inputs: Sender<UiEvent>,
+    thread: JoinHandle<()>,
+}
+
+#[derive(Deserialize)]
+struct TextBody {
+    text: String,
+}
+
+/// 守护进程模式：一个进程内运行多个互相独立的会话（各自的窗口、上下文与状态），
+/// 通过 HTTP 控制接口创建与销毁：
+///
+/// - `GET /sessions`：列出存活的会话
+/// - `POST /sessions` `{"text": "..."}`：以初始请求创建会话，返回 `{"id": n}`
+/// - `POST /sessions/<id>/text` `{"text": "..."}`：向会话发送文本输入
+/// - `DELETE /sessions/<id>`：关闭会话及其窗口
+pub fn serve(config: &'static Config, addr: &str) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(addr)?;
+    info!("AGD daemon listening on http://{}/sessions", listener.local_addr()?);
+
+    let mut sessions: BTreeMap<u64, Session> = BTreeMap::new();
+    let mut next_id = 1u64;
+    // 请求逐个在主线程处理，会话表无需加锁
+    for stream in listener.incoming().flatten() {
+        reap(&mut sessions);
+        if let Err(e) = handle(stream, config, &mut sessions, &mut next_id) {
+            warn!("daemon control request failed: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// 清理已自行结束的会话（出错或窗口关闭）
+fn reap(sessions: &mut BTreeMap<u64, Session>) {
+    let finished: Vec<u64> = sessions.iter().filter(|(_, s)| s.thread.is_finished()).map(|(id, _)| *id).collect();
+    for id in finished {
+        sessions.remove(&id);
+        info!("Session {} ended", id);
+    }
+}
+
+fn spawn_session(config: &'static Config, id: u64, prompt: String) -> Session {
+    let (inputs, rx) = mpsc::channel();
+    let initial = prompt.clone();
+    let thread = thread::spawn(move || {
+        if let Err(e) = orchestrator::run_session(config, &initial, None, rx, || {}) {
+            warn!("Session {} failed: {}", id, e);
+        }
+    });
+    Session { prompt, inputs, thread }
+}
+
+fn handle(
+    mut stream: TcpStream,
+    config: &'static Config,
+    sessions: &mut BTreeMap<u64, Session>,
+    next_id: &mut u64,
+) -> Result<(), Box<dyn Error>> {
+    let (method, path, body) = read_request(&stream)?;
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let (status, response) = match (method.as_str(), segments.as_slice()) {
+        ("GET", ["sessions"]) => {
+            let list: Vec<Value> = sessions.iter().map(|(id, s)| json!({ "id": id, "prompt": s.prompt })).collect();
+            ("200 OK", Value::Array(list))
+        }
+        ("POST", ["sessions"]) => match serde_json::from_slice::<TextBody>(&body) {
+            Ok(TextBody { text }) if !text.trim().is_empty() => {
+                let id = *next_id;
+                *next_id += 1;
+                sessions.insert(id, spawn_session(config, id, text.trim().to_string()));
+                info!("Session {} created", id);
+                ("201 Created", json!({ "id": id }))
+            }
+            _ => ("400 Bad Request", json!({ "error": "expected {\"text\": \"...\"}" })),
+        },
+        ("POST", ["sessions", id, "text"]) => match (lookup(sessions, id), serde_json::from_slice::<TextBody>(&body)) {
+            (None, _) => ("404 Not Found", json!({ "error": "no such session" })),
+            (Some(_), Err(_)) => ("400 Bad Request", json!({ "error": "expected {\"text\": \"...\"}" })),
+            (Some(session), Ok(TextBody { text })) => {
+                let _ = session.inputs.send(UiEvent::Text(text));
+                ("202 Accepted", json!({ "accepted": true }))
+            }
+        },
+        ("DELETE", ["sessions", id]) => match id.parse::<u64>().ok().and_then(|id| sessions.remove(&id).map(|s| (id, s))) {
+            Some((id, session)) => {
+                // 会话在下一次事件循环中退出，窗口随 X11 连接一起关闭
+                let _ = session.inputs.send(UiEvent::Quit);
+                info!("Session {} closed", id);
+                ("200 OK", json!({ "id": id, "closed": true }))
+            }
+            None => ("404 Not Found", json!({ "error": "no such session" })),
+        },
+        _ => ("404 Not Found", json!({ "error": "not found" })),
+    };
+    let body = response.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body
+    )?;
+    Ok(())
+}
+
+fn lookup<'a>(sessions: &'a BTreeMap<u64, Session>, id: &str) -> Option<&'a Session> {
+    id.parse::<u64>().ok().and_then(|id| sessions.get(&id))
+}
+
+/// 读取请求行、请求头与按 `Content-Length` 截取的请求体
+fn read_request(stream: &TcpStream) -> Result<(String, String, Vec<u8>), Box<dyn Error>> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or("/").to_string();
+
+    let mut content_length = 0usize;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse()?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Err(format!("request body too large: {} bytes", content_length).into());
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok((method, path, body))
+}
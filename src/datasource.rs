@@ -0,0 +1,293 @@
+//! `data_sources` 声明：界面显示期间按间隔在后台线程里读取 URL、文件或白名单命令的输出，
+//! 取到的值直接填进 `bind` 指向它的文本与进度条，仪表盘每次刷新都不必请求模型。
+//!
+//! 每个来源按 id 对应一个轮询线程：界面更新后仍在的来源（声明不变）继续轮询并保留最近一次的值，
+//! 消失或改动的来源停止轮询并丢弃旧值。绑定写作 `来源 id` 或 `来源 id/JSON 指针`，
+//! 编排器在新值到达后把它填进界面再绘制。
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use crate::config;
+use crate::dsl::formatting;
+use crate::dsl::model::{Command, DataSource, RenderEnvelope};
+use crate::images;
+use crate::metrics;
+use crate::runner;
+
+/// 轮询线程检查停止标志的间隔
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+/// 写进生成上下文的数据文件数上限
+const MAX_LISTED_FILES: usize = 30;
+/// 进度条满格对应的默认值
+pub const DEFAULT_BIND_MAX: f64 = 100.0;
+
+/// 来源的类型：`url`、`file` 或 `run`；三者不是恰好给出一个时返回错误
+pub fn kind(source: &DataSource) -> Result<&'static str, String> {
+    match (&source.url, &source.file, &source.run) {
+        (Some(_), None, None) => Ok("url"),
+        (None, Some(_), None) => Ok("file"),
+        (None, None, Some(_)) => Ok("run"),
+        _ => Err("data source needs exactly one of url, file or run".to_string()),
+    }
+}
+
+/// 校验器用：来源的写法与地址、文件、命令是否允许
+pub fn check(source: &DataSource) -> Result<(), String> {
+    match kind(source)? {
+        "url" => check_url(source.url.as_deref().unwrap_or_default()).map(|_| ()),
+        "file" => images::resolve_under(&config::get().data.dir, source.file.as_deref().unwrap_or_default(), "data directory").map(|_| ()),
+        _ => {
+            let name = source.run.as_deref().unwrap_or_default();
+            if runner::is_allowed(name) {
+                Ok(())
+            } else {
+                Err(format!("{} is not in run.commands", name))
+            }
+        }
+    }
+}
+
+/// 只接受 http(s)，主机必须在 `data.hosts` 中
+fn check_url(url: &str) -> Result<Url, String> {
+    let parsed = Url::parse(url).map_err(|e| format!("invalid URL: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("unsupported URL scheme {}", parsed.scheme()));
+    }
+    let host = parsed.host_str().unwrap_or_default().to_ascii_lowercase();
+    if config::get().data.hosts.iter().any(|domain| images::domain_matches(&host, domain)) {
+        Ok(parsed)
+    } else {
+        Err(format!("host {} is not in data.hosts", host))
+    }
+}
+
+/// 拆开绑定：`cpu/load/0` -> (`cpu`, `/load/0`)；没有指针时第二项为空
+pub fn split_bind(bind: &str) -> (&str, &str) {
+    match bind.find('/') {
+        Some(index) => bind.split_at(index),
+        None => (bind, ""),
+    }
+}
+
+/// 给生成提示词的可用数据来源：数据目录中的文件与允许的主机
+pub fn describe() -> String {
+    let data = &config::get().data;
+    let mut files: Vec<String> = fs::read_dir(&data.dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    files.truncate(MAX_LISTED_FILES);
+    let files = if files.is_empty() { "none".to_string() } else { files.join(", ") };
+    let hosts = if data.hosts.is_empty() { "none".to_string() } else { data.hosts.join(", ") };
+    format!("files {}; URL hosts {}", files, hosts)
+}
+
+/// 绑定到数据来源的 rect 填充的宽度：还没有取到值时为 0，未绑定时是整个 `w`
+pub fn bar_width(w: u32, bind: &Option<String>, progress: Option<f32>) -> u32 {
+    match bind {
+        Some(_) => (w as f32 * progress.unwrap_or(0.0)).round() as u32,
+        None => w,
+    }
+}
+
+/// 读取一次来源；内容能解析为 JSON 时按 JSON 返回，否则是去掉首尾空白的文本
+fn fetch(source: &DataSource) -> Result<Value, String> {
+    let data = &config::get().data;
+    let text = match kind(source)? {
+        "url" => {
+            let url = check_url(source.url.as_deref().unwrap_or_default())?;
+            let client = Client::builder()
+                .timeout(Duration::from_secs(data.timeout_secs))
+                .redirect(Policy::none())
+                .build()
+                .map_err(|e| e.to_string())?;
+            let response = client.get(url).send().and_then(|r| r.error_for_status()).map_err(|e| e.to_string())?;
+            let mut bytes = Vec::new();
+            response.take(data.max_bytes as u64).read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        "file" => {
+            let path = images::resolve_under(&data.dir, source.file.as_deref().unwrap_or_default(), "data directory")?;
+            let file = fs::File::open(&path).map_err(|e| e.to_string())?;
+            let mut bytes = Vec::new();
+            file.take(data.max_bytes as u64).read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        _ => {
+            let output = runner::run(source.run.as_deref().unwrap_or_default());
+            if output.exit_code != Some(0) {
+                return Err(format!("exit code {:?}: {}", output.exit_code, output.stderr.trim()));
+            }
+            output.stdout
+        }
+    };
+    let text = text.trim();
+    Ok(serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string())))
+}
+
+/// 显示用的文字：字符串不带引号，其余按 JSON 写出
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// 进度条用的数值：数字，或能解析为数字的字符串（如命令输出的 `42`、`42%`）
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().trim_end_matches('%').trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// 一个轮询线程；丢弃时通知线程停止
+struct Feed {
+    source: DataSource,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Feed {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+#[derive(Default)]
+pub struct DataFeeds {
+    feeds: HashMap<String, Feed>,
+    /// 各来源最近一次取到的值
+    values: Arc<Mutex<HashMap<String, Value>>>,
+    /// 有来源取到了与上次不同的值
+    dirty: Arc<AtomicBool>,
+}
+
+impl DataFeeds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按界面中的 `data_sources` 启动或停止轮询
+    pub fn sync(&mut self, render: &RenderEnvelope) {
+        let before = self.feeds.len();
+        self.feeds.retain(|id, feed| render.data_sources.iter().any(|s| s.id == *id && *s == feed.source));
+        if self.feeds.len() != before {
+            let feeds = &self.feeds;
+            self.values.lock().unwrap().retain(|id, _| feeds.contains_key(id));
+        }
+        for source in &render.data_sources {
+            if self.feeds.contains_key(&source.id) {
+                continue;
+            }
+            let stop = Arc::new(AtomicBool::new(false));
+            self.spawn(source.clone(), stop.clone());
+            self.feeds.insert(source.id.clone(), Feed { source: source.clone(), stop });
+        }
+    }
+
+    fn spawn(&self, source: DataSource, stop: Arc<AtomicBool>) {
+        let (values, dirty) = (self.values.clone(), self.dirty.clone());
+        thread::spawn(move || {
+            let kind = kind(&source).unwrap_or("invalid");
+            let mut failing = false;
+            while !stop.load(Ordering::SeqCst) {
+                match fetch(&source) {
+                    Ok(value) => {
+                        metrics::inc("agd_data_fetches_total", &[("kind", kind), ("result", "ok")], 1.0);
+                        failing = false;
+                        // 持锁检查停止标志：来源被移除后不能再写回旧值
+                        let mut values = values.lock().unwrap();
+                        if stop.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        if values.get(&source.id) != Some(&value) {
+                            debug!("data source {} updated", source.id);
+                            values.insert(source.id.clone(), value);
+                            dirty.store(true, Ordering::SeqCst);
+                        }
+                    }
+                    Err(e) => {
+                        metrics::inc("agd_data_fetches_total", &[("kind", kind), ("result", "error")], 1.0);
+                        // 连续失败只记一次，恢复后再失败时重新记录
+                        if !failing {
+                            warn!("data source {}: {}", source.id, e);
+                            failing = true;
+                        }
+                    }
+                }
+                let mut waited = Duration::ZERO;
+                while waited < Duration::from_millis(source.every_ms) && !stop.load(Ordering::SeqCst) {
+                    thread::sleep(STOP_CHECK_INTERVAL);
+                    waited += STOP_CHECK_INTERVAL;
+                }
+            }
+        });
+    }
+
+    /// 有来源取到了新值
+    pub fn has_update(&self) -> bool {
+        self.dirty.load(Ordering::SeqCst)
+    }
+
+    /// 把各来源最近的值填进绑定的文本与进度条；内容有变化时返回 true
+    pub fn fill(&self, render: &mut RenderEnvelope) -> bool {
+        self.dirty.store(false, Ordering::SeqCst);
+        let values = self.values.lock().unwrap();
+        let lookup = |bind: &str| {
+            let (id, pointer) = split_bind(bind);
+            values.get(id).and_then(|value| value.pointer(pointer))
+        };
+        let mut changed = false;
+        for command in render.commands.iter_mut() {
+            match command {
+                Command::Text { bind: Some(bind), text, value, format, .. } => {
+                    let Some(next) = lookup(bind) else {
+                        continue;
+                    };
+                    let next_text = match format {
+                        Some(format) => {
+                            *value = Some(next.clone());
+                            formatting::format_value(next, format).unwrap_or_else(|_| as_text(next))
+                        }
+                        None => as_text(next),
+                    };
+                    if *text != next_text {
+                        *text = next_text;
+                        changed = true;
+                    }
+                }
+                Command::Rect { bind: Some(bind), bind_max, progress, .. } => {
+                    let Some(number) = lookup(bind).and_then(as_number) else {
+                        continue;
+                    };
+                    let max = bind_max.unwrap_or(DEFAULT_BIND_MAX);
+                    let next = Some((number / max).clamp(0.0, 1.0) as f32);
+                    if *progress != next {
+                        *progress = next;
+                        changed = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        changed
+    }
+}
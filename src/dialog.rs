@@ -0,0 +1,169 @@
+//! `dialog` 命令：模态对话框。窗口把其下的画面压暗，命中测试只登记对话框的两个按钮，
+//! 键盘输入也只交给它（Tab / 方向键切换焦点，Enter 触发有焦点的按钮，Esc 取消），
+//! 模型不必再用一组 rect 手工拼出遮罩、还要提防用户点到遮罩下面的按钮。
+//!
+//! 确认与取消按钮的 id 固定为 `<id>.confirm` 与 `<id>.cancel`，按下时与普通可点击 rect 一样发出 click 事件。
+//! 一个画面最多一个对话框。
+
+use std::borrow::Cow;
+
+use crate::display::KeyEvent;
+use crate::dsl::builder::Render;
+use crate::dsl::model::{Command, RenderEnvelope};
+use crate::i18n;
+use crate::x11::renderer;
+
+pub const CONFIRM: &str = "confirm";
+pub const CANCEL: &str = "cancel";
+/// 压暗背景的不透明度
+pub const DIM_ALPHA: f32 = 0.45;
+/// 卡片的宽度上限；窗口较窄时随窗口收缩
+const WIDTH: u32 = 440;
+/// 卡片内边距、标题与正文的行高、按钮尺寸与间距
+const PADDING: i32 = 24;
+const LINE_HEIGHT: i32 = 36;
+const BUTTON_W: u32 = 120;
+const BUTTON_H: u32 = 44;
+const BUTTON_GAP: i32 = 16;
+/// 正文最多显示的行数
+const MAX_TEXT_LINES: usize = 6;
+
+/// 按钮的 id：`<对话框 id>.confirm` 或 `<对话框 id>.cancel`
+pub fn button_id(dialog_id: &str, which: &str) -> String {
+    format!("{}.{}", dialog_id, which)
+}
+
+/// 画面中打开的对话框（有多个时取最后一个）
+pub fn active(render: &RenderEnvelope) -> Option<&Command> {
+    render.commands.iter().rev().find(|c| matches!(c, Command::Dialog { .. }))
+}
+
+/// 把对话框居中排在窗口里
+pub fn layout(render: &mut RenderEnvelope) {
+    let (width, height) = (render.window.width, render.window.height);
+    for command in render.commands.iter_mut() {
+        let Command::Dialog { text, frame, .. } = command else {
+            continue;
+        };
+        let lines = text.as_deref().map_or(0, |t| t.lines().count().min(MAX_TEXT_LINES)) as i32;
+        let w = WIDTH.min(width.saturating_sub(32)).max(2 * BUTTON_W + 2 * PADDING as u32 + BUTTON_GAP as u32);
+        let h = (PADDING + LINE_HEIGHT * (1 + lines) + PADDING + BUTTON_H as i32 + PADDING) as u32;
+        let x = (width as i32 - w as i32) / 2;
+        let y = (height as i32 - h as i32) / 2;
+        *frame = Some((x, y, w, h));
+    }
+}
+
+/// 对话框本地画出的一个按钮
+pub struct Button {
+    pub id: String,
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+    pub label: String,
+    /// 确认按钮；否则是取消按钮
+    pub confirm: bool,
+}
+
+/// 对话框的按钮，取消在左、确认在右
+pub fn buttons(command: &Command) -> Vec<Button> {
+    let Command::Dialog { id, confirm, cancel, frame: Some((x, y, w, h)), .. } = command else {
+        return Vec::new();
+    };
+    let top = y + *h as i32 - PADDING - BUTTON_H as i32;
+    let confirm_x = x + *w as i32 - PADDING - BUTTON_W as i32;
+    let cancel_x = confirm_x - BUTTON_GAP - BUTTON_W as i32;
+    let confirm = confirm.clone().unwrap_or_else(|| i18n::text("dialog.confirm").to_string());
+    let cancel = cancel.clone().unwrap_or_else(|| i18n::text("dialog.cancel").to_string());
+    vec![
+        Button { id: button_id(id, CANCEL), x: cancel_x, y: top, w: BUTTON_W, h: BUTTON_H, label: cancel, confirm: false },
+        Button { id: button_id(id, CONFIRM), x: confirm_x, y: top, w: BUTTON_W, h: BUTTON_H, label: confirm, confirm: true },
+    ]
+}
+
+/// 把对话框移到其余命令之后，并在它后面插入卡片、标题、正文与按钮交给光栅化器；
+/// 对话框命令本身只负责压暗背景。没有对话框时原样借用
+pub fn expand(render: &RenderEnvelope) -> Cow<'_, RenderEnvelope> {
+    if active(render).is_none() {
+        return Cow::Borrowed(render);
+    }
+    let (dialogs, rest): (Vec<Command>, Vec<Command>) = render.commands.iter().cloned().partition(|c| matches!(c, Command::Dialog { .. }));
+    let mut base = render.clone();
+    base.commands = rest;
+    let mut out = Render::from(base);
+    for command in &dialogs {
+        out = out.push(command.clone());
+        let Command::Dialog { title, text, destructive, frame: Some((x, y, w, h)), focus, .. } = command else {
+            continue;
+        };
+        let inner_w = w.saturating_sub(2 * PADDING as u32);
+        out = out
+            .round_rect(*x, *y, *w, *h, 12, "@surface")
+            .stroke("@border", 2)
+            .text(x + PADDING, y + PADDING, title.clone(), "@text")
+            .role("heading")
+            .fit(inner_w, LINE_HEIGHT as u32, "ellipsis");
+        if let Some(text) = text {
+            let body: Vec<&str> = text.lines().take(MAX_TEXT_LINES).collect();
+            out = out
+                .text(x + PADDING, y + PADDING + LINE_HEIGHT, body.join("\n"), "@muted")
+                .fit(inner_w, (LINE_HEIGHT * body.len() as i32) as u32, "ellipsis");
+        }
+        let focused = focus.as_deref().unwrap_or(CONFIRM);
+        for Button { id, x: bx, y: by, w: bw, h: bh, label, confirm } in buttons(command) {
+            let fill = match (confirm, *destructive) {
+                (true, true) => "@danger",
+                (true, false) => "@primary",
+                (false, _) => "@muted",
+            };
+            // 标签按渲染字体实测后水平居中，过长时省略
+            let label_x = bx + (bw as i32 - renderer::measure_text(&label, 24.0, None).width).max(8) / 2;
+            // 有焦点的按钮先垫一层稍大的底色，露出的边缘就是焦点框
+            if id.ends_with(focused) {
+                out = out.rect(bx - 4, by - 4, bw + 8, bh + 8, "@text");
+            }
+            out = out
+                .rect(bx, by, bw, bh, fill)
+                .clickable(&id)
+                .role("button")
+                .label(&label)
+                .text(label_x, by + 9, label.clone(), "@on_primary")
+                .fit(bw - 16, bh - 9, "ellipsis");
+        }
+    }
+    Cow::Owned(out.build())
+}
+
+/// 对话框打开时按键的去向
+pub enum KeyAction {
+    /// 焦点移到了另一个按钮，需要重画
+    Focus,
+    /// 触发按钮：等同于点击这个位置（按钮的中心）
+    Press(i32, i32),
+    /// 其余按键被对话框吞掉，不再交给下面的界面
+    Ignore,
+}
+
+/// 处理对话框打开时的按键；没有对话框时返回 None，按键照常处理
+pub fn handle_key(render: &mut RenderEnvelope, key: &KeyEvent) -> Option<KeyAction> {
+    let dialog = render.commands.iter_mut().rev().find(|c| matches!(c, Command::Dialog { .. }))?;
+    let press = |dialog: &Command, which: &str| {
+        let button = buttons(dialog).into_iter().find(|b| b.id.ends_with(which))?;
+        Some(KeyAction::Press(button.x + button.w as i32 / 2, button.y + button.h as i32 / 2))
+    };
+    let Command::Dialog { focus, .. } = dialog else {
+        return None;
+    };
+    let focused = focus.clone().unwrap_or_else(|| CONFIRM.to_string());
+    match key.keysym {
+        // Tab、Shift+Tab（ISO_Left_Tab）与左右方向键：只有两个按钮，都是切到另一个
+        0xFF09 | 0xFE20 | 0xFF51 | 0xFF53 => {
+            *focus = Some(if focused == CONFIRM { CANCEL } else { CONFIRM }.to_string());
+            Some(KeyAction::Focus)
+        }
+        0xFF0D | 0xFF8D => press(dialog, &focused),
+        0xFF1B => press(dialog, CANCEL),
+        _ => Some(KeyAction::Ignore),
+    }
+}
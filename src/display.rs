@@ -0,0 +1,298 @@
+use std::error::Error;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::{self, SoundConfig, ToneConfig};
+use crate::dsl::builder::Render;
+use crate::dsl::model::{RenderEnvelope, WindowSpec};
+use crate::gesture::{SwipeDirection, FLICK_MIN_VELOCITY};
+use crate::i18n;
+use crate::metrics;
+use crate::state::hit_test::HitTarget;
+use crate::x11::backend::X11Backend;
+use crate::x11::renderer::{self, PressStyle};
+use crate::x11::animation;
+use crate::x11::events;
+use crate::x11::selection;
+
+pub struct ClickEvent {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// 窗口内的按键，keysym 取自 X11 键盘映射（如 F12 为 0xFFC9），同时记下按住的修饰键
+pub struct KeyEvent {
+    pub keysym: u32,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+/// 滑动手势：起点、方向与松开时的速度（像素/秒）
+pub struct GestureEvent {
+    pub x: i32,
+    pub y: i32,
+    pub direction: SwipeDirection,
+    pub velocity: f32,
+}
+
+impl GestureEvent {
+    /// 快速甩动（flick）还是普通滑动（swipe）
+    pub fn is_flick(&self) -> bool {
+        self.velocity >= FLICK_MIN_VELOCITY
+    }
+}
+
+pub enum UiEvent {
+    Click(ClickEvent),
+    Key(KeyEvent),
+    /// 按住左键（或触摸）快速划过，见 `gesture`
+    Gesture(GestureEvent),
+    /// 远程客户端发来的文本输入，与终端输入等价
+    Text(String),
+    /// 窗口内容需要重绘（被遮挡后恢复等）
+    Expose,
+    /// 嵌入方请求退出（JSON-RPC `shutdown` 或 stdin 关闭）
+    Quit,
+}
+
+/// 触发提示音的交互事件，音色见配置项 `theme.sounds`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundCue {
+    Click,
+    Error,
+    Complete,
+}
+
+impl SoundCue {
+    pub fn name(self) -> &'static str {
+        match self {
+            SoundCue::Click => "click",
+            SoundCue::Error => "error",
+            SoundCue::Complete => "complete",
+        }
+    }
+
+    pub fn tone(self, sounds: &SoundConfig) -> ToneConfig {
+        match self {
+            SoundCue::Click => sounds.click,
+            SoundCue::Error => sounds.error,
+            SoundCue::Complete => sounds.complete,
+        }
+    }
+}
+
+/// 显示面：本地 X11 窗口、远程 WebSocket 客户端或 JSON-RPC 嵌入方。
+/// 编排器把每一帧推给所有显示面，并从所有显示面收集输入。
+pub trait Surface {
+    fn present(&self, render: &RenderEnvelope) -> Result<(), Box<dyn Error>>;
+
+    /// 点击的按下反馈；默认不做任何事
+    fn present_pressed(&self, _render: &RenderEnvelope, _target: &HitTarget) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// 临时帧（加载动画等）：不代表新的界面状态，默认不显示，
+    /// 只有能低成本重绘的显示面才需要实现
+    fn present_transient(&self, _render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// 播放提示音；默认不做任何事
+    fn play_sound(&self, _cue: SoundCue, _tone: ToneConfig) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn poll_event(&self) -> Result<Option<UiEvent>, Box<dyn Error>>;
+
+    /// 本显示面自己复制到剪贴板、且仍持有剪贴板的文字；粘贴时优先用它，不必绕道外部工具
+    fn copied_text(&self) -> Option<String> {
+        None
+    }
+
+    fn dpi(&self) -> Option<f32> {
+        None
+    }
+
+    /// 所在屏幕的像素尺寸
+    fn screen_size(&self) -> Option<(u32, u32)> {
+        None
+    }
+}
+
+impl Surface for X11Backend {
+    fn present(&self, render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
+        animation::present(self, render)
+    }
+
+    fn present_pressed(&self, render: &RenderEnvelope, target: &HitTarget) -> Result<(), Box<dyn Error>> {
+        let style = PressStyle::for_target(render, &target.id);
+        // 动画样式分几帧播放，总时长与静态样式一致
+        let steps = if style.is_animated() { PRESS_ANIMATION_STEPS } else { 1 };
+        for step in 1..=steps {
+            let progress = step as f32 / steps as f32;
+            renderer::render_frame_with_press(self, render, target.x, target.y, target.w, target.h, style, progress)?;
+            thread::sleep(PRESS_DURATION / steps);
+        }
+        renderer::release_press(self, target.x, target.y, target.w, target.h)
+    }
+
+    fn present_transient(&self, render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
+        renderer::render_frame(self, render)
+    }
+
+    fn play_sound(&self, _cue: SoundCue, tone: ToneConfig) -> Result<(), Box<dyn Error>> {
+        self.bell(tone.volume, tone.pitch_hz, tone.duration_ms)
+    }
+
+    fn poll_event(&self) -> Result<Option<UiEvent>, Box<dyn Error>> {
+        events::poll_event(self)
+    }
+
+    fn copied_text(&self) -> Option<String> {
+        selection::copied_text(self)
+    }
+
+    fn dpi(&self) -> Option<f32> {
+        Some(X11Backend::dpi(self))
+    }
+
+    fn screen_size(&self) -> Option<(u32, u32)> {
+        Some(X11Backend::screen_size(self))
+    }
+}
+
+/// 按下反馈显示多久，以及动画样式（ripple）分几帧
+const PRESS_DURATION: Duration = Duration::from_millis(60);
+const PRESS_ANIMATION_STEPS: u32 = 4;
+
+/// 加载动画的刷新间隔
+pub const BUSY_FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+const STATUS_STRIP_HEIGHT: u32 = 36;
+
+/// 在当前画面底部叠加“thinking…”状态条和旋转指示器，由编排器在等待模型期间本地绘制，
+/// 让用户知道点击已被接收
+pub fn loading_overlay(render: &RenderEnvelope, elapsed: Duration) -> RenderEnvelope {
+    let width = render.window.width;
+    let top = render.window.height.saturating_sub(STATUS_STRIP_HEIGHT) as i32;
+    let center_y = top + STATUS_STRIP_HEIGHT as i32 / 2;
+    // 每秒转一圈，弧长 270°
+    let start = (elapsed.as_millis() % 1000) as f32 * 0.36;
+    let dots = ".".repeat(1 + (elapsed.as_millis() / 400 % 3) as usize);
+    Render::from(render.clone())
+        .rect(0, top, width, STATUS_STRIP_HEIGHT, "#1F2937")
+        .arc(20, center_y, 9, start, start + 270.0, "#93C5FD", 2)
+        .text(38, top + 4, i18n::text_with("loading.thinking", &[("dots", dots), ("secs", format!("{:.1}", elapsed.as_secs_f32()))]), "#F9FAFB")
+        .build()
+}
+
+/// 评估循环超出时间预算时，在显示的草稿顶部加一条警告横幅
+pub fn budget_banner(render: &RenderEnvelope, budget: Duration) -> RenderEnvelope {
+    Render::from(render.clone())
+        .rect(0, 0, render.window.width, STATUS_STRIP_HEIGHT, "#92400E")
+        .text(12, 4, i18n::text_with("banner.budget", &[("secs", budget.as_secs().to_string())]), "#FEF3C7").role("status")
+        .build()
+}
+
+/// 内置错误画面上按钮的 id，点击由编排器本地处理，不发给模型
+pub const RETRY_TARGET: &str = "agd.error.retry";
+pub const DISMISS_TARGET: &str = "agd.error.dismiss";
+
+/// 链接确认卡片上按钮的 id，同样由编排器本地处理
+pub const LINK_OPEN_TARGET: &str = "agd.link.open";
+pub const LINK_CANCEL_TARGET: &str = "agd.link.cancel";
+
+/// `link` 动作打开浏览器前叠加在当前画面中央的确认卡片：完整地址与打开 / 取消按钮
+pub fn link_prompt(render: &RenderEnvelope, href: &str) -> RenderEnvelope {
+    let (width, height) = (render.window.width, render.window.height);
+    let card_w = width.saturating_sub(32).clamp(300, 560);
+    let card_h = 176;
+    let x = (width.saturating_sub(card_w) / 2) as i32;
+    let y = (height.saturating_sub(card_h) / 2) as i32;
+    let button_y = y + card_h as i32 - 64;
+    let (open, cancel) = (i18n::text("link.open"), i18n::text("link.cancel"));
+    Render::from(render.clone())
+        .round_rect(x, y, card_w, card_h, 12, "@surface").stroke("@border", 2)
+        .text(x + 24, y + 20, i18n::text("link.title"), "@text").role("heading")
+        .text(x + 24, y + 60, href, "@muted").fit(card_w - 48, 32, "ellipsis")
+        .rect(x + 24, button_y, 120, 44, "@primary").clickable(LINK_OPEN_TARGET).access_key('o').role("button").label(i18n::text("link.open_label"))
+        .text(centered(x + 24, 120, open), button_y + 9, open, "@on_primary")
+        .rect(x + 160, button_y, 120, 44, "@muted").clickable(LINK_CANCEL_TARGET).access_key('c').role("button").label(cancel)
+        .text(centered(x + 160, 120, cancel), button_y + 9, cancel, "@on_primary")
+        .build()
+}
+
+/// 按钮标签在按钮内水平居中时的 x：标签随界面语言变化，宽度按渲染字体实测
+fn centered(x: i32, w: u32, label: &str) -> i32 {
+    x + (w as i32 - renderer::measure_text(label, 24.0, None).width).max(0) / 2
+}
+
+/// 错误信息按字符数折行，最多显示的行数
+const ERROR_MAX_LINES: usize = 8;
+
+/// 生成/解析/校验最终失败时显示的内置错误画面：错误信息、重试与返回按钮。
+/// 沿用当前窗口尺寸，避免窗口跳动
+pub fn error_screen(message: &str, window: &WindowSpec) -> RenderEnvelope {
+    let width = window.width.max(320);
+    let height = window.height.max(240);
+    // 24px 字体下按每字符约 13px 估算可用列数
+    let columns = ((width.saturating_sub(64)) / 13).max(16) as usize;
+    // 校验错误每行一条问题，先按行拆开再折行
+    let mut lines: Vec<String> = message
+        .lines()
+        .flat_map(|line| line.chars().collect::<Vec<char>>().chunks(columns).map(|c| c.iter().collect::<String>()).collect::<Vec<_>>())
+        .collect();
+    if lines.len() > ERROR_MAX_LINES {
+        lines.truncate(ERROR_MAX_LINES);
+        lines.push("…".to_string());
+    }
+    let button_y = (height as i32 - 72).max(120);
+    let (retry, back) = (i18n::text("error.retry"), i18n::text("error.back"));
+    // 用主题 token 着色，错误画面随浅色/深色方案变化
+    Render::window(width, height)
+        .title(window.title.clone())
+        .clear("@background")
+        .text(32, 28, i18n::text("error.title"), "@danger").role("heading")
+        .text(32, 72, lines.join("\n"), "@text").role("status")
+        .rect(32, button_y, 140, 44, "@danger").clickable(RETRY_TARGET).access_key('r').role("button").label(i18n::text("error.retry_label"))
+        .text(centered(32, 140, retry), button_y + 9, retry, "@on_primary")
+        .rect(188, button_y, 140, 44, "@muted").clickable(DISMISS_TARGET).access_key('b').role("button").label(i18n::text("error.back_label"))
+        .text(centered(188, 140, back), button_y + 9, back, "@on_primary")
+        .build()
+}
+
+const HUD_WIDTH: u32 = 250;
+
+/// 右上角的指标 HUD：上一帧渲染耗时、最近一次 LLM 调用耗时、评估迭代次数、累计 token
+pub fn hud_overlay(render: &RenderEnvelope) -> RenderEnvelope {
+    let ms = |name: &str, scale: f64, unit: &str| {
+        metrics::last(name).map(|v| format!("{:.1} {}", v * scale, unit)).unwrap_or_else(|| "-".to_string())
+    };
+    let tokens = |kind: &str| {
+        let n = metrics::total("agd_llm_tokens_total", ("kind", kind));
+        if n >= 1000.0 { format!("{:.1}k", n / 1000.0) } else { format!("{}", n) }
+    };
+    // 按配置的单价换算；有模型没配单价时只显示已知部分并标上 +?，一个单价都没有时显示 -
+    let cost = || {
+        let prices = &config::get().prices;
+        let (cost, unpriced) = metrics::token_cost(|model| prices.get(model).map(|p| (p.input, p.output)));
+        match (prices.is_empty(), unpriced) {
+            (true, _) => "-".to_string(),
+            (false, false) => format!("${:.4}", cost),
+            (false, true) => format!("${:.4}+?", cost),
+        }
+    };
+    let lines = [
+        format!("{} {}", i18n::text("hud.frame"), ms("agd_render_duration_seconds", 1000.0, "ms")),
+        format!("{} {}", i18n::text("hud.llm"), ms("agd_llm_request_duration_seconds", 1.0, "s")),
+        format!("{} {}", i18n::text("hud.iter"), metrics::last("agd_evaluate_iterations").map(|v| v.to_string()).unwrap_or_else(|| "-".to_string())),
+        format!("{} {}/{}", i18n::text("hud.tokens"), tokens("input"), tokens("output")),
+        format!("{} {}", i18n::text("hud.cost"), cost()),
+    ];
+    let x = render.window.width.saturating_sub(HUD_WIDTH + 8) as i32;
+    Render::from(render.clone())
+        .rect(x, 8, HUD_WIDTH, lines.len() as u32 * 34 + 8, "#111827").stroke("#374151", 1)
+        .text(x + 10, 12, lines.join("\n"), "#A7F3D0")
+        .build()
+}
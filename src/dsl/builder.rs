@@ -0,0 +1,289 @@
+use crate::dsl::model::{Command, Point, RenderEnvelope, Shortcut, Timer, WindowSpec};
+use crate::dsl::validator::{self, ValidationErrors};
+use crate::dsl::version;
+
+/// 在 Rust 代码里构造 render 信封（内置画面、覆盖层、基准场景），不用手写 JSON：
+///
+/// ```ignore
+/// let render = Render::window(800, 600)
+///     .title("Demo")
+///     .clear("@background")
+///     .rect(40, 40, 200, 56, "@primary").clickable("ok")
+///     .text(60, 54, "OK", "@on_primary")
+///     .build();
+/// ```
+///
+/// 图形方法只带必需的参数；描边、点击、背景色等可选属性用紧跟其后的修饰方法设置，
+/// 作用于最近添加的一条命令（类型不适用时忽略）。
+/// 部分方法目前只给外部代码（测试、本地组件）使用，标了 `allow(dead_code)`。
+#[derive(Debug, Clone)]
+pub struct Render {
+    envelope: RenderEnvelope,
+}
+
+impl Render {
+    /// 当前版本的空信封，标题为空，seq 为 0
+    pub fn window(width: u32, height: u32) -> Self {
+        Self {
+            envelope: RenderEnvelope {
+                version: version::CURRENT_VERSION.to_string(),
+                render_type: "render".to_string(),
+                seq: 0,
+                window: WindowSpec { width, height, title: String::new(), background: None },
+                commands: Vec::new(),
+                xdsl: None,
+                timers: Vec::new(),
+                shortcuts: Vec::new(),
+                data_sources: Vec::new(),
+                screen: None,
+                navigation: Vec::new(),
+            },
+        }
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.envelope.window.title = title.into();
+        self
+    }
+
+    pub fn seq(mut self, seq: u64) -> Self {
+        self.envelope.seq = seq;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn timer(mut self, id: impl Into<String>, every_ms: u64) -> Self {
+        self.envelope.timers.push(Timer { id: id.into(), every_ms });
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn shortcut(mut self, keys: impl Into<String>, target: impl Into<String>) -> Self {
+        self.envelope.shortcuts.push(Shortcut { keys: keys.into(), target: target.into() });
+        self
+    }
+
+    /// 追加任意命令，覆盖构造方法没有提供的写法
+    pub fn push(mut self, command: Command) -> Self {
+        self.envelope.commands.push(command);
+        self
+    }
+
+    pub fn commands(mut self, commands: impl IntoIterator<Item = Command>) -> Self {
+        self.envelope.commands.extend(commands);
+        self
+    }
+
+    pub fn clear(self, color: &str) -> Self {
+        self.push(Command::Clear { color: color.to_string() })
+    }
+
+    pub fn rect(self, x: i32, y: i32, w: u32, h: u32, fill: &str) -> Self {
+        self.push(Command::Rect {
+            id: None,
+            x,
+            y,
+            w,
+            h,
+            fill: Some(fill.to_string()),
+            stroke: None,
+            stroke_width: None,
+            clickable: false,
+            action: None,
+            form: None,
+            href: None,
+            run: None,
+            access_key: None,
+            press_style: None,
+            press_color: None,
+            role: None,
+            label: None,
+            enter: None,
+            exit: None,
+            bind: None,
+            bind_max: None,
+            progress: None,
+            checked: None,
+        })
+    }
+
+    pub fn round_rect(self, x: i32, y: i32, w: u32, h: u32, r: u32, fill: &str) -> Self {
+        self.push(Command::RoundRect {
+            x: Some(x),
+            y: Some(y),
+            w: Some(w),
+            h: Some(h),
+            r: Some(r),
+            fill: Some(fill.to_string()),
+            stroke: None,
+            stroke_width: None,
+            role: None,
+            label: None,
+            enter: None,
+            exit: None,
+        })
+    }
+
+    pub fn text(self, x: i32, y: i32, text: impl Into<String>, color: &str) -> Self {
+        self.push(Command::Text { x, y, text: text.into(), color: Some(color.to_string()), bg: None, value: None, format: None, w: None, h: None, fit: None, orientation: None, rotate: None, role: None, label: None, enter: None, exit: None, bind: None })
+    }
+
+    #[allow(dead_code)]
+    pub fn line(self, x1: i32, y1: i32, x2: i32, y2: i32, color: &str, width: u32) -> Self {
+        self.push(Command::Line { x1, y1, x2, y2, color: Some(color.to_string()), width: Some(width) })
+    }
+
+    pub fn circle(self, cx: i32, cy: i32, r: u32, fill: &str) -> Self {
+        self.push(Command::Circle {
+            cx: Some(cx),
+            cy: Some(cy),
+            r: Some(r),
+            fill: Some(fill.to_string()),
+            stroke: None,
+            stroke_width: None,
+        })
+    }
+
+    /// 角度单位为度，与 DSL 一致
+    #[allow(clippy::too_many_arguments)]
+    pub fn arc(self, cx: i32, cy: i32, r: u32, start_angle: f32, end_angle: f32, color: &str, width: u32) -> Self {
+        self.push(Command::Arc {
+            cx: Some(cx),
+            cy: Some(cy),
+            r: Some(r),
+            start_angle: Some(start_angle),
+            end_angle: Some(end_angle),
+            color: Some(color.to_string()),
+            width: Some(width),
+            fill: None,
+            pie: false,
+        })
+    }
+
+    pub fn polyline(self, points: &[(i32, i32)], color: &str, width: u32) -> Self {
+        self.push(Command::Polyline { points: Some(to_points(points)), color: Some(color.to_string()), width: Some(width) })
+    }
+
+    pub fn polygon(self, points: &[(i32, i32)], fill: &str) -> Self {
+        self.push(Command::Polygon { points: Some(to_points(points)), fill: Some(fill.to_string()), stroke: None, stroke_width: None })
+    }
+
+    pub fn image_base64(self, x: i32, y: i32, w: u32, h: u32, data: impl Into<String>) -> Self {
+        self.push(Command::Image {
+            x: Some(x),
+            y: Some(y),
+            w: Some(w),
+            h: Some(h),
+            src_type: Some("base64".to_string()),
+            src: Some(data.into()),
+            role: None,
+            label: None,
+            enter: None,
+            exit: None,
+        })
+    }
+
+    /// 给最近一条带描边的图形加描边
+    pub fn stroke(mut self, color: &str, width: u32) -> Self {
+        if let Some(
+            Command::Rect { stroke, stroke_width, .. }
+            | Command::RoundRect { stroke, stroke_width, .. }
+            | Command::Circle { stroke, stroke_width, .. }
+            | Command::Ellipse { stroke, stroke_width, .. }
+            | Command::Polygon { stroke, stroke_width, .. }
+            | Command::Path { stroke, stroke_width, .. },
+        ) = self.envelope.commands.last_mut()
+        {
+            *stroke = Some(color.to_string());
+            *stroke_width = Some(width);
+        }
+        self
+    }
+
+    /// 把最近一条 rect 设为可点击，并给出点击事件的 target_id
+    pub fn clickable(mut self, target_id: &str) -> Self {
+        if let Some(Command::Rect { id, clickable, .. }) = self.envelope.commands.last_mut() {
+            *id = Some(target_id.to_string());
+            *clickable = true;
+        }
+        self
+    }
+
+    /// 最近一条 rect 被点击时在本地执行的动作（如 `exit`）
+    #[allow(dead_code)]
+    pub fn action(mut self, name: &str) -> Self {
+        if let Some(Command::Rect { action, .. }) = self.envelope.commands.last_mut() {
+            *action = Some(name.to_string());
+        }
+        self
+    }
+
+    /// 最近一条 rect 的访问键
+    pub fn access_key(mut self, key: char) -> Self {
+        if let Some(Command::Rect { access_key, .. }) = self.envelope.commands.last_mut() {
+            *access_key = Some(key.to_string());
+        }
+        self
+    }
+
+    /// 最近一条 rect / round_rect / text / image 的语义角色
+    pub fn role(mut self, name: &str) -> Self {
+        if let Some(
+            Command::Rect { role, .. } | Command::RoundRect { role, .. } | Command::Text { role, .. } | Command::Image { role, .. },
+        ) = self.envelope.commands.last_mut()
+        {
+            *role = Some(name.to_string());
+        }
+        self
+    }
+
+    /// 最近一条 rect / round_rect / text / image 的可访问名称
+    pub fn label(mut self, text: &str) -> Self {
+        if let Some(
+            Command::Rect { label, .. } | Command::RoundRect { label, .. } | Command::Text { label, .. } | Command::Image { label, .. },
+        ) = self.envelope.commands.last_mut()
+        {
+            *label = Some(text.to_string());
+        }
+        self
+    }
+
+    /// 最近一条文本的背景色
+    pub fn bg(mut self, color: &str) -> Self {
+        if let Some(Command::Text { bg, .. }) = self.envelope.commands.last_mut() {
+            *bg = Some(color.to_string());
+        }
+        self
+    }
+
+    /// 最近一条文本的文字框与放不下时的处理（`shrink` / `ellipsis` / `clip`）
+    #[allow(dead_code)]
+    pub fn fit(mut self, w: u32, h: u32, mode: &str) -> Self {
+        if let Some(Command::Text { w: tw, h: th, fit, .. }) = self.envelope.commands.last_mut() {
+            (*tw, *th, *fit) = (Some(w), Some(h), Some(mode.to_string()));
+        }
+        self
+    }
+
+    pub fn build(self) -> RenderEnvelope {
+        self.envelope
+    }
+
+    /// 构造并按运行时规则校验，内置画面写错时尽早暴露
+    #[allow(dead_code)]
+    pub fn build_validated(self) -> Result<RenderEnvelope, ValidationErrors> {
+        validator::validate_render(&self.envelope)?;
+        Ok(self.envelope)
+    }
+}
+
+/// 在已有信封上继续追加命令（覆盖层）
+impl From<RenderEnvelope> for Render {
+    fn from(envelope: RenderEnvelope) -> Self {
+        Self { envelope }
+    }
+}
+
+fn to_points(points: &[(i32, i32)]) -> Vec<Point> {
+    points.iter().map(|&(x, y)| Point { x, y }).collect()
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use serde_json::Value;
+
+use crate::dsl::model::{Command, RenderEnvelope, WindowSpec};
+use crate::dsl::validator;
+
+/// 估算文本外接框用的字宽（按全角字符取上限）与行高，与渲染器的 24px 字体对应
+const TEXT_CHAR_WIDTH: i32 = 24;
+const TEXT_LINE_HEIGHT: i32 = 36;
+/// 外接框四周额外留出的像素，覆盖描边、抗锯齿的溢出
+const DIRTY_PADDING: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// 一条命令的变化。`key` 是命令的 id；没有 id 的命令按 `类型#序号`（同类无 id 命令中的出现次序）配对
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub key: String,
+    pub before: Option<Command>,
+    pub after: Option<Command>,
+}
+
+/// 两个相邻 render 信封之间的差异
+#[derive(Debug, Clone)]
+pub struct RenderDiff {
+    pub window_changed: bool,
+    /// 两边都有的命令相对顺序变了（叠放次序变化）
+    pub reordered: bool,
+    pub changes: Vec<Change>,
+}
+
+/// 按 id 计算新增、删除与修改的命令
+pub fn diff(old: &RenderEnvelope, new: &RenderEnvelope) -> RenderDiff {
+    let before = keyed(&old.commands);
+    let after = keyed(&new.commands);
+    let before_map: HashMap<&str, &Command> = before.iter().map(|(k, c)| (k.as_str(), *c)).collect();
+    let after_map: HashMap<&str, &Command> = after.iter().map(|(k, c)| (k.as_str(), *c)).collect();
+
+    let mut changes = Vec::new();
+    for (key, command) in &before {
+        match after_map.get(key.as_str()) {
+            None => changes.push(Change { kind: ChangeKind::Removed, key: key.clone(), before: Some((*command).clone()), after: None }),
+            Some(new_command) if *new_command != *command => changes.push(Change {
+                kind: ChangeKind::Changed,
+                key: key.clone(),
+                before: Some((*command).clone()),
+                after: Some((*new_command).clone()),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (key, command) in &after {
+        if !before_map.contains_key(key.as_str()) {
+            changes.push(Change { kind: ChangeKind::Added, key: key.clone(), before: None, after: Some((*command).clone()) });
+        }
+    }
+
+    let common_before: Vec<&str> = before.iter().map(|(k, _)| k.as_str()).filter(|k| after_map.contains_key(k)).collect();
+    let common_after: Vec<&str> = after.iter().map(|(k, _)| k.as_str()).filter(|k| before_map.contains_key(k)).collect();
+
+    RenderDiff {
+        window_changed: old.window != new.window,
+        reordered: common_before != common_after,
+        changes,
+    }
+}
+
+impl RenderDiff {
+    pub fn is_empty(&self) -> bool {
+        !self.window_changed && !self.reordered && self.changes.is_empty()
+    }
+
+    /// 需要重新提交到窗口的区域 (x, y, w, h)，已裁剪到窗口内；没有变化时为 None。
+    /// 窗口尺寸、清屏色、叠放次序变化或某条命令无法估算范围时返回整个窗口
+    pub fn dirty_rect(&self, window: &WindowSpec) -> Option<(i32, i32, u32, u32)> {
+        if self.is_empty() {
+            return None;
+        }
+        let full = Some((0, 0, window.width, window.height));
+        if self.window_changed || self.reordered {
+            return full;
+        }
+        let mut union: Option<(i32, i32, i32, i32)> = None;
+        for command in self.changes.iter().flat_map(|c| c.before.iter().chain(c.after.iter())) {
+            let Some((l, t, r, b)) = bounds(command) else {
+                return full;
+            };
+            union = Some(match union {
+                None => (l, t, r, b),
+                Some((ul, ut, ur, ub)) => (ul.min(l), ut.min(t), ur.max(r), ub.max(b)),
+            });
+        }
+        let (l, t, r, b) = union?;
+        let (l, t) = (l.max(0), t.max(0));
+        let (r, b) = (r.min(window.width as i32), b.min(window.height as i32));
+        if r <= l || b <= t {
+            // 变化全部在窗口外
+            return None;
+        }
+        Some((l, t, (r - l) as u32, (b - t) as u32))
+    }
+
+    /// 给评估模型的简短变化说明，每条变化一行
+    pub fn summary(&self) -> String {
+        if self.is_empty() {
+            return "none".to_string();
+        }
+        let mut lines = Vec::new();
+        if self.window_changed {
+            lines.push("- window size or title changed".to_string());
+        }
+        if self.reordered {
+            lines.push("- drawing order of existing commands changed".to_string());
+        }
+        for change in &self.changes {
+            let line = match (change.kind, &change.before, &change.after) {
+                (ChangeKind::Added, _, Some(after)) => format!("- added {}", describe(&change.key, after)),
+                (ChangeKind::Removed, Some(before), _) => format!("- removed {}", describe(&change.key, before)),
+                (ChangeKind::Changed, Some(before), Some(after)) => {
+                    format!("- changed {}: {}", describe(&change.key, after), changed_fields(before, after).join(", "))
+                }
+                _ => continue,
+            };
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+}
+
+fn keyed(commands: &[Command]) -> Vec<(String, &Command)> {
+    let mut seen: HashMap<&'static str, usize> = HashMap::new();
+    commands
+        .iter()
+        .map(|command| match command {
+            Command::Rect { id: Some(id), .. } if !id.is_empty() => (id.clone(), command),
+            _ => {
+                let name = validator::command_name(command);
+                let n = seen.entry(name).or_insert(0);
+                *n += 1;
+                (format!("{}#{}", name, *n - 1), command)
+            }
+        })
+        .collect()
+}
+
+/// 命令的像素范围：图形用校验器的外接矩形，文本按字数估算；加上描边宽度与留白
+pub fn bounds(command: &Command) -> Option<(i32, i32, i32, i32)> {
+    let (l, t, r, b) = match command {
+        Command::Text { x, y, text, w, h, fit, orientation, .. } => {
+            let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0) as i32;
+            let rows = text.lines().count().max(1) as i32;
+            let (mut right, mut bottom) = (x + columns * TEXT_CHAR_WIDTH, y + rows * TEXT_LINE_HEIGHT);
+            // 竖排时每行是一列，每字按一个全角字高估算
+            if validator::is_vertical(orientation) {
+                (right, bottom) = (x + rows * TEXT_LINE_HEIGHT, y + columns * TEXT_CHAR_WIDTH);
+            }
+            // 带 fit 的文字不会超出给定的文字框
+            if fit.is_some() {
+                right = w.map_or(right, |w| x + w as i32);
+                bottom = h.map_or(bottom, |h| y + h as i32);
+            }
+            (*x, *y, right, bottom)
+        }
+        _ => validator::extent(command)?,
+    };
+    let stroke = match command {
+        Command::Rect { stroke_width, .. }
+        | Command::RoundRect { stroke_width, .. }
+        | Command::Circle { stroke_width, .. }
+        | Command::Ellipse { stroke_width, .. }
+        | Command::Polygon { stroke_width, .. }
+        | Command::Path { stroke_width, .. } => stroke_width.unwrap_or(1),
+        Command::Line { width, .. } | Command::Arc { width, .. } | Command::Polyline { width, .. } => width.unwrap_or(1),
+        _ => 0,
+    } as i32;
+    let pad = stroke + DIRTY_PADDING;
+    Some((l - pad, t - pad, r + pad, b + pad))
+}
+
+fn describe(key: &str, command: &Command) -> String {
+    let name = validator::command_name(command);
+    match command {
+        Command::Text { text, .. } => {
+            let snippet: String = text.chars().take(32).collect();
+            format!("{} \"{}\"", key, snippet.replace('\n', " "))
+        }
+        _ if key.starts_with(name) => key.to_string(),
+        _ => format!("{} `{}`", name, key),
+    }
+}
+
+/// 两条同类命令之间取值不同的字段名
+fn changed_fields(before: &Command, after: &Command) -> Vec<String> {
+    let (Ok(Value::Object(before)), Ok(Value::Object(after))) = (serde_json::to_value(before), serde_json::to_value(after)) else {
+        return Vec::new();
+    };
+    let mut fields: Vec<String> = after
+        .iter()
+        .filter(|(key, value)| before.get(*key) != Some(*value))
+        .map(|(key, _)| key.clone())
+        .chain(before.keys().filter(|key| !after.contains_key(*key)).cloned())
+        .collect();
+    fields.sort();
+    fields
+}
@@ -0,0 +1,288 @@
+//! 按用户语言区域格式化数值：text 命令给出原始的 `value` 和 `format`（如 `currency:EUR`），
+//! 解析 render 时在本地换算成显示文字，模型不必自己处理千位分隔符、货币符号和日期顺序。
+
+use serde_json::Value;
+
+use crate::dsl::model::{Command, RenderEnvelope};
+use crate::sysinfo::{self, Locale};
+
+/// `format` 可用的种类；`number` 与 `percent` 可加 `:小数位数`，`currency` 必须加 `:ISO 货币代码`
+pub const FORMATS: &[&str] = &["number", "percent", "currency", "date", "time", "datetime"];
+
+/// `number:N` / `percent:N` 允许的最多小数位
+const MAX_DECIMALS: usize = 6;
+/// 不带小数位数的 `number` 最多保留的小数位（末尾的 0 去掉）
+const DEFAULT_NUMBER_DECIMALS: usize = 2;
+/// 不换行空格：法语、俄语等用作千位分隔符，也隔开数字与后置的货币符号、百分号
+const NBSP: &str = "\u{a0}";
+
+/// 一种语言书写数字与日期的习惯
+struct NumberStyle {
+    group: &'static str,
+    decimal: char,
+    /// 货币符号写在数字之后（`1.234,50 €`）
+    currency_after: bool,
+    /// 百分号前加空格（`25 %`）
+    percent_space: bool,
+    date_separator: char,
+}
+
+impl NumberStyle {
+    fn for_locale(locale: &Locale) -> Self {
+        let style = |group, decimal, currency_after, percent_space, date_separator| NumberStyle {
+            group,
+            decimal,
+            currency_after,
+            percent_space,
+            date_separator,
+        };
+        if locale.region == "CH" {
+            return style("'", '.', false, false, '.');
+        }
+        match locale.language.as_str() {
+            "de" | "da" => style(".", ',', true, true, '.'),
+            "tr" => style(".", ',', false, false, '.'),
+            "es" | "it" | "pt" | "id" | "el" => style(".", ',', true, false, '/'),
+            "nl" => style(".", ',', false, false, '-'),
+            "fr" | "sv" | "nb" | "no" | "fi" | "cs" | "sk" => style(NBSP, ',', true, true, '/'),
+            "ru" | "uk" | "pl" | "bg" | "hu" => style(NBSP, ',', true, true, '.'),
+            _ => style(",", '.', false, false, '/'),
+        }
+    }
+}
+
+/// 把信封中所有带 `format` 的 text 换算成显示文字；格式或值不合法的保持原样，由校验报告
+pub fn apply(render: &mut RenderEnvelope) {
+    for command in &mut render.commands {
+        if let Command::Text { text, value: Some(value), format: Some(format), .. } = command {
+            if let Ok(formatted) = format_value(value, format) {
+                *text = formatted;
+            }
+        }
+    }
+}
+
+/// 按用户的语言区域格式化一个值
+pub fn format_value(value: &Value, format: &str) -> Result<String, String> {
+    format_with(value, format, &sysinfo::get().locale)
+}
+
+fn format_with(value: &Value, format: &str, locale: &Locale) -> Result<String, String> {
+    let style = NumberStyle::for_locale(locale);
+    let (kind, arg) = match format.split_once(':') {
+        Some((kind, arg)) => (kind, Some(arg)),
+        None => (format, None),
+    };
+    match kind {
+        "number" | "percent" => {
+            let number = value.as_f64().ok_or_else(|| format!("format {} needs a numeric value", kind))?;
+            let decimals = match arg {
+                Some(arg) => arg
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n <= MAX_DECIMALS)
+                    .ok_or_else(|| format!("{}:<decimals> needs 0-{} decimals, got {}", kind, MAX_DECIMALS, arg))?,
+                None if kind == "number" => DEFAULT_NUMBER_DECIMALS,
+                None => 0,
+            };
+            if kind == "percent" {
+                let space = if style.percent_space { NBSP } else { "" };
+                Ok(format!("{}{}%", group_digits(number * 100.0, decimals, &style), space))
+            } else {
+                let mut text = group_digits(number, decimals, &style);
+                // 不带小数位数时按需显示小数：1234.5 -> 1,234.5，1234 -> 1,234
+                if arg.is_none() && text.contains(style.decimal) {
+                    text = text.trim_end_matches('0').trim_end_matches(style.decimal).to_string();
+                }
+                Ok(text)
+            }
+        }
+        "currency" => {
+            let code = arg
+                .filter(|code| code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()))
+                .ok_or("currency needs a 3-letter ISO code, e.g. currency:EUR")?
+                .to_ascii_uppercase();
+            let number = value.as_f64().ok_or("format currency needs a numeric value")?;
+            let decimals = if matches!(code.as_str(), "JPY" | "KRW" | "VND" | "CLP" | "ISK") { 0 } else { 2 };
+            let amount = group_digits(number.abs(), decimals, &style);
+            let sign = if number < 0.0 && amount.chars().any(|c| c.is_ascii_digit() && c != '0') { "-" } else { "" };
+            let symbol = currency_symbol(&code, locale);
+            if style.currency_after {
+                Ok(format!("{}{}{}{}", sign, amount, NBSP, symbol))
+            } else if symbol.chars().all(|c| c.is_ascii_alphabetic()) {
+                // 没有符号、只能写代码时和数字隔开：CHF 1'234.50
+                Ok(format!("{}{}{}{}", sign, symbol, NBSP, amount))
+            } else {
+                Ok(format!("{}{}{}", sign, symbol, amount))
+            }
+        }
+        "date" | "time" | "datetime" => {
+            if let Some(arg) = arg {
+                return Err(format!("format {} takes no argument, got {}", kind, arg));
+            }
+            let moment = parse_moment(value)?;
+            let date = || {
+                let (y, m, d) = (moment.year, moment.month, moment.day);
+                let sep = style.date_separator;
+                match locale.date_order() {
+                    "Y-M-D" => {
+                        // 年份在前时除中文、日文习惯用斜线外，一般写成 ISO 的 2026-10-16
+                        let sep = match (sep, locale.language.as_str()) {
+                            ('.', _) => '.',
+                            (_, "zh" | "ja") => '/',
+                            _ => '-',
+                        };
+                        format!("{:04}{}{:02}{}{:02}", y, sep, m, sep, d)
+                    }
+                    "M/D/Y" => format!("{}/{}/{:04}", m, d, y),
+                    _ => format!("{:02}{}{:02}{}{:04}", d, sep, m, sep, y),
+                }
+            };
+            let time = || {
+                if locale.twelve_hour() {
+                    let hour = match moment.hour % 12 {
+                        0 => 12,
+                        h => h,
+                    };
+                    format!("{}:{:02} {}", hour, moment.minute, if moment.hour < 12 { "AM" } else { "PM" })
+                } else {
+                    format!("{:02}:{:02}", moment.hour, moment.minute)
+                }
+            };
+            Ok(match kind {
+                "date" => date(),
+                "time" => time(),
+                _ => format!("{} {}", date(), time()),
+            })
+        }
+        _ => Err(format!("unknown text.format: {} (expected one of {})", format, FORMATS.join(", "))),
+    }
+}
+
+/// 四舍五入到 `decimals` 位小数并插入千位分隔符
+fn group_digits(number: f64, decimals: usize, style: &NumberStyle) -> String {
+    let fixed = format!("{:.*}", decimals, number.abs());
+    let (int_part, frac_part) = fixed.split_once('.').unwrap_or((&fixed, ""));
+    let mut grouped = String::new();
+    for (i, digit) in int_part.chars().enumerate() {
+        if i > 0 && (int_part.len() - i) % 3 == 0 {
+            grouped.push_str(style.group);
+        }
+        grouped.push(digit);
+    }
+    if !frac_part.is_empty() {
+        grouped.push(style.decimal);
+        grouped.push_str(frac_part);
+    }
+    // 四舍五入成 0 的负数不带负号
+    if number < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        grouped.insert(0, '-');
+    }
+    grouped
+}
+
+fn currency_symbol(code: &str, locale: &Locale) -> String {
+    let symbol = match code {
+        "EUR" => "€",
+        "GBP" => "£",
+        "INR" => "₹",
+        "KRW" => "₩",
+        "RUB" => "₽",
+        "TRY" => "₺",
+        // 美元、人民币、日元的符号在本国以外有歧义，只在对应地区写成裸符号
+        "USD" if locale.region == "US" || locale.region.is_empty() => "$",
+        "USD" => "US$",
+        "CNY" if locale.region == "CN" => "¥",
+        "CNY" => "CN¥",
+        "JPY" if locale.region == "JP" => "¥",
+        "JPY" => "JP¥",
+        other => return other.to_string(),
+    };
+    symbol.to_string()
+}
+
+/// 日期时间的各部分（用户所在时区的墙上时间）
+struct Moment {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+}
+
+/// 数字是 Unix 时间戳（秒），换算到本地时区；字符串是 ISO 8601 的 `YYYY-MM-DD[THH:MM[:SS]]`，
+/// 原样当作本地时间，以 `Z` 或 `±HH:MM` 结尾时先换算到本地时区
+fn parse_moment(value: &Value) -> Result<Moment, String> {
+    if let Some(secs) = value.as_f64() {
+        return Ok(local_moment(secs.floor() as i64));
+    }
+    let raw = value.as_str().ok_or("date/time formats need a Unix timestamp or an ISO 8601 string")?;
+    let invalid = || format!("invalid ISO 8601 date: {}", raw);
+    let (date, rest) = raw.split_at(raw.len().min(10));
+    let mut fields = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (Some(Some(year)), Some(Some(month)), Some(Some(day))) = (fields.next(), fields.next(), fields.next()) else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    let Some(clock) = rest.strip_prefix(['T', ' ']) else {
+        return if rest.is_empty() { Ok(Moment { year, month, day, hour: 0, minute: 0 }) } else { Err(invalid()) };
+    };
+    // 时区后缀：Z、+08:00、-0500
+    let zone_at = clock.find(['Z', '+', '-']).unwrap_or(clock.len());
+    let (clock, zone) = clock.split_at(zone_at);
+    let mut parts = clock.split(':').map(|part| part.split('.').next().unwrap_or("").parse::<i64>().ok());
+    let (Some(Some(hour)), Some(Some(minute))) = (parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+    let second = parts.next().flatten().unwrap_or(0);
+    if hour > 23 || minute > 59 {
+        return Err(invalid());
+    }
+    let zone_offset = match zone {
+        "" => return Ok(Moment { year, month, day, hour, minute }),
+        "Z" => 0,
+        _ => {
+            let digits: String = zone[1..].chars().filter(char::is_ascii_digit).collect();
+            let (h, m) = (digits.get(..2).and_then(|h| h.parse::<i64>().ok()), digits.get(2..4).and_then(|m| m.parse::<i64>().ok()).or(Some(0)));
+            let (Some(h), Some(m)) = (h, m) else {
+                return Err(invalid());
+            };
+            let offset = h * 3600 + m * 60;
+            if zone.starts_with('-') { -offset } else { offset }
+        }
+    };
+    let utc = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second - zone_offset;
+    Ok(local_moment(utc))
+}
+
+/// Unix 时间戳在用户时区的墙上时间（按该时刻的偏移，跨夏令时也正确）
+fn local_moment(timestamp: i64) -> Moment {
+    from_timestamp(timestamp + sysinfo::utc_offset_at(timestamp))
+}
+
+/// 从 1970-01-01 起的秒数拆成日期与时刻（Howard Hinnant 的 civil_from_days）
+fn from_timestamp(secs: i64) -> Moment {
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    Moment { year, month, day, hour: rem / 3600, minute: rem / 60 % 60 }
+}
+
+/// civil_from_days 的逆运算：日期到 1970-01-01 起的天数
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
@@ -1,3 +1,10 @@
+pub mod builder;
+pub mod diff;
+pub mod formatting;
 pub mod model;
 pub mod parser;
-pub mod validator;
\ No newline at end of file
+pub mod schema;
+pub mod semantics;
+pub mod theme;
+pub mod validator;
+pub mod version;
\ No newline at end of file
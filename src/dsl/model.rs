@@ -1,20 +1,82 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Deserialize)]
+use crate::dsl::schema;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RenderEnvelope {
+    #[schemars(schema_with = "schema::agd_version")]
     pub version: String,
     #[serde(rename = "type")]
+    #[schemars(schema_with = "schema::render_type")]
     pub render_type: String,
     pub seq: u64,
     pub window: WindowSpec,
     pub commands: Vec<Command>,
+    /// 预留的扩展 DSL 声明，为 null 或 `{"version": "X-DSL/0.2"}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xdsl: Option<XDslSpec>,
+    /// 周期定时器：界面显示期间由编排器按间隔发出 `timer` 事件（时钟、仪表盘、轮询）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub timers: Vec<Timer>,
+    /// 键盘快捷键：按下组合键等同于点击对应的可点击 rect
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shortcuts: Vec<Shortcut>,
+    /// 本地轮询的数据来源：取到的值按 `bind` 填进文本与进度条，界面显示期间不必为每次刷新请求模型
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub data_sources: Vec<DataSource>,
+    /// 画面名称：导航栈与面包屑中显示的名字，缺省时用窗口标题
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screen: Option<String>,
+    /// 导航栈中此画面之下的画面名（最早的在前），由编排器在本地填写
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(skip)]
+    pub navigation: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Timer {
+    pub id: String,
+    pub every_ms: u64,
+}
+
+/// 一个数据来源，`url`、`file`、`run` 三者取其一：URL 只限 `data.hosts` 中的主机，文件在 `data.dir` 内，
+/// 命令取 `run.commands` 白名单中的名称。内容能解析为 JSON 时按 JSON 使用，否则是去掉首尾空白的文本
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DataSource {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run: Option<String>,
+    pub every_ms: u64,
+}
+
+/// 一条快捷键声明，如 `{"keys": "Ctrl+S", "target": "save"}`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Shortcut {
+    pub keys: String,
+    pub target: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct XDslSpec {
+    #[schemars(schema_with = "schema::xdsl_version")]
+    pub version: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct WindowSpec {
     pub width: u32,
     pub height: u32,
     pub title: String,
+    /// 窗口背景色：首帧之前以及窗口被拉大时露出的区域用它填充；未设置时取第一条 clear 的颜色
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -23,55 +85,188 @@ pub struct EventEnvelope {
     #[serde(rename = "type")]
     pub event_type: String,
     pub seq: u64,
-    pub event: ClickEvent,
+    pub event: EventBody,
 }
 
+/// 发给模型的事件：`click` 带目标与坐标，`submit` 另带表单内容，`gesture` 带起点、方向与速度，
+/// `timer` 只带定时器 id，`idle` / `resume` 不带目标
 #[derive(Debug, Clone, Serialize)]
-pub struct ClickEvent {
+pub struct EventBody {
     pub kind: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub target_id: String,
-    pub x: i32,
-    pub y: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<i32>,
+    /// `submit` 事件携带的表单内容
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub form: Option<FormSubmission>,
+    /// `gesture` 事件的滑动方向与速度
+    #[serde(flatten)]
+    pub gesture: Option<GestureInfo>,
+    /// `run` 事件携带的命令执行结果
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<RunOutput>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// 滑动方向（left/right/up/down）、松开时的速度（像素/秒），以及是否为快速甩动
+#[derive(Debug, Clone, Serialize)]
+pub struct GestureInfo {
+    pub direction: String,
+    pub velocity: u32,
+    pub flick: bool,
+}
+
+/// 表单区域 id 与区域内各控件的本地状态（控件 id -> 值）
+#[derive(Debug, Clone, Serialize)]
+pub struct FormSubmission {
+    pub id: String,
+    pub fields: BTreeMap<String, serde_json::Value>,
+}
+
+/// `run` 动作执行白名单命令的结果：退出码（被信号结束时为 None）、截断后的标准输出与标准错误
+#[derive(Debug, Clone, Serialize)]
+pub struct RunOutput {
+    pub name: String,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
+    /// 输出超过 `run.max_output_bytes`，只保留了开头部分
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PathSegment {
+    #[schemars(schema_with = "schema::path_cmd")]
     pub cmd: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub x: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub y: Option<i32>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "cmd")]
 pub enum Command {
     #[serde(rename = "clear")]
     Clear { color: String },
     #[serde(rename = "rect")]
     Rect {
+        #[serde(skip_serializing_if = "Option::is_none")]
         id: Option<String>,
         x: i32,
         y: i32,
         w: u32,
         h: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
         fill: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke_width: Option<u32>,
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "is_false")]
         clickable: bool,
+        /// 点击后在本地执行的动作：`exit` 退出；`submit` 收集 `form` 区域内控件的状态，作为一个 `submit` 事件发给模型；
+        /// `link` 经用户确认后用 `xdg-open` 打开 `href`；`run` 执行白名单中的本地命令，结果作为 `run` 事件发给模型
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        action: Option<String>,
+        /// `submit` 按钮所属的表单区域：另一个带该 id 的 rect，落在其中的控件随事件一起提交
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        form: Option<String>,
+        /// `link` 打开的地址（http、https 或 mailto）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        href: Option<String>,
+        /// `run` 执行的命令：配置 `run.commands` 白名单中的名称
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        run: Option<String>,
+        /// 访问键：单个字母或数字，在窗口中按下等同于点击；rect 内标签里的对应字符带下划线
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        access_key: Option<String>,
+        /// 按下反馈：`outline`（默认）、`invert`、`darken` 或 `ripple`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        press_style: Option<String>,
+        /// `outline` / `ripple` 的颜色，默认深灰
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        press_color: Option<String>,
+        /// 语义角色（button、heading、list、status、image），供无障碍层与评估模型使用，不影响绘制
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        role: Option<String>,
+        /// 读屏用的可访问名称；省略时按 rect 内的文本推断
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+        /// 出现 / 消失动画（`fade`、`slide-from-left` 等，见 `validator::ANIMATIONS`）：
+        /// 与上一画面相比新增或删除这条命令时由窗口在本地播放
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        enter: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit: Option<String>,
+        /// 绑定的数据来源：`来源 id` 或 `来源 id/JSON 指针`（如 `cpu/load/0`），取到的数值把 rect 画成进度条，
+        /// 填充宽度为 `w` 乘以值占 `bind_max` 的比例，描边仍按整个 `w` 绘制
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bind: Option<String>,
+        /// 进度条满格对应的值，默认 100
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bind_max: Option<f64>,
+        /// 本地填入的进度（0 到 1），不出现在 schema 里
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(skip)]
+        progress: Option<f32>,
+        /// 勾选框 / 单选按钮的状态：设置后点击在本地切换（连同 rect 内的 ☐/☑ 等字形），随所在表单的 submit 事件提交
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        checked: Option<bool>,
     },
     #[serde(rename = "text")]
     Text {
         x: i32,
         y: i32,
+        /// 显示的文字；带 `format` 时由解析端按用户语言区域格式化 `value` 后填入
+        #[serde(default)]
         text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
         color: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         bg: Option<String>,
+        /// 交给本地格式化的原始值：数字，或日期时间（Unix 时间戳、ISO 8601 字符串）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(schema_with = "schema::format_value")]
+        value: Option<serde_json::Value>,
+        /// `value` 的格式，如 `number:2`、`percent`、`currency:EUR`、`date`（见 `formatting::FORMATS`）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        format: Option<String>,
+        /// 文字框的宽高，配合 `fit` 使用：超出时按 `fit` 缩小字号、截断加省略号或裁掉
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        w: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        h: Option<u32>,
+        /// 放不下时的处理：`shrink`、`ellipsis` 或 `clip`（见 `validator::TEXT_FITS`）；省略时照常溢出
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fit: Option<String>,
+        /// `horizontal`（默认）或 `vertical`：竖排时原文每行成一列，自上而下书写，列从右向左排
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        orientation: Option<String>,
+        /// 竖排时字形的旋转角度：省略为直立（CJK 竖排），`90` 顺时针转、自上而下读，
+        /// `-90` 逆时针转、自下而上读（纵轴标签）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rotate: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        role: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        enter: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit: Option<String>,
+        /// 绑定的数据来源（写法同 rect 的 `bind`）：带 `format` 时取到的值填进 `value` 再格式化，否则直接作为文字
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bind: Option<String>,
     },
     #[serde(rename = "line")]
     Line {
@@ -79,79 +274,287 @@ pub enum Command {
         y1: i32,
         x2: i32,
         y2: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
         color: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         width: Option<u32>,
     },
     #[serde(rename = "circle")]
     Circle {
+        #[serde(skip_serializing_if = "Option::is_none")]
         cx: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         cy: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         r: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         fill: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke_width: Option<u32>,
     },
     #[serde(rename = "ellipse")]
     Ellipse {
+        #[serde(skip_serializing_if = "Option::is_none")]
         cx: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         cy: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         rx: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         ry: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         fill: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke_width: Option<u32>,
     },
     #[serde(rename = "round_rect")]
     RoundRect {
+        #[serde(skip_serializing_if = "Option::is_none")]
         x: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         y: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         w: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         h: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         r: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         fill: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke_width: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        role: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        enter: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit: Option<String>,
     },
     #[serde(rename = "arc")]
     Arc {
+        #[serde(skip_serializing_if = "Option::is_none")]
         cx: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         cy: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         r: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         start_angle: Option<f32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         end_angle: Option<f32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         color: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         width: Option<u32>,
+        /// 填充色：`pie` 为 true 时填充从圆心出发的扇形，否则填充外缘向内 `width` 厚的环形段（甜甜圈图）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fill: Option<String>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        pie: bool,
     },
     #[serde(rename = "polyline")]
     Polyline {
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         points: Option<Vec<Point>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         color: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         width: Option<u32>,
     },
     #[serde(rename = "polygon")]
     Polygon {
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         points: Option<Vec<Point>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         fill: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke_width: Option<u32>,
     },
     #[serde(rename = "image")]
     Image {
+        #[serde(skip_serializing_if = "Option::is_none")]
         x: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         y: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         w: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         h: Option<u32>,
+        #[schemars(schema_with = "schema::image_src_type")]
+        #[serde(skip_serializing_if = "Option::is_none")]
         src_type: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         src: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        role: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        enter: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit: Option<String>,
     },
     #[serde(rename = "path")]
     Path {
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         segments: Option<Vec<PathSegment>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         fill: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         stroke_width: Option<u32>,
     },
+    /// 本地伪终端：运行 `run.commands` 白名单中的命令，把输出实时画在这块区域里；点击后键盘输入交给它
+    #[serde(rename = "terminal")]
+    Terminal {
+        id: String,
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+        /// 运行的命令：配置 `run.commands` 白名单中的名称
+        run: String,
+        /// 字号，默认 14；列数与行数按字号从区域大小折算
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        font_size: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bg: Option<String>,
+        /// 当前屏幕内容，每行一个字符串；由编排器按终端的输出在本地填入，模型不必给出
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        #[schemars(skip)]
+        lines: Vec<String>,
+        /// 有键盘焦点时的光标位置（行, 列），同样在本地填入
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(skip)]
+        cursor: Option<(u16, u16)>,
+    },
+    /// 角落里的提示条：由窗口在本地按 `duration_ms` 自动移除，同一角落的多条依次堆叠，点击可提前关闭
+    #[serde(rename = "toast")]
+    Toast {
+        /// 跨画面识别同一条提示；省略时按文字识别
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        text: String,
+        /// `info`（默认）、`success`、`warning` 或 `error`（见 `toast::KINDS`），决定左侧色条的颜色
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<String>,
+        /// `top-right`（默认）、`top-left`、`bottom-right` 或 `bottom-left`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<String>,
+        /// 显示多久后自动移除，默认 4000
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration_ms: Option<u64>,
+        /// 按角落与堆叠次序在本地排好的位置与大小 (x, y, w, h)，模型不必给出
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(skip)]
+        frame: Option<(i32, i32, u32, u32)>,
+    },
+    /// 模态对话框：压暗其下的画面，点击与按键只交给对话框。确认、取消按钮的 id 固定为 `<id>.confirm` 与
+    /// `<id>.cancel`，按下时作为普通 click 事件发给模型；Enter 触发有焦点的按钮，Esc 取消，Tab 在两个按钮间切换
+    #[serde(rename = "dialog")]
+    Dialog {
+        id: String,
+        title: String,
+        /// 正文，可用 `\n` 分行
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+        /// 确认按钮的文字，默认 `OK`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        confirm: Option<String>,
+        /// 取消按钮的文字，默认 `Cancel`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cancel: Option<String>,
+        /// 确认按钮用 `@danger` 配色，用于删除等不可撤销的操作
+        #[serde(default, skip_serializing_if = "is_false")]
+        destructive: bool,
+        /// 在本地居中排好的位置与大小 (x, y, w, h)，模型不必给出
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(skip)]
+        frame: Option<(i32, i32, u32, u32)>,
+        /// 有键盘焦点的按钮（`cancel`，省略时为确认按钮），由窗口在本地切换
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(skip)]
+        focus: Option<String>,
+    },
+    /// 单行文字输入框：点击后获得键盘焦点，输入的文字只保存在本地，随所在表单的 submit 事件发给模型。
+    /// `mask` 为 true 时画成圆点（密码），提交时默认打码
+    #[serde(rename = "input")]
+    Input {
+        id: String,
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+        /// 没有内容时显示的提示文字
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        placeholder: Option<String>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        mask: bool,
+        /// 显示的内容（`mask` 时是等长的圆点，不含真实的值），由编排器在本地填入
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(skip)]
+        shown: Option<String>,
+        /// 有键盘焦点，同样在本地填入
+        #[serde(default, skip_serializing_if = "is_false")]
+        #[schemars(skip)]
+        focused: bool,
+    },
+    /// 滑块：点击轨道在本地把值设为该位置对应的数值，随所在表单的 submit 事件发给模型
+    #[serde(rename = "slider")]
+    Slider {
+        id: String,
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+        /// 取值范围，默认 0 到 100
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<f64>,
+        /// 取值的步长，省略时连续取值
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        step: Option<f64>,
+        /// 初始值，默认 `min`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value: Option<f64>,
+        /// 用户调整后的当前值，由编排器在本地填入
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(skip)]
+        current: Option<f64>,
+    },
+    /// 下拉选择框：点击在本地切换到下一个选项，选中的文字随所在表单的 submit 事件发给模型
+    #[serde(rename = "select")]
+    Select {
+        id: String,
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+        options: Vec<String>,
+        /// 初始选中项的下标，默认 0
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        selected: Option<usize>,
+        /// 用户切换后的选中项下标，由编排器在本地填入
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(skip)]
+        current: Option<usize>,
+    },
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
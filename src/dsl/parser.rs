@@ -1,19 +1,319 @@
+use std::collections::{BTreeSet, HashSet};
 use std::error::Error;
-use crate::dsl::model::RenderEnvelope;
+use serde_json::Value;
+use tracing::warn;
+use crate::config;
+use crate::dsl::formatting;
+use crate::dsl::model::{Command, RenderEnvelope};
+use crate::dsl::validator::{self, ValidationError};
+use crate::dsl::version;
+use crate::dialog;
+use crate::toast;
 
+/// 解析模式，见配置项 `parse_mode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    Strict,
+    Lenient,
+}
+
+impl ParseMode {
+    pub fn from_config() -> Self {
+        match config::get().parse_mode.as_str() {
+            "lenient" => ParseMode::Lenient,
+            _ => ParseMode::Strict,
+        }
+    }
+}
+
+/// 按配置的解析模式解析；宽松模式下丢弃的命令记录为警告日志
 pub fn parse_render(raw: &str) -> Result<RenderEnvelope, Box<dyn Error>> {
+    let (render, dropped) = parse_render_with(raw, ParseMode::from_config())?;
+    for reason in &dropped {
+        warn!("dropped malformed command: {}", reason);
+    }
+    Ok(render)
+}
+
+/// 解析 render 信封，同时返回宽松模式下被丢弃的命令及原因（严格模式总是为空）
+pub fn parse_render_with(raw: &str, mode: ParseMode) -> Result<(RenderEnvelope, Vec<String>), Box<dyn Error>> {
+    if let Some(yaml) = yaml_body(raw) {
+        let value: Value = serde_yaml::from_str(yaml).map_err(|e| format!("YAML parse error: {}", e))?;
+        return from_value(value, mode, |e| snippet_error("YAML", e, yaml));
+    }
+
     let mut cleaned = raw.trim();
-    
+
     // 尝试寻找第一个 { 和最后一个 } 之间的内容，这能过滤掉前后多余的解释文本
     if let (Some(start), Some(end)) = (cleaned.find('{'), cleaned.rfind('}')) {
         cleaned = &cleaned[start..=end];
-    } else {
+    } else if !raw.contains('{') {
         return Err(format!("No JSON object found in LLM output: {}", raw).into());
     }
 
-    let render: RenderEnvelope = serde_json::from_str(cleaned).map_err(|e| {
-        let snippet = if cleaned.len() > 100 { &cleaned[..100] } else { cleaned };
-        format!("JSON parse error: {} | Content snippet: {}", e, snippet)
-    })?;
-    Ok(render)
-}
\ No newline at end of file
+    let parse_error = |e: serde_json::Error| snippet_error("JSON", e, cleaned);
+    let value = match serde_json::from_str::<Value>(cleaned) {
+        Ok(value) => value,
+        // 严格解析失败时先在本地做一次容错修复，修复后仍失败则报告原始错误
+        Err(strict_err) => serde_json::from_str::<Value>(&repair_json(raw)).map_err(|_| parse_error(strict_err))?,
+    };
+    from_value(value, mode, parse_error)
+}
+
+/// 自动识别 YAML：去掉代码围栏后不以 `{` 开头，并且以 `---` 开头或有顶层的 `version:` 键。
+/// 手写的测试信封、部分本地模型的输出用 YAML 更不容易出错
+pub fn yaml_body(raw: &str) -> Option<&str> {
+    let body = strip_code_fence(raw.trim()).trim();
+    let is_yaml = !body.starts_with('{') && (body.starts_with("---") || body.lines().any(|l| l.starts_with("version:")));
+    is_yaml.then_some(body)
+}
+
+fn snippet_error(format: &str, e: serde_json::Error, source: &str) -> Box<dyn Error> {
+    let snippet: String = source.chars().take(100).collect();
+    format!("{} parse error: {} | Content snippet: {}", format, e, snippet).into()
+}
+
+/// 解析后在本地补齐的字段：格式化后的文本，提示条与对话框的位置。
+/// 所有把外部输入变成信封的地方（模型输出、脚本返回的画面、lint）都要经过它
+pub fn prepare(render: &mut RenderEnvelope) {
+    formatting::apply(render);
+    toast::layout(render);
+    dialog::layout(render);
+}
+
+/// JSON / YAML 解析出的值：版本升级后按解析模式转成 render 信封
+fn from_value(
+    value: Value,
+    mode: ParseMode,
+    parse_error: impl Fn(serde_json::Error) -> Box<dyn Error>,
+) -> Result<(RenderEnvelope, Vec<String>), Box<dyn Error>> {
+    // 旧版本信封先升级为当前版本的结构
+    let value = version::upgrade(value)?;
+    if mode == ParseMode::Strict {
+        let mut render = serde_json::from_value(value).map_err(parse_error)?;
+        prepare(&mut render);
+        return Ok((render, Vec::new()));
+    }
+    let (mut render, mut dropped, origin) = match serde_json::from_value::<RenderEnvelope>(value.clone()) {
+        Ok(render) => {
+            let origin = (0..render.commands.len()).collect();
+            (render, Vec::new(), origin)
+        }
+        Err(e) => lenient_commands(value).ok_or_else(|| parse_error(e))?,
+    };
+    prepare(&mut render);
+    dropped.extend(drop_invalid_commands(&mut render, &origin));
+    Ok((render, dropped))
+}
+
+/// 逐条反序列化命令，跳过无法解析的；同时返回保留下来的命令在原数组中的下标。
+/// 信封其余部分本身无法解析时返回 None
+fn lenient_commands(mut value: Value) -> Option<(RenderEnvelope, Vec<String>, Vec<usize>)> {
+    let commands = value.get_mut("commands")?.as_array_mut().map(std::mem::take)?;
+    let mut render: RenderEnvelope = serde_json::from_value(value).ok()?;
+    let mut dropped = Vec::new();
+    let mut origin = Vec::new();
+    for (index, command) in commands.into_iter().enumerate() {
+        match serde_json::from_value::<Command>(command) {
+            Ok(command) => {
+                render.commands.push(command);
+                origin.push(index);
+            }
+            Err(e) => dropped.push(format!("commands[{}]: {}", index, e)),
+        }
+    }
+    Some((render, dropped, origin))
+}
+
+/// 去掉单条命令层面校验失败的命令（信封层面的问题，如缺少 clear，仍交给校验报错）；
+/// 报告里的下标换算回原始信封中的位置。删掉的命令可能正被别处引用（表单区域 rect 与指向它的提交按钮、
+/// 快捷键的目标按钮），所以反复检查直到没有新的问题，再去掉目标已被删除的快捷键
+fn drop_invalid_commands(render: &mut RenderEnvelope, origin: &[usize]) -> Vec<String> {
+    let mut origin = origin.to_vec();
+    let mut reasons = Vec::new();
+    let mut dropped_ids = HashSet::new();
+    loop {
+        // 校验错误不保证按命令下标排序（如表单区域在主循环之后才核对），用有序集合去重后再从后往前删
+        let mut dropped = BTreeSet::new();
+        for error in validator::check_render(render) {
+            if let ValidationError::Command { index, field, code, message } = error {
+                if dropped.insert(index) {
+                    reasons.push(ValidationError::Command { index: origin[index], field, code, message }.to_string());
+                }
+            }
+        }
+        if dropped.is_empty() {
+            break;
+        }
+        for index in dropped.iter().rev() {
+            if let Command::Rect { id: Some(id), .. } = render.commands.remove(*index) {
+                dropped_ids.insert(id);
+            }
+            origin.remove(*index);
+        }
+    }
+    render.shortcuts.retain(|shortcut| {
+        let dangling = dropped_ids.contains(&shortcut.target);
+        if dangling {
+            reasons.push(format!("shortcut {}: its target {} was dropped", shortcut.keys, shortcut.target));
+        }
+        !dangling
+    });
+    reasons
+}
+
+/// 修复 LLM 常见的 JSON 瑕疵：代码围栏、单引号字符串、尾随逗号、未闭合的括号/字符串
+pub fn repair_json(raw: &str) -> String {
+    let unfenced = strip_code_fence(raw.trim());
+    let body = match unfenced.find('{') {
+        Some(start) => &unfenced[start..],
+        None => unfenced,
+    };
+
+    let mut out = String::with_capacity(body.len() + 8);
+    let mut closers: Vec<char> = Vec::new();
+    // 当前所在字符串的引号字符（' 或 "），None 表示不在字符串内
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for ch in body.chars() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+                // JSON 没有 \' 转义，直接输出引号本身
+                if ch != '\'' {
+                    out.push('\\');
+                }
+                out.push(ch);
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == q {
+                quote = None;
+                out.push('"');
+            } else if ch == '"' {
+                // 单引号字符串里的双引号需要转义
+                out.push_str("\\\"");
+            } else {
+                out.push(ch);
+            }
+            continue;
+        }
+
+        match ch {
+            '"' | '\'' => {
+                quote = Some(ch);
+                out.push('"');
+            }
+            '{' => {
+                closers.push('}');
+                out.push(ch);
+            }
+            '[' => {
+                closers.push(']');
+                out.push(ch);
+            }
+            '}' | ']' => {
+                strip_trailing_comma(&mut out);
+                if closers.last() == Some(&ch) {
+                    closers.pop();
+                }
+                out.push(ch);
+                if closers.is_empty() {
+                    // 根对象已闭合，丢弃后面的解释文本
+                    return out;
+                }
+            }
+            _ => out.push(ch),
+        }
+    }
+
+    if quote.is_some() {
+        out.push('"');
+    }
+    while let Some(closer) = closers.pop() {
+        strip_trailing_comma(&mut out);
+        out.push(closer);
+    }
+    out
+}
+
+fn strip_code_fence(text: &str) -> &str {
+    let Some(start) = text.find("```") else {
+        return text;
+    };
+    let after = &text[start + 3..];
+    // 跳过 ```json 这样的语言标记
+    let after = match after.find('\n') {
+        Some(nl) if !after[..nl].contains('{') => &after[nl + 1..],
+        _ => after,
+    };
+    match after.find("```") {
+        Some(end) => &after[..end],
+        None => after,
+    }
+}
+
+fn strip_trailing_comma(out: &mut String) {
+    let trimmed_len = out.trim_end().len();
+    if out[..trimmed_len].ends_with(',') {
+        out.truncate(trimmed_len - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lenient_drops_dangling_form_and_later_invalid_command() {
+        // 悬空的 form 在主循环之后才报错，错误下标不再有序
+        let raw = r##"{"version": "AGD/0.2", "type": "render", "seq": 1,
+            "window": {"width": 400, "height": 300, "title": "t"},
+            "commands": [
+                {"cmd": "clear", "color": "#FFFFFF"},
+                {"cmd": "rect", "id": "go", "x": 10, "y": 10, "w": 80, "h": 40, "fill": "#2563EB", "clickable": true, "action": "submit", "form": "nope"},
+                {"cmd": "rect", "x": 10, "y": 60, "w": 0, "h": 40, "fill": "#2563EB"}
+            ]}"##;
+        let (render, dropped) = parse_render_with(raw, ParseMode::Lenient).unwrap();
+        assert_eq!(render.commands.len(), 1);
+        assert!(matches!(render.commands[0], Command::Clear { .. }));
+        assert_eq!(dropped.len(), 2);
+    }
+
+    #[test]
+    fn lenient_drops_references_to_dropped_form_region() {
+        // 表单区域 rect 尺寸为 0 被删掉后，指向它的提交按钮与按钮的快捷键也要一并去掉
+        let raw = r##"{"version": "AGD/0.2", "type": "render", "seq": 1,
+            "window": {"width": 400, "height": 300, "title": "t"},
+            "shortcuts": [{"keys": "Ctrl+S", "target": "go"}],
+            "commands": [
+                {"cmd": "clear", "color": "#FFFFFF"},
+                {"cmd": "rect", "id": "login", "x": 0, "y": 0, "w": 0, "h": 300, "fill": "#FFFFFF"},
+                {"cmd": "input", "id": "user", "x": 10, "y": 10, "w": 200, "h": 40},
+                {"cmd": "rect", "id": "go", "x": 10, "y": 60, "w": 80, "h": 40, "fill": "#2563EB", "clickable": true, "action": "submit", "form": "login"}
+            ]}"##;
+        let (render, dropped) = parse_render_with(raw, ParseMode::Lenient).unwrap();
+        assert_eq!(render.commands.len(), 2);
+        assert!(render.shortcuts.is_empty());
+        assert_eq!(dropped.len(), 3);
+        assert!(dropped[1].starts_with("commands[3]"));
+        validator::validate_render(&render).unwrap();
+    }
+
+    #[test]
+    fn huge_coordinates_are_rejected_without_overflow() {
+        let raw = r##"{"version": "AGD/0.2", "type": "render", "seq": 1,
+            "window": {"width": 400, "height": 300, "title": "t"},
+            "commands": [
+                {"cmd": "clear", "color": "#FFFFFF"},
+                {"cmd": "rect", "x": 2147483000, "y": 10, "w": 4294967295, "h": 40, "fill": "#2563EB"},
+                {"cmd": "text", "x": 2147483647, "y": 2147483647, "text": "far", "color": "#000000"}
+            ]}"##;
+        let render = parse_render_with(raw, ParseMode::Strict).unwrap().0;
+        let errors = validator::check_render(&render);
+        assert_eq!(errors.iter().filter(|e| e.to_string().contains("limit_exceeded")).count(), 2);
+        validator::check_bounds(&render);
+        validator::check_overflow(&render);
+        validator::check_contrast(&render);
+    }
+}
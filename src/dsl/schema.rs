@@ -0,0 +1,156 @@
+//! 从 `model.rs` 的类型推导发送给 LLM 的 JSON Schema，避免模型、校验器与 Schema 三处定义脱节。
+//!
+//! schemars 生成的是标准 Schema，这里再转换成 OpenAI 严格模式能接受的“压缩”形式：
+//! - 内联所有 `$ref`，去掉 `format` / `minimum` 等无用关键字
+//! - `Command` 的各个变体合并成一个对象：`cmd` 为枚举，其余字段取并集
+//! - 每个对象的全部字段都列入 `required`，并禁止额外字段
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::schema_for;
+use serde_json::{json, Map, Value};
+
+use crate::dsl::model::RenderEnvelope;
+use crate::dsl::version;
+
+/// 文档注释会变成 description，字段含义已在提示词中说明，这里一并去掉以压缩 Schema
+const DROPPED_KEYWORDS: &[&str] = &["$schema", "title", "description", "format", "minimum", "default"];
+
+/// render 信封的严格模式 Schema
+pub fn render_schema() -> Value {
+    let root = serde_json::to_value(schema_for!(RenderEnvelope)).unwrap_or_default();
+    let definitions = root.get("definitions").cloned().unwrap_or_default();
+    let mut schema = root;
+    if let Some(obj) = schema.as_object_mut() {
+        obj.remove("definitions");
+    }
+    strictify(inline_refs(schema, &definitions))
+}
+
+fn inline_refs(value: Value, definitions: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            if let Some(name) = map.get("$ref").and_then(|r| r.as_str()).and_then(|r| r.strip_prefix("#/definitions/")) {
+                return inline_refs(definitions.get(name).cloned().unwrap_or_default(), definitions);
+            }
+            Value::Object(
+                map.into_iter()
+                    .filter(|(key, _)| !DROPPED_KEYWORDS.contains(&key.as_str()))
+                    .map(|(key, v)| {
+                        // properties 的键是字段名（可能恰好叫 title），不能按关键字过滤
+                        let v = match (key.as_str(), v) {
+                            ("properties", Value::Object(props)) => Value::Object(
+                                props.into_iter().map(|(name, p)| (name, inline_refs(p, definitions))).collect(),
+                            ),
+                            (_, v) => inline_refs(v, definitions),
+                        };
+                        (key, v)
+                    })
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(|v| inline_refs(v, definitions)).collect()),
+        other => other,
+    }
+}
+
+fn strictify(value: Value) -> Value {
+    match value {
+        Value::Object(mut map) => {
+            // 带 tag 的枚举（Command）生成为 oneOf，合并成单个对象
+            if let Some(Value::Array(variants)) = map.remove("oneOf") {
+                return strictify(merge_variants(variants));
+            }
+            if map.contains_key("properties") {
+                let keys: Vec<Value> = map["properties"].as_object().map(|p| p.keys().cloned().map(Value::String).collect()).unwrap_or_default();
+                map.insert("required".to_string(), Value::Array(keys));
+                map.insert("additionalProperties".to_string(), Value::Bool(false));
+            }
+            Value::Object(map.into_iter().map(|(k, v)| (k, strictify(v))).collect())
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(strictify).collect()),
+        other => other,
+    }
+}
+
+/// 把 tag 枚举的各变体合并为一个对象。一个字段不是所有命令都有，所以一般允许为 null；
+/// 但带 serde default 的非 Option 字段（如 `clickable`）不能接受 null，保持原类型，
+/// 其它命令随便填的值会被反序列化忽略。
+fn merge_variants(variants: Vec<Value>) -> Value {
+    let mut tags = Vec::new();
+    let mut properties = Map::new();
+    for variant in &variants {
+        let required: Vec<&str> = variant["required"].as_array().map(|r| r.iter().filter_map(|v| v.as_str()).collect()).unwrap_or_default();
+        let Some(props) = variant["properties"].as_object() else {
+            continue;
+        };
+        for (name, prop) in props {
+            if name == "cmd" {
+                if let Some(tag) = prop["enum"].as_array().and_then(|e| e.first()) {
+                    tags.push(tag.clone());
+                }
+                continue;
+            }
+            if properties.contains_key(name) {
+                continue;
+            }
+            let prop = if required.contains(&name.as_str()) { nullable(prop.clone()) } else { prop.clone() };
+            properties.insert(name.clone(), prop);
+        }
+    }
+    let mut merged = Map::new();
+    merged.insert("cmd".to_string(), json!({ "type": "string", "enum": tags }));
+    merged.extend(properties);
+    json!({ "type": "object", "properties": merged })
+}
+
+fn nullable(mut schema: Value) -> Value {
+    match schema.get("type").cloned() {
+        Some(Value::String(t)) => {
+            schema["type"] = json!([t, "null"]);
+            schema
+        }
+        Some(Value::Array(mut types)) => {
+            if !types.contains(&json!("null")) {
+                types.push(json!("null"));
+            }
+            schema["type"] = Value::Array(types);
+            schema
+        }
+        _ => json!({ "anyOf": [schema, { "type": "null" }] }),
+    }
+}
+
+/// 手写的字段 Schema（常量、枚举），schemars 无法从 `String` 推导出这些约束
+fn literal(schema: Value) -> Schema {
+    serde_json::from_value(schema).unwrap_or(Schema::Bool(true))
+}
+
+fn const_string(value: &str) -> Schema {
+    literal(json!({ "type": "string", "const": value }))
+}
+
+pub fn agd_version(_: &mut SchemaGenerator) -> Schema {
+    const_string(version::CURRENT_VERSION)
+}
+
+pub fn render_type(_: &mut SchemaGenerator) -> Schema {
+    const_string("render")
+}
+
+pub fn xdsl_version(_: &mut SchemaGenerator) -> Schema {
+    const_string("X-DSL/0.2")
+}
+
+pub fn path_cmd(_: &mut SchemaGenerator) -> Schema {
+    literal(json!({ "type": "string", "enum": ["M", "L", "Z"] }))
+}
+
+pub fn image_src_type(_: &mut SchemaGenerator) -> Schema {
+    literal(json!({ "type": ["string", "null"], "enum": ["path", "base64", "url", null] }))
+}
+
+/// text 的 `value`：数字或日期时间字符串（serde_json::Value 推导出的 Schema 不带类型，严格模式不接受）
+pub fn format_value(_: &mut SchemaGenerator) -> Schema {
+    literal(json!({ "type": ["number", "string", "null"] }))
+}
@@ -0,0 +1,170 @@
+use serde::Serialize;
+
+use crate::dialog;
+use crate::input;
+use crate::dsl::model::{Command, RenderEnvelope};
+use crate::dsl::validator;
+
+/// 估算文本范围用的平均字宽与行高（24px 字体）
+const TEXT_CHAR_WIDTH: u32 = 13;
+const TEXT_LINE_HEIGHT: u32 = 36;
+
+/// 无障碍树中的一个节点。坐标为窗口像素，文本的宽高是估算值
+#[derive(Debug, Clone, Serialize)]
+pub struct Node {
+    pub role: String,
+    pub label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_key: Option<char>,
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+}
+
+/// 从 render 信封推导无障碍树（按绘制顺序的扁平列表）：
+/// 显式的 `role`/`label` 优先；可点击 rect 默认是 button，名称取 rect 内的文本；
+/// 没有被用作名称的文本是 `text` 节点（或其声明的角色）；图片默认是 `image`
+pub fn outline(render: &RenderEnvelope) -> Vec<Node> {
+    let texts: Vec<(i32, i32, &str)> = render
+        .commands
+        .iter()
+        .filter_map(|c| match c {
+            Command::Text { x, y, text, .. } if !text.trim().is_empty() => Some((*x, *y, text.as_str())),
+            _ => None,
+        })
+        .collect();
+    // 被可点击 rect 当作名称的文本不再单独成为节点
+    let mut named_by_button = Vec::new();
+    let mut nodes = Vec::new();
+    for command in &render.commands {
+        match command {
+            Command::Rect { id, x, y, w, h, clickable, access_key, role, label, .. } => {
+                let role = match (role, clickable) {
+                    (Some(role), _) => role.clone(),
+                    (None, true) => "button".to_string(),
+                    (None, false) => continue,
+                };
+                let inside: Vec<&(i32, i32, &str)> = texts.iter().filter(|t| contains(*x, *y, *w, *h, t.0, t.1)).collect();
+                if *clickable {
+                    named_by_button.extend(inside.iter().map(|t| (t.0, t.1)));
+                }
+                let label = label.clone().unwrap_or_else(|| inside.iter().map(|t| t.2).collect::<Vec<_>>().join(" "));
+                let access_key = access_key.as_deref().and_then(validator::access_key_char);
+                nodes.push(Node { role, label, id: id.clone(), access_key, x: *x, y: *y, w: *w, h: *h });
+            }
+            Command::RoundRect { x: Some(x), y: Some(y), w: Some(w), h: Some(h), role: Some(role), label, .. } => {
+                let label = label.clone().unwrap_or_else(|| {
+                    texts.iter().filter(|t| contains(*x, *y, *w, *h, t.0, t.1)).map(|t| t.2).collect::<Vec<_>>().join(" ")
+                });
+                nodes.push(Node { role: role.clone(), label, id: None, access_key: None, x: *x, y: *y, w: *w, h: *h });
+            }
+            Command::Text { x, y, text, role, label, .. } if !text.trim().is_empty() => {
+                if role.is_none() && named_by_button.contains(&(*x, *y)) {
+                    continue;
+                }
+                let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0) as u32;
+                let rows = text.lines().count().max(1) as u32;
+                nodes.push(Node {
+                    role: role.clone().unwrap_or_else(|| "text".to_string()),
+                    label: label.clone().unwrap_or_else(|| text.replace('\n', " ")),
+                    id: None,
+                    access_key: None,
+                    x: *x,
+                    y: *y,
+                    w: columns * TEXT_CHAR_WIDTH,
+                    h: rows * TEXT_LINE_HEIGHT,
+                });
+            }
+            Command::Toast { id, text, frame: Some((x, y, w, h)), .. } => nodes.push(Node {
+                role: "status".to_string(),
+                label: text.clone(),
+                id: id.clone(),
+                access_key: None,
+                x: *x,
+                y: *y,
+                w: *w,
+                h: *h,
+            }),
+            // 对话框与它在本地画出的两个按钮
+            Command::Dialog { id, title, frame: Some((x, y, w, h)), .. } => {
+                nodes.push(Node { role: "dialog".to_string(), label: title.clone(), id: Some(id.clone()), access_key: None, x: *x, y: *y, w: *w, h: *h });
+                for b in dialog::buttons(command) {
+                    nodes.push(Node { role: "button".to_string(), label: b.label, id: Some(b.id), access_key: None, x: b.x, y: b.y, w: b.w, h: b.h });
+                }
+            }
+            // 输入框的标签取提示文字，不含输入的内容
+            Command::Input { id, x, y, w, h, placeholder, .. } => nodes.push(Node {
+                role: "textbox".to_string(),
+                label: placeholder.clone().unwrap_or_default(),
+                id: Some(id.clone()),
+                access_key: None,
+                x: *x,
+                y: *y,
+                w: *w,
+                h: *h,
+            }),
+            // 滑块的标签是当前值，选择框的标签是选中的选项
+            Command::Slider { id, x, y, w, h, .. } => nodes.push(Node {
+                role: "slider".to_string(),
+                label: input::slider_value(command).map(|v| v.to_string()).unwrap_or_default(),
+                id: Some(id.clone()),
+                access_key: None,
+                x: *x,
+                y: *y,
+                w: *w,
+                h: *h,
+            }),
+            Command::Select { id, x, y, w, h, .. } => nodes.push(Node {
+                role: "combobox".to_string(),
+                label: input::selected_option(command).unwrap_or_default().to_string(),
+                id: Some(id.clone()),
+                access_key: None,
+                x: *x,
+                y: *y,
+                w: *w,
+                h: *h,
+            }),
+            Command::Image { x: Some(x), y: Some(y), w: Some(w), h: Some(h), role, label, .. } => nodes.push(Node {
+                role: role.clone().unwrap_or_else(|| "image".to_string()),
+                label: label.clone().unwrap_or_default(),
+                id: None,
+                access_key: None,
+                x: *x,
+                y: *y,
+                w: *w,
+                h: *h,
+            }),
+            _ => {}
+        }
+    }
+    nodes
+}
+
+/// 给评估模型的无障碍树摘要，每个节点一行；缺少名称的节点会标出来
+pub fn describe(render: &RenderEnvelope) -> String {
+    let lines: Vec<String> = outline(render)
+        .iter()
+        .map(|node| {
+            let mut line = if node.label.trim().is_empty() {
+                format!("- {} (NO LABEL)", node.role)
+            } else {
+                format!("- {} \"{}\"", node.role, node.label)
+            };
+            if let Some(id) = &node.id {
+                line.push_str(&format!(" id={}", id));
+            }
+            if let Some(key) = node.access_key {
+                line.push_str(&format!(" key={}", key));
+            }
+            line
+        })
+        .collect();
+    if lines.is_empty() { "none".to_string() } else { lines.join("\n") }
+}
+
+fn contains(x: i32, y: i32, w: u32, h: u32, px: i32, py: i32) -> bool {
+    px >= x && py >= y && px < x + w as i32 && py < y + h as i32
+}
@@ -0,0 +1,268 @@
+//! 主题：render 中的颜色除了 `#RRGGBB` 还可以写成 `@primary` 这样的调色板 token，
+//! 由渲染端按用户配置的调色板解析，同一份生成结果可以整体换肤，而不需要修改提示词。
+//! 调色板分浅色、深色两套，按 `theme.mode`（`auto` 时检测桌面配色偏好）选择；
+//! 高对比度模式下改用两套固定的高对比度调色板。
+
+use std::collections::BTreeMap;
+use std::process::Command as Process;
+use std::sync::{Mutex, OnceLock};
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use crate::config;
+use crate::dsl::model::RenderEnvelope;
+
+/// 内置浅色调色板；`theme.palette` 中的同名条目覆盖它们，也可以新增 token
+pub const DEFAULT_PALETTE: &[(&str, &str)] = &[
+    ("background", "#F4F6FA"),
+    ("surface", "#FFFFFF"),
+    ("primary", "#2563EB"),
+    ("on_primary", "#FFFFFF"),
+    ("text", "#1F2937"),
+    ("muted", "#6B7280"),
+    ("border", "#D1D5DB"),
+    ("accent", "#F59E0B"),
+    ("success", "#16A34A"),
+    ("danger", "#DC2626"),
+];
+
+/// 内置深色调色板，token 与浅色一致；`theme.dark_palette` 覆盖
+pub const DARK_PALETTE: &[(&str, &str)] = &[
+    ("background", "#111827"),
+    ("surface", "#1F2937"),
+    ("primary", "#3B82F6"),
+    ("on_primary", "#FFFFFF"),
+    ("text", "#F3F4F6"),
+    ("muted", "#9CA3AF"),
+    ("border", "#374151"),
+    ("accent", "#FBBF24"),
+    ("success", "#22C55E"),
+    ("danger", "#EF4444"),
+];
+
+/// 高对比度调色板（浅色方案）：白底黑字，强调色都与白色有 7:1 以上的对比度
+pub const HIGH_CONTRAST_LIGHT_PALETTE: &[(&str, &str)] = &[
+    ("background", "#FFFFFF"),
+    ("surface", "#FFFFFF"),
+    ("primary", "#0000B3"),
+    ("on_primary", "#FFFFFF"),
+    ("text", "#000000"),
+    ("muted", "#333333"),
+    ("border", "#000000"),
+    ("accent", "#6B2C00"),
+    ("success", "#005A00"),
+    ("danger", "#A00000"),
+];
+
+/// 高对比度调色板（深色方案）：黑底白字，强调色用黄、青等亮色
+pub const HIGH_CONTRAST_DARK_PALETTE: &[(&str, &str)] = &[
+    ("background", "#000000"),
+    ("surface", "#000000"),
+    ("primary", "#FFFF00"),
+    ("on_primary", "#000000"),
+    ("text", "#FFFFFF"),
+    ("muted", "#E0E0E0"),
+    ("border", "#FFFFFF"),
+    ("accent", "#00FFFF"),
+    ("success", "#7CFF7C"),
+    ("danger", "#FF8080"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+impl ColorScheme {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorScheme::Light => "light",
+            ColorScheme::Dark => "dark",
+        }
+    }
+}
+
+static MODE_OVERRIDE: OnceLock<String> = OnceLock::new();
+static HIGH_CONTRAST_OVERRIDE: OnceLock<bool> = OnceLock::new();
+/// 方案与调色板按配置代数缓存：切换配置档案后重新检测、重新合并
+static SCHEME: Mutex<Option<(u64, ColorScheme)>> = Mutex::new(None);
+/// 携带颜色的字段名；只替换这些字段，避免误改文本内容里的 `@`
+const COLOR_FIELDS: &[&str] = &["color", "fill", "stroke", "bg"];
+
+static PALETTE: Mutex<Option<(u64, &'static BTreeMap<String, String>)>> = Mutex::new(None);
+
+/// 命令行 `--theme <auto|light|dark>`，优先于配置；须在首次解析颜色之前调用
+pub fn set_mode(mode: &str) -> Result<(), String> {
+    if !matches!(mode, "auto" | "light" | "dark") {
+        return Err(format!("unknown theme mode: {} (expected auto|light|dark)", mode));
+    }
+    let _ = MODE_OVERRIDE.set(mode.to_string());
+    Ok(())
+}
+
+/// 命令行 `--high-contrast`，优先于配置；须在首次解析颜色之前调用
+pub fn set_high_contrast() {
+    let _ = HIGH_CONTRAST_OVERRIDE.set(true);
+}
+
+pub fn high_contrast() -> bool {
+    *HIGH_CONTRAST_OVERRIDE.get().unwrap_or(&config::get().theme.high_contrast)
+}
+
+/// 写进生成上下文的方案描述，如 `dark` 或 `dark, high contrast`
+pub fn describe_scheme() -> String {
+    if high_contrast() {
+        format!("{}, high contrast", scheme().name())
+    } else {
+        scheme().name().to_string()
+    }
+}
+
+/// 当前生效的配色方案
+pub fn scheme() -> ColorScheme {
+    let generation = config::generation();
+    let mut cached = SCHEME.lock().unwrap();
+    if let Some((g, scheme)) = *cached {
+        if g == generation {
+            return scheme;
+        }
+    }
+    let scheme = {
+        let mode = MODE_OVERRIDE.get().unwrap_or(&config::get().theme.mode);
+        match mode.as_str() {
+            "light" => ColorScheme::Light,
+            "dark" => ColorScheme::Dark,
+            other => {
+                if other != "auto" {
+                    warn!("unknown theme.mode {:?}, falling back to auto", other);
+                }
+                let detected = detect_scheme();
+                debug!("Detected desktop color scheme: {}", detected.name());
+                detected
+            }
+        }
+    };
+    *cached = Some((generation, scheme));
+    scheme
+}
+
+/// 桌面配色偏好：`GTK_THEME` 的 `:dark` 变体，其次是 GNOME 的 `color-scheme` / `gtk-theme` 设置；都拿不到时按浅色处理
+fn detect_scheme() -> ColorScheme {
+    if let Ok(theme) = std::env::var("GTK_THEME") {
+        return if theme.to_ascii_lowercase().contains("dark") { ColorScheme::Dark } else { ColorScheme::Light };
+    }
+    for key in ["color-scheme", "gtk-theme"] {
+        let Ok(output) = Process::new("gsettings").args(["get", "org.gnome.desktop.interface", key]).output() else {
+            break;
+        };
+        let value = String::from_utf8_lossy(&output.stdout).to_ascii_lowercase();
+        if output.status.success() && value.contains("dark") {
+            return ColorScheme::Dark;
+        }
+        if output.status.success() && key == "color-scheme" && value.contains("prefer-light") {
+            return ColorScheme::Light;
+        }
+    }
+    ColorScheme::Light
+}
+
+pub fn palette() -> &'static BTreeMap<String, String> {
+    let generation = config::generation();
+    let mut cached = PALETTE.lock().unwrap();
+    if let Some((g, palette)) = *cached {
+        if g == generation {
+            return palette;
+        }
+    }
+    let palette = {
+        let theme = &config::get().theme;
+        let (defaults, overrides) = match (scheme(), high_contrast()) {
+            // 强制配色：不接受自定义调色板
+            (ColorScheme::Light, true) => (HIGH_CONTRAST_LIGHT_PALETTE, None),
+            (ColorScheme::Dark, true) => (HIGH_CONTRAST_DARK_PALETTE, None),
+            (ColorScheme::Light, false) => (DEFAULT_PALETTE, Some(&theme.palette)),
+            (ColorScheme::Dark, false) => (DARK_PALETTE, Some(&theme.dark_palette)),
+        };
+        let mut palette: BTreeMap<String, String> =
+            defaults.iter().map(|(name, color)| (name.to_string(), color.to_string())).collect();
+        palette.extend(overrides.cloned().unwrap_or_default());
+        palette
+    };
+    // 与配置一样只在切换档案时重建，旧的调色板不释放，已取出的引用保持有效
+    let palette: &'static BTreeMap<String, String> = Box::leak(Box::new(palette));
+    *cached = Some((generation, palette));
+    palette
+}
+
+/// `#RRGGBB` 原样返回；`@token` 返回调色板中的颜色，未知 token 返回 None
+pub fn resolve(color: &str) -> Option<&str> {
+    match color.strip_prefix('@') {
+        Some(token) => palette().get(token).map(String::as_str),
+        None => Some(color),
+    }
+}
+
+pub fn is_token(color: &str) -> bool {
+    color.strip_prefix('@').is_some_and(|token| palette().contains_key(token))
+}
+
+/// 提示词里列出的可用 token，如 `@primary=#2563EB`
+pub fn describe_tokens() -> String {
+    palette().iter().map(|(name, color)| format!("@{}={}", name, color)).collect::<Vec<_>>().join(", ")
+}
+
+/// 把信封中的 token 全部换成具体颜色，给不做主题解析的下游（JSON-RPC 嵌入方、commands 格式的远程客户端）
+pub fn resolve_render(render: &RenderEnvelope) -> RenderEnvelope {
+    let Ok(mut value) = serde_json::to_value(render) else {
+        return render.clone();
+    };
+    if let Some(Value::String(color)) = value.pointer_mut("/window/background") {
+        if let Some(resolved) = resolve(color).filter(|_| color.starts_with('@')) {
+            *color = resolved.to_string();
+        }
+    }
+    if let Some(commands) = value.get_mut("commands").and_then(Value::as_array_mut) {
+        for command in commands.iter_mut().filter_map(Value::as_object_mut) {
+            for field in COLOR_FIELDS {
+                if let Some(Value::String(color)) = command.get_mut(*field) {
+                    if let Some(resolved) = resolve(color).filter(|_| color.starts_with('@')) {
+                        *color = resolved.to_string();
+                    }
+                }
+            }
+        }
+    }
+    serde_json::from_value(value).unwrap_or_else(|_| render.clone())
+}
+
+/// `#RRGGBB` 或 `@token` 解析为 RGB
+pub fn parse_rgb(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = resolve(color)?.strip_prefix('#')?;
+    let value = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)?;
+    Some(((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+/// WCAG 2 相对亮度
+pub fn relative_luminance((r, g, b): (u8, u8, u8)) -> f32 {
+    let channel = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.03928 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    0.2126 * channel(r) + 0.7152 * channel(g) + 0.0722 * channel(b)
+}
+
+/// WCAG 对比度，1.0（相同）到 21.0（黑白）
+pub fn contrast_ratio(a: (u8, u8, u8), b: (u8, u8, u8)) -> f32 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// 前景色与背景的对比度不足 `min` 时换成黑或白中对比度更高的一个
+pub fn ensure_contrast(fg: (u8, u8, u8), bg: (u8, u8, u8), min: f32) -> (u8, u8, u8) {
+    if contrast_ratio(fg, bg) >= min {
+        return fg;
+    }
+    let (black, white) = ((0, 0, 0), (255, 255, 255));
+    if contrast_ratio(black, bg) >= contrast_ratio(white, bg) { black } else { white }
+}
@@ -1,281 +1,1142 @@
-use std::error::Error;
-use std::fmt;
 use std::collections::HashSet;
+use std::fmt;
+use thiserror::Error;
 
-use crate::dsl::model::{Command, RenderEnvelope};
+use crate::config;
+use crate::datasource;
+use crate::dialog;
+use crate::images;
+use crate::dsl::formatting;
+use crate::dsl::model::{Command, PathSegment, Point, RenderEnvelope};
+use crate::dsl::theme;
+use crate::dsl::version;
+use crate::runner;
+use crate::terminal;
+use crate::toast;
+use crate::x11::renderer;
+
+/// 机器可读的问题类别，随错误一起交给修复循环和 lint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    UnsupportedVersion,
+    UnsupportedType,
+    InvalidWindow,
+    EmptyCommands,
+    MissingClear,
+    Required,
+    NotPositive,
+    InvalidColor,
+    InvalidValue,
+    MissingId,
+    DuplicateId,
+    DuplicateAccessKey,
+    TooFewPoints,
+    MissingPaint,
+    UnknownAction,
+    IntervalTooShort,
+    OutOfBounds,
+    TextOffscreen,
+    StrokeTooWide,
+    LowContrast,
+    TextOverflow,
+    OverlappingClickables,
+    LimitExceeded,
+}
 
-#[derive(Debug)]
-struct ValidationError(String);
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::UnsupportedVersion => "unsupported_version",
+            ErrorCode::UnsupportedType => "unsupported_type",
+            ErrorCode::InvalidWindow => "invalid_window",
+            ErrorCode::EmptyCommands => "empty_commands",
+            ErrorCode::MissingClear => "missing_clear",
+            ErrorCode::Required => "required",
+            ErrorCode::NotPositive => "not_positive",
+            ErrorCode::InvalidColor => "invalid_color",
+            ErrorCode::InvalidValue => "invalid_value",
+            ErrorCode::MissingId => "missing_id",
+            ErrorCode::DuplicateId => "duplicate_id",
+            ErrorCode::DuplicateAccessKey => "duplicate_access_key",
+            ErrorCode::TooFewPoints => "too_few_points",
+            ErrorCode::MissingPaint => "missing_paint",
+            ErrorCode::UnknownAction => "unknown_action",
+            ErrorCode::IntervalTooShort => "interval_too_short",
+            ErrorCode::OutOfBounds => "out_of_bounds",
+            ErrorCode::TextOffscreen => "text_offscreen",
+            ErrorCode::StrokeTooWide => "stroke_too_wide",
+            ErrorCode::LowContrast => "low_contrast",
+            ErrorCode::TextOverflow => "text_overflow",
+            ErrorCode::OverlappingClickables => "overlapping_clickables",
+            ErrorCode::LimitExceeded => "limit_exceeded",
+        }
+    }
+}
 
-impl fmt::Display for ValidationError {
+impl fmt::Display for ErrorCode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        f.write_str(self.as_str())
+    }
+}
+
+/// 一条校验问题：出错位置、类别与说明
+#[derive(Debug, Clone, Error)]
+pub enum ValidationError {
+    /// 信封本身（version、window、timers 等）的问题，`path` 如 `window.title`
+    #[error("{path}: {message} [{code}]")]
+    Envelope { path: String, code: ErrorCode, message: String },
+    /// 第 `index` 条命令的问题，`field` 是命令内的字段路径，如 `segments[2].cmd`
+    #[error("commands[{index}].{field}: {message} [{code}]")]
+    Command { index: usize, field: String, code: ErrorCode, message: String },
+}
+
+impl ValidationError {
+    /// 完整字段路径，如 `commands[3].fill`
+    pub fn path(&self) -> String {
+        match self {
+            ValidationError::Envelope { path, .. } => path.clone(),
+            ValidationError::Command { index, field, .. } => format!("commands[{}].{}", index, field),
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ValidationError::Envelope { code, .. } | ValidationError::Command { code, .. } => *code,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ValidationError::Envelope { message, .. } | ValidationError::Command { message, .. } => message,
+        }
     }
 }
 
-impl Error for ValidationError {}
+/// 一次校验发现的全部问题；显示为每行一条，修复循环把它原样交给模型
+#[derive(Debug, Error)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
 
-pub fn validate_render(render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
-    if render.version != "AGD/0.2" {
-        return Err(Box::new(ValidationError("unsupported version".to_string())));
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.as_slice() {
+            [single] => write!(f, "{}", single),
+            all => {
+                write!(f, "{} problems:", all.len())?;
+                for error in all {
+                    write!(f, "\n- {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// rect 可声明的本地动作
+pub const RECT_ACTIONS: &[&str] = &["exit", "submit", "link", "run", "navigate:push", "navigate:pop"];
+
+/// `link` 的 href 允许的协议；file、javascript 等可能在本机执行或读取任意内容的协议一律拒绝
+pub const LINK_SCHEMES: &[&str] = &["http://", "https://", "mailto:"];
+
+/// 文字与背景的最低对比度（WCAG AA 正文级别）
+pub const MIN_TEXT_CONTRAST: f32 = 4.5;
+
+/// rect 可声明的按下反馈样式
+pub const PRESS_STYLES: &[&str] = &["outline", "invert", "darken", "ripple"];
+
+/// `role` 字段允许的语义角色
+pub const ROLES: &[&str] = &["button", "heading", "list", "status", "image"];
+
+/// `enter` / `exit` 可用的动画预设；`slide-from-*` 用作 exit 时朝同一方向滑出
+pub const ANIMATIONS: &[&str] = &["fade", "pop", "slide-from-left", "slide-from-right", "slide-from-top", "slide-from-bottom"];
+
+/// text 放不下文字框时的处理方式：缩小字号、截断加省略号、裁掉超出部分
+pub const TEXT_FITS: &[&str] = &["shrink", "ellipsis", "clip"];
+
+/// text 的书写方向
+pub const TEXT_ORIENTATIONS: &[&str] = &["horizontal", "vertical"];
+
+/// 竖排 text 可用的字形旋转角度（度）
+pub const TEXT_ROTATIONS: &[i32] = &[90, -90];
+
+/// 定时器的最短间隔，避免模型声明过于频繁的定时器而不断触发生成
+pub const MIN_TIMER_MS: u64 = 1000;
+
+/// 坐标与范围的绝对值上限：远超任何窗口，通过校验的命令做 `x + w` 之类的运算时不会溢出 i32
+pub const MAX_COORDINATE: i32 = 1_000_000;
+
+/// 文本按默认 24px 字号估算：首行基线约在 y + 24，行距约 36px
+const TEXT_BASELINE: i32 = 24;
+const TEXT_LINE_HEIGHT: i32 = 36;
+
+/// 校验 render 信封，一次报告全部问题；配置了 `strict_bounds` 时布局检查的发现也算错误
+pub fn validate_render(render: &RenderEnvelope) -> Result<(), ValidationErrors> {
+    let mut errors = check_render(render);
+    if config::get().strict_bounds {
+        errors.extend(check_bounds(render));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationErrors(errors))
+    }
+}
+
+/// 收集 render 信封的全部问题，按信封字段、命令顺序排列
+pub fn check_render(render: &RenderEnvelope) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    let mut top = |path: &str, code: ErrorCode, message: &str| {
+        errors.push(ValidationError::Envelope { path: path.to_string(), code, message: message.to_string() })
+    };
+    if render.version != version::CURRENT_VERSION {
+        top("version", ErrorCode::UnsupportedVersion, "unsupported version");
     }
     if render.render_type != "render" {
-        return Err(Box::new(ValidationError("unsupported type".to_string())));
+        top("type", ErrorCode::UnsupportedType, "unsupported type");
+    }
+    if let Some(xdsl) = &render.xdsl {
+        if xdsl.version != "X-DSL/0.2" {
+            top("xdsl.version", ErrorCode::UnsupportedVersion, "unsupported xdsl version");
+        }
     }
     if render.window.width == 0 || render.window.height == 0 {
-        return Err(Box::new(ValidationError("window size must be positive".to_string())));
+        top("window", ErrorCode::InvalidWindow, "window size must be positive");
     }
     if render.window.title.trim().is_empty() {
-        return Err(Box::new(ValidationError("window title must not be empty".to_string())));
+        top("window.title", ErrorCode::InvalidWindow, "window title must not be empty");
+    }
+    if let Some(background) = &render.window.background {
+        if !is_hex_color(background) && !theme::is_token(background) {
+            top("window.background", ErrorCode::InvalidColor, "window.background must be #RRGGBB or a theme token");
+        }
+    }
+    let limits = &config::get().limits;
+    if render.window.width > limits.max_window_width || render.window.height > limits.max_window_height {
+        let message = format!("window size exceeds the limit of {}x{}", limits.max_window_width, limits.max_window_height);
+        top("window", ErrorCode::LimitExceeded, &message);
+    }
+    if render.commands.len() > limits.max_commands {
+        let message = format!("{} commands exceed the limit of {}", render.commands.len(), limits.max_commands);
+        top("commands", ErrorCode::LimitExceeded, &message);
+    }
+    let mut timer_ids = HashSet::new();
+    for (index, timer) in render.timers.iter().enumerate() {
+        let path = format!("timers[{}]", index);
+        if timer.id.trim().is_empty() {
+            top(&format!("{}.id", path), ErrorCode::MissingId, "timer.id must not be empty");
+        } else if !timer_ids.insert(timer.id.as_str()) {
+            top(&format!("{}.id", path), ErrorCode::DuplicateId, "duplicate timer id");
+        }
+        if timer.every_ms < MIN_TIMER_MS {
+            let message = format!("timer.every_ms must be at least {}", MIN_TIMER_MS);
+            top(&format!("{}.every_ms", path), ErrorCode::IntervalTooShort, &message);
+        }
+    }
+    let mut source_ids = HashSet::new();
+    for (index, source) in render.data_sources.iter().enumerate() {
+        let path = format!("data_sources[{}]", index);
+        if source.id.trim().is_empty() || source.id.contains('/') {
+            top(&format!("{}.id", path), ErrorCode::MissingId, "data_source.id must not be empty or contain /");
+        } else if !source_ids.insert(source.id.as_str()) {
+            top(&format!("{}.id", path), ErrorCode::DuplicateId, "duplicate data source id");
+        }
+        if source.every_ms < MIN_TIMER_MS {
+            let message = format!("data_source.every_ms must be at least {}", MIN_TIMER_MS);
+            top(&format!("{}.every_ms", path), ErrorCode::IntervalTooShort, &message);
+        }
+        if let Err(message) = datasource::check(source) {
+            top(&path, ErrorCode::InvalidValue, &message);
+        }
+    }
+    let mut chords = HashSet::new();
+    for (index, shortcut) in render.shortcuts.iter().enumerate() {
+        let path = format!("shortcuts[{}].keys", index);
+        match shortcut_chord(&shortcut.keys) {
+            None => {
+                let message = format!("shortcut {:?} must be like Ctrl+S, Ctrl+Shift+Z or F5 (F1-F11 alone, other keys need Ctrl or Alt)", shortcut.keys);
+                top(&path, ErrorCode::InvalidValue, &message);
+            }
+            Some(chord) if !chords.insert(chord) => {
+                top(&path, ErrorCode::DuplicateAccessKey, &format!("shortcut {} is declared twice", shortcut.keys));
+            }
+            Some(_) => {}
+        }
+        let clickable = render.commands.iter().any(|c| matches!(c, Command::Rect { id: Some(id), clickable: true, .. } if *id == shortcut.target));
+        if !clickable {
+            let message = format!("shortcut target {} does not name a clickable rect id", shortcut.target);
+            top(&format!("shortcuts[{}].target", index), ErrorCode::InvalidValue, &message);
+        }
     }
     if render.commands.is_empty() {
-        return Err(Box::new(ValidationError("commands must not be empty".to_string())));
+        top("commands", ErrorCode::EmptyCommands, "commands must not be empty");
+        return errors;
     }
 
     let mut has_clear = false;
     let mut ids = HashSet::new();
-    for command in &render.commands {
+    let mut access_keys = HashSet::new();
+    let mut dialogs = 0;
+    for (index, command) in render.commands.iter().enumerate() {
+        let mut c = Checker { errors: &mut errors, index, kind: command_name(command) };
+        c.semantics(command);
+        c.animations(command);
+        c.coordinates(command);
         match command {
             Command::Clear { color } => {
                 has_clear = true;
-                validate_color(color, "clear.color")?;
-            }
-            Command::Rect { id, clickable, .. } => {
-                if *clickable {
-                    let id = id.as_ref().ok_or_else(|| {
-                        Box::new(ValidationError("clickable rect requires id".to_string()))
-                            as Box<dyn Error>
-                    })?;
-                    if id.trim().is_empty() {
-                        return Err(Box::new(ValidationError("id must not be empty".to_string())));
-                    }
-                    if !ids.insert(id.clone()) {
-                        return Err(Box::new(ValidationError("duplicate id".to_string())));
+                c.color(color, "color");
+            }
+            Command::Rect { id, clickable, access_key, bind, bind_max, .. } => {
+                check_bind(&mut c, bind, render);
+                if bind_max.is_some_and(|max| max <= 0.0) {
+                    c.fail("bind_max", ErrorCode::NotPositive, "bind_max must be positive".to_string());
+                }
+                if let Some(key) = access_key.as_deref().and_then(access_key_char) {
+                    if !access_keys.insert(key) {
+                        c.fail("access_key", ErrorCode::DuplicateAccessKey, format!("access key '{}' is already used", key));
                     }
-                } else if let Some(id) = id {
+                }
+                if *clickable && id.is_none() {
+                    c.fail("id", ErrorCode::MissingId, "clickable rect requires id".to_string());
+                }
+                if let Some(id) = id {
                     if id.trim().is_empty() {
-                        return Err(Box::new(ValidationError("id must not be empty".to_string())));
-                    }
-                    if !ids.insert(id.clone()) {
-                        return Err(Box::new(ValidationError("duplicate id".to_string())));
+                        c.fail("id", ErrorCode::MissingId, "id must not be empty".to_string());
+                    } else if !ids.insert(id.clone()) {
+                        c.fail("id", ErrorCode::DuplicateId, "duplicate id".to_string());
                     }
                 }
-                validate_rect(command)?;
+                check_rect(&mut c, command);
             }
-            Command::Text { text, color, .. } => {
+            Command::Text { text, color, value, format, w, h, fit, orientation, rotate, bind, .. } => {
+                check_bind(&mut c, bind, render);
+                match (value, format) {
+                    (Some(value), Some(format)) => {
+                        if let Err(message) = formatting::format_value(value, format) {
+                            c.fail("format", ErrorCode::InvalidValue, message);
+                        }
+                    }
+                    (None, Some(_)) if bind.is_none() => c.fail("value", ErrorCode::Required, "text.format requires value or bind".to_string()),
+                    (Some(_), None) => c.fail("format", ErrorCode::Required, "text.value requires format".to_string()),
+                    _ => {}
+                }
                 if text.trim().is_empty() {
                     continue;
                 }
-                if let Some(color) = color {
-                    validate_color(color, "text.color")?;
+                c.optional_color(color, "color");
+                c.optional_positive(w, "w");
+                c.optional_positive(h, "h");
+                let vertical = is_vertical(orientation);
+                if let Some(fit) = fit {
+                    if !TEXT_FITS.contains(&fit.as_str()) {
+                        let message = format!("unknown text.fit: {} (expected one of {})", fit, TEXT_FITS.join(", "));
+                        c.fail("fit", ErrorCode::InvalidValue, message);
+                    } else if w.is_none() && h.is_none() {
+                        c.fail("w", ErrorCode::Required, "text.fit requires w or h".to_string());
+                    } else if vertical {
+                        c.fail("fit", ErrorCode::InvalidValue, "text.fit is only supported for horizontal text".to_string());
+                    }
                 }
-            }
-            Command::Line { color, width, .. } => {
-                if let Some(color) = color {
-                    validate_color(color, "line.color")?;
+                if let Some(orientation) = orientation.as_deref().filter(|o| !TEXT_ORIENTATIONS.contains(o)) {
+                    let message = format!("unknown text.orientation: {} (expected one of {})", orientation, TEXT_ORIENTATIONS.join(", "));
+                    c.fail("orientation", ErrorCode::InvalidValue, message);
                 }
-                if let Some(width) = width {
-                    if *width == 0 {
-                        return Err(Box::new(ValidationError("line.width must be positive".to_string())));
+                if let Some(rotate) = rotate {
+                    if !TEXT_ROTATIONS.contains(rotate) {
+                        c.fail("rotate", ErrorCode::InvalidValue, format!("text.rotate must be 90 or -90, got {}", rotate));
+                    } else if !vertical {
+                        c.fail("rotate", ErrorCode::InvalidValue, "text.rotate requires orientation vertical".to_string());
                     }
                 }
             }
+            Command::Line { color, width, .. } => {
+                c.optional_color(color, "color");
+                c.optional_positive(width, "width");
+            }
             Command::Circle { cx, cy, r, fill, stroke, stroke_width } => {
-                require_i32(cx, "circle.cx")?;
-                require_i32(cy, "circle.cy")?;
-                require_u32(r, "circle.r")?;
-                validate_fill_stroke(fill, stroke, stroke_width, "circle")?;
+                c.require(cx, "cx");
+                c.require(cy, "cy");
+                c.require_positive(r, "r");
+                c.fill_stroke(fill, stroke, stroke_width);
             }
             Command::Ellipse { cx, cy, rx, ry, fill, stroke, stroke_width } => {
-                require_i32(cx, "ellipse.cx")?;
-                require_i32(cy, "ellipse.cy")?;
-                require_u32(rx, "ellipse.rx")?;
-                require_u32(ry, "ellipse.ry")?;
-                validate_fill_stroke(fill, stroke, stroke_width, "ellipse")?;
-            }
-            Command::RoundRect { x, y, w, h, r, fill, stroke, stroke_width } => {
-                require_i32(x, "round_rect.x")?;
-                require_i32(y, "round_rect.y")?;
-                require_u32(w, "round_rect.w")?;
-                require_u32(h, "round_rect.h")?;
-                require_u32(r, "round_rect.r")?;
-                validate_fill_stroke(fill, stroke, stroke_width, "round_rect")?;
-            }
-            Command::Arc { cx, cy, r, start_angle, end_angle, color, width } => {
-                require_i32(cx, "arc.cx")?;
-                require_i32(cy, "arc.cy")?;
-                require_u32(r, "arc.r")?;
-                require_f32(start_angle, "arc.start_angle")?;
-                require_f32(end_angle, "arc.end_angle")?;
-                if let Some(color) = color {
-                    validate_color(color, "arc.color")?;
+                c.require(cx, "cx");
+                c.require(cy, "cy");
+                c.require_positive(rx, "rx");
+                c.require_positive(ry, "ry");
+                c.fill_stroke(fill, stroke, stroke_width);
+            }
+            Command::RoundRect { x, y, w, h, r, fill, stroke, stroke_width, .. } => {
+                c.require(x, "x");
+                c.require(y, "y");
+                c.require_positive(w, "w");
+                c.require_positive(h, "h");
+                c.require_positive(r, "r");
+                c.fill_stroke(fill, stroke, stroke_width);
+            }
+            Command::Arc { cx, cy, r, start_angle, end_angle, color, width, fill, pie } => {
+                c.require(cx, "cx");
+                c.require(cy, "cy");
+                c.require_positive(r, "r");
+                c.require(start_angle, "start_angle");
+                c.require(end_angle, "end_angle");
+                c.optional_color(color, "color");
+                c.optional_positive(width, "width");
+                c.optional_color(fill, "fill");
+                if fill.is_some() && !*pie && width.is_none() {
+                    c.fail("width", ErrorCode::Required, "arc.width (ring thickness) is required for a filled arc without pie".to_string());
                 }
-                if let Some(width) = width {
-                    if *width == 0 {
-                        return Err(Box::new(ValidationError("arc.width must be positive".to_string())));
+            }
+            Command::Polyline { points, color, width } => {
+                c.points(points, 2);
+                c.max_points(points.as_ref().map(Vec::len), "points", limits.max_points);
+                c.optional_color(color, "color");
+                c.optional_positive(width, "width");
+            }
+            Command::Polygon { points, fill, stroke, stroke_width } => {
+                c.points(points, 3);
+                c.max_points(points.as_ref().map(Vec::len), "points", limits.max_points);
+                c.fill_stroke(fill, stroke, stroke_width);
+            }
+            Command::Image { x, y, w, h, src_type, src, .. } => {
+                c.require(x, "x");
+                c.require(y, "y");
+                c.require_positive(w, "w");
+                c.require_positive(h, "h");
+                match src_type.as_deref() {
+                    None => c.fail("src_type", ErrorCode::Required, "image.src_type is required".to_string()),
+                    Some("path" | "base64" | "url") => {}
+                    Some(_) => c.fail("src_type", ErrorCode::InvalidValue, "image.src_type must be path|base64|url".to_string()),
+                }
+                match src.as_deref() {
+                    None => c.fail("src", ErrorCode::Required, "image.src is required".to_string()),
+                    Some(src) if src.trim().is_empty() => {
+                        c.fail("src", ErrorCode::InvalidValue, "image.src must not be empty".to_string())
+                    }
+                    Some(src) if src_type.as_deref() == Some("base64") && src.len() > limits.max_image_bytes => {
+                        let message = format!("image.src has {} bytes, exceeding the limit of {}", src.len(), limits.max_image_bytes);
+                        c.fail("src", ErrorCode::LimitExceeded, message)
+                    }
+                    Some(src) if src_type.as_deref() == Some("url") => {
+                        if let Err(reason) = images::check_url(src) {
+                            c.fail("src", ErrorCode::InvalidValue, format!("image.src: {}", reason))
+                        }
                     }
+                    Some(src) if src_type.as_deref() == Some("path") => {
+                        if let Err(reason) = images::resolve_path(src) {
+                            c.fail("src", ErrorCode::InvalidValue, format!("image.src: {}", reason))
+                        }
+                    }
+                    Some(_) => {}
                 }
             }
-            Command::Polyline { points, color, width } => {
-                validate_points(points, "polyline.points", 2)?;
-                if let Some(color) = color {
-                    validate_color(color, "polyline.color")?;
+            Command::Path { segments, fill, stroke, stroke_width } => {
+                c.segments(segments);
+                c.max_points(segments.as_ref().map(Vec::len), "segments", limits.max_points);
+                c.fill_stroke(fill, stroke, stroke_width);
+            }
+            Command::Terminal { id, w, h, run, font_size, color, bg, .. } => {
+                if id.trim().is_empty() {
+                    c.fail("id", ErrorCode::MissingId, "id must not be empty".to_string());
+                } else if !ids.insert(id.clone()) {
+                    c.fail("id", ErrorCode::DuplicateId, "duplicate id".to_string());
+                }
+                if !runner::is_allowed(run) {
+                    let message = format!("terminal.run: {} is not an allowed command (allowed: {})", run, runner::describe());
+                    c.fail("run", ErrorCode::InvalidValue, message);
+                }
+                if let Some(size) = font_size.filter(|s| !(terminal::MIN_FONT_SIZE..=terminal::MAX_FONT_SIZE).contains(s)) {
+                    let message = format!("terminal.font_size must be between {} and {}, got {}", terminal::MIN_FONT_SIZE, terminal::MAX_FONT_SIZE, size);
+                    c.fail("font_size", ErrorCode::InvalidValue, message);
+                }
+                let (cols, rows) = terminal::grid_size(*w, *h, *font_size);
+                if cols < terminal::MIN_COLS || rows < terminal::MIN_ROWS {
+                    let message = format!("terminal is too small: {}x{} cells, needs at least {}x{}", cols, rows, terminal::MIN_COLS, terminal::MIN_ROWS);
+                    c.fail("w", ErrorCode::NotPositive, message);
                 }
-                if let Some(width) = width {
-                    if *width == 0 {
-                        return Err(Box::new(ValidationError("polyline.width must be positive".to_string())));
+                c.optional_color(color, "color");
+                c.optional_color(bg, "bg");
+            }
+            Command::Toast { id, text, kind, position, duration_ms, .. } => {
+                if let Some(id) = id {
+                    if id.trim().is_empty() {
+                        c.fail("id", ErrorCode::MissingId, "id must not be empty".to_string());
+                    } else if !ids.insert(id.clone()) {
+                        c.fail("id", ErrorCode::DuplicateId, "duplicate id".to_string());
                     }
                 }
+                if text.trim().is_empty() {
+                    c.fail("text", ErrorCode::Required, "toast.text must not be empty".to_string());
+                }
+                if let Some(kind) = kind.as_deref().filter(|k| !toast::KINDS.contains(k)) {
+                    let message = format!("unknown toast.kind: {} (expected one of {})", kind, toast::KINDS.join(", "));
+                    c.fail("kind", ErrorCode::InvalidValue, message);
+                }
+                if let Some(position) = position.as_deref().filter(|p| !toast::POSITIONS.contains(p)) {
+                    let message = format!("unknown toast.position: {} (expected one of {})", position, toast::POSITIONS.join(", "));
+                    c.fail("position", ErrorCode::InvalidValue, message);
+                }
+                if let Some(ms) = duration_ms.filter(|ms| !(toast::MIN_DURATION_MS..=toast::MAX_DURATION_MS).contains(ms)) {
+                    let message = format!("toast.duration_ms must be between {} and {}, got {}", toast::MIN_DURATION_MS, toast::MAX_DURATION_MS, ms);
+                    c.fail("duration_ms", ErrorCode::InvalidValue, message);
+                }
             }
-            Command::Polygon { points, fill, stroke, stroke_width } => {
-                validate_points(points, "polygon.points", 3)?;
-                validate_fill_stroke(fill, stroke, stroke_width, "polygon")?;
-            }
-            Command::Image { x, y, w, h, src_type, src } => {
-                require_i32(x, "image.x")?;
-                require_i32(y, "image.y")?;
-                require_u32(w, "image.w")?;
-                require_u32(h, "image.h")?;
-                let src_type = src_type.as_deref().ok_or_else(|| {
-                    Box::new(ValidationError("image.src_type is required".to_string())) as Box<dyn Error>
-                })?;
-                if src_type != "path" && src_type != "base64" {
-                    return Err(Box::new(ValidationError("image.src_type must be path|base64".to_string())));
+            Command::Dialog { id, title, confirm, cancel, .. } => {
+                dialogs += 1;
+                if dialogs > 1 {
+                    c.fail("cmd", ErrorCode::LimitExceeded, "only one dialog per screen".to_string());
                 }
-                let src = src.as_deref().ok_or_else(|| {
-                    Box::new(ValidationError("image.src is required".to_string())) as Box<dyn Error>
-                })?;
-                if src.trim().is_empty() {
-                    return Err(Box::new(ValidationError("image.src must not be empty".to_string())));
+                // 两个按钮的 id 也占用 id 空间，不能再给别的 rect
+                if id.trim().is_empty() {
+                    c.fail("id", ErrorCode::MissingId, "id must not be empty".to_string());
+                } else if ![id.clone(), dialog::button_id(id, dialog::CONFIRM), dialog::button_id(id, dialog::CANCEL)].into_iter().all(|id| ids.insert(id)) {
+                    let message = format!("duplicate id: the dialog uses {0}, {0}.confirm and {0}.cancel", id);
+                    c.fail("id", ErrorCode::DuplicateId, message);
+                }
+                if title.trim().is_empty() {
+                    c.fail("title", ErrorCode::Required, "dialog.title must not be empty".to_string());
+                }
+                for (label, field) in [(confirm, "confirm"), (cancel, "cancel")] {
+                    if label.as_deref().is_some_and(|l| l.trim().is_empty()) {
+                        c.fail(field, ErrorCode::Required, format!("dialog.{} must not be empty", field));
+                    }
                 }
             }
-            Command::Path { segments, fill, stroke, stroke_width } => {
-                validate_segments(segments, "path.segments")?;
-                validate_fill_stroke(fill, stroke, stroke_width, "path")?;
+            Command::Input { id, w, h, .. } | Command::Slider { id, w, h, .. } | Command::Select { id, w, h, .. } => {
+                if id.trim().is_empty() {
+                    c.fail("id", ErrorCode::MissingId, "id must not be empty".to_string());
+                } else if !ids.insert(id.clone()) {
+                    c.fail("id", ErrorCode::DuplicateId, "duplicate id".to_string());
+                }
+                if *w == 0 || *h == 0 {
+                    c.fail("w", ErrorCode::NotPositive, format!("{} must have positive size", command_name(command)));
+                }
+                match command {
+                    Command::Slider { min, max, step, value, .. } => {
+                        let (min, max) = (min.unwrap_or(0.0), max.unwrap_or(100.0));
+                        if min >= max {
+                            c.fail("max", ErrorCode::InvalidValue, "slider.max must be greater than slider.min".to_string());
+                        }
+                        if step.is_some_and(|step| step <= 0.0) {
+                            c.fail("step", ErrorCode::NotPositive, "slider.step must be positive".to_string());
+                        }
+                        if value.is_some_and(|value| !(min..=max).contains(&value)) {
+                            c.fail("value", ErrorCode::InvalidValue, "slider.value must lie between min and max".to_string());
+                        }
+                    }
+                    Command::Select { options, selected, .. } => {
+                        if options.is_empty() {
+                            c.fail("options", ErrorCode::Required, "select.options must not be empty".to_string());
+                        } else if selected.is_some_and(|i| i >= options.len()) {
+                            c.fail("selected", ErrorCode::InvalidValue, "select.selected must index an option".to_string());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // 表单区域要在全部 id 收集完后才能核对
+    for (index, command) in render.commands.iter().enumerate() {
+        if let Command::Rect { form: Some(form), .. } = command {
+            if !ids.contains(form) {
+                let mut c = Checker { errors: &mut errors, index, kind: "rect" };
+                c.fail("form", ErrorCode::InvalidValue, format!("rect.form {} does not name a rect id", form));
             }
         }
     }
 
     if !has_clear {
-        return Err(Box::new(ValidationError("commands must include clear".to_string())));
+        errors.push(ValidationError::Envelope {
+            path: "commands".to_string(),
+            code: ErrorCode::MissingClear,
+            message: "commands must include clear".to_string(),
+        });
     }
 
-    Ok(())
+    errors
 }
 
-fn validate_rect(command: &Command) -> Result<(), Box<dyn Error>> {
-    if let Command::Rect {
-        w,
-        h,
-        fill,
-        stroke,
-        stroke_width,
-        ..
-    } = command
-    {
-        if *w == 0 || *h == 0 {
-            return Err(Box::new(ValidationError("rect must have positive size".to_string())));
-        }
-        if let Some(fill) = fill {
-            validate_color(fill, "rect.fill")?;
-        }
-        if let Some(stroke) = stroke {
-            validate_color(stroke, "rect.stroke")?;
+/// 布局检查：命令整个落在窗口外、文本基线出界、描边比图形本身还宽。
+/// 这些不一定是错误（如有意超出窗口的装饰），默认只作为警告交给评估提示词
+pub fn check_bounds(render: &RenderEnvelope) -> Vec<ValidationError> {
+    let (width, height) = (render.window.width as i32, render.window.height as i32);
+    let mut errors = Vec::new();
+    for (index, command) in render.commands.iter().enumerate() {
+        let mut c = Checker { errors: &mut errors, index, kind: command_name(command) };
+        if let Command::Text { x, y, text, orientation, .. } = command {
+            // 竖排时多行排成多列，只有首字的基线需要落在窗口内
+            let rows = if is_vertical(orientation) { 1 } else { text.lines().count().max(1) as i32 };
+            let baseline = y.saturating_add(TEXT_BASELINE + (rows - 1).min(MAX_COORDINATE) * TEXT_LINE_HEIGHT);
+            if !text.trim().is_empty() && (*x < 0 || *x >= width || *y < 0 || baseline > height) {
+                c.fail("y", ErrorCode::TextOffscreen, format!("text at ({}, {}) has its baseline outside the {}x{} window", x, y, width, height));
+            }
+        } else if let Some((left, top, right, bottom)) = extent(command) {
+            if right < 0 || bottom < 0 || left >= width || top >= height {
+                c.fail("x", ErrorCode::OutOfBounds, format!("{} is entirely outside the {}x{} window", c.kind, width, height));
+            }
         }
-        if let Some(stroke_width) = stroke_width {
-            if *stroke_width == 0 {
-                return Err(Box::new(ValidationError("rect.stroke_width must be positive".to_string())));
+        if let Some((stroke_width, limit)) = stroke_limit(command) {
+            if stroke_width > limit {
+                let message = format!("{}.stroke_width {} exceeds the shape size (max {})", c.kind, stroke_width, limit);
+                c.fail("stroke_width", ErrorCode::StrokeTooWide, message);
             }
         }
     }
-    Ok(())
+    errors
 }
 
-fn require_i32(value: &Option<i32>, field: &str) -> Result<i32, Box<dyn Error>> {
-    value.ok_or_else(|| Box::new(ValidationError(format!("{field} is required"))) as Box<dyn Error>)
+/// 对比度检查：文字颜色与其实际背景——`bg`，否则是之前绘制、覆盖文字起点的最上层填充，
+/// 再否则是 clear 的颜色——的 WCAG 对比度低于 `MIN_TEXT_CONTRAST` 时给出警告。
+/// 背景是图片或无法确定时跳过
+pub fn check_contrast(render: &RenderEnvelope) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    for (index, color, background, ratio) in low_contrast_texts(render) {
+        let mut c = Checker { errors: &mut errors, index, kind: "text" };
+        let message = format!(
+            "text color {} on background {} has contrast {:.1}:1 (needs at least {}:1)",
+            color, background, ratio, MIN_TEXT_CONTRAST
+        );
+        c.fail("color", ErrorCode::LowContrast, message);
+    }
+    errors
 }
 
-fn require_u32(value: &Option<u32>, field: &str) -> Result<u32, Box<dyn Error>> {
-    let v = value.ok_or_else(|| Box::new(ValidationError(format!("{field} is required"))) as Box<dyn Error>)?;
-    if v == 0 {
-        return Err(Box::new(ValidationError(format!("{field} must be positive"))));
+/// 本地修正对比度不足的文字：改用与背景对比度更高的黑色或白色，返回修改的条数
+pub fn fix_contrast(render: &mut RenderEnvelope) -> usize {
+    let fixes: Vec<(usize, &'static str)> = low_contrast_texts(render)
+        .into_iter()
+        .filter_map(|(index, _, background, _)| {
+            let bg = theme::parse_rgb(&background)?;
+            let black = theme::contrast_ratio((0, 0, 0), bg);
+            let white = theme::contrast_ratio((255, 255, 255), bg);
+            Some((index, if black >= white { "#000000" } else { "#FFFFFF" }))
+        })
+        .collect();
+    for (index, fixed) in &fixes {
+        if let Some(Command::Text { color, .. }) = render.commands.get_mut(*index) {
+            *color = Some(fixed.to_string());
+        }
     }
-    Ok(v)
+    fixes.len()
 }
 
-fn require_f32(value: &Option<f32>, field: &str) -> Result<f32, Box<dyn Error>> {
-    value.ok_or_else(|| Box::new(ValidationError(format!("{field} is required"))) as Box<dyn Error>)
+/// (命令下标, 文字颜色, 背景颜色, 对比度)
+fn low_contrast_texts(render: &RenderEnvelope) -> Vec<(usize, String, String, f32)> {
+    let mut found = Vec::new();
+    for (index, command) in render.commands.iter().enumerate() {
+        let Command::Text { x, y, text, color, bg, .. } = command else {
+            continue;
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        let color = color.as_deref().unwrap_or("#000000");
+        // 取第一个字形内部的一点，避开文字框的边缘
+        let background = bg.as_deref().or_else(|| background_at(&render.commands[..index], x.saturating_add(6), y.saturating_add(16)));
+        let (Some(fg), Some(background)) = (theme::parse_rgb(color), background) else {
+            continue;
+        };
+        let Some(bg_rgb) = theme::parse_rgb(background) else {
+            continue;
+        };
+        let ratio = theme::contrast_ratio(fg, bg_rgb);
+        if ratio < MIN_TEXT_CONTRAST {
+            found.push((index, color.to_string(), background.to_string(), ratio));
+        }
+    }
+    found
 }
 
-fn validate_fill_stroke(
-    fill: &Option<String>,
-    stroke: &Option<String>,
-    stroke_width: &Option<u32>,
-    prefix: &str,
-) -> Result<(), Box<dyn Error>> {
-    if let Some(fill) = fill {
-        validate_color(fill, &format!("{prefix}.fill"))?;
+/// 文字溢出检查：按渲染字体实测的宽度（见 `renderer::measure_text`），文本越过窗口右边缘，或越过它起点所在的填充 rect / round_rect（按钮、卡片）
+pub fn check_overflow(render: &RenderEnvelope) -> Vec<ValidationError> {
+    let width = render.window.width as i32;
+    let mut errors = Vec::new();
+    for (index, command) in render.commands.iter().enumerate() {
+        let Command::Text { x, y, text, w, fit, orientation, .. } = command else {
+            continue;
+        };
+        // 竖排文字不会向右溢出
+        if is_vertical(orientation) {
+            continue;
+        }
+        let mut text_width = renderer::measure_text(text, renderer::font_size_px(), None).width;
+        // 带 fit 的文字按文字框收缩、截断或裁剪，最多占满框宽
+        if let (Some(_), Some(w)) = (fit, w) {
+            text_width = text_width.min(*w as i32);
+        }
+        if text_width <= 0 {
+            continue;
+        }
+        let right = x.saturating_add(text_width);
+        let mut c = Checker { errors: &mut errors, index, kind: "text" };
+        if *x < width && right > width {
+            let message = format!("text is about {}px wide and runs past the right edge of the {}px window", text_width, width);
+            c.fail("text", ErrorCode::TextOverflow, message);
+        } else if let Some((container, container_right)) = container_at(&render.commands[..index], x.saturating_add(1), y.saturating_add(1)) {
+            if right > container_right {
+                let message = format!(
+                    "text is about {}px wide and overflows its container commands[{}] (ends near x={}, container ends at x={})",
+                    text_width, container, right, container_right
+                );
+                c.fail("text", ErrorCode::TextOverflow, message);
+            }
+        }
     }
-    if let Some(stroke) = stroke {
-        validate_color(stroke, &format!("{prefix}.stroke"))?;
+    errors
+}
+
+/// 可点击区域重叠检查：重叠部分的点击只会落到后绘制的那个 rect 上
+pub fn check_clickables(render: &RenderEnvelope) -> Vec<ValidationError> {
+    let clickables: Vec<_> = render
+        .commands
+        .iter()
+        .enumerate()
+        .filter_map(|(index, command)| match command {
+            Command::Rect { id, clickable: true, .. } => Some((index, id.as_deref().unwrap_or_default(), extent(command)?)),
+            _ => None,
+        })
+        .collect();
+    let mut errors = Vec::new();
+    for (n, (index, id, (l, t, r, b))) in clickables.iter().enumerate() {
+        for (_, other, (ol, ot, or, ob)) in &clickables[..n] {
+            if l < or && *ol < *r && t < ob && *ot < *b {
+                let mut c = Checker { errors: &mut errors, index: *index, kind: "rect" };
+                let message = format!("clickable rect {} overlaps clickable rect {}; clicks in the overlap only reach {}", id, other, id);
+                c.fail("x", ErrorCode::OverlappingClickables, message);
+            }
+        }
     }
-    if let Some(width) = stroke_width {
-        if *width == 0 {
-            return Err(Box::new(ValidationError(format!("{prefix}.stroke_width must be positive"))));
+    errors
+}
+
+/// (x, y) 处最上层的填充 rect / round_rect：(命令下标, 右边缘)；先遇到 clear 或图片时返回 None
+fn container_at(commands: &[Command], x: i32, y: i32) -> Option<(usize, i32)> {
+    let in_box = |bx: i32, by: i32, w: u32, h: u32| x >= bx && y >= by && x < bx.saturating_add_unsigned(w) && y < by.saturating_add_unsigned(h);
+    for (index, command) in commands.iter().enumerate().rev() {
+        match command {
+            Command::Clear { .. } => return None,
+            Command::Rect { x: bx, y: by, w, h, fill: Some(_), .. } if in_box(*bx, *by, *w, *h) => return Some((index, bx.saturating_add_unsigned(*w))),
+            Command::RoundRect { x: Some(bx), y: Some(by), w: Some(w), h: Some(h), fill: Some(_), .. } if in_box(*bx, *by, *w, *h) => {
+                return Some((index, bx.saturating_add_unsigned(*w)))
+            }
+            Command::Image { x: Some(bx), y: Some(by), w: Some(w), h: Some(h), .. } if in_box(*bx, *by, *w, *h) => return None,
+            _ => {}
         }
     }
-    if fill.is_none() && stroke.is_none() {
-        return Err(Box::new(ValidationError(format!("{prefix} must have fill or stroke"))));
+    None
+}
+
+/// 在 (x, y) 处可见的背景颜色：倒序找第一个覆盖该点的填充图形或 clear；被图片覆盖时返回 None
+fn background_at(commands: &[Command], x: i32, y: i32) -> Option<&str> {
+    let in_box = |bx: i32, by: i32, w: u32, h: u32| x >= bx && y >= by && x < bx.saturating_add_unsigned(w) && y < by.saturating_add_unsigned(h);
+    let in_ellipse = |cx: i32, cy: i32, rx: u32, ry: u32| {
+        let (dx, dy) = ((x as f32 - cx as f32) / rx.max(1) as f32, (y as f32 - cy as f32) / ry.max(1) as f32);
+        dx * dx + dy * dy <= 1.0
+    };
+    for command in commands.iter().rev() {
+        match command {
+            Command::Clear { color } => return Some(color),
+            Command::Rect { x: bx, y: by, w, h, fill: Some(fill), .. } if in_box(*bx, *by, *w, *h) => return Some(fill),
+            Command::RoundRect { x: Some(bx), y: Some(by), w: Some(w), h: Some(h), fill: Some(fill), .. }
+                if in_box(*bx, *by, *w, *h) =>
+            {
+                return Some(fill)
+            }
+            Command::Circle { cx: Some(cx), cy: Some(cy), r: Some(r), fill: Some(fill), .. } if in_ellipse(*cx, *cy, *r, *r) => {
+                return Some(fill)
+            }
+            Command::Ellipse { cx: Some(cx), cy: Some(cy), rx: Some(rx), ry: Some(ry), fill: Some(fill), .. }
+                if in_ellipse(*cx, *cy, *rx, *ry) =>
+            {
+                return Some(fill)
+            }
+            Command::Image { x: Some(bx), y: Some(by), w: Some(w), h: Some(h), .. } if in_box(*bx, *by, *w, *h) => return None,
+            _ => {}
+        }
     }
-    Ok(())
+    None
 }
 
-fn validate_points(points: &Option<Vec<crate::dsl::model::Point>>, field: &str, min_len: usize) -> Result<(), Box<dyn Error>> {
-    let points = points.as_ref().ok_or_else(|| {
-        Box::new(ValidationError(format!("{field} is required"))) as Box<dyn Error>
-    })?;
-    if points.len() < min_len {
-        return Err(Box::new(ValidationError(format!("{field} must have at least {min_len} points"))));
+/// 命令的外接矩形 (left, top, right, bottom)；clear、文本、对话框（压暗整个窗口）与缺少坐标的命令返回 None
+pub fn extent(command: &Command) -> Option<(i32, i32, i32, i32)> {
+    // 模型给出的数值未经限制，加减都取饱和值，超大的尺寸由 `coordinates` 检查报告
+    let sized = |x: i32, y: i32, w: u32, h: u32| (x, y, x.saturating_add_unsigned(w), y.saturating_add_unsigned(h));
+    let around = |cx: &Option<i32>, cy: &Option<i32>, rx: u32, ry: u32| {
+        let (cx, cy) = ((*cx)?, (*cy)?);
+        Some((cx.saturating_sub_unsigned(rx), cy.saturating_sub_unsigned(ry), cx.saturating_add_unsigned(rx), cy.saturating_add_unsigned(ry)))
+    };
+    let boxed = |x: &Option<i32>, y: &Option<i32>, w: &Option<u32>, h: &Option<u32>| Some(sized((*x)?, (*y)?, (*w)?, (*h)?));
+    let spanning = |points: &mut dyn Iterator<Item = (i32, i32)>| {
+        points.fold(None, |acc: Option<(i32, i32, i32, i32)>, (x, y)| match acc {
+            None => Some((x, y, x, y)),
+            Some((l, t, r, b)) => Some((l.min(x), t.min(y), r.max(x), b.max(y))),
+        })
+    };
+    match command {
+        Command::Clear { .. } | Command::Text { .. } | Command::Dialog { .. } => None,
+        Command::Rect { x, y, w, h, .. } => Some(sized(*x, *y, *w, *h)),
+        Command::Line { x1, y1, x2, y2, .. } => Some((*x1.min(x2), *y1.min(y2), *x1.max(x2), *y1.max(y2))),
+        Command::Circle { cx, cy, r, .. } | Command::Arc { cx, cy, r, .. } => around(cx, cy, (*r)?, (*r)?),
+        Command::Ellipse { cx, cy, rx, ry, .. } => around(cx, cy, (*rx)?, (*ry)?),
+        Command::RoundRect { x, y, w, h, .. } => boxed(x, y, w, h),
+        Command::Image { x, y, w, h, .. } => boxed(x, y, w, h),
+        Command::Terminal { x, y, w, h, .. } => Some(sized(*x, *y, *w, *h)),
+        Command::Toast { frame, .. } => frame.map(|(x, y, w, h)| sized(x, y, w, h)),
+        Command::Input { x, y, w, h, .. } | Command::Slider { x, y, w, h, .. } | Command::Select { x, y, w, h, .. } => {
+            Some(sized(*x, *y, *w, *h))
+        }
+        Command::Polyline { points, .. } | Command::Polygon { points, .. } => {
+            spanning(&mut points.as_ref()?.iter().map(|p| (p.x, p.y)))
+        }
+        Command::Path { segments, .. } => spanning(&mut segments.as_ref()?.iter().filter_map(|s| Some((s.x?, s.y?)))),
     }
-    Ok(())
 }
 
-fn validate_segments(
-    segments: &Option<Vec<crate::dsl::model::PathSegment>>,
-    field: &str,
-) -> Result<(), Box<dyn Error>> {
-    let segments = segments.as_ref().ok_or_else(|| {
-        Box::new(ValidationError(format!("{field} is required"))) as Box<dyn Error>
-    })?;
-    if segments.is_empty() {
-        return Err(Box::new(ValidationError(format!("{field} must not be empty"))));
-    }
-    let mut has_move = false;
-    for seg in segments {
-        match seg.cmd.as_str() {
-            "M" | "L" => {
-                if seg.x.is_none() || seg.y.is_none() {
-                    return Err(Box::new(ValidationError(format!("{field} M/L must include x,y"))));
-                }
-                has_move = true;
+/// (描边宽度, 合理上限)：描边沿轮廓居中绘制，超过图形半宽/半径就会盖住整个图形
+fn stroke_limit(command: &Command) -> Option<(u32, u32)> {
+    match command {
+        Command::Rect { w, h, stroke_width, stroke: Some(_), .. } => Some(((*stroke_width)?, (*w).min(*h) / 2)),
+        Command::RoundRect { w, h, stroke_width, stroke: Some(_), .. } => Some(((*stroke_width)?, (*w)?.min((*h)?) / 2)),
+        Command::Circle { r, stroke_width, stroke: Some(_), .. } => Some(((*stroke_width)?, (*r)?)),
+        Command::Ellipse { rx, ry, stroke_width, stroke: Some(_), .. } => Some(((*stroke_width)?, (*rx)?.min((*ry)?))),
+        Command::Arc { r, width, .. } => Some(((*width)?, (*r)?)),
+        _ => None,
+    }
+}
+
+/// 可以交给浏览器打开的地址：允许的协议之后还有内容，且不含空白与控制字符
+pub fn is_link(href: &str) -> bool {
+    LINK_SCHEMES.iter().any(|scheme| href.len() > scheme.len() && href.get(..scheme.len()).is_some_and(|p| p.eq_ignore_ascii_case(scheme)))
+        && !href.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// text 是否竖排（`orientation: vertical`）
+pub fn is_vertical(orientation: &Option<String>) -> bool {
+    orientation.as_deref() == Some("vertical")
+}
+
+/// DSL 中的命令名（`cmd` 字段）
+pub fn command_name(command: &Command) -> &'static str {
+    match command {
+        Command::Clear { .. } => "clear",
+        Command::Rect { .. } => "rect",
+        Command::Text { .. } => "text",
+        Command::Line { .. } => "line",
+        Command::Circle { .. } => "circle",
+        Command::Ellipse { .. } => "ellipse",
+        Command::RoundRect { .. } => "round_rect",
+        Command::Arc { .. } => "arc",
+        Command::Polyline { .. } => "polyline",
+        Command::Polygon { .. } => "polygon",
+        Command::Image { .. } => "image",
+        Command::Path { .. } => "path",
+        Command::Terminal { .. } => "terminal",
+        Command::Toast { .. } => "toast",
+        Command::Dialog { .. } => "dialog",
+        Command::Input { .. } => "input",
+        Command::Slider { .. } => "slider",
+        Command::Select { .. } => "select",
+    }
+}
+
+/// `bind` 必须指向信封中声明的数据来源
+fn check_bind(c: &mut Checker, bind: &Option<String>, render: &RenderEnvelope) {
+    if let Some(bind) = bind {
+        let (id, _) = datasource::split_bind(bind);
+        if !render.data_sources.iter().any(|s| s.id == id) {
+            c.fail("bind", ErrorCode::InvalidValue, format!("bind {} does not name a data source id", bind));
+        }
+    }
+}
+
+fn check_rect(c: &mut Checker, command: &Command) {
+    if let Command::Rect { w, h, fill, stroke, stroke_width, clickable, action, form, href, run, access_key, press_style, press_color, .. } = command {
+        if *w == 0 || *h == 0 {
+            c.fail("w", ErrorCode::NotPositive, "rect must have positive size".to_string());
+        }
+        if let Some(action) = action {
+            if !RECT_ACTIONS.contains(&action.as_str()) {
+                c.fail("action", ErrorCode::UnknownAction, format!("unknown rect.action: {}", action));
             }
-            "Z" => {}
-            _ => return Err(Box::new(ValidationError(format!("{field} cmd must be M|L|Z")))),
+            if !*clickable {
+                c.fail("action", ErrorCode::InvalidValue, "rect.action requires clickable".to_string());
+            }
+        }
+        match (action.as_deref(), form) {
+            (Some("submit"), None) => c.fail("form", ErrorCode::Required, "rect.form is required for action submit".to_string()),
+            (action, Some(_)) if action != Some("submit") => {
+                c.fail("form", ErrorCode::InvalidValue, "rect.form requires action submit".to_string())
+            }
+            _ => {}
+        }
+        match (action.as_deref(), href) {
+            (Some("link"), None) => c.fail("href", ErrorCode::Required, "rect.href is required for action link".to_string()),
+            (Some("link"), Some(href)) if !is_link(href) => {
+                c.fail("href", ErrorCode::InvalidValue, "rect.href must be an absolute http(s) or mailto address".to_string())
+            }
+            (action, Some(_)) if action != Some("link") => {
+                c.fail("href", ErrorCode::InvalidValue, "rect.href requires action link".to_string())
+            }
+            _ => {}
+        }
+        match (action.as_deref(), run) {
+            (Some("run"), None) => c.fail("run", ErrorCode::Required, "rect.run is required for action run".to_string()),
+            (Some("run"), Some(name)) if !runner::is_allowed(name) => {
+                let message = format!("rect.run: {} is not an allowed command (allowed: {})", name, runner::describe());
+                c.fail("run", ErrorCode::InvalidValue, message)
+            }
+            (action, Some(_)) if action != Some("run") => c.fail("run", ErrorCode::InvalidValue, "rect.run requires action run".to_string()),
+            _ => {}
+        }
+        if let Some(key) = access_key {
+            if access_key_char(key).is_none() {
+                c.fail("access_key", ErrorCode::InvalidValue, "rect.access_key must be a single letter or digit".to_string());
+            }
+            if !*clickable {
+                c.fail("access_key", ErrorCode::InvalidValue, "rect.access_key requires clickable".to_string());
+            }
+        }
+        if let Some(style) = press_style {
+            if !PRESS_STYLES.contains(&style.as_str()) {
+                let message = format!("unknown rect.press_style: {} (expected one of {})", style, PRESS_STYLES.join(", "));
+                c.fail("press_style", ErrorCode::InvalidValue, message);
+            }
+        }
+        if (press_style.is_some() || press_color.is_some()) && !*clickable {
+            let field = if press_style.is_some() { "press_style" } else { "press_color" };
+            c.fail(field, ErrorCode::InvalidValue, format!("rect.{} requires clickable", field));
         }
+        c.optional_color(fill, "fill");
+        c.optional_color(stroke, "stroke");
+        c.optional_color(press_color, "press_color");
+        c.optional_positive(stroke_width, "stroke_width");
     }
-    if !has_move {
-        return Err(Box::new(ValidationError(format!("{field} must include M"))));
+}
+
+/// 访问键规范化为小写字符；不是单个字母或数字时返回 None
+pub fn access_key_char(key: &str) -> Option<char> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if ch.is_ascii_alphanumeric() => Some(ch.to_ascii_lowercase()),
+        _ => None,
     }
-    Ok(())
 }
 
-fn validate_color(value: &str, field: &str) -> Result<(), Box<dyn Error>> {
-    if is_hex_color(value) {
-        Ok(())
+/// 快捷键组合：修饰键加一个 X11 keysym（字母统一为小写）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub keysym: u32,
+}
+
+impl KeyChord {
+    /// 按下的键是否就是这个组合；Shift 会把字母变成大写 keysym，先折回小写再比较
+    pub fn matches(&self, keysym: u32, ctrl: bool, alt: bool, shift: bool) -> bool {
+        let keysym = match char::from_u32(keysym) {
+            Some(ch) if ch.is_ascii_uppercase() => ch.to_ascii_lowercase() as u32,
+            _ => keysym,
+        };
+        self.keysym == keysym && self.ctrl == ctrl && self.alt == alt && self.shift == shift
+    }
+}
+
+/// 快捷键里可用的具名键及其 keysym；F12 留给截图
+const NAMED_KEYS: &[(&str, u32)] = &[
+    ("enter", 0xFF0D),
+    ("esc", 0xFF1B),
+    ("escape", 0xFF1B),
+    ("tab", 0xFF09),
+    ("space", 0x20),
+    ("backspace", 0xFF08),
+    ("delete", 0xFFFF),
+    ("home", 0xFF50),
+    ("end", 0xFF57),
+    ("pageup", 0xFF55),
+    ("pagedown", 0xFF56),
+    ("left", 0xFF51),
+    ("up", 0xFF52),
+    ("right", 0xFF53),
+    ("down", 0xFF54),
+];
+
+/// 解析 `Ctrl+S`、`Ctrl+Shift+Z`、`F5` 这样的组合（不区分大小写）。
+/// 除 F1–F11 外必须带 Ctrl 或 Alt，免得与访问键和普通输入冲突；无法识别时返回 None
+pub fn shortcut_chord(keys: &str) -> Option<KeyChord> {
+    let mut chord = KeyChord { ctrl: false, alt: false, shift: false, keysym: 0 };
+    let mut parts: Vec<String> = keys.split('+').map(|p| p.trim().to_ascii_lowercase()).collect();
+    let key = parts.pop()?;
+    for part in parts {
+        let flag = match part.as_str() {
+            "ctrl" | "control" => &mut chord.ctrl,
+            "alt" => &mut chord.alt,
+            "shift" => &mut chord.shift,
+            _ => return None,
+        };
+        if *flag {
+            return None;
+        }
+        *flag = true;
+    }
+    let function = key.strip_prefix('f').and_then(|n| n.parse::<u32>().ok()).filter(|n| (1..=11).contains(n));
+    chord.keysym = if let Some(n) = function {
+        0xFFBE + n - 1
+    } else if let Some(ch) = access_key_char(&key) {
+        ch as u32
     } else {
-        Err(Box::new(ValidationError(format!(
-            "{field} must be #RRGGBB"
-        ))))
+        NAMED_KEYS.iter().find(|(name, _)| *name == key)?.1
+    };
+    (function.is_some() || chord.ctrl || chord.alt).then_some(chord)
+}
+
+/// 单条命令的检查上下文：`index` 是命令下标，`kind` 是命令名（出现在错误信息里，如 `circle.r`）
+struct Checker<'a> {
+    errors: &'a mut Vec<ValidationError>,
+    index: usize,
+    kind: &'static str,
+}
+
+impl Checker<'_> {
+    fn semantics(&mut self, command: &Command) {
+        let (role, label) = match command {
+            Command::Rect { role, label, .. }
+            | Command::RoundRect { role, label, .. }
+            | Command::Text { role, label, .. }
+            | Command::Image { role, label, .. } => (role, label),
+            _ => return,
+        };
+        if let Some(role) = role {
+            if !ROLES.contains(&role.as_str()) {
+                let message = format!("unknown {}.role: {} (expected one of {})", self.kind, role, ROLES.join(", "));
+                self.fail("role", ErrorCode::InvalidValue, message);
+            } else if role == "button" && !matches!(command, Command::Rect { clickable: true, .. }) {
+                self.fail("role", ErrorCode::InvalidValue, "role button requires a clickable rect".to_string());
+            }
+        }
+        if label.as_deref().is_some_and(|l| l.trim().is_empty()) {
+            self.fail("label", ErrorCode::InvalidValue, format!("{}.label must not be empty", self.kind));
+        }
+    }
+
+    fn coordinates(&mut self, command: &Command) {
+        let (left, top, right, bottom) = match command {
+            Command::Text { x, y, .. } => (*x, *y, *x, *y),
+            _ => match extent(command) {
+                Some(extent) => extent,
+                None => return,
+            },
+        };
+        if [left, top, right, bottom].iter().any(|v| v.unsigned_abs() > MAX_COORDINATE as u32) {
+            let message = format!("{} extends beyond ±{} px", self.kind, MAX_COORDINATE);
+            self.fail("x", ErrorCode::LimitExceeded, message);
+        }
+    }
+
+    fn animations(&mut self, command: &Command) {
+        let (enter, exit) = match command {
+            Command::Rect { enter, exit, .. }
+            | Command::RoundRect { enter, exit, .. }
+            | Command::Text { enter, exit, .. }
+            | Command::Image { enter, exit, .. } => (enter, exit),
+            _ => return,
+        };
+        for (field, preset) in [("enter", enter), ("exit", exit)] {
+            if let Some(preset) = preset.as_deref().filter(|p| !ANIMATIONS.contains(p)) {
+                let message = format!("unknown {}.{}: {} (expected one of {})", self.kind, field, preset, ANIMATIONS.join(", "));
+                self.fail(field, ErrorCode::InvalidValue, message);
+            }
+        }
+    }
+
+    fn fail(&mut self, field: &str, code: ErrorCode, message: String) {
+        self.errors.push(ValidationError::Command { index: self.index, field: field.to_string(), code, message });
+    }
+
+    fn require<T>(&mut self, value: &Option<T>, field: &str) {
+        if value.is_none() {
+            self.fail(field, ErrorCode::Required, format!("{}.{} is required", self.kind, field));
+        }
+    }
+
+    fn require_positive(&mut self, value: &Option<u32>, field: &str) {
+        match value {
+            None => self.fail(field, ErrorCode::Required, format!("{}.{} is required", self.kind, field)),
+            Some(0) => self.fail(field, ErrorCode::NotPositive, format!("{}.{} must be positive", self.kind, field)),
+            Some(_) => {}
+        }
+    }
+
+    fn optional_positive(&mut self, value: &Option<u32>, field: &str) {
+        if *value == Some(0) {
+            self.fail(field, ErrorCode::NotPositive, format!("{}.{} must be positive", self.kind, field));
+        }
+    }
+
+    fn color(&mut self, value: &str, field: &str) {
+        if !is_hex_color(value) && !theme::is_token(value) {
+            self.fail(field, ErrorCode::InvalidColor, format!("{}.{} must be #RRGGBB or a theme token", self.kind, field));
+        }
+    }
+
+    fn optional_color(&mut self, value: &Option<String>, field: &str) {
+        if let Some(value) = value {
+            self.color(value, field);
+        }
+    }
+
+    fn fill_stroke(&mut self, fill: &Option<String>, stroke: &Option<String>, stroke_width: &Option<u32>) {
+        self.optional_color(fill, "fill");
+        self.optional_color(stroke, "stroke");
+        self.optional_positive(stroke_width, "stroke_width");
+        if fill.is_none() && stroke.is_none() {
+            self.fail("fill", ErrorCode::MissingPaint, format!("{} must have fill or stroke", self.kind));
+        }
+    }
+
+    fn points(&mut self, points: &Option<Vec<Point>>, min_len: usize) {
+        match points {
+            None => self.fail("points", ErrorCode::Required, format!("{}.points is required", self.kind)),
+            Some(points) if points.len() < min_len => self.fail(
+                "points",
+                ErrorCode::TooFewPoints,
+                format!("{}.points must have at least {min_len} points", self.kind),
+            ),
+            Some(_) => {}
+        }
+    }
+
+    fn max_points(&mut self, count: Option<usize>, field: &str, max: usize) {
+        if let Some(count) = count.filter(|n| *n > max) {
+            self.fail(field, ErrorCode::LimitExceeded, format!("{}.{} has {} entries, exceeding the limit of {}", self.kind, field, count, max));
+        }
+    }
+
+    fn segments(&mut self, segments: &Option<Vec<PathSegment>>) {
+        let field = format!("{}.segments", self.kind);
+        let Some(segments) = segments else {
+            return self.fail("segments", ErrorCode::Required, format!("{field} is required"));
+        };
+        if segments.is_empty() {
+            return self.fail("segments", ErrorCode::TooFewPoints, format!("{field} must not be empty"));
+        }
+        let mut has_move = false;
+        for (i, seg) in segments.iter().enumerate() {
+            match seg.cmd.as_str() {
+                "M" | "L" => {
+                    if seg.x.is_none() || seg.y.is_none() {
+                        self.fail(&format!("segments[{i}]"), ErrorCode::Required, format!("{field} M/L must include x,y"));
+                    }
+                    has_move = true;
+                }
+                "Z" => {}
+                _ => self.fail(&format!("segments[{i}].cmd"), ErrorCode::InvalidValue, format!("{field} cmd must be M|L|Z")),
+            }
+        }
+        if !has_move {
+            self.fail("segments", ErrorCode::InvalidValue, format!("{field} must include M"));
+        }
     }
 }
 
@@ -284,8 +1145,5 @@ fn is_hex_color(value: &str) -> bool {
     if bytes.len() != 7 || bytes[0] != b'#' {
         return false;
     }
-    bytes[1..].iter().all(|b| match b {
-        b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F' => true,
-        _ => false,
-    })
+    bytes[1..].iter().all(|b| b.is_ascii_hexdigit())
 }
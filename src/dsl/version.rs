@@ -0,0 +1,37 @@
+//! DSL 版本协商：解析时按信封的 `version` 分派，旧版本逐级升级成当前版本的结构，
+//! 校验器与渲染器只需要理解当前版本。新增版本时在 `SUPPORTED_VERSIONS` 末尾追加，
+//! 并为上一版本写一个升级函数。
+
+use std::error::Error;
+use serde_json::{json, Value};
+
+/// 当前版本：模型被要求输出、渲染器直接理解的版本
+pub const CURRENT_VERSION: &str = "AGD/0.2";
+
+/// 能解析的全部版本，从旧到新
+pub const SUPPORTED_VERSIONS: &[&str] = &["AGD/0.1", CURRENT_VERSION];
+
+/// 把任意受支持版本的信封升级为当前版本；没有 `version` 字段时原样返回，由反序列化报告缺失字段
+pub fn upgrade(mut value: Value) -> Result<Value, Box<dyn Error>> {
+    loop {
+        let Some(version) = value.get("version").and_then(Value::as_str) else {
+            return Ok(value);
+        };
+        value = match version {
+            CURRENT_VERSION => return Ok(value),
+            "AGD/0.1" => upgrade_0_1(value),
+            other => {
+                return Err(format!("unsupported version: {} (supported: {})", other, SUPPORTED_VERSIONS.join(", ")).into());
+            }
+        };
+    }
+}
+
+/// AGD/0.1 没有 `type` 字段（信封只有 render 一种）；0.2 新增的字段都有默认值，其余结构相同
+fn upgrade_0_1(mut value: Value) -> Value {
+    if let Some(envelope) = value.as_object_mut() {
+        envelope.entry("type").or_insert_with(|| json!("render"));
+        envelope.insert("version".to_string(), json!("AGD/0.2"));
+    }
+    value
+}
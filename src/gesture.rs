@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+
+use crate::display::GestureEvent;
+
+/// 主方向移动至少这么远（像素）才算滑动
+const SWIPE_MIN_DISTANCE: i32 = 80;
+/// 快速甩动（flick）距离可以更短，但松开前的速度要够快
+const FLICK_MIN_DISTANCE: i32 = 24;
+/// 超过这个速度（像素/秒）算 flick
+pub const FLICK_MIN_VELOCITY: f32 = 800.0;
+/// 按住超过这个时长（毫秒）再松开是慢速拖动，不算手势
+const SWIPE_MAX_DURATION_MS: u32 = 800;
+/// 速度按松开前这段时间（毫秒）内的移动计算，反映手指离开时的快慢
+const VELOCITY_WINDOW_MS: u32 = 100;
+
+/// 滑动方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwipeDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl SwipeDirection {
+    pub fn name(self) -> &'static str {
+        match self {
+            SwipeDirection::Left => "left",
+            SwipeDirection::Right => "right",
+            SwipeDirection::Up => "up",
+            SwipeDirection::Down => "down",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(SwipeDirection::Left),
+            "right" => Some(SwipeDirection::Right),
+            "up" => Some(SwipeDirection::Up),
+            "down" => Some(SwipeDirection::Down),
+            _ => None,
+        }
+    }
+}
+
+/// 按下到松开之间的指针轨迹；时间戳取自窗口系统（毫秒，可能回绕）
+#[derive(Debug, Default)]
+pub struct GestureTracker {
+    start: Option<(i32, i32, u32)>,
+    /// 最近 VELOCITY_WINDOW_MS 内的移动采样
+    recent: VecDeque<(i32, i32, u32)>,
+}
+
+impl GestureTracker {
+    pub fn press(&mut self, x: i32, y: i32, time: u32) {
+        self.start = Some((x, y, time));
+        self.recent.clear();
+        self.recent.push_back((x, y, time));
+    }
+
+    pub fn motion(&mut self, x: i32, y: i32, time: u32) {
+        if self.start.is_none() {
+            return;
+        }
+        self.recent.push_back((x, y, time));
+        while self.recent.len() > 1 && time.wrapping_sub(self.recent[0].2) > VELOCITY_WINDOW_MS {
+            self.recent.pop_front();
+        }
+    }
+
+    /// 松开时判断这次按下是否构成滑动：主方向明显（至少是另一方向的两倍）、距离与时长符合要求
+    pub fn release(&mut self, x: i32, y: i32, time: u32) -> Option<GestureEvent> {
+        let (sx, sy, st) = self.start.take()?;
+        let (dx, dy) = (x - sx, y - sy);
+        let (primary, secondary) = (dx.abs().max(dy.abs()), dx.abs().min(dy.abs()));
+        if time.wrapping_sub(st) > SWIPE_MAX_DURATION_MS || primary < FLICK_MIN_DISTANCE || secondary * 2 > primary {
+            return None;
+        }
+        let direction = match (dx.abs() >= dy.abs(), dx > 0, dy > 0) {
+            (true, true, _) => SwipeDirection::Right,
+            (true, false, _) => SwipeDirection::Left,
+            (false, _, true) => SwipeDirection::Down,
+            (false, _, false) => SwipeDirection::Up,
+        };
+        let (ox, oy, ot) = self.recent.front().copied().unwrap_or((sx, sy, st));
+        let along = match direction {
+            SwipeDirection::Left | SwipeDirection::Right => (x - ox).abs(),
+            SwipeDirection::Up | SwipeDirection::Down => (y - oy).abs(),
+        };
+        let velocity = along as f32 * 1000.0 / time.wrapping_sub(ot).max(1) as f32;
+        if primary < SWIPE_MIN_DISTANCE && velocity < FLICK_MIN_VELOCITY {
+            return None;
+        }
+        Some(GestureEvent { x: sx, y: sy, direction, velocity })
+    }
+}
@@ -0,0 +1,81 @@
+//! 本地绘制的界面文字（错误画面、链接确认卡片、对话框的默认按钮、状态条与横幅、HUD）与启动提示的多语言目录。
+//! 界面语言由 `language` 配置决定，`auto` 时跟随用户的语言区域；目前提供英文与中文，
+//! 中文目录缺少的条目回退到英文。
+
+use crate::config;
+use crate::sysinfo;
+
+/// 可选的界面语言；`auto` 按语言区域在其中选择，没有对应目录时用英文
+pub const LANGUAGES: &[&str] = &["en", "zh"];
+
+const EN: &[(&str, &str)] = &[
+    ("bridge.active", "AGD UI Bridge active."),
+    ("loading.thinking", "thinking{dots} {secs}s"),
+    ("banner.budget", "Shown before review finished ({secs}s time budget)"),
+    ("link.title", "Open this link in your browser?"),
+    ("link.open", "Open"),
+    ("link.open_label", "Open the link"),
+    ("link.cancel", "Cancel"),
+    ("dialog.confirm", "OK"),
+    ("dialog.cancel", "Cancel"),
+    ("error.title", "Something went wrong"),
+    ("error.retry", "Retry"),
+    ("error.retry_label", "Retry the last request"),
+    ("error.back", "Back"),
+    ("error.back_label", "Back to the previous screen"),
+    ("hud.frame", "frame"),
+    ("hud.llm", "llm"),
+    ("hud.iter", "iter"),
+    ("hud.tokens", "tok"),
+    ("hud.cost", "cost"),
+];
+
+/// 按钮文字后的括号字母是访问键，中文标签里没有这个字母，按 Windows 的习惯写在括号里才能带下划线
+const ZH: &[(&str, &str)] = &[
+    ("bridge.active", "AGD UI Bridge 已启动。"),
+    ("loading.thinking", "思考中{dots} {secs} 秒"),
+    ("banner.budget", "评审未完成即显示（时间预算 {secs} 秒）"),
+    ("link.title", "在浏览器中打开此链接？"),
+    ("link.open", "打开(O)"),
+    ("link.open_label", "打开链接"),
+    ("link.cancel", "取消(C)"),
+    ("dialog.confirm", "确定"),
+    ("dialog.cancel", "取消"),
+    ("error.title", "出错了"),
+    ("error.retry", "重试(R)"),
+    ("error.retry_label", "重试上一次请求"),
+    ("error.back", "返回(B)"),
+    ("error.back_label", "返回上一个画面"),
+    ("hud.frame", "渲染"),
+    ("hud.llm", "模型"),
+    ("hud.iter", "迭代"),
+    ("hud.tokens", "令牌"),
+    ("hud.cost", "花费"),
+];
+
+/// 当前界面语言；每次按配置判断，切换配置档案后立即生效
+pub fn language() -> &'static str {
+    let wanted = match config::get().language.as_str() {
+        "auto" => sysinfo::get().locale.language.clone(),
+        other => other.to_ascii_lowercase(),
+    };
+    LANGUAGES.iter().copied().find(|l| *l == wanted).unwrap_or("en")
+}
+
+/// 当前语言的文字；目录里没有时回退到英文，英文也没有时返回键名本身
+pub fn text(key: &'static str) -> &'static str {
+    let catalog = match language() {
+        "zh" => ZH,
+        _ => EN,
+    };
+    lookup(catalog, key).or_else(|| lookup(EN, key)).unwrap_or(key)
+}
+
+/// 带参数的文字：把 `{name}` 换成对应的值
+pub fn text_with(key: &'static str, args: &[(&str, String)]) -> String {
+    args.iter().fold(text(key).to_string(), |acc, (name, value)| acc.replace(&format!("{{{}}}", name), value))
+}
+
+fn lookup(catalog: &[(&str, &'static str)], key: &str) -> Option<&'static str> {
+    catalog.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+}
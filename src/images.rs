@@ -0,0 +1,209 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use base64::{Engine as _, engine::general_purpose};
+use image::DynamicImage;
+use reqwest::blocking::Client;
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use tracing::{debug, warn};
+
+use crate::assets;
+use crate::config;
+use crate::dsl::model::{Command, RenderEnvelope};
+
+/// 跟随重定向的最多次数；每一跳的目标同样要在白名单内
+const MAX_REDIRECTS: usize = 5;
+/// 进程内缓存的 URL 数，超出时整体清空
+const MAX_CACHED_URLS: usize = 64;
+
+/// 下载结果（包括失败）按 URL 缓存：同一画面每次重绘都会加载图片，不能每帧都发请求
+type FetchResult = Result<Arc<Vec<u8>>, String>;
+
+/// 后台下载完成了新的图片，界面需要重画
+static ARRIVED: AtomicBool = AtomicBool::new(false);
+
+/// 按 `src_type` 加载 image 命令的图片。远程图片只从缓存里取，绘制时不等网络：
+/// 还没下载过的在后台开始下载并返回 None，下载失败的同样返回 None，调用方画占位框
+pub fn load(src_type: &str, src: &str) -> Result<Option<DynamicImage>, Box<dyn Error>> {
+    match src_type {
+        "path" => Ok(Some(image::open(resolve_path(src)?)?)),
+        "base64" => {
+            let bytes = general_purpose::STANDARD.decode(src.as_bytes())?;
+            Ok(Some(image::load_from_memory(&bytes)?))
+        }
+        "url" => match cached(src) {
+            Some(Ok(bytes)) => Ok(Some(image::load_from_memory(&bytes)?)),
+            Some(Err(_)) => Ok(None),
+            None => {
+                fetch_in_background(src);
+                Ok(None)
+            }
+        },
+        _ => Err("unsupported image src_type".into()),
+    }
+}
+
+/// 下载画面里还没缓存的远程图片（阻塞）。生成任务在工作线程上、呈现之前调用，
+/// 画面上屏时图片已经就绪
+pub fn prefetch(render: &RenderEnvelope) {
+    for command in &render.commands {
+        if let Command::Image { src_type: Some(src_type), src: Some(src), .. } = command {
+            if src_type == "url" && cached(src).is_none() {
+                let _ = fetch(src);
+            }
+        }
+    }
+}
+
+/// 上次调用之后是否有后台下载完成；有则界面应重画，把占位框换成图片
+pub fn take_arrived() -> bool {
+    ARRIVED.swap(false, Ordering::SeqCst)
+}
+
+/// 解码一张图片文件的字节（PNG、JPEG 等），转成 BGRA 像素，返回 (宽, 高, 像素)
+pub fn decode_bgra(bytes: &[u8]) -> Result<(usize, usize, Vec<u8>), Box<dyn Error>> {
+    let image = image::load_from_memory(bytes)?.to_rgba8();
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut pixels = image.into_raw();
+    for px in pixels.chunks_exact_mut(4) {
+        px.swap(0, 2);
+    }
+    Ok((width, height, pixels))
+}
+
+/// 把 `src_type: "path"` 的路径解析到资源目录 `images.assets_dir` 内；`asset:名称` 按资源清单查找。
+/// 拒绝含 `..` 的路径、目录外的绝对路径，以及经符号链接指向目录外的文件：
+/// 模型臆造的路径不能读取进程能访问的任意文件
+pub fn resolve_path(src: &str) -> Result<PathBuf, String> {
+    if let Some(name) = src.strip_prefix(assets::ASSET_PREFIX) {
+        let asset = assets::find(name).ok_or_else(|| format!("unknown asset {} (not in the asset manifest)", name))?;
+        return resolve_path(&asset.file);
+    }
+    resolve_under(&config::get().images.assets_dir, src, "assets directory")
+}
+
+/// 把相对路径解析到目录 `root_dir` 内，`what` 是错误信息里对该目录的称呼；规则同 `resolve_path`
+pub fn resolve_under(root_dir: &str, src: &str, what: &str) -> Result<PathBuf, String> {
+    let root = fs::canonicalize(root_dir).map_err(|e| format!("{} {} is not available: {}", what, root_dir, e))?;
+    let path = Path::new(src);
+    if path.components().any(|c| c == Component::ParentDir) {
+        return Err(format!("path {} must not use .. to leave the {} {}", src, what, root_dir));
+    }
+    let outside = || format!("path {} is outside the {} {}", src, what, root_dir);
+    if path.is_absolute() && !path.starts_with(&root) {
+        return Err(outside());
+    }
+    let resolved = fs::canonicalize(root.join(path)).map_err(|_| format!("{} not found in the {} {}", src, what, root_dir))?;
+    if !resolved.starts_with(&root) {
+        return Err(outside());
+    }
+    Ok(resolved)
+}
+
+/// 检查 URL 是否允许加载：只接受 http(s)，主机必须是 `images.allowed_domains` 中的域名或其子域名。
+/// 校验器与下载（包括重定向）共用
+pub fn check_url(src: &str) -> Result<(), String> {
+    let url = Url::parse(src).map_err(|e| format!("invalid URL: {}", e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("unsupported URL scheme {}", url.scheme()));
+    }
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    if config::get().images.allowed_domains.iter().any(|domain| domain_matches(&host, domain)) {
+        Ok(())
+    } else {
+        Err(format!("host {} is not in images.allowed_domains", host))
+    }
+}
+
+/// 给生成提示词的可用远程图片主机
+pub fn describe_hosts() -> String {
+    let domains = &config::get().images.allowed_domains;
+    if domains.is_empty() {
+        "none (remote images are disabled)".to_string()
+    } else {
+        domains.join(", ")
+    }
+}
+
+/// `*.example.com` 与 `example.com` 等价，都匹配该域名及其子域名
+pub fn domain_matches(host: &str, domain: &str) -> bool {
+    let domain = domain.trim().trim_start_matches("*.").to_ascii_lowercase();
+    !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+}
+
+fn cache() -> &'static Mutex<HashMap<String, FetchResult>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, FetchResult>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn cached(src: &str) -> Option<FetchResult> {
+    cache().lock().unwrap().get(src).cloned()
+}
+
+/// 在后台线程下载，同一 URL 同时只下载一次
+fn fetch_in_background(src: &str) {
+    static IN_FLIGHT: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    let in_flight = IN_FLIGHT.get_or_init(|| Mutex::new(HashSet::new()));
+    if !in_flight.lock().unwrap().insert(src.to_string()) {
+        return;
+    }
+    let src = src.to_string();
+    thread::spawn(move || {
+        let _ = fetch(&src);
+        in_flight.lock().unwrap().remove(&src);
+        ARRIVED.store(true, Ordering::SeqCst);
+    });
+}
+
+fn fetch(src: &str) -> Result<Arc<Vec<u8>>, Box<dyn Error>> {
+    if let Some(hit) = cached(src) {
+        return hit.map_err(Into::into);
+    }
+    let result = download(src).map(Arc::new).map_err(|e| e.to_string());
+    match &result {
+        Ok(bytes) => debug!("fetched image {} ({} bytes)", src, bytes.len()),
+        Err(e) => warn!("failed to fetch image {}: {}", src, e),
+    }
+    let mut cache = cache().lock().unwrap();
+    if cache.len() >= MAX_CACHED_URLS {
+        cache.clear();
+    }
+    cache.insert(src.to_string(), result.clone());
+    result.map_err(Into::into)
+}
+
+fn download(src: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    check_url(src)?;
+    let images = &config::get().images;
+    let client = Client::builder()
+        .timeout(Duration::from_secs(images.timeout_secs))
+        .redirect(Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if let Err(e) = check_url(attempt.url().as_str()) {
+                attempt.error(format!("redirect rejected: {}", e))
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()?;
+    let response = client.get(src).send()?.error_for_status()?;
+    if let Some(length) = response.content_length().filter(|l| *l > images.max_bytes as u64) {
+        return Err(format!("image is {} bytes, exceeding images.max_bytes ({})", length, images.max_bytes).into());
+    }
+    // 没有 Content-Length 或声明不实时，读到上限多一个字节即停止
+    let mut bytes = Vec::new();
+    response.take(images.max_bytes as u64 + 1).read_to_end(&mut bytes)?;
+    if bytes.len() > images.max_bytes {
+        return Err(format!("image exceeds images.max_bytes ({})", images.max_bytes).into());
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,336 @@
+//! `input` 命令：单行文字输入框。点击获得键盘焦点，输入的文字只保存在编排器本地，
+//! 随所在表单的 submit 事件作为字段发给模型；界面快照里只有显示用的内容。
+//! `mask` 为 true 的输入框画成等长的圆点，提交时默认把值打码（见 `redact_masked_inputs` 配置）。
+//!
+//! Backspace 删除最后一个字符，Enter 按下所在表单的提交按钮，Esc / Tab 取消焦点；
+//! 带 Ctrl / Alt 的按键不拦截，快捷键照常可用。
+//!
+//! 同样只在本地保存值的还有 `slider`（点击轨道取该位置对应的值）与 `select`（点击切换到下一个选项），
+//! 它们的值随表单提交，界面快照里只有画出来的位置与选项。
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use crate::display::KeyEvent;
+use crate::dsl::builder::Render;
+use crate::dsl::model::{Command, RenderEnvelope};
+use crate::state::form;
+use crate::x11::renderer;
+
+/// 密码框显示的字符
+const BULLET: char = '•';
+/// 一个输入框最多保存的字符数
+const MAX_CHARS: usize = 256;
+/// 文字与边框的水平间距
+const PADDING: i32 = 10;
+const FONT_SIZE: f32 = 24.0;
+
+/// 各输入框的内容与键盘焦点，以及用户调整过的滑块与选择框的值
+#[derive(Default)]
+pub struct Inputs {
+    values: HashMap<String, String>,
+    focused: Option<String>,
+    sliders: HashMap<String, f64>,
+    selects: HashMap<String, usize>,
+}
+
+/// 输入框接收按键后的结果
+pub enum KeyOutcome {
+    /// 内容或焦点变了，需要重画
+    Edited,
+    /// Enter：等同于点击这个位置（所在表单提交按钮的中心）
+    Submit(i32, i32),
+}
+
+impl Inputs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 界面更新后丢掉已不在画面上的输入框的内容与焦点，以及不在画面上（或选项已变少）的滑块与选择框的值
+    pub fn sync(&mut self, render: &RenderEnvelope) {
+        let ids: HashSet<&str> = render
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                Command::Input { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        self.values.retain(|id, _| ids.contains(id.as_str()));
+        if self.focused.as_deref().is_some_and(|id| !ids.contains(id)) {
+            self.focused = None;
+        }
+        let sliders: HashSet<&str> = render
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                Command::Slider { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        self.sliders.retain(|id, _| sliders.contains(id.as_str()));
+        let selects: HashMap<&str, usize> = render
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                Command::Select { id, options, .. } => Some((id.as_str(), options.len())),
+                _ => None,
+            })
+            .collect();
+        self.selects.retain(|id, index| selects.get(id.as_str()).is_some_and(|len| *index < *len));
+    }
+
+    /// 把显示内容（`mask` 时是圆点）、焦点与滑块、选择框的当前值填进界面；返回是否有变化
+    pub fn fill(&self, render: &mut RenderEnvelope) -> bool {
+        let mut changed = false;
+        for command in render.commands.iter_mut() {
+            match command {
+                Command::Input { id, mask, shown, focused, .. } => {
+                    let value = self.values.get(id.as_str()).filter(|v| !v.is_empty());
+                    let next_shown = value.map(|v| if *mask { BULLET.to_string().repeat(v.chars().count()) } else { v.clone() });
+                    let next_focused = self.focused.as_deref() == Some(id.as_str());
+                    if *shown != next_shown || *focused != next_focused {
+                        *shown = next_shown;
+                        *focused = next_focused;
+                        changed = true;
+                    }
+                }
+                Command::Slider { id, current, .. } => {
+                    let next = self.sliders.get(id.as_str()).copied();
+                    changed |= *current != next;
+                    *current = next;
+                }
+                Command::Select { id, current, .. } => {
+                    let next = self.selects.get(id.as_str()).copied();
+                    changed |= *current != next;
+                    *current = next;
+                }
+                _ => {}
+            }
+        }
+        changed
+    }
+
+    /// 处理落在控件上的点击并返回 true：输入框获得焦点，滑块取点击位置对应的值，选择框切换到下一个选项。
+    /// 点在别处（包括滑块与选择框）时输入框失去焦点
+    pub fn click_at(&mut self, render: &RenderEnvelope, x: i32, y: i32) -> bool {
+        let hit = render.commands.iter().rev().find(|c| match c {
+            Command::Input { x: cx, y: cy, w, h, .. } | Command::Slider { x: cx, y: cy, w, h, .. } | Command::Select { x: cx, y: cy, w, h, .. } => {
+                x >= *cx && y >= *cy && x < cx + *w as i32 && y < cy + *h as i32
+            }
+            _ => false,
+        });
+        self.focused = None;
+        match hit {
+            Some(Command::Input { id, .. }) => self.focused = Some(id.clone()),
+            Some(Command::Slider { id, x: sx, w, min, max, step, .. }) => {
+                let (min, max) = (min.unwrap_or(0.0), max.unwrap_or(100.0));
+                let ratio = ((x - sx) as f64 / (*w as f64 - 1.0).max(1.0)).clamp(0.0, 1.0);
+                let mut value = min + ratio * (max - min);
+                if let Some(step) = step.filter(|s| *s > 0.0) {
+                    value = (min + ((value - min) / step).round() * step).min(max);
+                }
+                self.sliders.insert(id.clone(), value);
+            }
+            Some(command @ Command::Select { id, options, .. }) => {
+                let next = selected_index(command).map_or(0, |i| (i + 1) % options.len().max(1));
+                self.selects.insert(id.clone(), next);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// 有焦点时处理按键；没有焦点或按键不归输入框（带 Ctrl / Alt、功能键）时返回 None
+    pub fn send_key(&mut self, render: &RenderEnvelope, key: &KeyEvent) -> Option<KeyOutcome> {
+        let id = self.focused.clone()?;
+        if key.ctrl || key.alt {
+            return None;
+        }
+        match key.keysym {
+            0xFF08 => {
+                self.values.entry(id).or_default().pop();
+                Some(KeyOutcome::Edited)
+            }
+            0xFF0D | 0xFF8D => {
+                let (x, y) = render.commands.iter().find_map(|c| match c {
+                    Command::Input { id: input, x, y, .. } if *input == id => Some((*x, *y)),
+                    _ => None,
+                })?;
+                Some(match form::submitter_at(render, x, y) {
+                    Some((bx, by)) => KeyOutcome::Submit(bx, by),
+                    None => KeyOutcome::Edited,
+                })
+            }
+            // Esc、Tab 与 Shift+Tab
+            0xFF1B | 0xFF09 | 0xFE20 => {
+                self.focused = None;
+                Some(KeyOutcome::Edited)
+            }
+            keysym => {
+                // Latin-1 范围的 keysym 就是字符码，其余 Unicode 字符的 keysym 为 0x01000000 + 码位
+                let code = match keysym {
+                    k @ 0x20..=0xFF => k,
+                    k @ 0x0100_0100..=0x0110_FFFF => k - 0x0100_0000,
+                    _ => return None,
+                };
+                let value = self.values.entry(id).or_default();
+                if let Some(c) = char::from_u32(code).filter(|_| value.chars().count() < MAX_CHARS) {
+                    value.push(c);
+                }
+                Some(KeyOutcome::Edited)
+            }
+        }
+    }
+
+    /// 输入框的真实内容
+    pub fn value(&self, id: &str) -> &str {
+        self.values.get(id).map_or("", String::as_str)
+    }
+}
+
+/// 滑块的当前值：用户调整过的值，其次是模型给的初始值，都没有时为 `min`
+pub fn slider_value(command: &Command) -> Option<f64> {
+    match command {
+        Command::Slider { min, value, current, .. } => Some(current.or(*value).unwrap_or(min.unwrap_or(0.0))),
+        _ => None,
+    }
+}
+
+/// 选择框当前选中项的下标；没有选项时为 None
+pub fn selected_index(command: &Command) -> Option<usize> {
+    match command {
+        Command::Select { options, selected, current, .. } => {
+            Some(current.or(*selected).unwrap_or(0)).filter(|i| *i < options.len())
+        }
+        _ => None,
+    }
+}
+
+/// 选择框当前选中的选项文字
+pub fn selected_option(command: &Command) -> Option<&str> {
+    match command {
+        Command::Select { options, .. } => selected_index(command).map(|i| options[i].as_str()),
+        _ => None,
+    }
+}
+
+/// 把输入框、滑块与选择框换成边框、文字、光标等图元交给光栅化器；没有这些控件时原样借用。
+/// 输入框内容放不下时只显示末尾，光标始终可见
+pub fn expand(render: &RenderEnvelope) -> Cow<'_, RenderEnvelope> {
+    if !render.commands.iter().any(|c| matches!(c, Command::Input { .. } | Command::Slider { .. } | Command::Select { .. })) {
+        return Cow::Borrowed(render);
+    }
+    let mut base = render.clone();
+    let commands = std::mem::take(&mut base.commands);
+    let mut out = Render::from(base);
+    for command in commands {
+        match &command {
+            Command::Slider { x, y, w, h, min, max, .. } => {
+                let (min, max) = (min.unwrap_or(0.0), max.unwrap_or(100.0));
+                let value = slider_value(&command).unwrap_or(min);
+                let ratio = if max > min { ((value - min) / (max - min)).clamp(0.0, 1.0) } else { 0.0 };
+                let (x, w) = (*x, *w);
+                let cy = y + *h as i32 / 2;
+                // 圆钮整个留在滑块范围内
+                let r = (*h / 2).clamp(1, 10).min(w / 2).max(1);
+                let knob_x = x + r as i32 + (ratio * w.saturating_sub(2 * r) as f64).round() as i32;
+                out = out
+                    .round_rect(x, cy - 3, w, 6, 3, "@border")
+                    .round_rect(x, cy - 3, (knob_x - x).max(0) as u32, 6, 3, "@primary")
+                    .circle(knob_x, cy, r, "@primary");
+                continue;
+            }
+            Command::Select { x, y, w, h, .. } => {
+                let (x, y, w, h) = (*x, *y, *w, *h);
+                let text_y = y + (h as i32 - 30) / 2;
+                out = out.round_rect(x, y, w, h, 6, "@surface").stroke("@border", 1);
+                if let Some(option) = selected_option(&command) {
+                    out = out.text(x + PADDING, text_y, option, "@text").fit(w.saturating_sub(2 * PADDING as u32 + 24), h, "ellipsis");
+                }
+                out = out.text(x + w as i32 - PADDING - 20, text_y, "▾", "@muted");
+                continue;
+            }
+            _ => {}
+        }
+        let Command::Input { x, y, w, h, placeholder, shown, focused, .. } = &command else {
+            out = out.push(command);
+            continue;
+        };
+        let (x, y, w, h) = (*x, *y, *w, *h);
+        let inner_w = w.saturating_sub(2 * PADDING as u32);
+        let text_y = y + (h as i32 - 30) / 2;
+        out = out.round_rect(x, y, w, h, 6, "@surface").stroke(if *focused { "@primary" } else { "@border" }, if *focused { 2 } else { 1 });
+        let mut caret_x = x + PADDING;
+        match shown {
+            Some(shown) => {
+                let visible = tail_fitting(shown, inner_w.saturating_sub(2) as i32);
+                caret_x += renderer::measure_text(&visible, FONT_SIZE, None).width;
+                out = out.text(x + PADDING, text_y, visible, "@text").fit(inner_w, h, "clip");
+            }
+            None => {
+                if let Some(placeholder) = placeholder {
+                    out = out.text(x + PADDING, text_y, placeholder.clone(), "@muted").fit(inner_w, h, "ellipsis");
+                }
+            }
+        }
+        if *focused {
+            out = out.rect(caret_x + 1, text_y + 2, 2, 26, "@text");
+        }
+    }
+    Cow::Owned(out.build())
+}
+
+/// 宽度不超过 `width` 的最长后缀
+fn tail_fitting(text: &str, width: i32) -> String {
+    if renderer::measure_text(text, FONT_SIZE, None).width <= width {
+        return text.to_string();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let mut start = 0;
+    while start < chars.len() {
+        let tail: String = chars[start..].iter().collect();
+        if renderer::measure_text(&tail, FONT_SIZE, None).width <= width {
+            return tail;
+        }
+        start += 1;
+    }
+    String::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::parser;
+
+    #[test]
+    fn masked_value_is_shown_as_bullets_and_redacted_on_submit() {
+        let mut render = parser::parse_render(
+            r##"{"version":"AGD/0.2","type":"render","seq":1,"window":{"width":400,"height":300,"title":"Login"},"commands":[
+                {"cmd":"rect","id":"login","x":0,"y":0,"w":400,"h":300,"fill":"#FFFFFF"},
+                {"cmd":"input","id":"user","x":10,"y":10,"w":200,"h":40},
+                {"cmd":"input","id":"pass","x":10,"y":60,"w":200,"h":40,"mask":true},
+                {"cmd":"rect","id":"go","x":10,"y":120,"w":100,"h":40,"fill":"#0000FF","clickable":true,"action":"submit","form":"login"}]}"##,
+        )
+        .unwrap();
+        let key = |keysym| KeyEvent { keysym, ctrl: false, alt: false, shift: false };
+        let mut inputs = Inputs::new();
+        assert!(inputs.click_at(&render, 20, 20));
+        inputs.send_key(&render, &key('a' as u32));
+        assert!(inputs.click_at(&render, 20, 70));
+        for c in "pw!x".chars() {
+            inputs.send_key(&render, &key(c as u32));
+        }
+        inputs.send_key(&render, &key(0xFF08));
+        assert!(inputs.fill(&mut render));
+        let Command::Input { shown, focused, .. } = &render.commands[2] else { unreachable!() };
+        assert_eq!((shown.as_deref(), *focused), (Some("•••"), true));
+        assert!(!serde_json::to_string(&render).unwrap().contains("pw!"));
+        assert!(matches!(inputs.send_key(&render, &key(0xFF0D)), Some(KeyOutcome::Submit(60, 140))));
+        let form = form::collect(&render, "go", &inputs).unwrap();
+        assert_eq!(form.fields["user"], "a");
+        assert_eq!(form.fields["pass"], "<redacted>");
+        assert_eq!(inputs.value("pass"), "pw!");
+    }
+}
@@ -0,0 +1,125 @@
+use std::error::Error;
+use serde_json::{json, Value};
+use tracing::{debug, error};
+
+use crate::config::ProviderConfig;
+use crate::logging;
+use crate::metrics;
+use crate::llm::gpt52::get_condensed_schema;
+use crate::llm::prompts::PromptVars;
+use crate::llm::provider::{self, LLMMode, LLMProvider, PromptPart};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const TOOL_NAME: &str = "gui_response";
+/// Messages API 要求必须给出 max_tokens
+const DEFAULT_MAX_TOKENS: u32 = 16000;
+
+/// Anthropic Messages API 驱动。
+/// Claude 没有 json_schema 严格模式，这里用“强制调用唯一工具”的方式拿到结构化输出：
+/// 工具的 input_schema 即渲染 Schema，tool_use.input 就是我们要的 JSON。
+pub struct AnthropicProvider {
+    config: ProviderConfig,
+}
+
+impl AnthropicProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl LLMProvider for AnthropicProvider {
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    fn request_render(
+        &self,
+        event_json: Option<&str>,
+        user_text: Option<&str>,
+        mode: LLMMode,
+        vars: &PromptVars,
+    ) -> Result<String, Box<dyn Error>> {
+        let api_key = self.config.api_key()?;
+        let client = provider::build_client()?;
+
+        let model_name = provider::model_for(&self.config, &mode);
+        let params = provider::params_for(&self.config, &mode);
+
+        let media_type = crate::config::get().model_screenshot.media_type();
+        let user_content: Vec<Value> = provider::build_user_parts(event_json, user_text, &mode, vars)
+            .into_iter()
+            .map(|part| match part {
+                PromptPart::Text(text) => json!({ "type": "text", "text": text }),
+                PromptPart::ImageBase64(data) => json!({
+                    "type": "image",
+                    "source": { "type": "base64", "media_type": media_type, "data": data }
+                }),
+            })
+            .collect();
+
+        let schema = get_condensed_schema(&mode);
+        let system_prompt = provider::load_system_prompt(vars);
+
+        let mut payload = json!({
+            "model": model_name,
+            "max_tokens": params.max_output_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            // system 是静态前缀，标记为可缓存
+            "system": [{
+                "type": "text",
+                "text": system_prompt,
+                "cache_control": { "type": "ephemeral" }
+            }],
+            "messages": [{ "role": "user", "content": user_content }],
+            "tools": [{
+                "name": TOOL_NAME,
+                "description": "Submit the UI response. Always call this tool exactly once.",
+                "input_schema": schema
+            }],
+            "tool_choice": { "type": "tool", "name": TOOL_NAME }
+        });
+        if let Some(temperature) = params.temperature {
+            payload["temperature"] = json!(temperature);
+        }
+
+        let value = provider::send_with_retry(&payload, |body| {
+            client
+                .post(&self.config.base_url)
+                .header("x-api-key", &api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .json(body)
+        })?;
+
+        if let Some(usage) = value.get("usage") {
+            let input = usage.get("input_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+            let output = usage.get("output_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+            let cached = usage.get("cache_read_input_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+            debug!(target: "agd::llm", model = model_name, total = input + output, input, output, cached, "token usage");
+            metrics::record_tokens("anthropic", model_name, input, output, cached);
+        }
+
+        if let Some(output_text) = extract_tool_output(&value) {
+            Ok(output_text)
+        } else {
+            error!(model = model_name, response = %logging::redact(&value), "failed to extract tool output");
+            Err("missing tool_use output from messages API".into())
+        }
+    }
+}
+
+fn extract_tool_output(value: &Value) -> Option<String> {
+    let contents = value.get("content")?.as_array()?;
+    for content in contents {
+        match content.get("type").and_then(|v| v.as_str()) {
+            Some("tool_use") if content.get("name").and_then(|v| v.as_str()) == Some(TOOL_NAME) => {
+                return content.get("input").map(|input| input.to_string());
+            }
+            _ => {}
+        }
+    }
+    // 没有工具调用时退回到纯文本，交给 parser 去截取 JSON
+    contents
+        .iter()
+        .find(|c| c.get("type").and_then(|v| v.as_str()) == Some("text"))
+        .and_then(|c| c.get("text").and_then(|v| v.as_str()))
+        .map(|text| text.trim().to_string())
+}
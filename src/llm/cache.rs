@@ -0,0 +1,214 @@
+use std::error::Error;
+use std::path::PathBuf;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::dsl::parser::{self, ParseMode};
+use crate::dsl::validator;
+use crate::llm::prompts::PromptVars;
+use crate::llm::provider::{self, LLMMode, LLMProvider, PromptPart};
+use crate::llm::tools;
+
+/// 磁盘响应缓存：按 (模式, 提示词, 事件/用户文本, 上下文) 的规范化哈希命中，
+/// 命中时直接返回上次的输出，不再访问网络。
+/// 只缓存能严格解析并通过校验的输出；调用过工具的回答依赖当时的剪贴板、画面等运行时状态，不缓存
+pub struct CachingProvider {
+    inner: Box<dyn LLMProvider>,
+    dir: PathBuf,
+    /// 区分 provider 与模型，切换模型后不会误用旧缓存
+    namespace: String,
+}
+
+impl CachingProvider {
+    pub fn new(inner: Box<dyn LLMProvider>, dir: impl Into<PathBuf>, namespace: String) -> Self {
+        Self { inner, dir: dir.into(), namespace }
+    }
+
+    /// 提示词按未替换变量的模板计入，事件历史、字体与系统信息等每轮或每台机器不同的内容不影响命中；
+    /// 只有画面本身（见 `key_vars`）、规范化后的事件 / 用户文本与截图参与哈希
+    fn cache_key(&self, event_json: Option<&str>, user_text: Option<&str>, mode: &LLMMode, vars: &PromptVars) -> String {
+        let event = event_json.map(normalize_event);
+        let text = user_text.map(normalize_text);
+        // 草稿里的 seq 同样每轮递增
+        let normalized;
+        let mode = match mode {
+            LLMMode::Evaluate { image_base64, region, dsl_code } => {
+                normalized = LLMMode::Evaluate { image_base64: image_base64.clone(), region: region.clone(), dsl_code: normalize_event(dsl_code) };
+                &normalized
+            }
+            LLMMode::Select { images_base64, dsl_codes } => {
+                normalized = LLMMode::Select { images_base64: images_base64.clone(), dsl_codes: dsl_codes.iter().map(|d| normalize_event(d)).collect() };
+                &normalized
+            }
+            _ => mode,
+        };
+        let vars = key_vars(vars);
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.namespace.as_bytes());
+        hasher.update(mode.name().as_bytes());
+        hasher.update(normalize_text(&provider::load_system_prompt(&PromptVars::new())).as_bytes());
+        for part in provider::build_user_parts(event.as_deref(), text.as_deref(), mode, &vars) {
+            hasher.update([0u8]);
+            match part {
+                PromptPart::Text(t) => hasher.update(normalize_text(&t).as_bytes()),
+                PromptPart::ImageBase64(data) => hasher.update(data.as_bytes()),
+            }
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+}
+
+impl LLMProvider for CachingProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn request_render(
+        &self,
+        event_json: Option<&str>,
+        user_text: Option<&str>,
+        mode: LLMMode,
+        vars: &PromptVars,
+    ) -> Result<String, Box<dyn Error>> {
+        let path = self.dir.join(format!("{}.json", self.cache_key(event_json, user_text, &mode, vars)));
+        if let Ok(cached) = std::fs::read_to_string(&path) {
+            debug!("[cache] hit {}", path.display());
+            return Ok(cached);
+        }
+
+        let tool_calls = tools::calls_on_this_thread();
+        let output = self.inner.request_render(event_json, user_text, mode.clone(), vars)?;
+        if tools::calls_on_this_thread() != tool_calls {
+            debug!("[cache] not storing {}: tools were called", path.display());
+        } else if !is_valid_output(&mode, &output) {
+            debug!("[cache] not storing {}: output does not parse or validate", path.display());
+        } else if std::fs::create_dir_all(&self.dir).is_ok() {
+            let _ = std::fs::write(&path, &output);
+        }
+        Ok(output)
+    }
+}
+
+/// 输出能按该模式严格解析，其中的 render 信封通过校验
+fn is_valid_output(mode: &LLMMode, output: &str) -> bool {
+    let valid_render = |raw: &str| {
+        parser::parse_render_with(raw, ParseMode::Strict).is_ok_and(|(render, _)| validator::validate_render(&render).is_ok())
+    };
+    match mode {
+        LLMMode::Generate { .. } | LLMMode::Repair { .. } => valid_render(output),
+        LLMMode::Evaluate { .. } => serde_json::from_str::<Value>(output).is_ok_and(|v| {
+            v["is_final"].is_boolean() && (v["render"].is_null() || valid_render(&v["render"].to_string()))
+        }),
+        LLMMode::Select { .. } => serde_json::from_str::<Value>(output).is_ok_and(|v| v["best_index"].is_u64()),
+    }
+}
+
+/// 折叠空白，避免提示词文件里的换行/缩进差异导致缓存失效
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 参与缓存键的模板变量：画面的尺寸与状态，以及评估时由草稿算出的检查结果
+const KEY_VARS: &[&str] = &["window_width", "window_height", "state", "layout_warnings", "accessibility_outline", "draft_changes"];
+
+/// 只保留 `KEY_VARS`，界面状态去掉 `seq`；其余变量在模板里保持 `{{name}}` 原样
+fn key_vars(vars: &PromptVars) -> PromptVars {
+    let mut out = PromptVars::new();
+    for name in KEY_VARS {
+        if let Some(value) = vars.get(name) {
+            out.set(name, if *name == "state" { normalize_event(value) } else { value.to_string() });
+        }
+    }
+    out
+}
+
+/// 去掉每次递增的 `seq`（事件、界面状态与草稿都带它），让同一个按钮的重复点击命中同一条缓存
+fn normalize_event(event_json: &str) -> String {
+    match serde_json::from_str::<Value>(event_json) {
+        Ok(Value::Object(mut map)) => {
+            map.remove("seq");
+            Value::Object(map).to_string()
+        }
+        _ => normalize_text(event_json),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use super::*;
+    use crate::config::ToolsConfig;
+    use crate::llm::mock::MockProvider;
+
+    const SCREEN: &str = r##"{"version":"AGD/0.2","type":"render","seq":1,"window":{"width":200,"height":100,"title":"T"},"commands":[{"cmd":"clear","color":"#FFFFFF"}]}"##;
+
+    /// 固定回答并统计请求次数的模型；`use_tool` 时回答前先调用一次工具
+    struct Counting {
+        requests: Arc<AtomicUsize>,
+        output: &'static str,
+        use_tool: bool,
+    }
+
+    impl LLMProvider for Counting {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn request_render(&self, _: Option<&str>, _: Option<&str>, _: LLMMode, vars: &PromptVars) -> Result<String, Box<dyn Error>> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            if self.use_tool {
+                tools::call("get_state", vars, None, &ToolsConfig::default());
+            }
+            Ok(self.output.to_string())
+        }
+    }
+
+    /// 连续请求 `texts`，返回内层模型实际收到的请求数
+    fn requests(name: &str, output: &'static str, use_tool: bool, texts: &[&str]) -> usize {
+        let dir = std::env::temp_dir().join(format!("agd-cache-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let count = Arc::new(AtomicUsize::new(0));
+        let counting = Counting { requests: count.clone(), output, use_tool };
+        let cache = CachingProvider::new(Box::new(counting), &dir, "test".to_string());
+        let mode = LLMMode::Generate { screenshot_base64: None, attach_screenshot: false, attachment: None, companion: None };
+        for text in texts {
+            assert_eq!(cache.request_render(None, Some(text), mode.clone(), &PromptVars::new()).unwrap(), output);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+        count.load(Ordering::Relaxed)
+    }
+
+    #[test]
+    fn caches_only_valid_outputs_without_tool_calls() {
+        // 同一请求第二次命中，不同的请求未命中
+        assert_eq!(requests("valid", SCREEN, false, &["home", "home", "settings"]), 2);
+        // 解析失败或校验不通过的输出每次都重新请求
+        assert_eq!(requests("invalid", "not json", false, &["home", "home"]), 2);
+        assert_eq!(requests("no-clear", r#"{"version":"AGD/0.2","type":"render","seq":1,"window":{"width":200,"height":100,"title":"T"},"commands":[]}"#, false, &["home", "home"]), 2);
+        // 调用过工具的回答不缓存
+        assert_eq!(requests("tools", SCREEN, true, &["home", "home"]), 2);
+    }
+
+    #[test]
+    fn key_ignores_history_and_seq_but_not_screen() {
+        let cache = CachingProvider::new(Box::new(MockProvider::new("mock")), "cache", "test".to_string());
+        let mode = LLMMode::Generate { screenshot_base64: None, attach_screenshot: false, attachment: None, companion: None };
+        let key = |seq: u64, title: &str, history: &str| {
+            let mut vars = PromptVars::new();
+            vars.set("window_width", "800").set("window_height", "600");
+            vars.set("state", serde_json::json!({ "title": title, "seq": seq, "clickable": ["ok"] }).to_string());
+            vars.set("event_history", history);
+            let event = serde_json::json!({ "type": "event", "seq": seq, "event": { "kind": "click", "target_id": "ok" } }).to_string();
+            cache.cache_key(Some(&event), None, &mode, &vars)
+        };
+        assert_eq!(key(3, "Home", "  - click: ok"), key(7, "Home", "  - click: ok\n  - click: ok"));
+        assert_ne!(key(3, "Home", ""), key(3, "Settings", ""));
+    }
+}
@@ -0,0 +1,48 @@
+use std::error::Error;
+use tracing::{debug, warn};
+
+use crate::llm::prompts::PromptVars;
+use crate::llm::provider::{LLMMode, LLMProvider};
+
+/// 按顺序尝试多个 provider：前一个失败（含重试耗尽、超时）时自动切到下一个
+pub struct FallbackProvider {
+    /// (用于日志的标签, provider)
+    chain: Vec<(String, Box<dyn LLMProvider>)>,
+}
+
+impl FallbackProvider {
+    pub fn new(chain: Vec<(String, Box<dyn LLMProvider>)>) -> Self {
+        Self { chain }
+    }
+}
+
+impl LLMProvider for FallbackProvider {
+    fn name(&self) -> &str {
+        self.chain.first().map(|(_, p)| p.name()).unwrap_or("fallback")
+    }
+
+    fn request_render(
+        &self,
+        event_json: Option<&str>,
+        user_text: Option<&str>,
+        mode: LLMMode,
+        vars: &PromptVars,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut last_error: Option<Box<dyn Error>> = None;
+        for (i, (label, provider)) in self.chain.iter().enumerate() {
+            match provider.request_render(event_json, user_text, mode.clone(), vars) {
+                Ok(output) => {
+                    debug!("{} request served by {}", mode.name(), label);
+                    return Ok(output);
+                }
+                Err(e) => {
+                    if i + 1 < self.chain.len() {
+                        warn!("{} failed ({}), falling back to {}", label, e, self.chain[i + 1].0);
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| "fallback chain is empty".into()))
+    }
+}
@@ -1,71 +1,83 @@
 use std::error::Error;
-use reqwest::blocking::Client;
 use serde_json::{json, Value};
+use tracing::{debug, error, warn};
 
-const OPENAI_API_URL: &str = "https://api.openai.com/v1/responses";
+use crate::config::ProviderConfig;
+use crate::dsl::schema;
+use crate::logging;
+use crate::metrics;
+use crate::llm::prompts::PromptVars;
+use crate::llm::provider::{self, LLMMode, LLMProvider, PromptPart};
+use crate::llm::tools::{self, ToolOutput};
 
-pub enum LLMMode {
-    Generate,
-    Evaluate { image_base64: String, dsl_code: String },
+pub struct OpenAIProvider {
+    config: ProviderConfig,
 }
 
-pub fn request_render(
+impl OpenAIProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl LLMProvider for OpenAIProvider {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    fn request_render(
+        &self,
+        event_json: Option<&str>,
+        user_text: Option<&str>,
+        mode: LLMMode,
+        vars: &PromptVars,
+    ) -> Result<String, Box<dyn Error>> {
+        request_render(&self.config, event_json, user_text, mode, vars)
+    }
+}
+
+fn request_render(
+    config: &ProviderConfig,
     event_json: Option<&str>,
     user_text: Option<&str>,
     mode: LLMMode,
+    vars: &PromptVars,
 ) -> Result<String, Box<dyn Error>> {
-    let api_key = std::env::var("OPENAI_API_KEY")?;
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(120))
-        .connect_timeout(std::time::Duration::from_secs(10))
-        .build()?;
-
-    let mut user_content = Vec::new();
+    let api_key = config.api_key()?;
+    let client = provider::build_client()?;
 
     // 动态选择模型
-    let model_name = match &mode {
-        LLMMode::Generate => "gpt-5.2",
-        LLMMode::Evaluate { .. } => "gpt-5-mini-2025-08-07",
-    };
+    let model_name = provider::model_for(config, &mode);
+    let params = provider::params_for(config, &mode);
 
-    match &mode {
-        LLMMode::Generate => {
-            let base_prompt = std::fs::read_to_string("prompts/generate.txt").unwrap_or_default();
-            user_content.push(json!({ "type": "input_text", "text": base_prompt }));
-            
-            if let Some(event) = event_json {
-                user_content.push(json!({ "type": "input_text", "text": format!("Event JSON:\n{}", event) }));
-            } else if let Some(text) = user_text {
-                user_content.push(json!({ "type": "input_text", "text": format!("User text:\n{}", text) }));
-            } else {
-                user_content.push(json!({ "type": "input_text", "text": "Initial request." }));
-            };
-        }
-        LLMMode::Evaluate { image_base64, dsl_code } => {
-            let base_prompt = std::fs::read_to_string("prompts/evaluate.txt").unwrap_or_default();
-            user_content.push(json!({ "type": "input_text", "text": base_prompt }));
-            user_content.push(json!({
+    let media_type = crate::config::get().model_screenshot.media_type();
+    let user_content: Vec<Value> = provider::build_user_parts(event_json, user_text, &mode, vars)
+        .into_iter()
+        .map(|part| match part {
+            PromptPart::Text(text) => json!({ "type": "input_text", "text": text }),
+            PromptPart::ImageBase64(data) => json!({
                 "type": "input_image",
-                "image_url": format!("data:image/jpeg;base64,{}", image_base64)
-            }));
-            user_content.push(json!({ "type": "input_text", "text": format!("DSL CODE TO EVALUATE:\n{}", dsl_code) }));
-        }
-    }
+                "image_url": format!("data:{};base64,{}", media_type, data)
+            }),
+        })
+        .collect();
 
     let schema = get_condensed_schema(&mode);
-    let system_prompt = std::fs::read_to_string("prompts/system.txt").unwrap_or_else(|_| "You are a UI renderer.".to_string());
+    let system_prompt = provider::load_system_prompt(vars);
 
-    let reasoning_effort = match &mode {
-        LLMMode::Generate => "none",
-        LLMMode::Evaluate { .. } => "minimal",
-    };
+    // 未配置时沿用默认：生成/修复不推理，评估/择优最小推理；空字符串表示不发送
+    let reasoning_effort = params.reasoning_effort.as_deref().unwrap_or(match &mode {
+        LLMMode::Generate { .. } | LLMMode::Repair { .. } => "none",
+        LLMMode::Evaluate { .. } | LLMMode::Select { .. } => "minimal",
+    });
+    let verbosity = params.verbosity.as_deref().unwrap_or("low");
 
     let mut payload_map = serde_json::Map::new();
     payload_map.insert("model".to_string(), json!(model_name));
-    payload_map.insert("prompt_cache_key".to_string(), json!(format!("agd_v0.2_{}", model_name.replace('.', "_").replace('-', "_"))));
+    payload_map.insert("prompt_cache_key".to_string(), json!(format!("agd_v0.2_{}", model_name.replace(['.', '-'], "_"))));
     
     // 仅为 gpt-5.2 开启 24h 缓存保留
-    if model_name == "gpt-5.2" {
+    if model_name.starts_with("gpt-5.2") {
         payload_map.insert("prompt_cache_retention".to_string(), json!("24h"));
     }
 
@@ -80,203 +92,145 @@ pub fn request_render(
         }
     ]));
 
-    payload_map.insert("text".to_string(), json!({
-        "verbosity": "low",
+    let mut text = json!({
         "format": {
             "type": "json_schema",
             "name": "gui_response",
             "strict": true,
             "schema": schema
         }
-    }));
-
-    payload_map.insert("reasoning".to_string(), json!({ "effort": reasoning_effort }));
-
-    let payload = Value::Object(payload_map);
+    });
+    if !verbosity.is_empty() {
+        text["verbosity"] = json!(verbosity);
+    }
+    payload_map.insert("text".to_string(), text);
 
-    let mut attempts = 0;
-    let max_attempts = 3;
+    if !reasoning_effort.is_empty() {
+        payload_map.insert("reasoning".to_string(), json!({ "effort": reasoning_effort }));
+    }
+    if let Some(temperature) = params.temperature {
+        payload_map.insert("temperature".to_string(), json!(temperature));
+    }
+    if let Some(max_output_tokens) = params.max_output_tokens {
+        payload_map.insert("max_output_tokens".to_string(), json!(max_output_tokens));
+    }
 
-    loop {
-        let response = client
-            .post(OPENAI_API_URL)
-            .bearer_auth(&api_key)
-            .json(&payload)
-            .send();
+    // 工具调用只用于生成/修复：模型可以按需查询运行时状态，而不是凭空猜测
+    let tools_config = &crate::config::get().tools;
+    let use_tools = tools_config.enabled && matches!(mode, LLMMode::Generate { .. } | LLMMode::Repair { .. });
+    if use_tools {
+        payload_map.insert("tools".to_string(), json!(tools::definitions(tools_config)));
+    }
+    let frame = match &mode {
+        LLMMode::Generate { screenshot_base64, .. } => screenshot_base64.as_deref(),
+        _ => None,
+    };
 
-        match response {
-            Ok(resp) => {
-                if resp.status().is_success() {
-                    let value: Value = resp.json()?;
-                    
-                    if std::env::var("AGD_DEBUG").map(|v| v == "1").unwrap_or(false) {
-                        if let Some(usage) = value.get("usage") {
-                            println!("[DEBUG] [{}] Raw Usage: {}", model_name, usage);
-                            
-                            let total = usage.get("total_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
-                            let input = usage.get("input_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
-                            let output = usage.get("output_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
-                            
-                            let mut cached = 0;
-                            if let Some(details) = usage.get("input_tokens_details") {
-                                cached = details.get("cached_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
-                            }
+    let mut payload = Value::Object(payload_map);
+    let mut rounds = 0;
+    let value = loop {
+        let value = provider::send_with_retry(&payload, |body| {
+            let request = client.post(&config.base_url).json(body);
+            if api_key.is_empty() { request } else { request.bearer_auth(&api_key) }
+        })?;
+        log_usage(model_name, &value);
 
-                            println!("[DEBUG] [{}] Tokens: Total={}, Input={}, Output={}, Cached={}", 
-                                     model_name, total, input, output, cached);
-                        }
-                    }
+        let calls = if use_tools { tools::function_calls(&value) } else { Vec::new() };
+        if calls.is_empty() {
+            break value;
+        }
+        rounds += 1;
 
-                    if let Some(output_text) = extract_output_text(&value) {
-                        return Ok(output_text.trim().to_string());
-                    } else {
-                        // 如果提取失败，打印整个响应 body
-                        println!("[ERROR] [{}] Failed to extract output text. Full response: {}", model_name, value);
-                        return Err("missing output text from responses API".into());
-                    }
-                } else if resp.status().is_server_error() && attempts < max_attempts {
-                    attempts += 1;
-                    eprintln!("warn: HTTP {}, retrying (attempt {}/{})...", resp.status(), attempts, max_attempts);
-                    std::thread::sleep(std::time::Duration::from_secs(2));
-                    continue;
-                } else {
-                    let status = resp.status();
-                    let body = resp.text().unwrap_or_default();
-                    return Err(format!("HTTP {}: {}\n", status, body).into());
+        // 把本轮输出（含 reasoning 与 function_call 项）和工具结果追加到 input 后重新请求
+        let Some(input) = payload["input"].as_array_mut() else {
+            return Err("request payload has no input array".into());
+        };
+        if let Some(outputs) = value.get("output").and_then(|v| v.as_array()) {
+            input.extend(outputs.iter().cloned());
+        }
+        for call in calls {
+            debug!(target: "agd::llm", model = model_name, tool = %call.name, "tool call");
+            match tools::call(&call.name, vars, frame, tools_config) {
+                ToolOutput::Text(text) => {
+                    input.push(json!({ "type": "function_call_output", "call_id": call.call_id, "output": text }));
+                }
+                ToolOutput::ImageBase64(data) => {
+                    input.push(json!({
+                        "type": "function_call_output",
+                        "call_id": call.call_id,
+                        "output": "The frame is attached in the next message."
+                    }));
+                    input.push(json!({
+                        "role": "user",
+                        "content": [{ "type": "input_image", "image_url": format!("data:{};base64,{}", media_type, data) }]
+                    }));
                 }
             }
-            Err(e) if attempts < max_attempts => {
-                attempts += 1;
-                eprintln!("warn: Network error {}, retrying...", e);
-                std::thread::sleep(std::time::Duration::from_secs(2));
-                continue;
-            }
-            Err(e) => return Err(e.into()),
         }
+        // 轮次用尽后禁止继续调用工具，强制模型直接给出结果
+        if rounds >= tools_config.max_rounds {
+            payload["tool_choice"] = json!("none");
+        }
+    };
+
+    if let Some(output_text) = extract_output_text(&value) {
+        Ok(output_text.trim().to_string())
+    } else {
+        // 如果提取失败，打印整个响应 body
+        error!(model = model_name, response = %logging::redact(&value), "failed to extract output text");
+        Err("missing output text from responses API".into())
     }
 }
 
-fn get_condensed_schema(mode: &LLMMode) -> Value {
-    let xdsl_schema = json!({
-        "type": "object",
-        "properties": {
-            "version": { "type": "string", "const": "X-DSL/0.2" }
-        },
-        "required": ["version"],
-        "additionalProperties": false
-    });
-
-    let render_envelope_schema = json!({
-        "type": "object",
-        "properties": {
-            "version": { "type": "string", "const": "AGD/0.2" },
-            "type": { "type": "string", "const": "render" },
-            "seq": { "type": "integer" },
-            "window": {
-                "type": "object",
-                "properties": {
-                    "width": { "type": "integer" },
-                    "height": { "type": "integer" },
-                    "title": { "type": "string" }
-                },
-                "required": ["width", "height", "title"],
-                "additionalProperties": false
-            },
-            "commands": {
-                "type": "array",
-                "items": {
-                    "type": "object",
-                    "properties": {
-                        "cmd": { "type": "string", "enum": ["clear", "rect", "text", "line", "circle", "ellipse", "round_rect", "arc", "polyline", "polygon", "image", "path"] },
-                        "id": { "type": ["string", "null"] },
-                        "x": { "type": ["integer", "null"] },
-                        "y": { "type": ["integer", "null"] },
-                        "w": { "type": ["integer", "null"] },
-                        "h": { "type": ["integer", "null"] },
-                        "cx": { "type": ["integer", "null"] },
-                        "cy": { "type": ["integer", "null"] },
-                        "r": { "type": ["integer", "null"] },
-                        "rx": { "type": ["integer", "null"] },
-                        "ry": { "type": ["integer", "null"] },
-                        "start_angle": { "type": ["number", "null"] },
-                        "end_angle": { "type": ["number", "null"] },
-                        "x1": { "type": ["integer", "null"] },
-                        "y1": { "type": ["integer", "null"] },
-                        "x2": { "type": ["integer", "null"] },
-                        "y2": { "type": ["integer", "null"] },
-                        "points": {
-                            "type": ["array", "null"],
-                            "items": {
-                                "type": "object",
-                                "properties": {
-                                    "x": { "type": "integer" },
-                                    "y": { "type": "integer" }
-                                },
-                                "required": ["x", "y"],
-                                "additionalProperties": false
-                            }
-                        },
-                        "segments": {
-                            "type": ["array", "null"],
-                            "items": {
-                                "type": "object",
-                                "properties": {
-                                    "cmd": { "type": "string", "enum": ["M", "L", "Z"] },
-                                    "x": { "type": ["integer", "null"] },
-                                    "y": { "type": ["integer", "null"] }
-                                },
-                                "required": ["cmd", "x", "y"],
-                                "additionalProperties": false
-                            }
-                        },
-                        "src_type": { "type": ["string", "null"], "enum": ["path", "base64", null] },
-                        "src": { "type": ["string", "null"] },
-                        "text": { "type": ["string", "null"] },
-                        "color": { "type": ["string", "null"] },
-                        "bg": { "type": ["string", "null"] },
-                        "fill": { "type": ["string", "null"] },
-                        "stroke": { "type": ["string", "null"] },
-                        "stroke_width": { "type": ["integer", "null"] },
-                        "width": { "type": ["integer", "null"] },
-                        "clickable": { "type": "boolean" }
-                    },
-                    "required": [
-                        "cmd", "id", "x", "y", "w", "h", "cx", "cy", "r", "rx", "ry",
-                        "start_angle", "end_angle", "x1", "y1", "x2", "y2",
-                        "points", "segments", "src_type", "src", "text", "color", "bg",
-                        "fill", "stroke", "stroke_width", "width", "clickable"
-                    ],
-                    "additionalProperties": false
-                }
-            },
-            "xdsl": {
-                "anyOf": [
-                    xdsl_schema,
-                    { "type": "null" }
-                ]
-            }
-        },
-        "required": ["version", "type", "seq", "window", "commands", "xdsl"],
-        "additionalProperties": false
-    });
+/// 各模式的结构化输出 Schema；render 部分由 `dsl::schema` 从模型类型推导
+pub fn get_condensed_schema(mode: &LLMMode) -> Value {
+    let render_envelope_schema = schema::render_schema();
 
     match mode {
-        LLMMode::Generate => render_envelope_schema,
+        LLMMode::Generate { .. } | LLMMode::Repair { .. } => render_envelope_schema,
         LLMMode::Evaluate { .. } => {
             json!({
                 "type": "object",
                 "properties": {
                     "is_final": { "type": "boolean" },
+                    "confidence": { "type": "number" },
                     "rejection_reason": { "type": ["string", "null"] },
                     "render": render_envelope_schema
                 },
-                "required": ["is_final", "rejection_reason", "render"],
+                "required": ["is_final", "confidence", "rejection_reason", "render"],
+                "additionalProperties": false
+            })
+        }
+        LLMMode::Select { .. } => {
+            json!({
+                "type": "object",
+                "properties": {
+                    "best_index": { "type": "integer" },
+                    "reason": { "type": ["string", "null"] }
+                },
+                "required": ["best_index", "reason"],
                 "additionalProperties": false
             })
         }
     }
 }
 
+fn log_usage(model_name: &str, value: &Value) {
+    if let Some(usage) = value.get("usage") {
+        let total = usage.get("total_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+        let input = usage.get("input_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+        let output = usage.get("output_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+        
+        let mut cached = 0;
+        if let Some(details) = usage.get("input_tokens_details") {
+            cached = details.get("cached_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+        }
+
+        debug!(target: "agd::llm", model = model_name, total, input, output, cached, "token usage");
+        metrics::record_tokens("openai", model_name, input, output, cached);
+    }
+}
+
 fn extract_output_text(value: &Value) -> Option<String> {
     let outputs = value.get("output")?.as_array()?;
     for item in outputs {
@@ -294,7 +248,7 @@ fn extract_output_text(value: &Value) -> Option<String> {
                 // 处理模型拒绝的情况
                 if content_type == Some("refusal") {
                     if let Some(refusal) = content.get("refusal").and_then(|v| v.as_str()) {
-                        println!("[WARN] Model refused to respond: {}", refusal);
+                        warn!("model refused to respond: {}", refusal);
                         return None;
                     }
                 }
@@ -0,0 +1,44 @@
+use std::error::Error;
+use std::time::Instant;
+
+use crate::llm::prompts::PromptVars;
+use crate::llm::provider::{LLMMode, LLMProvider};
+use crate::metrics;
+
+/// 记录真实模型调用的次数、耗时与失败（包在缓存内层，缓存命中不计入）
+pub struct MeteredProvider {
+    inner: Box<dyn LLMProvider>,
+}
+
+impl MeteredProvider {
+    pub fn new(inner: Box<dyn LLMProvider>) -> Self {
+        Self { inner }
+    }
+}
+
+impl LLMProvider for MeteredProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn request_render(
+        &self,
+        event_json: Option<&str>,
+        user_text: Option<&str>,
+        mode: LLMMode,
+        vars: &PromptVars,
+    ) -> Result<String, Box<dyn Error>> {
+        let mode_tag = mode.name();
+        let started = Instant::now();
+        let result = self.inner.request_render(event_json, user_text, mode, vars);
+        let provider = self.inner.name();
+        let outcome = if result.is_ok() { "ok" } else { "error" };
+        metrics::inc("agd_llm_requests_total", &[("provider", provider), ("mode", mode_tag), ("outcome", outcome)], 1.0);
+        metrics::observe(
+            "agd_llm_request_duration_seconds",
+            &[("provider", provider), ("mode", mode_tag)],
+            started.elapsed().as_secs_f64(),
+        );
+        result
+    }
+}
@@ -0,0 +1,100 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::dsl::parser;
+use crate::llm::prompts::PromptVars;
+use crate::llm::provider::{LLMMode, LLMProvider};
+
+/// 离线开发用的假模型：从目录中读取预先写好的 render 信封（`.json`，也可以是 `.yaml`/`.yml`）。
+///
+/// - 生成/修复：优先按输入命中文件（用户文本 `hello world` -> `hello_world.json`，
+///   点击事件 -> `click_<target_id>.json`，定时器 -> `timer_<id>.json`，空闲 -> `idle.json`），否则按文件名顺序循环返回。
+/// - 评估：总是直接定稿当前草稿；Best-of-N 选择总是选第一份。
+pub struct MockProvider {
+    dir: PathBuf,
+    next: AtomicUsize,
+}
+
+impl MockProvider {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), next: AtomicUsize::new(0) }
+    }
+
+    fn keyed_file(&self, event_json: Option<&str>, user_text: Option<&str>) -> Option<PathBuf> {
+        let key = if let Some(event) = event_json {
+            let v: Value = serde_json::from_str(event).ok()?;
+            let kind = v["event"]["kind"].as_str().unwrap_or("event");
+            let target = v["event"]["target_id"].as_str().unwrap_or_default();
+            if target.is_empty() { kind.to_string() } else { format!("{}_{}", kind, target) }
+        } else {
+            user_text?.to_string()
+        };
+        let key = slug(&key);
+        ENVELOPE_EXTENSIONS
+            .iter()
+            .map(|ext| self.dir.join(format!("{}.{}", key, ext)))
+            .find(|path| path.is_file())
+    }
+
+    fn cycled_file(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let files = list_json_files(&self.dir)?;
+        if files.is_empty() {
+            return Err(format!("mock provider: no *.json/*.yaml files in {}", self.dir.display()).into());
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % files.len();
+        Ok(files[index].clone())
+    }
+}
+
+impl LLMProvider for MockProvider {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn request_render(
+        &self,
+        event_json: Option<&str>,
+        user_text: Option<&str>,
+        mode: LLMMode,
+        _vars: &PromptVars,
+    ) -> Result<String, Box<dyn Error>> {
+        match mode {
+            LLMMode::Evaluate { dsl_code, .. } => {
+                // 草稿可能是 YAML 或带有代码围栏等噪声，交给 parser 解析后转成 JSON
+                let render = serde_json::to_value(parser::parse_render(&dsl_code)?)?;
+                Ok(json!({ "is_final": true, "confidence": 1.0, "rejection_reason": null, "render": render }).to_string())
+            }
+            LLMMode::Select { .. } => Ok(json!({ "best_index": 0, "reason": null }).to_string()),
+            LLMMode::Generate { .. } | LLMMode::Repair { .. } => {
+                let path = match self.keyed_file(event_json, user_text) {
+                    Some(path) => path,
+                    None => self.cycled_file()?,
+                };
+                debug!("[mock] serving {}", path.display());
+                Ok(std::fs::read_to_string(path)?)
+            }
+        }
+    }
+}
+
+const ENVELOPE_EXTENSIONS: [&str; 3] = ["json", "yaml", "yml"];
+
+fn list_json_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().and_then(|e| e.to_str()).is_some_and(|e| ENVELOPE_EXTENSIONS.contains(&e)))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
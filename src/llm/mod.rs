@@ -1 +1,10 @@
-pub mod gpt52;
\ No newline at end of file
+pub mod anthropic;
+pub mod cache;
+pub mod fallback;
+pub mod gpt52;
+pub mod metered;
+pub mod mock;
+pub mod prompts;
+pub mod provider;
+pub mod tools;
+pub mod transcript;
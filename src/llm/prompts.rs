@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::config;
+
+/// 注入到提示词模板中的变量，模板里用 `{{name}}` 引用
+#[derive(Debug, Clone, Default)]
+pub struct PromptVars {
+    vars: BTreeMap<String, String>,
+}
+
+impl PromptVars {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Into<String>) -> &mut Self {
+        self.vars.insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.vars.get(key).map(String::as_str)
+    }
+
+    /// 替换模板中的 `{{name}}`；未定义的变量原样保留，避免误伤提示词里的字面量
+    pub fn render(&self, template: &str) -> String {
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            match after.find("}}") {
+                Some(end) => {
+                    let key = after[..end].trim();
+                    match self.vars.get(key) {
+                        Some(value) => out.push_str(value),
+                        None => out.push_str(&rest[start..start + 2 + end + 2]),
+                    }
+                    rest = &after[end + 2..];
+                }
+                None => {
+                    out.push_str(&rest[start..]);
+                    rest = "";
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// 读取并渲染 `name` 对应的提示词模板。
+/// 查找顺序：`<prompt_dir>/<prompt_set>/<name>.txt` -> `<prompt_dir>/<name>.txt`。
+pub fn load(name: &str, vars: &PromptVars) -> Option<String> {
+    let config = config::get();
+    let root = PathBuf::from(&config.prompt_dir);
+    let mut candidates = Vec::new();
+    if let Some(set) = &config.prompt_set {
+        candidates.push(root.join(set).join(format!("{name}.txt")));
+    }
+    candidates.push(root.join(format!("{name}.txt")));
+
+    candidates
+        .into_iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .map(|template| vars.render(&template))
+}
@@ -0,0 +1,399 @@
+use std::error::Error;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::{Certificate, Identity, NoProxy, Proxy};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use crate::config::{self, Config, ModelParams, ProviderConfig, RetryConfig};
+use crate::logging;
+use crate::llm::anthropic::AnthropicProvider;
+use crate::llm::cache::CachingProvider;
+use crate::llm::fallback::FallbackProvider;
+use crate::llm::gpt52::OpenAIProvider;
+use crate::llm::metered::MeteredProvider;
+use crate::llm::mock::MockProvider;
+use crate::llm::prompts::{self, PromptVars};
+use crate::llm::transcript::{RecordingProvider, ReplayProvider};
+
+#[derive(Clone)]
+pub enum LLMMode {
+    /// `screenshot_base64` 为当前屏幕画面，让模型在现有界面上修改而不是凭空重画；
+    /// `attach_screenshot` 为 false 时截图不放进提示词，只供 `get_last_frame` 工具按需取用
+    /// `attachment` 是用户随输入附上的图片（粘贴、截取），`companion` 是 `/follow` 跟随的其他程序窗口，
+    /// 编码格式与截图相同
+    Generate {
+        screenshot_base64: Option<String>,
+        attach_screenshot: bool,
+        attachment: Option<String>,
+        companion: Option<String>,
+    },
+    /// `region` 为变化区域的原尺寸局部截图（事件只改动了部分画面时附带）
+    Evaluate { image_base64: String, region: Option<RegionCrop>, dsl_code: String },
+    /// 上一次输出未通过解析/校验，附带错误信息请模型修正
+    Repair { raw_output: String, error: String },
+    /// Best-of-N：从多份候选草稿中选出最好的一份
+    Select { images_base64: Vec<String>, dsl_codes: Vec<String> },
+}
+
+/// 窗口中一块区域的原分辨率截图，坐标为窗口像素
+#[derive(Clone)]
+pub struct RegionCrop {
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+    pub image_base64: String,
+}
+
+impl LLMMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LLMMode::Generate { .. } => "generate",
+            LLMMode::Evaluate { .. } => "evaluate",
+            LLMMode::Repair { .. } => "repair",
+            LLMMode::Select { .. } => "select",
+        }
+    }
+}
+
+/// 与具体厂商无关的用户消息片段，由各 provider 转换成自己的 content 格式
+pub enum PromptPart {
+    Text(String),
+    ImageBase64(String),
+}
+
+/// 模型驱动的统一接口：输入事件/用户文本，返回符合 Schema 的 JSON 文本
+pub trait LLMProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn request_render(
+        &self,
+        event_json: Option<&str>,
+        user_text: Option<&str>,
+        mode: LLMMode,
+        vars: &PromptVars,
+    ) -> Result<String, Box<dyn Error>>;
+}
+
+/// 根据配置中的 `provider`（openai | anthropic | mock | replay，默认 openai）选择模型驱动，
+/// 配置了 `fallback` 时组成按序降级的链，开启缓存时在外层包一层 `CachingProvider`，
+/// 配置了 `record_to` 时再包一层 `RecordingProvider`
+pub fn from_config(config: &Config) -> Result<Box<dyn LLMProvider>, Box<dyn Error>> {
+    let llm = base_provider(config)?;
+    match &config.record_to {
+        Some(path) => Ok(Box::new(RecordingProvider::new(llm, path)?)),
+        None => Ok(llm),
+    }
+}
+
+fn base_provider(config: &Config) -> Result<Box<dyn LLMProvider>, Box<dyn Error>> {
+    let models = match config.provider.as_str() {
+        "openai" => &config.openai,
+        "anthropic" | "claude" => &config.anthropic,
+        // 本地 provider 读文件，缓存没有意义
+        "mock" => return Ok(Box::new(MockProvider::new(&config.mock_dir))),
+        "replay" => {
+            let path = config.replay_from.as_deref().ok_or("replay provider requires replay_from")?;
+            return Ok(Box::new(ReplayProvider::open(path)?));
+        }
+        other => return Err(format!("unknown provider: {other}").into()),
+    };
+    let primary = remote_provider(&config.provider, models)?;
+    let llm = if config.fallback.is_empty() {
+        primary
+    } else {
+        let mut chain = vec![(fallback_label(&config.provider, models), primary)];
+        for entry in &config.fallback {
+            let entry_config = config.fallback_provider_config(entry);
+            let provider = remote_provider(&entry.provider, &entry_config)?;
+            chain.push((fallback_label(&entry.provider, &entry_config), provider));
+        }
+        Box::new(FallbackProvider::new(chain))
+    };
+    if !config.cache.enabled {
+        return Ok(llm);
+    }
+    let namespace = format!("{}:{}:{}", llm.name(), models.generate_model, models.evaluate_model);
+    Ok(Box::new(CachingProvider::new(llm, &config.cache.dir, namespace)))
+}
+
+/// 远程模型驱动，外面包一层指标统计
+fn remote_provider(kind: &str, config: &ProviderConfig) -> Result<Box<dyn LLMProvider>, Box<dyn Error>> {
+    let llm: Box<dyn LLMProvider> = match kind {
+        "openai" => Box::new(OpenAIProvider::new(config.clone())),
+        "anthropic" | "claude" => Box::new(AnthropicProvider::new(config.clone())),
+        other => return Err(format!("unknown provider: {other}").into()),
+    };
+    Ok(Box::new(MeteredProvider::new(llm)))
+}
+
+fn fallback_label(kind: &str, config: &ProviderConfig) -> String {
+    format!("{}({}/{})", kind, config.generate_model, config.evaluate_model)
+}
+
+/// 生成/修复走主模型，评估/择优走轻量模型
+pub fn model_for<'a>(config: &'a ProviderConfig, mode: &LLMMode) -> &'a str {
+    match mode {
+        LLMMode::Generate { .. } | LLMMode::Repair { .. } => config.generate_model.as_str(),
+        LLMMode::Evaluate { .. } | LLMMode::Select { .. } => config.evaluate_model.as_str(),
+    }
+}
+
+pub fn params_for<'a>(config: &'a ProviderConfig, mode: &LLMMode) -> &'a ModelParams {
+    match mode {
+        LLMMode::Generate { .. } => &config.params.generate,
+        LLMMode::Evaluate { .. } => &config.params.evaluate,
+        LLMMode::Repair { .. } => &config.params.repair,
+        LLMMode::Select { .. } => &config.params.select,
+    }
+}
+
+pub fn load_system_prompt(vars: &PromptVars) -> String {
+    let prompt = prompts::load("system", vars).unwrap_or_else(|| "You are a UI renderer.".to_string());
+    // 配置档案的人设追加在末尾，不必为每个档案复制一整套提示词
+    match config::get().persona.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(persona) => format!("{}\n\nPERSONA:\n{}", prompt.trim_end(), persona),
+        None => prompt,
+    }
+}
+
+/// 按 (静态指令) -> (动态图片) -> (动态代码) 的顺序组装用户消息，保护 Cache Prefix
+pub fn build_user_parts(
+    event_json: Option<&str>,
+    user_text: Option<&str>,
+    mode: &LLMMode,
+    vars: &PromptVars,
+) -> Vec<PromptPart> {
+    let mut parts = Vec::new();
+    match mode {
+        LLMMode::Generate { screenshot_base64, attach_screenshot, attachment, companion } => {
+            let base_prompt = prompts::load("generate", vars).unwrap_or_default();
+            parts.push(PromptPart::Text(base_prompt));
+            // 运行时上下文（窗口尺寸、事件历史等）是动态内容，放在静态指令之后
+            if let Some(context) = prompts::load("context", vars).filter(|c| !c.trim().is_empty()) {
+                parts.push(PromptPart::Text(context));
+            }
+            if let Some(image) = screenshot_base64.as_ref().filter(|_| *attach_screenshot) {
+                parts.push(PromptPart::Text("CURRENT SCREEN (what the user sees right now):".to_string()));
+                parts.push(PromptPart::ImageBase64(image.clone()));
+            }
+            if let Some(image) = companion {
+                parts.push(PromptPart::Text(
+                    "THE USER'S OTHER WINDOW (what they are working on; this UI is a companion to it):".to_string(),
+                ));
+                parts.push(PromptPart::ImageBase64(image.clone()));
+            }
+            if let Some(image) = attachment {
+                parts.push(PromptPart::Text("IMAGE FROM THE USER (the request below refers to it):".to_string()));
+                parts.push(PromptPart::ImageBase64(image.clone()));
+            }
+
+            if let Some(event) = event_json {
+                parts.push(PromptPart::Text(format!("Event JSON:\n{}", event)));
+            } else if let Some(text) = user_text {
+                parts.push(PromptPart::Text(format!("User text:\n{}", text)));
+            } else {
+                parts.push(PromptPart::Text("Initial request.".to_string()));
+            };
+        }
+        LLMMode::Evaluate { image_base64, region, dsl_code } => {
+            let base_prompt = prompts::load("evaluate", vars).unwrap_or_default();
+            parts.push(PromptPart::Text(base_prompt));
+            parts.push(PromptPart::ImageBase64(image_base64.clone()));
+            if let Some(region) = region {
+                parts.push(PromptPart::Text(format!(
+                    "CHANGED REGION at full resolution (window pixels x={}, y={}, {}x{}):",
+                    region.x, region.y, region.w, region.h
+                )));
+                parts.push(PromptPart::ImageBase64(region.image_base64.clone()));
+            }
+            parts.push(PromptPart::Text(format!("DSL CODE TO EVALUATE:\n{}", dsl_code)));
+            // 布局检查结果随草稿变化，放在最后，不破坏缓存前缀
+            if let Some(findings) = vars.get("layout_warnings") {
+                parts.push(PromptPart::Text(format!("AUTOMATED LAYOUT CHECKS:\n{}", findings)));
+            }
+            if let Some(outline) = vars.get("accessibility_outline") {
+                parts.push(PromptPart::Text(format!("ACCESSIBILITY OUTLINE (what a screen reader announces):\n{}", outline)));
+            }
+            if let Some(changes) = vars.get("draft_changes") {
+                parts.push(PromptPart::Text(format!("CHANGES SINCE PREVIOUS DRAFT:\n{}", changes)));
+            }
+        }
+        LLMMode::Repair { raw_output, error } => {
+            let base_prompt = prompts::load("repair", vars).unwrap_or_default();
+            parts.push(PromptPart::Text(base_prompt));
+            if let Some(event) = event_json {
+                parts.push(PromptPart::Text(format!("Event JSON:\n{}", event)));
+            } else if let Some(text) = user_text {
+                parts.push(PromptPart::Text(format!("User text:\n{}", text)));
+            }
+            parts.push(PromptPart::Text(format!("VALIDATION ERROR:\n{}", error)));
+            parts.push(PromptPart::Text(format!("INVALID OUTPUT:\n{}", raw_output)));
+        }
+        LLMMode::Select { images_base64, dsl_codes } => {
+            let base_prompt = prompts::load("select", vars).unwrap_or_default();
+            parts.push(PromptPart::Text(base_prompt));
+            for (i, image) in images_base64.iter().enumerate() {
+                parts.push(PromptPart::Text(format!("CANDIDATE {}:", i)));
+                parts.push(PromptPart::ImageBase64(image.clone()));
+            }
+            for (i, dsl) in dsl_codes.iter().enumerate() {
+                parts.push(PromptPart::Text(format!("CANDIDATE {} DSL:\n{}", i, dsl)));
+            }
+        }
+    }
+    parts
+}
+
+/// 按 `http` 配置构建客户端：超时、显式代理（未配置时 reqwest 仍会读取 `HTTPS_PROXY` 等环境变量）、
+/// 额外信任的 CA 以及双向 TLS 客户端证书
+pub fn build_client() -> Result<Client, Box<dyn Error>> {
+    let http = &config::get().http;
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(http.timeout_secs))
+        .connect_timeout(Duration::from_secs(http.connect_timeout_secs));
+
+    if let Some(url) = &http.proxy {
+        let mut proxy = Proxy::all(url.as_str())?;
+        if let Some(no_proxy) = &http.no_proxy {
+            proxy = proxy.no_proxy(NoProxy::from_string(no_proxy));
+        }
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &http.ca_bundle {
+        let pem = std::fs::read(path).map_err(|e| format!("cannot read CA bundle {}: {}", path, e))?;
+        for cert in Certificate::from_pem_bundle(&pem)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    match (&http.client_cert, &http.client_key) {
+        (Some(cert), Some(key)) => {
+            let cert_pem = std::fs::read(cert).map_err(|e| format!("cannot read client cert {}: {}", cert, e))?;
+            let key_pem = std::fs::read(key).map_err(|e| format!("cannot read client key {}: {}", key, e))?;
+            builder = builder.identity(Identity::from_pkcs8_pem(&cert_pem, &key_pem)?);
+        }
+        (None, None) => {}
+        _ => return Err("http.client_cert and http.client_key must be set together".into()),
+    }
+    Ok(builder.build()?)
+}
+
+/// 发送请求并按 `retry` 配置重试，成功时返回解析后的 JSON body。
+/// - 429：优先遵守 `Retry-After`（或 `retry-after-ms`），否则指数退避，单独计数
+/// - 5xx / 网络错误：指数退避 + 随机抖动
+/// - 其它 4xx：立即失败
+///
+/// 请求与响应以脱敏后的形式记录在 `agd::llm` 目标下（debug 级别）。
+pub fn send_with_retry<F>(payload: &Value, build: F) -> Result<Value, Box<dyn Error>>
+where
+    F: Fn(&Value) -> RequestBuilder,
+{
+    let policy = &config::get().retry;
+    let mut server_attempts = 0;
+    let mut rate_limit_attempts = 0;
+    debug!(target: "agd::llm", request = %logging::redact(payload), "LLM request");
+
+    loop {
+        match build(payload).send() {
+            Ok(resp) => {
+                let status = resp.status();
+                if status.is_success() {
+                    let value: Value = resp.json()?;
+                    debug!(target: "agd::llm", response = %logging::redact(&value), "LLM response");
+                    return Ok(value);
+                } else if status == StatusCode::TOO_MANY_REQUESTS && rate_limit_attempts < policy.max_rate_limit_retries {
+                    rate_limit_attempts += 1;
+                    let max_delay = Duration::from_millis(policy.max_delay_ms);
+                    let delay = retry_after(resp.headers(), max_delay)
+                        .unwrap_or_else(|| backoff_delay(policy, rate_limit_attempts))
+                        .min(max_delay);
+                    warn!("rate limited (HTTP 429), waiting {:.1}s (attempt {}/{})...",
+                          delay.as_secs_f32(), rate_limit_attempts, policy.max_rate_limit_retries);
+                    std::thread::sleep(delay);
+                } else if is_retryable_status(status) && server_attempts < policy.max_attempts {
+                    server_attempts += 1;
+                    let delay = backoff_delay(policy, server_attempts);
+                    warn!("HTTP {}, retrying in {:.1}s (attempt {}/{})...",
+                          status, delay.as_secs_f32(), server_attempts, policy.max_attempts);
+                    std::thread::sleep(delay);
+                } else {
+                    let body = resp.text().unwrap_or_default();
+                    return Err(format!("HTTP {}: {}\n", status, body).into());
+                }
+            }
+            Err(e) if server_attempts < policy.max_attempts => {
+                server_attempts += 1;
+                let delay = backoff_delay(policy, server_attempts);
+                warn!("network error {}, retrying in {:.1}s (attempt {}/{})...",
+                      e, delay.as_secs_f32(), server_attempts, policy.max_attempts);
+                std::thread::sleep(delay);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// 5xx 以及 Anthropic 的 529 (overloaded)
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status.as_u16() == 529
+}
+
+/// `base * 2^(attempt-1)`，封顶 `max_delay_ms`，开启抖动时在 [50%, 100%] 之间随机
+fn backoff_delay(policy: &RetryConfig, attempt: u32) -> Duration {
+    let exp = policy.base_delay_ms.saturating_mul(1u64 << (attempt.saturating_sub(1)).min(16));
+    let capped = exp.min(policy.max_delay_ms);
+    if !policy.jitter {
+        return Duration::from_millis(capped);
+    }
+    // 不为抖动引入随机数依赖，纳秒时间戳足够打散并发重试
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    let factor = 0.5 + (nanos % 1000) as f64 / 2000.0;
+    Duration::from_millis((capped as f64 * factor) as u64)
+}
+
+/// 服务端要求的等待时间：`retry-after-ms`，或 `Retry-After` 的秒数 / HTTP 日期（RFC 7231）。
+/// 负数、NaN、无穷等非法值忽略，过大的值封顶 `max`
+fn retry_after(headers: &HeaderMap, max: Duration) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+    let seconds = |secs: f64| {
+        (secs.is_finite() && secs >= 0.0).then(|| secs.min(max.as_secs_f64())).and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+    };
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+        return seconds(ms / 1000.0);
+    }
+    let value = header(RETRY_AFTER.as_str())?;
+    match value.parse::<f64>() {
+        Ok(secs) => seconds(secs),
+        // 已经过去的日期表示可以立即重试
+        Err(_) => httpdate::parse_http_date(value).ok().map(|at| at.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO).min(max)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn retry_after_rejects_invalid_and_caps_large_values() {
+        let max = Duration::from_secs(30);
+        for value in ["-1", "nan", "inf", "garbage"] {
+            assert_eq!(retry_after(&headers("retry-after", value), max), None, "{}", value);
+        }
+        assert_eq!(retry_after(&headers("retry-after", "1e30"), max), Some(max));
+        assert_eq!(retry_after(&headers("retry-after", "2"), max), Some(Duration::from_secs(2)));
+        assert_eq!(retry_after(&headers("retry-after-ms", "-5"), max), None);
+        assert_eq!(retry_after(&headers("retry-after", "Sun, 06 Nov 1994 08:49:37 GMT"), max), Some(Duration::ZERO));
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(3600));
+        assert_eq!(retry_after(&headers("retry-after", &later), max), Some(max));
+    }
+}
@@ -0,0 +1,91 @@
+use std::cell::Cell;
+use serde_json::{json, Value};
+
+use crate::clipboard;
+use crate::config::ToolsConfig;
+use crate::llm::prompts::PromptVars;
+
+/// 工具调用的返回值；图片无法放进 function_call_output，由调用方另行附在消息里
+pub enum ToolOutput {
+    Text(String),
+    ImageBase64(String),
+}
+
+/// 模型发起的一次函数调用
+pub struct ToolCall {
+    pub call_id: String,
+    pub name: String,
+}
+
+/// Responses API 格式的工具声明。所有工具都是无参查询，答案来自本次任务的运行时快照。
+pub fn definitions(config: &ToolsConfig) -> Vec<Value> {
+    let mut tools = vec![
+        function("get_window_info", "Current window width, height (px) and screen DPI."),
+        function("get_state", "Snapshot of the UI state: window title, render seq and clickable element ids."),
+        function("get_event_history", "The most recent user inputs and clicks, oldest first."),
+        function("get_fonts", "Font families available to the renderer."),
+        function("get_last_frame", "Screenshot of the frame currently displayed to the user (downscaled)."),
+    ];
+    if config.allow_clipboard {
+        tools.push(function("get_clipboard", "Text currently on the user's clipboard."));
+    }
+    tools
+}
+
+fn function(name: &str, description: &str) -> Value {
+    json!({
+        "type": "function",
+        "name": name,
+        "description": description,
+        "parameters": { "type": "object", "properties": {}, "required": [], "additionalProperties": false },
+        "strict": true
+    })
+}
+
+/// 取出响应中的所有 function_call 项
+pub fn function_calls(response: &Value) -> Vec<ToolCall> {
+    let Some(outputs) = response.get("output").and_then(|v| v.as_array()) else {
+        return Vec::new();
+    };
+    outputs
+        .iter()
+        .filter(|item| item.get("type").and_then(|v| v.as_str()) == Some("function_call"))
+        .filter_map(|item| {
+            Some(ToolCall {
+                call_id: item.get("call_id")?.as_str()?.to_string(),
+                name: item.get("name")?.as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+thread_local! {
+    /// 本线程发起过的工具调用次数；请求在调用线程上同步完成，缓存层据此判断一次请求是否用过工具
+    static CALLS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// 本线程至今的工具调用次数
+pub fn calls_on_this_thread() -> u64 {
+    CALLS.with(Cell::get)
+}
+
+pub fn call(name: &str, vars: &PromptVars, frame: Option<&str>, config: &ToolsConfig) -> ToolOutput {
+    CALLS.with(|calls| calls.set(calls.get() + 1));
+    let var = |key: &str| vars.get(key).unwrap_or("unknown").to_string();
+    match name {
+        "get_window_info" => ToolOutput::Text(
+            json!({ "width": var("window_width"), "height": var("window_height"), "dpi": var("dpi") }).to_string(),
+        ),
+        "get_state" => ToolOutput::Text(var("state")),
+        "get_event_history" => ToolOutput::Text(var("event_history")),
+        "get_fonts" => ToolOutput::Text(var("fonts")),
+        "get_last_frame" => match frame {
+            Some(image) => ToolOutput::ImageBase64(image.to_string()),
+            None => ToolOutput::Text("No frame has been displayed yet.".to_string()),
+        },
+        "get_clipboard" if config.allow_clipboard => ToolOutput::Text(
+            clipboard::read_text().unwrap_or_else(|| "Clipboard is empty or unavailable.".to_string()),
+        ),
+        _ => ToolOutput::Text(format!("Unknown tool: {}", name)),
+    }
+}
@@ -0,0 +1,127 @@
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::llm::prompts::PromptVars;
+use crate::llm::provider::{LLMMode, LLMProvider};
+
+/// 转录文件中的一条记录（JSON Lines，一行一条）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TranscriptEntry {
+    mode: String,
+    event_json: Option<String>,
+    user_text: Option<String>,
+    /// 评估图片与用户附图只记录哈希，避免转录文件膨胀
+    image_sha256: Option<String>,
+    response: String,
+}
+
+/// 包装任意 provider，把每一对请求/响应追加写入转录文件
+pub struct RecordingProvider {
+    inner: Box<dyn LLMProvider>,
+    file: Mutex<File>,
+}
+
+impl RecordingProvider {
+    pub fn new(inner: Box<dyn LLMProvider>, path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { inner, file: Mutex::new(file) })
+    }
+}
+
+impl LLMProvider for RecordingProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn request_render(
+        &self,
+        event_json: Option<&str>,
+        user_text: Option<&str>,
+        mode: LLMMode,
+        vars: &PromptVars,
+    ) -> Result<String, Box<dyn Error>> {
+        let mode_tag = mode.name();
+        let image_sha256 = match &mode {
+            LLMMode::Evaluate { image_base64, .. } | LLMMode::Generate { attachment: Some(image_base64), .. } => Some(
+                Sha256::digest(image_base64.as_bytes()).iter().map(|b| format!("{b:02x}")).collect(),
+            ),
+            _ => None,
+        };
+
+        let response = self.inner.request_render(event_json, user_text, mode, vars)?;
+
+        let entry = TranscriptEntry {
+            mode: mode_tag.to_string(),
+            event_json: event_json.map(str::to_string),
+            user_text: user_text.map(str::to_string),
+            image_sha256,
+            response: response.clone(),
+        };
+        if let Ok(mut file) = self.file.lock() {
+            writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        }
+        Ok(response)
+    }
+}
+
+/// 按录制顺序逐条返回转录文件中的响应，用于确定性的端到端回放
+pub struct ReplayProvider {
+    path: PathBuf,
+    entries: Vec<TranscriptEntry>,
+    next: Mutex<usize>,
+}
+
+impl ReplayProvider {
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        let path = path.into();
+        let reader = BufReader::new(File::open(&path)?);
+        let mut entries = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line)
+                .map_err(|e| format!("{}:{}: invalid transcript entry: {}", path.display(), index + 1, e))?;
+            entries.push(entry);
+        }
+        Ok(Self { path, entries, next: Mutex::new(0) })
+    }
+}
+
+impl LLMProvider for ReplayProvider {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    fn request_render(
+        &self,
+        event_json: Option<&str>,
+        user_text: Option<&str>,
+        mode: LLMMode,
+        _vars: &PromptVars,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut next = self.next.lock().map_err(|_| "replay state poisoned")?;
+        let entry = self.entries.get(*next).ok_or_else(|| {
+            format!("replay transcript {} exhausted after {} entries", self.path.display(), self.entries.len())
+        })?;
+        let expected = mode.name();
+        if entry.mode != expected {
+            return Err(format!(
+                "replay mismatch at entry {}: recorded {} but pipeline requested {}",
+                *next + 1, entry.mode, expected
+            ).into());
+        }
+        if entry.event_json.as_deref() != event_json || entry.user_text.as_deref() != user_text {
+            debug!("[replay] entry {} input differs from recording", *next + 1);
+        }
+        *next += 1;
+        Ok(entry.response.clone())
+    }
+}
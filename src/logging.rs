@@ -0,0 +1,80 @@
+use std::error::Error;
+use serde_json::Value;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer, Registry};
+
+use crate::config::LoggingConfig;
+
+/// 超过该长度且只含 base64 字符的字符串视为图片数据
+const BASE64_REDACT_MIN_LEN: usize = 256;
+
+/// 初始化全局日志。级别优先取 `AGD_LOG`（EnvFilter 语法，如 `agd::llm=debug`），
+/// 其次 `AGD_DEBUG=1` 时为 debug，否则为配置中的 `level`。
+/// 返回的 guard 需要在进程退出前一直持有，否则文件日志可能丢尾。
+pub fn init(config: &LoggingConfig) -> Result<Option<WorkerGuard>, Box<dyn Error>> {
+    let default_level = if std::env::var("AGD_DEBUG").map(|v| v == "1").unwrap_or(false) {
+        "debug"
+    } else {
+        config.level.as_str()
+    };
+    let filter = EnvFilter::try_from_env("AGD_LOG").or_else(|_| EnvFilter::try_new(default_level))?;
+
+    let mut layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> = Vec::new();
+    layers.push(fmt::layer().with_writer(std::io::stderr).with_target(false).boxed());
+
+    let mut guard = None;
+    if let Some(dir) = &config.dir {
+        let rotation = match config.rotation.as_str() {
+            "minutely" => Rotation::MINUTELY,
+            "hourly" => Rotation::HOURLY,
+            "never" => Rotation::NEVER,
+            _ => Rotation::DAILY,
+        };
+        let appender = RollingFileAppender::new(rotation, dir, "agd.log");
+        let (writer, worker_guard) = tracing_appender::non_blocking(appender);
+        guard = Some(worker_guard);
+        let file_layer = fmt::layer().with_writer(writer).with_ansi(false);
+        layers.push(if config.json { file_layer.json().boxed() } else { file_layer.boxed() });
+    }
+
+    tracing_subscriber::registry().with(layers).with(filter).try_init()?;
+    Ok(guard)
+}
+
+/// 返回一份可安全写入日志的副本：密钥类字段打码，base64 图片替换为长度摘要
+pub fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, v)| {
+                    let redacted = if is_secret_key(key) { Value::String("<redacted>".to_string()) } else { redact(v) };
+                    (key.clone(), redacted)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        Value::String(s) => Value::String(redact_str(s)),
+        other => other.clone(),
+    }
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    key.contains("api_key") || key.contains("api-key") || key == "authorization" || key.contains("secret")
+}
+
+fn redact_str(s: &str) -> String {
+    if let Some(rest) = s.strip_prefix("data:") {
+        let media_type = rest.split(';').next().unwrap_or("unknown");
+        return format!("<{} {} bytes>", media_type, s.len());
+    }
+    if s.len() >= BASE64_REDACT_MIN_LEN
+        && s.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'='))
+    {
+        return format!("<base64 {} bytes>", s.len());
+    }
+    s.to_string()
+}
@@ -1,13 +1,70 @@
 #![recursion_limit = "256"]
 
+mod assets;
+mod bench;
+mod capture;
+mod cli;
+mod clipboard;
+mod config;
+mod daemon;
+mod datasource;
+mod dialog;
+mod display;
+mod gesture;
+mod i18n;
+mod images;
+mod input;
 mod orchestrator;
+mod pipeline;
+mod recorder;
+mod remote;
+mod rpc;
+mod runner;
+mod scripting;
 mod dsl;
 mod llm;
+mod logging;
+mod metrics;
 mod state;
+mod sysinfo;
+mod terminal;
+mod toast;
 mod x11;
 
 fn main() {
-    if let Err(err) = orchestrator::run() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // `--theme <auto|light|dark>` 可出现在任意位置，对所有子命令生效
+    if let Some(pos) = args.iter().position(|a| a == "--theme") {
+        let mode = args.get(pos + 1).cloned().unwrap_or_default();
+        if let Err(err) = dsl::theme::set_mode(&mode) {
+            eprintln!("fatal: {err}");
+            std::process::exit(1);
+        }
+        args.drain(pos..(pos + 2).min(args.len()));
+    }
+    // `--profile <名称>` 选择配置档案，同样可出现在任意位置
+    if let Some(pos) = args.iter().position(|a| a == "--profile") {
+        let name = args.get(pos + 1).cloned().unwrap_or_default();
+        if let Err(err) = config::set_profile(&name) {
+            eprintln!("fatal: {err}");
+            std::process::exit(1);
+        }
+        args.drain(pos..(pos + 2).min(args.len()));
+    }
+    // `--high-contrast` 同样可出现在任意位置
+    if let Some(pos) = args.iter().position(|a| a == "--high-contrast") {
+        dsl::theme::set_high_contrast();
+        args.remove(pos);
+    }
+    let result = match args.first().map(String::as_str) {
+        Some("preview") => cli::preview(&args[1..]),
+        Some("lint") => cli::lint(&args[1..]),
+        Some("bench") => bench::run(&args[1..]),
+        // `--watch file.json` 是 `preview file.json --watch` 的简写
+        Some("--watch") => cli::preview(&[&args[1..], &["--watch".to_string()]].concat()),
+        _ => orchestrator::run(),
+    };
+    if let Err(err) = result {
         eprintln!("fatal: {err}");
         std::process::exit(1);
     }
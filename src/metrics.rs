@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use tracing::{info, warn};
+
+/// 指标定义：名称、说明、直方图分桶（为空表示计数器）
+const DEFINITIONS: &[(&str, &str, &[f64])] = &[
+    ("agd_llm_requests_total", "LLM requests by provider, mode and outcome.", &[]),
+    ("agd_llm_request_duration_seconds", "LLM request latency including retries.", &[0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 40.0, 80.0, 160.0]),
+    ("agd_llm_tokens_total", "Tokens reported by the LLM API.", &[]),
+    ("agd_render_duration_seconds", "Time to rasterize and present one frame.", &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
+    ("agd_events_total", "User inputs received (clicks, text lines).", &[]),
+    ("agd_local_routes_total", "Clicks handled by the local intent router.", &[]),
+    ("agd_evaluate_iterations", "Evaluate-loop iterations needed to finalize a UI.", &[1.0, 2.0, 3.0, 4.0, 6.0, 8.0]),
+    ("agd_local_rejections_total", "Drafts sent back by the local layout checks instead of the evaluator.", &[]),
+    ("agd_evaluate_budget_exceeded_total", "Evaluate loops cut short by the wall-clock budget.", &[]),
+];
+
+type Labels = Vec<(String, String)>;
+
+#[derive(Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Default)]
+struct Registry {
+    counters: BTreeMap<(&'static str, Labels), f64>,
+    histograms: BTreeMap<(&'static str, Labels), Histogram>,
+    /// 每个直方图最近一次的观测值（不分标签），供屏幕 HUD 显示
+    last: BTreeMap<&'static str, f64>,
+}
+
+static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+
+fn registry() -> &'static Mutex<Registry> {
+    REGISTRY.get_or_init(|| Mutex::new(Registry::default()))
+}
+
+fn labels(pairs: &[(&str, &str)]) -> Labels {
+    pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+fn buckets_of(name: &str) -> &'static [f64] {
+    DEFINITIONS.iter().find(|(n, _, _)| *n == name).map(|(_, _, b)| *b).unwrap_or(&[])
+}
+
+/// 计数器累加
+pub fn inc(name: &'static str, pairs: &[(&str, &str)], value: f64) {
+    if let Ok(mut reg) = registry().lock() {
+        *reg.counters.entry((name, labels(pairs))).or_default() += value;
+    }
+}
+
+/// 直方图记录一个观测值
+pub fn observe(name: &'static str, pairs: &[(&str, &str)], value: f64) {
+    let bounds = buckets_of(name);
+    if let Ok(mut reg) = registry().lock() {
+        let hist = reg.histograms.entry((name, labels(pairs))).or_default();
+        if hist.buckets.len() != bounds.len() {
+            hist.buckets = vec![0; bounds.len()];
+        }
+        for (count, bound) in hist.buckets.iter_mut().zip(bounds) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        hist.sum += value;
+        hist.count += 1;
+        reg.last.insert(name, value);
+    }
+}
+
+/// 直方图最近一次的观测值
+pub fn last(name: &str) -> Option<f64> {
+    registry().lock().ok()?.last.get(name).copied()
+}
+
+/// 计数器在所有带 `label` 的序列上的累计值
+pub fn total(name: &str, label: (&str, &str)) -> f64 {
+    let Ok(reg) = registry().lock() else {
+        return 0.0;
+    };
+    reg.counters
+        .iter()
+        .filter(|((n, lbls), _)| *n == name && lbls.iter().any(|(k, v)| k == label.0 && v == label.1))
+        .map(|(_, value)| value)
+        .sum()
+}
+
+/// 按 token 单价（每百万 token 的美元价格，按模型名查找）估算的累计花费；
+/// 第二项为是否有用过的模型没有配置单价（它们的 token 不计入花费）
+pub fn token_cost(price: impl Fn(&str) -> Option<(f64, f64)>) -> (f64, bool) {
+    let Ok(reg) = registry().lock() else {
+        return (0.0, false);
+    };
+    let label = |lbls: &Labels, key: &str| lbls.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone()).unwrap_or_default();
+    let mut cost = 0.0;
+    let mut unpriced = false;
+    for ((_, lbls), count) in reg.counters.iter().filter(|((n, _), count)| *n == "agd_llm_tokens_total" && **count > 0.0) {
+        let per_million = price(&label(lbls, "model")).map(|(input, output)| match label(lbls, "kind").as_str() {
+            "input" => input,
+            "output" => output,
+            // 缓存命中的 token 已计在输入里
+            _ => 0.0,
+        });
+        match per_million {
+            Some(per_million) => cost += count * per_million / 1_000_000.0,
+            None => unpriced = true,
+        }
+    }
+    (cost, unpriced)
+}
+
+pub fn record_tokens(provider: &str, model: &str, input: u64, output: u64, cached: u64) {
+    for (kind, count) in [("input", input), ("output", output), ("cached", cached)] {
+        inc("agd_llm_tokens_total", &[("provider", provider), ("model", model), ("kind", kind)], count as f64);
+    }
+}
+
+/// Prometheus 文本格式（0.0.4）
+pub fn render() -> String {
+    let Ok(reg) = registry().lock() else {
+        return String::new();
+    };
+    let mut out = String::new();
+    for (name, help, bounds) in DEFINITIONS {
+        let kind = if bounds.is_empty() { "counter" } else { "histogram" };
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for ((_, lbls), value) in reg.counters.iter().filter(|((n, _), _)| n == name) {
+            let _ = writeln!(out, "{}{} {}", name, format_labels(lbls, None), value);
+        }
+        for ((_, lbls), hist) in reg.histograms.iter().filter(|((n, _), _)| n == name) {
+            for (bound, count) in bounds.iter().zip(&hist.buckets) {
+                let _ = writeln!(out, "{}_bucket{} {}", name, format_labels(lbls, Some(&bound.to_string())), count);
+            }
+            let _ = writeln!(out, "{}_bucket{} {}", name, format_labels(lbls, Some("+Inf")), hist.count);
+            let _ = writeln!(out, "{}_sum{} {}", name, format_labels(lbls, None), hist.sum);
+            let _ = writeln!(out, "{}_count{} {}", name, format_labels(lbls, None), hist.count);
+        }
+    }
+    out
+}
+
+fn format_labels(lbls: &Labels, le: Option<&str>) -> String {
+    let mut parts: Vec<String> = lbls
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    if let Some(le) = le {
+        parts.push(format!("le=\"{}\"", le));
+    }
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", parts.join(","))
+    }
+}
+
+/// 在后台线程中提供 `GET /metrics`
+pub fn serve(addr: &str) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(addr)?;
+    info!("Metrics endpoint listening on http://{}/metrics", listener.local_addr()?);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = handle(stream) {
+                warn!("metrics request failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream) -> Result<(), Box<dyn Error>> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let (status, content_type, body) = if path == "/metrics" {
+        ("200 OK", "text/plain; version=0.0.4", render())
+    } else {
+        ("404 Not Found", "text/plain", "not found\n".to_string())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body
+    )?;
+    Ok(())
+}
@@ -1,52 +1,796 @@
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
 use std::sync::mpsc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::io::{self, Write};
-use base64::{Engine as _, engine::general_purpose};
-use image::{ImageBuffer, Rgba};
-use serde_json::Value;
+use std::process::{Command as Process, Stdio};
 
-use crate::dsl::{parser, validator};
-use crate::llm::gpt52::{self, LLMMode};
-use crate::dsl::model::{ClickEvent, Command, EventEnvelope, RenderEnvelope};
+use tracing::{debug, info, warn};
+
+use crate::assets;
+use crate::capture;
+use crate::clipboard::{self, Clipboard};
+use crate::config::{self, Config};
+use crate::daemon;
+use crate::datasource::{self, DataFeeds};
+use crate::dialog::{self, KeyAction};
+use crate::display::{
+    error_screen, link_prompt, ClickEvent, GestureEvent, hud_overlay, loading_overlay, SoundCue, Surface, UiEvent, BUSY_FRAME_INTERVAL, DISMISS_TARGET,
+    LINK_CANCEL_TARGET, LINK_OPEN_TARGET, RETRY_TARGET,
+};
+use crate::i18n;
+use crate::logging;
+use crate::images;
+use crate::input::{Inputs, KeyOutcome};
+use crate::metrics;
+use crate::dsl::validator;
+use crate::dsl::theme;
+use crate::llm::prompts::PromptVars;
+use crate::llm::provider;
+use crate::pipeline::{self, LlmJob};
+use crate::dsl::model::{Command, EventBody, EventEnvelope, FormSubmission, GestureInfo, RenderEnvelope, RunOutput, WindowSpec};
+use crate::state::form;
 use crate::state::hit_test::{HitTarget, HitTestIndex};
-use crate::x11::{backend, events, renderer};
+use crate::state::navigation::{self, Navigation};
+use crate::state::router::IntentRouter;
+use crate::remote::{FrameFormat, RemoteDisplay};
+use crate::recorder::{self, Recorder};
+use crate::rpc::RpcSurface;
+use crate::runner;
+use crate::scripting;
+use crate::sysinfo;
+use crate::terminal::{self, Terminals};
+use crate::toast::Toasts;
+use crate::x11::{backend, renderer};
 
 pub fn run() -> Result<(), Box<dyn Error>> {
+    let config = config::init()?;
+    // guard 必须活到进程结束，文件日志才能完整落盘
+    let _log_guard = logging::init(&config.logging)?;
+    if let Some(addr) = &config.metrics_addr {
+        metrics::serve(addr)?;
+    }
+    if let Some(addr) = &config.daemon_addr {
+        return daemon::serve(config, addr);
+    }
+
+    info!("{}", i18n::text("bridge.active"));
+
+    // 机器模式下 stdin/stdout 归 JSON-RPC 使用，不再打印提示符
+    let rpc = config.rpc.then(RpcSurface::spawn);
+
+    // 等待用户输入后再开始
+    let initial_input = match &rpc {
+        Some(rpc) => rpc.wait_for_text()?,
+        None => {
+            print!(">> ");
+            io::stdout().flush()?;
+            let mut line = String::new();
+            io::stdin().read_line(&mut line)?;
+            line
+        }
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let interactive = rpc.is_none();
+    run_session(config, initial_input.trim(), rpc, rx, move || {
+        if interactive {
+            spawn_prompt(tx);
+        }
+    })
+}
+
+/// 一个会话：独立的 LLM 工作线程、显示窗口、事件历史与本地路由状态。
+/// `inputs` 是显示面之外的输入来源（终端、守护进程控制接口），收到 `Quit` 即结束会话；
+/// `on_ready` 在首帧显示后调用一次。
+pub fn run_session(
+    mut config: &'static Config,
+    initial_input: &str,
+    rpc: Option<RpcSurface>,
+    inputs: mpsc::Receiver<UiEvent>,
+    on_ready: impl FnOnce(),
+) -> Result<(), Box<dyn Error>> {
     let mut hit_test = HitTestIndex::new();
-    let (primary, emoji) = backend::load_fonts();
+    let llm = provider::from_config(config)?;
+    let scripts = scripting::load(&config.scripts)?;
     let is_debug = std::env::var("AGD_DEBUG").map(|v| v == "1").unwrap_or(false);
 
     if is_debug {
         let _ = std::fs::create_dir_all("debug_out");
     }
+    debug!("LLM provider: {}", llm.name());
 
-    println!("AGD UI Bridge active.");
-    
-    // 等待用户输入后再开始
-    print!(">> ");
-    io::stdout().flush()?;
-    let mut initial_input = String::new();
-    io::stdin().read_line(&mut initial_input)?;
-    
-    let initial_dsl = gpt52::request_render(None, Some(initial_input.trim()), LLMMode::Generate)?;
-    let parsed = iterate_to_final(&initial_dsl, None, Some(initial_input.trim()), primary.as_ref(), emoji.as_ref(), is_debug)?;
+    // LLM 请求全部在工作线程中执行，UI 线程只负责事件与绘制
+    let (mut worker, result_rx) = pipeline::spawn_llm_worker(llm, config, scripts, is_debug);
+    let mut history = VecDeque::new();
+    push_history(&mut history, format!("user: {}", initial_input));
+    let mut last_job = LlmJob {
+        event_json: None,
+        user_text: Some(initial_input.to_string()),
+        current: None,
+        attachment: None,
+        companion: None,
+        vars: prompt_vars(None, None, &history),
+    };
+    worker.submit(last_job.clone());
+    // 本地画面（失败时的内置错误画面、打开链接前的确认卡片）显示期间盖住当前界面，上面的按钮由编排器处理；
+    // 失败时显示错误画面而不是结束进程，首帧就失败时错误画面本身作为当前界面
+    let mut local_screen: Option<RenderEnvelope> = None;
+    // 等待用户确认的 link 地址
+    let mut pending_link: Option<String> = None;
+    let parsed = match checked(result_rx.recv()?) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            warn!("initial generation failed: {}", e);
+            let window = WindowSpec { width: 800, height: 600, title: "AGD".to_string(), background: None };
+            let screen = error_screen(&e.to_string(), &window);
+            local_screen = Some(screen.clone());
+            screen
+        }
+    };
     
     let mut last_render_seq = parsed.seq;
     let mut event_seq = 0u64;
     let mut current_render = parsed.clone();
+    let mut router = IntentRouter::new(&config.router);
+    // 界面中输入框的内容与键盘焦点，只保存在本地
+    let mut text_fields = Inputs::new();
+    // 最近一次提交给模型的任务由哪个点击目标触发（文本输入为 None）
+    let mut pending_trigger: Option<String> = None;
+    // 有任务在等待模型时，记录提交时间用于绘制加载动画
+    let mut busy_since: Option<Instant> = None;
+    let mut last_busy_frame = Instant::now();
+    // 界面声明的定时器下次触发的时刻
+    let mut timer_due: HashMap<String, Instant> = HashMap::new();
+    // 最近一次用户输入；超过 idle_timeout_secs 发出 idle 事件，之后的第一次输入发出 resume
+    let mut last_input = Instant::now();
+    let mut idle = false;
+    // 自适应轮询：最近一次处理输入或结果的时刻、当前等待间隔，以及等待期间提前到达的模型结果
+    let mut last_activity = Instant::now();
+    let mut poll_interval = MIN_POLL_INTERVAL;
+    let mut woken_result: Option<pipeline::LlmResult> = None;
     
-    let x11 = backend::X11Backend::connect(
-        parsed.window.width as u16,
-        parsed.window.height as u16,
-        &parsed.window.title,
-    )?;
-    
-    renderer::render_frame(&x11, &parsed)?;
-    build_hit_test(&mut hit_test, &parsed);
+    // `/capture` 在后台截取，完成后的图片附在下一次文字输入上
+    let mut capture_rx: Option<mpsc::Receiver<Result<String, String>>> = None;
+    let mut pending_attachment: Option<String> = None;
+    // 正在后台执行的 run 动作：触发它的目标 id、点击位置，以及结果通道
+    let mut running: Option<(String, (i32, i32), mpsc::Receiver<RunOutput>)> = None;
+    // `/follow` 等待用户点选窗口
+    let mut follow_rx: Option<mpsc::Receiver<Result<u32, String>>> = None;
+    // 界面中 terminal 命令对应的伪终端会话，以及上一次因终端输出重画的时刻
+    let mut terminals = Terminals::new();
+    let mut last_terminal_frame = Instant::now();
+    // 界面中 data_sources 对应的轮询线程
+    let mut data_feeds = DataFeeds::new();
+    // 界面中提示条的计时：到期或被点击时在本地移除
+    let mut toasts = Toasts::new();
+    // navigate:push / navigate:pop 按钮维护的导航栈
+    let mut navigation = Navigation::new();
+    // 第一个画面与之后的模型结果一样先在本地填好，呈现与命中测试都用填好的这一份
+    terminals.sync(&current_render);
+    terminals.fill(&mut current_render);
+    data_feeds.sync(&current_render);
+    data_feeds.fill(&mut current_render);
+    toasts.sync(&mut current_render);
+    text_fields.sync(&current_render);
+    text_fields.fill(&mut current_render);
+    let surfaces = open_surfaces(config, &current_render, rpc)?;
+    let dpi = surfaces.iter().find_map(|s| s.dpi());
+    sysinfo::init(surfaces.iter().find_map(|s| s.screen_size()), dpi);
+    present_all(&surfaces, &current_render)?;
+    build_hit_test(&mut hit_test, &current_render);
+    on_ready();
+
+    loop {
+        for result in woken_result.take().into_iter().chain(result_rx.try_iter()) {
+            last_activity = Instant::now();
+            busy_since = None;
+            match checked(result) {
+                Ok(mut parsed) => {
+                    local_screen = None;
+                    pending_link = None;
+                    // 先启动新界面里的终端，沿用下来的终端直接带着已有输出显示
+                    terminals.sync(&parsed);
+                    terminals.fill(&mut parsed);
+                    data_feeds.sync(&parsed);
+                    data_feeds.fill(&mut parsed);
+                    toasts.sync(&mut parsed);
+                    text_fields.sync(&parsed);
+                    text_fields.fill(&mut parsed);
+                    // navigate:push 按钮请求的新画面：它之前的画面压入导航栈
+                    if pending_trigger.as_deref().is_some_and(|id| rect_action(&current_render, id) == Some("navigate:push")) {
+                        navigation.push(&current_render);
+                    }
+                    navigation.fill(&mut parsed);
+                    update_ui(&surfaces, &parsed, &mut last_render_seq, &mut hit_test)?;
+                    play_sound(config, &surfaces, SoundCue::Complete);
+                    router.record(pending_trigger.as_deref(), &current_render, &parsed);
+                    current_render = parsed;
+                }
+                Err(e) => {
+                    warn!("generation failed: {}", e);
+                    pending_link = None;
+                    let screen = error_screen(&e.to_string(), &current_render.window);
+                    update_ui(&surfaces, &screen, &mut last_render_seq, &mut hit_test)?;
+                    play_sound(config, &surfaces, SoundCue::Error);
+                    local_screen = Some(screen);
+                }
+            }
+        }
+
+        if let Some(result) = capture_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            capture_rx = None;
+            match result {
+                Ok(image) => {
+                    info!("Capture ready; it will be attached to the next input.");
+                    pending_attachment = Some(image);
+                }
+                Err(e) => warn!("capture failed: {}", e),
+            }
+        }
+
+        if let Some((target_id, position, rx)) = running.take() {
+            match rx.try_recv() {
+                Ok(output) => {
+                    info!("{} exited with {:?}{}", output.name, output.exit_code, if output.timed_out { " (timed out)" } else { "" });
+                    last_activity = Instant::now();
+                    event_seq += 1;
+                    pending_trigger = Some(target_id.clone());
+                    last_job = run_job(output, &target_id, position, event_seq, &current_render, dpi, &history)?;
+                    worker.submit(last_job.clone());
+                }
+                Err(mpsc::TryRecvError::Empty) => running = Some((target_id, position, rx)),
+                Err(mpsc::TryRecvError::Disconnected) => busy_since = None,
+            }
+        }
+
+        if let Some(result) = follow_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            follow_rx = None;
+            match result {
+                Ok(window) => {
+                    info!("Following window 0x{:x}; its screenshot goes with every request.", window);
+                    worker.follow_window(Some(window));
+                }
+                Err(e) => warn!("/follow: {}", e),
+            }
+        }
+
+        // 终端/控制接口输入与各显示面（X11 窗口、远程客户端、JSON-RPC）的输入统一处理
+        let mut pending: Vec<UiEvent> = inputs.try_iter().collect();
+        for surface in &surfaces {
+            while let Some(event) = surface.poll_event()? {
+                pending.push(event);
+            }
+        }
+
+        if !pending.is_empty() {
+            last_activity = Instant::now();
+        }
+        // 唤醒界面的输入照常处理，历史里的 resume 随它触发的请求发给模型；
+        // 处理完仍没有请求模型时（本地处理、点在空白处）再单独发 resume。记下唤醒时已提交的任务数
+        let mut resume_after: Option<u64> = None;
+        for input in pending {
+            if matches!(input, UiEvent::Click(_) | UiEvent::Key(_) | UiEvent::Gesture(_) | UiEvent::Text(_)) {
+                last_input = Instant::now();
+                if idle {
+                    idle = false;
+                    info!("Activity after idle, resuming.");
+                    push_history(&mut history, "resume".to_string());
+                    resume_after = Some(worker.submitted());
+                }
+            }
+            // 对话框打开时按键只交给它：切换按钮焦点、按下按钮（等同于点击），其余按键吞掉
+            let input = match input {
+                UiEvent::Key(key) if local_screen.is_none() && !SCREENSHOT_KEYS.contains(&key.keysym) => {
+                    match dialog::handle_key(&mut current_render, &key) {
+                        Some(KeyAction::Focus) => {
+                            update_ui(&surfaces, &current_render, &mut last_render_seq, &mut hit_test)?;
+                            continue;
+                        }
+                        Some(KeyAction::Press(x, y)) => UiEvent::Click(ClickEvent { x, y }),
+                        Some(KeyAction::Ignore) => continue,
+                        None => UiEvent::Key(key),
+                    }
+                }
+                other => other,
+            };
+            // 有焦点的输入框接收文字与编辑键，Enter 等同于点击所在表单的提交按钮
+            let input = match input {
+                UiEvent::Key(key) if local_screen.is_none() && !SCREENSHOT_KEYS.contains(&key.keysym) => {
+                    match text_fields.send_key(&current_render, &key) {
+                        Some(KeyOutcome::Edited) => {
+                            last_input = Instant::now();
+                            if text_fields.fill(&mut current_render) {
+                                update_ui(&surfaces, &current_render, &mut last_render_seq, &mut hit_test)?;
+                            }
+                            continue;
+                        }
+                        Some(KeyOutcome::Submit(x, y)) => UiEvent::Click(ClickEvent { x, y }),
+                        None => UiEvent::Key(key),
+                    }
+                }
+                other => other,
+            };
+            // 有焦点的终端接收除截图键外的所有按键，不再当作快捷键或访问键
+            if let UiEvent::Key(key) = &input {
+                if local_screen.is_none() && !SCREENSHOT_KEYS.contains(&key.keysym) && terminals.send_key(key) {
+                    last_input = Instant::now();
+                    continue;
+                }
+            }
+            // 快捷键与访问键按下等同于点击对应目标的中心；带 Ctrl/Alt 的按键只匹配快捷键
+            let input = match input {
+                UiEvent::Key(key) => match hit_test
+                    .shortcut_target(&key)
+                    .or_else(|| if key.ctrl || key.alt { None } else { hit_test.access_key_target(key.keysym) })
+                {
+                    Some(target) => UiEvent::Click(ClickEvent { x: target.x + target.w as i32 / 2, y: target.y + target.h as i32 / 2 }),
+                    // 没有被快捷键占用的 Ctrl+V 等同于 /paste
+                    None if key.ctrl && !key.alt && matches!(char::from_u32(key.keysym), Some('v' | 'V')) => {
+                        UiEvent::Text("/paste".to_string())
+                    }
+                    None => UiEvent::Key(key),
+                },
+                other => other,
+            };
+            match input {
+                UiEvent::Text(text) => {
+                    // /paste（窗口中 Ctrl+V）：剪贴板里的文字当作这次输入，图片附给模型；粘贴的内容不解释为元命令
+                    let (text, attachment, pasted) = if text.trim() == "/paste" {
+                        match paste(&surfaces, config) {
+                            Some((text, attachment)) => (text, attachment, true),
+                            None => {
+                                warn!("Nothing to paste: the clipboard is empty or unavailable.");
+                                continue;
+                            }
+                        }
+                    } else {
+                        (text, None, false)
+                    };
+                    // 元命令：/quit 结束会话，/clear 清空上下文，/restart [文本] 以全新状态重新开始，/screenshot 保存截图，
+                    // /capture [x y w h] 截取一张图片附给下一次输入，/follow [off|窗口 id] 跟随另一个程序的窗口，
+                    // /profile [名称] 列出或切换配置档案
+                    if let Some(command) = text.strip_prefix('/').filter(|_| !pasted) {
+                        let (name, arg) = command.split_once(' ').map(|(n, a)| (n, a.trim())).unwrap_or((command, ""));
+                        match name {
+                            "quit" => {
+                                info!("Quit requested, exiting.");
+                                return Ok(());
+                            }
+                            "screenshot" => {
+                                take_screenshot(config, local_screen.as_ref().unwrap_or(&current_render));
+                                continue;
+                            }
+                            "follow" => {
+                                match arg {
+                                    "off" => {
+                                        info!("Stopped following the other window.");
+                                        worker.follow_window(None);
+                                    }
+                                    "" => {
+                                        info!("Click the window to follow.");
+                                        follow_rx = Some(start_pick_window());
+                                    }
+                                    id => match parse_window_id(id) {
+                                        Some(window) => {
+                                            info!("Following window 0x{:x}; its screenshot goes with every request.", window);
+                                            worker.follow_window(Some(window));
+                                        }
+                                        None => warn!("/follow: not a window id: {}", id),
+                                    },
+                                }
+                                continue;
+                            }
+                            "profile" => {
+                                if arg.is_empty() {
+                                    let current = config.profile.as_deref().unwrap_or("(none)");
+                                    info!("Profile: {}; available: {}", current, config::list_profiles().join(", "));
+                                    continue;
+                                }
+                                // 新配置、模型驱动与脚本都构造成功后才替换，失败时保持原样
+                                let switched = Config::load_profile(Some(arg)).and_then(|next| {
+                                    let llm = provider::from_config(&next)?;
+                                    let scripts = scripting::load(&next.scripts)?;
+                                    Ok((next, llm, scripts))
+                                });
+                                match switched {
+                                    Ok((next, llm, scripts)) => {
+                                        config = config::install(next);
+                                        worker.cancel_pending();
+                                        worker.reconfigure(llm, config, scripts);
+                                        busy_since = None;
+                                        router = IntentRouter::new(&config.router);
+                                        // 主题可能随档案变化，按新调色板重画当前画面
+                                        present_all(&surfaces, local_screen.as_ref().unwrap_or(&current_render))?;
+                                    }
+                                    Err(e) => warn!("/profile: {}", e),
+                                }
+                                continue;
+                            }
+                            "capture" => {
+                                match capture::parse_region(arg) {
+                                    Ok(region) => capture_rx = Some(start_capture(config, region)),
+                                    Err(e) => warn!("/capture: {}", e),
+                                }
+                                continue;
+                            }
+                            "clear" | "restart" => {
+                                worker.cancel_pending();
+                                busy_since = None;
+                                history.clear();
+                                router = IntentRouter::new(&config.router);
+                                // 导航栈与当前画面的面包屑一起清空，旧画面不能再被 navigate:pop 弹回
+                                navigation = Navigation::new();
+                                navigation.fill(&mut current_render);
+                                pending_trigger = None;
+                                if name == "clear" {
+                                    info!("Context cleared.");
+                                    present_all(&surfaces, local_screen.as_ref().unwrap_or(&current_render))?;
+                                    continue;
+                                }
+                                let text = if arg.is_empty() { initial_input } else { arg };
+                                info!("Restarting session: {}", text);
+                                local_screen = None;
+                                event_seq = 0;
+                                push_history(&mut history, format!("user: {}", text));
+                                busy_since = Some(Instant::now());
+                                last_job = LlmJob {
+                                    event_json: None,
+                                    user_text: Some(text.to_string()),
+                                    current: None,
+                                    attachment: None,
+                                    companion: None,
+                                    vars: prompt_vars(None, dpi, &history),
+                                };
+                                worker.submit(last_job.clone());
+                                continue;
+                            }
+                            // 其它以 / 开头的文本照常交给模型
+                            _ => {}
+                        }
+                    }
+                    let attachment = attachment.or_else(|| pending_attachment.take());
+                    push_history(&mut history, format!("user: {}", text));
+                    metrics::inc("agd_events_total", &[("kind", if pasted { "paste" } else { "text" })], 1.0);
+                    pending_trigger = None;
+                    busy_since = Some(Instant::now());
+                    last_job = LlmJob {
+                        event_json: None,
+                        user_text: Some(text),
+                        current: Some(current_render.clone()),
+                        attachment,
+                        companion: None,
+                        vars: prompt_vars(Some(&current_render), dpi, &history),
+                    };
+                    worker.submit(last_job.clone());
+                }
+                UiEvent::Click(click) => {
+                    // 点击提示条提前关闭它，不再交给下面的控件
+                    if local_screen.is_none() && toasts.dismiss_at(&mut current_render, click.x, click.y) {
+                        update_ui(&surfaces, &current_render, &mut last_render_seq, &mut hit_test)?;
+                        continue;
+                    }
+                    // 点击输入框让它获得键盘焦点，点在别处取消焦点；滑块与选择框在本地改值。
+                    // 对话框打开时同样不接收点击
+                    if local_screen.is_none() && dialog::active(&current_render).is_none() {
+                        let hit = text_fields.click_at(&current_render, click.x, click.y);
+                        if text_fields.fill(&mut current_render) {
+                            update_ui(&surfaces, &current_render, &mut last_render_seq, &mut hit_test)?;
+                        }
+                        if hit {
+                            // 终端同时失去焦点
+                            terminals.focus_at(&current_render, click.x, click.y);
+                            last_input = Instant::now();
+                            continue;
+                        }
+                    }
+                    // 点击终端让它获得键盘焦点，点在别处取消焦点；对话框打开时终端在遮罩之下，不接收点击
+                    if local_screen.is_none() && dialog::active(&current_render).is_none() && terminals.focus_at(&current_render, click.x, click.y) {
+                        continue;
+                    }
+                    let Some(target) = hit_test.hit_target(click.x, click.y).cloned() else {
+                        continue;
+                    };
+                    metrics::inc("agd_events_total", &[("kind", "click")], 1.0);
+                    for surface in &surfaces {
+                        surface.present_pressed(local_screen.as_ref().unwrap_or(&current_render), &target)?;
+                    }
+                    play_sound(config, &surfaces, SoundCue::Click);
+                    // 本地画面上只有本地按钮：重试上一次任务、回到出错前的界面，或打开 / 放弃待确认的链接
+                    if local_screen.is_some() {
+                        match target.id.as_str() {
+                            RETRY_TARGET => {
+                                busy_since = Some(Instant::now());
+                                worker.submit(last_job.clone());
+                            }
+                            DISMISS_TARGET | LINK_CANCEL_TARGET => {
+                                local_screen = None;
+                                pending_link = None;
+                                update_ui(&surfaces, &current_render, &mut last_render_seq, &mut hit_test)?;
+                            }
+                            LINK_OPEN_TARGET => {
+                                if let Some(href) = pending_link.take() {
+                                    open_link(&href);
+                                    push_history(&mut history, format!("opened link: {}", href));
+                                }
+                                local_screen = None;
+                                update_ui(&surfaces, &current_render, &mut last_render_seq, &mut hit_test)?;
+                            }
+                            _ => {}
+                        }
+                        continue;
+                    }
+                    if rect_action(&current_render, &target.id) == Some("exit") {
+                        info!("Exit action triggered by {}, exiting.", target.id);
+                        return Ok(());
+                    }
+                    // 链接：模型无法替用户打开网页，由本地用 xdg-open 打开（默认先显示确认卡片），不发事件
+                    if rect_action(&current_render, &target.id) == Some("link") {
+                        let Some(href) = rect_href(&current_render, &target.id) else {
+                            continue;
+                        };
+                        metrics::inc("agd_events_total", &[("kind", "link")], 1.0);
+                        if config.confirm_links {
+                            let prompt = link_prompt(&current_render, &href);
+                            update_ui(&surfaces, &prompt, &mut last_render_seq, &mut hit_test)?;
+                            local_screen = Some(prompt);
+                            pending_link = Some(href);
+                        } else {
+                            open_link(&href);
+                            push_history(&mut history, format!("opened link: {}", href));
+                        }
+                        continue;
+                    }
+                    // 本地命令：在后台执行白名单中的命令，结束后把退出码与输出作为 run 事件交给模型
+                    if rect_action(&current_render, &target.id) == Some("run") {
+                        let Some(name) = rect_run(&current_render, &target.id) else {
+                            continue;
+                        };
+                        if running.is_some() {
+                            warn!("A command is still running; ignoring {}.", target.id);
+                            continue;
+                        }
+                        metrics::inc("agd_events_total", &[("kind", "run")], 1.0);
+                        push_history(&mut history, format!("run: {} ({})", target.id, name));
+                        busy_since = Some(Instant::now());
+                        running = Some((target.id.clone(), (click.x, click.y), start_run(name)));
+                        continue;
+                    }
+                    // 提交按钮：把表单区域内控件的本地状态合成一个 submit 事件，不再逐个控件发事件
+                    if rect_action(&current_render, &target.id) == Some("submit") {
+                        let form = form::collect(&current_render, &target.id, &text_fields);
+                        let fields = form.as_ref().map(|f| f.fields.len()).unwrap_or(0);
+                        metrics::inc("agd_events_total", &[("kind", "submit")], 1.0);
+                        event_seq += 1;
+                        pending_trigger = Some(target.id.clone());
+                        push_history(&mut history, format!("submit: {} ({} field(s))", target.id, fields));
+                        busy_since = Some(Instant::now());
+                        last_job = event_job("submit", &target.id, Some((click.x, click.y)), form, event_seq, &current_render, dpi, &history)?;
+                        worker.submit(last_job.clone());
+                        continue;
+                    }
+                    // 导航返回：在本地弹出上一个画面立即显示，不请求模型；栈为空时照常交给模型
+                    if rect_action(&current_render, &target.id) == Some("navigate:pop") {
+                        if let Some(mut previous) = navigation.pop() {
+                            metrics::inc("agd_events_total", &[("kind", "navigate")], 1.0);
+                            push_history(&mut history, format!("click: {} (back to {}, handled locally)", target.id, navigation::name(&previous)));
+                            worker.cancel_pending();
+                            busy_since = None;
+                            navigation.fill(&mut previous);
+                            data_feeds.sync(&previous);
+                            data_feeds.fill(&mut previous);
+                            toasts.sync(&mut previous);
+                            text_fields.sync(&previous);
+                            text_fields.fill(&mut previous);
+                            update_ui(&surfaces, &previous, &mut last_render_seq, &mut hit_test)?;
+                            current_render = previous;
+                            continue;
+                        }
+                    }
+                    if let Some(mut next) = router.route(&target.id, &current_render) {
+                        debug!("routed click on {} locally", target.id);
+                        metrics::inc("agd_local_routes_total", &[], 1.0);
+                        push_history(&mut history, format!("click: {} (handled locally)", target.id));
+                        // 在途的模型任务基于旧画面，已经过期
+                        worker.cancel_pending();
+                        busy_since = None;
+                        if rect_action(&current_render, &target.id) == Some("navigate:push") {
+                            navigation.push(&current_render);
+                        }
+                        navigation.fill(&mut next);
+                        data_feeds.sync(&next);
+                        data_feeds.fill(&mut next);
+                        toasts.sync(&mut next);
+                        text_fields.sync(&next);
+                        text_fields.fill(&mut next);
+                        update_ui(&surfaces, &next, &mut last_render_seq, &mut hit_test)?;
+                        current_render = next;
+                        continue;
+                    }
+                    event_seq += 1;
+                    pending_trigger = Some(target.id.clone());
+                    push_history(&mut history, format!("click: {}", target.id));
+                    busy_since = Some(Instant::now());
+                    last_job = event_job("click", &target.id, Some((click.x, click.y)), None, event_seq, &current_render, dpi, &history)?;
+                    worker.submit(last_job.clone());
+                }
+                UiEvent::Gesture(gesture) => {
+                    // 错误画面只有本地按钮，手势没有意义
+                    if local_screen.is_some() {
+                        continue;
+                    }
+                    // 起点落在可点击目标上时带上它的 id（如划走一张卡片），否则是对整个画面的手势
+                    let target_id = hit_test.hit_target(gesture.x, gesture.y).map(|t| t.id.clone()).unwrap_or_default();
+                    let name = if gesture.is_flick() { "flick" } else { "swipe" };
+                    debug!("{} {} at {:.0} px/s from ({}, {})", name, gesture.direction.name(), gesture.velocity, gesture.x, gesture.y);
+                    metrics::inc("agd_events_total", &[("kind", "gesture")], 1.0);
+                    event_seq += 1;
+                    pending_trigger = None;
+                    push_history(&mut history, format!("{}: {}", name, gesture.direction.name()));
+                    busy_since = Some(Instant::now());
+                    last_job = gesture_job(&gesture, &target_id, event_seq, &current_render, dpi, &history)?;
+                    worker.submit(last_job.clone());
+                }
+                UiEvent::Key(key) if SCREENSHOT_KEYS.contains(&key.keysym) => {
+                    take_screenshot(config, local_screen.as_ref().unwrap_or(&current_render));
+                }
+                UiEvent::Key(_) => {}
+                UiEvent::Expose => present_all(&surfaces, local_screen.as_ref().unwrap_or(&current_render))?,
+                UiEvent::Quit => {
+                    info!("Shutdown requested, exiting.");
+                    return Ok(());
+                }
+            }
+        }
+
+        if resume_after.is_some_and(|submitted| submitted == worker.submitted()) {
+            metrics::inc("agd_events_total", &[("kind", "resume")], 1.0);
+            event_seq += 1;
+            pending_trigger = None;
+            busy_since = Some(Instant::now());
+            last_job = event_job("resume", "", None, None, event_seq, &current_render, dpi, &history)?;
+            worker.submit(last_job.clone());
+        }
+
+        // 后台下载的远程图片到了：重画当前画面，占位框换成图片
+        if images::take_arrived() {
+            present_all(&surfaces, local_screen.as_ref().unwrap_or(&current_render))?;
+        }
+
+        // 终端的新输出与焦点变化：按帧间隔重画当前界面
+        if local_screen.is_none() {
+            terminals.sync(&current_render);
+            if last_terminal_frame.elapsed() >= TERMINAL_FRAME_INTERVAL && terminals.fill(&mut current_render) {
+                update_ui(&surfaces, &current_render, &mut last_render_seq, &mut hit_test)?;
+                last_terminal_frame = Instant::now();
+            }
+            // 数据来源取到新值：填进绑定的文本与进度条后重画，不请求模型
+            data_feeds.sync(&current_render);
+            if data_feeds.has_update() && data_feeds.fill(&mut current_render) {
+                update_ui(&surfaces, &current_render, &mut last_render_seq, &mut hit_test)?;
+            }
+            if toasts.expire(&mut current_render) {
+                update_ui(&surfaces, &current_render, &mut last_render_seq, &mut hit_test)?;
+            }
+        }
+
+        if let Some(id) = due_timer(&mut timer_due, &current_render, local_screen.is_none() && busy_since.is_none()) {
+            debug!("timer {} fired", id);
+            metrics::inc("agd_events_total", &[("kind", "timer")], 1.0);
+            event_seq += 1;
+            pending_trigger = None;
+            push_history(&mut history, format!("timer: {}", id));
+            busy_since = Some(Instant::now());
+            last_job = event_job("timer", &id, None, None, event_seq, &current_render, dpi, &history)?;
+            worker.submit(last_job.clone());
+        }
+
+        let ready = local_screen.is_none() && busy_since.is_none();
+        if let Some(timeout) = config.idle_timeout_secs.map(Duration::from_secs).filter(|_| !idle && ready) {
+            if last_input.elapsed() >= timeout {
+                info!("No input for {}s, sending idle event.", timeout.as_secs());
+                idle = true;
+                metrics::inc("agd_events_total", &[("kind", "idle")], 1.0);
+                event_seq += 1;
+                pending_trigger = None;
+                push_history(&mut history, "idle".to_string());
+                busy_since = Some(Instant::now());
+                last_job = event_job("idle", "", None, None, event_seq, &current_render, dpi, &history)?;
+                worker.submit(last_job.clone());
+            }
+        }
+
+        if let Some(since) = busy_since {
+            if last_busy_frame.elapsed() >= BUSY_FRAME_INTERVAL {
+                let mut overlay = loading_overlay(local_screen.as_ref().unwrap_or(&current_render), since.elapsed());
+                if config.hud {
+                    overlay = hud_overlay(&overlay);
+                }
+                for surface in &surfaces {
+                    surface.present_transient(&overlay)?;
+                }
+                last_busy_frame = Instant::now();
+            }
+        }
+
+        // 等到下一个截止时刻（加载动画帧、定时器、空闲超时）或轮询间隔；模型结果到达时立即醒来
+        let ready = local_screen.is_none() && busy_since.is_none();
+        let active = busy_since.is_some() || last_activity.elapsed() < ACTIVE_WINDOW;
+        poll_interval = if active { MIN_POLL_INTERVAL } else { (poll_interval * 2).min(MAX_POLL_INTERVAL) };
+        let mut deadlines: Vec<Instant> = Vec::new();
+        if busy_since.is_some() {
+            deadlines.push(last_busy_frame + BUSY_FRAME_INTERVAL);
+        }
+        if terminals.has_output() {
+            deadlines.push(last_terminal_frame + TERMINAL_FRAME_INTERVAL);
+        }
+        if local_screen.is_none() {
+            deadlines.extend(toasts.next_deadline());
+        }
+        if ready {
+            deadlines.extend(timer_due.values().copied());
+            if let Some(timeout) = config.idle_timeout_secs.filter(|_| !idle) {
+                deadlines.push(last_input + Duration::from_secs(timeout));
+            }
+        }
+        let now = Instant::now();
+        let wait = deadlines.iter().map(|d| d.saturating_duration_since(now)).fold(poll_interval, Duration::min);
+        match result_rx.recv_timeout(wait.max(Duration::from_millis(1))) {
+            Ok(result) => woken_result = Some(result),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            // 工作线程已退出时 recv_timeout 立即返回，照常按间隔等待
+            Err(mpsc::RecvTimeoutError::Disconnected) => thread::sleep(wait),
+        }
+    }
+}
+
+/// 事件循环的轮询间隔：最近有活动（输入、模型结果）或正在等待模型时用最短间隔以降低输入延迟，
+/// 空闲时每轮加倍直到上限，空闲 CPU 占用接近 0
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(4);
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// 最近一次活动后保持最短间隔的时长
+const ACTIVE_WINDOW: Duration = Duration::from_millis(500);
+/// 终端有新输出时重画界面的最短间隔，大量输出时合并成一帧
+const TERMINAL_FRAME_INTERVAL: Duration = Duration::from_millis(50);
 
-    let (tx, rx) = mpsc::channel::<String>();
+/// 打开显示面：默认是本地 X11 窗口；配置了 `remote.listen` 时再加一个 WebSocket 远程显示，
+/// `remote.headless` 时只保留远程显示，进程可以在没有 X server 的服务器上运行；
+/// JSON-RPC 模式下嵌入方本身也是一个显示面
+fn open_surfaces(config: &Config, first: &RenderEnvelope, rpc: Option<RpcSurface>) -> Result<Vec<Box<dyn Surface>>, Box<dyn Error>> {
+    let mut surfaces: Vec<Box<dyn Surface>> = Vec::new();
+    if !config.remote.headless {
+        surfaces.push(Box::new(backend::X11Backend::connect(
+            first.window.width as u16,
+            first.window.height as u16,
+            &first.window.title,
+            renderer::window_background(first),
+        )?));
+    }
+    // 守护进程中各会话无法共用同一个监听地址，远程显示只在单会话模式下开启
+    if let Some(addr) = config.remote.listen.as_ref().filter(|_| config.daemon_addr.is_none()) {
+        let format = match config.remote.format.as_str() {
+            "commands" => FrameFormat::Commands,
+            _ => FrameFormat::Png,
+        };
+        surfaces.push(Box::new(RemoteDisplay::bind(addr, format)?));
+    }
+    if let Some(rpc) = rpc {
+        surfaces.push(Box::new(rpc));
+    }
+    // 录像不算可交互的显示面，放在检查之后
+    let no_display = surfaces.is_empty();
+    if let Some(path) = config.screen_record.as_ref().filter(|_| config.daemon_addr.is_none()) {
+        surfaces.push(Box::new(Recorder::create(path)?));
+    }
+    if no_display {
+        return Err("headless mode requires remote.listen or rpc".into());
+    }
+    Ok(surfaces)
+}
+
+/// 交互模式：终端逐行读取文本输入
+fn spawn_prompt(tx: mpsc::Sender<UiEvent>) {
     thread::spawn(move || {
         let mut line = String::new();
         loop {
@@ -56,148 +800,353 @@ pub fn run() -> Result<(), Box<dyn Error>> {
             if io::stdin().read_line(&mut line).is_err() { break; }
             let trimmed = line.trim();
             if trimmed.is_empty() { continue; }
-            if tx.send(trimmed.to_string()).is_err() { break; }
+            if tx.send(UiEvent::Text(trimmed.to_string())).is_err() { break; }
         }
     });
+}
 
-    loop {
-        while let Ok(text) = rx.try_recv() {
-            let next_dsl = gpt52::request_render(None, Some(text.as_str()), LLMMode::Generate)?;
-            let parsed = iterate_to_final(&next_dsl, None, Some(text.as_str()), primary.as_ref(), emoji.as_ref(), is_debug)?;
-            update_ui(&x11, &parsed, &mut last_render_seq, &mut hit_test)?;
-            current_render = parsed.clone();
-        }
-
-        if let Some(click) = events::poll_for_click(&x11)? {
-            if let Some(target) = hit_test.hit_target(click.x, click.y) {
-                render_pressed_feedback(&x11, &current_render, target)?;
-                event_seq += 1;
-                let event_json = build_click_event_json(target.id.as_str(), click.x, click.y, event_seq)?;
-                let next_dsl = gpt52::request_render(Some(event_json.as_str()), None, LLMMode::Generate)?;
-                let parsed = iterate_to_final(&next_dsl, Some(&event_json), None, primary.as_ref(), emoji.as_ref(), is_debug)?;
-                update_ui(&x11, &parsed, &mut last_render_seq, &mut hit_test)?;
-                current_render = parsed.clone();
-            }
-        }
-
-        thread::sleep(Duration::from_millis(16));
+fn present_all(surfaces: &[Box<dyn Surface>], render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
+    let with_hud;
+    let render = if config::get().hud {
+        with_hud = hud_overlay(render);
+        &with_hud
+    } else {
+        render
+    };
+    for surface in surfaces {
+        surface.present(render)?;
     }
+    Ok(())
 }
 
-fn iterate_to_final(
-    initial_dsl: &str,
-    event_json: Option<&str>,
-    user_text: Option<&str>,
-    primary: Option<&fontdue::Font>,
-    emoji: Option<&fontdue::Font>,
-    is_debug: bool,
-) -> Result<RenderEnvelope, Box<dyn Error>> {
-    let mut current_dsl = initial_dsl.to_string();
-    let max_iterations = 4;
-
-    for i in 0..max_iterations {
-        let parsed = parser::parse_render(&current_dsl)?;
-        validator::validate_render(&parsed)?;
+const MAX_HISTORY: usize = 8;
 
-        let (w, h, pixels) = renderer::render_to_buffer(&parsed, primary, emoji)?;
-        let jpg_data = buffer_to_scaled_jpg(w, h, &pixels, 0.3)?;
-        let jpg_base64 = general_purpose::STANDARD.encode(&jpg_data);
-        
-        if is_debug {
-            let _ = std::fs::write(format!("debug_out/iter_{}_draft.json", i), &current_dsl);
-            let _ = std::fs::write(format!("debug_out/iter_{}_draft.jpg", i), &jpg_data);
+/// 在所有显示面上播放提示音（`theme.sounds.enabled` 时）；播放失败只记日志
+fn play_sound(config: &Config, surfaces: &[Box<dyn Surface>], cue: SoundCue) {
+    let sounds = &config.theme.sounds;
+    let tone = cue.tone(sounds);
+    if !sounds.enabled || tone.volume == 0 {
+        return;
+    }
+    for surface in surfaces {
+        if let Err(e) = surface.play_sound(cue, tone) {
+            warn!("failed to play {} sound: {}", cue.name(), e);
         }
+    }
+}
 
-        println!("Iteration {}: Evaluating UI quality...", i + 1);
-        let feedback_json = gpt52::request_render(event_json, user_text, LLMMode::Evaluate {
-            image_base64: jpg_base64,
-            dsl_code: current_dsl.clone(),
-        })?;
-
-        if is_debug {
-            let _ = std::fs::write(format!("debug_out/iter_{}_feedback.json", i), &feedback_json);
-        }
+fn push_history(history: &mut VecDeque<String>, entry: String) {
+    if history.len() == MAX_HISTORY {
+        history.pop_front();
+    }
+    history.push_back(entry);
+}
 
-        let v: Value = serde_json::from_str(&feedback_json)?;
-        let is_final = v["is_final"].as_bool().unwrap_or(false);
-        let reason = v["rejection_reason"].as_str().unwrap_or("No reason provided");
-        let render_val = v["render"].clone();
+/// 为提示词模板准备运行时变量
+fn prompt_vars(current: Option<&RenderEnvelope>, dpi: Option<f32>, history: &VecDeque<String>) -> PromptVars {
+    let mut vars = PromptVars::new();
+    let (width, height) = current
+        .map(|r| (r.window.width.to_string(), r.window.height.to_string()))
+        .unwrap_or_else(|| ("?".to_string(), "?".to_string()));
+    vars.set("window_width", width).set("window_height", height);
+    vars.set("dpi", dpi.map(|d| format!("{:.0}", d)).unwrap_or_else(|| "unknown".to_string()));
+    vars.set("fonts", backend::available_font_names().join(", "));
+    vars.set("system_info", sysinfo::describe());
+    vars.set("color_scheme", theme::describe_scheme());
+    vars.set("theme_tokens", theme::describe_tokens());
+    vars.set("assets_dir", config::get().images.assets_dir.clone());
+    vars.set("image_hosts", images::describe_hosts());
+    vars.set("assets", assets::describe());
+    vars.set("run_commands", runner::describe());
+    vars.set("data_sources", datasource::describe());
 
-        if is_final {
-            println!("UI Finalized in {} iterations.", i + 1);
-            return Ok(serde_json::from_value(render_val)?);
-        } else {
-            println!("LLM REJECTED DRAFT. Reason: {}", reason);
-            if is_debug {
-                let _ = std::fs::write(format!("debug_out/iter_{}_reason.txt", i), reason);
+    let state = match current {
+        Some(render) => {
+            // 对话框打开时只有它的两个按钮能点到
+            let targets: Vec<String> = match dialog::active(render) {
+                Some(dialog) => dialog::buttons(dialog).into_iter().map(|b| b.id).collect(),
+                None => render
+                    .commands
+                    .iter()
+                    .filter_map(|c| match c {
+                        Command::Rect { id: Some(id), clickable: true, .. } => Some(id.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+            };
+            let mut state = serde_json::json!({ "title": render.window.title, "seq": render.seq, "clickable": targets });
+            let output = terminal::snapshot(render);
+            if !output.is_empty() {
+                state["terminals"] = serde_json::json!(output);
+            }
+            // 面包屑：导航栈中的画面名，最后一项是当前画面
+            if !render.navigation.is_empty() {
+                let trail: Vec<String> = render.navigation.iter().cloned().chain([navigation::name(render)]).collect();
+                state["navigation"] = serde_json::json!(trail);
             }
-            current_dsl = serde_json::to_string(&render_val)?;
+            state.to_string()
         }
-    }
-
-    parser::parse_render(&current_dsl)
+        None => "no screen yet".to_string(),
+    };
+    vars.set("state", state);
+    let lines: Vec<String> = history.iter().map(|h| format!("  - {}", h)).collect();
+    vars.set("event_history", lines.join("\n"));
+    vars
 }
 
-fn buffer_to_scaled_jpg(w: usize, h: usize, pixels: &[u8], scale: f32) -> Result<Vec<u8>, Box<dyn Error>> {
-    let sw = (w as f32 * scale) as u32;
-    let sh = (h as f32 * scale) as u32;
-    let mut rgba = vec![0u8; w * h * 4];
-    for i in 0..(w * h) {
-        rgba[i*4] = pixels[i*4+2];
-        rgba[i*4+1] = pixels[i*4+1];
-        rgba[i*4+2] = pixels[i*4];
-        rgba[i*4+3] = 255;
-    }
-    let img: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_raw(w as u32, h as u32, rgba).ok_or("buffer size mismatch")?;
-    let scaled = image::imageops::resize(&img, sw, sh, image::imageops::FilterType::Lanczos3);
-    let mut cursor = std::io::Cursor::new(Vec::new());
-    scaled.write_to(&mut cursor, image::ImageFormat::Jpeg)?;
-    Ok(cursor.into_inner())
+/// 工作线程的结果再经过校验；两者的失败都显示为错误画面
+fn checked(result: pipeline::LlmResult) -> Result<RenderEnvelope, Box<dyn Error>> {
+    let parsed = result?;
+    validator::validate_render(&parsed)?;
+    Ok(parsed)
 }
 
 fn update_ui(
-    x11: &backend::X11Backend,
+    surfaces: &[Box<dyn Surface>],
     parsed: &RenderEnvelope,
     last_seq: &mut u64,
     hit_test: &mut HitTestIndex,
 ) -> Result<(), Box<dyn Error>> {
     validator::validate_render(parsed)?;
     if parsed.seq > *last_seq { *last_seq = parsed.seq; }
-    renderer::render_frame(x11, parsed)?;
+    let started = Instant::now();
+    present_all(surfaces, parsed)?;
+    metrics::observe("agd_render_duration_seconds", &[], started.elapsed().as_secs_f64());
     build_hit_test(hit_test, parsed);
     Ok(())
 }
 
-fn build_click_event_json(target_id: &str, x: i32, y: i32, seq: u64) -> Result<String, Box<dyn Error>> {
-    let event = EventEnvelope {
-        version: "AGD/0.2".to_string(),
-        event_type: "event".to_string(),
-        seq,
-        event: ClickEvent { kind: "click".to_string(), target_id: target_id.to_string(), x, y },
+/// 在后台截取一张图片并编码成附件：给了区域时截屏幕上的这块区域，
+/// 否则运行配置的截取工具（如摄像头），没有配置时截整个屏幕
+fn start_capture(config: &Config, region: Option<(i32, i32, u32, u32)>) -> mpsc::Receiver<Result<String, String>> {
+    let (tx, rx) = mpsc::channel();
+    let command = config.capture_command.clone();
+    let options = config.model_screenshot.clone();
+    thread::spawn(move || {
+        let result = if region.is_none() && !command.is_empty() { capture::run_tool(&command) } else { capture::grab_screen(region) };
+        let encoded = result.and_then(|c| {
+            info!("Captured a {}x{} image.", c.width, c.height);
+            pipeline::encode_attachment(c.width, c.height, &c.pixels, &options)
+        });
+        let _ = tx.send(encoded.map_err(|e| e.to_string()));
+    });
+    rx
+}
+
+/// 在后台等用户点选要跟随的窗口（点选期间指针被抓住，不能阻塞事件循环）
+fn start_pick_window() -> mpsc::Receiver<Result<u32, String>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(capture::pick_window().map_err(|e| e.to_string()));
+    });
+    rx
+}
+
+/// 窗口 id：十六进制（`0x3a00007`，xwininfo 的写法）或十进制
+fn parse_window_id(id: &str) -> Option<u32> {
+    match id.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => id.parse().ok(),
+    }
+    .filter(|&window| window != 0)
+}
+
+/// 读取剪贴板作为一次输入：优先取本窗口自己复制出去的文字；
+/// 剪贴板是图片时编码成附件，输入文字注明是粘贴的图片
+fn paste(surfaces: &[Box<dyn Surface>], config: &Config) -> Option<(String, Option<String>)> {
+    if let Some(text) = surfaces.iter().find_map(|s| s.copied_text()) {
+        return Some((text, None));
+    }
+    match clipboard::read()? {
+        Clipboard::Text(text) => Some((text, None)),
+        Clipboard::Image { width, height, pixels } => match pipeline::encode_attachment(width, height, &pixels, &config.model_screenshot) {
+            Ok(image) => {
+                info!("Pasted a {}x{} image from the clipboard.", width, height);
+                Some(("(pasted image)".to_string(), Some(image)))
+            }
+            Err(e) => {
+                warn!("clipboard image could not be encoded: {}", e);
+                None
+            }
+        },
+    }
+}
+
+/// 事件触发的生成任务：事件 JSON 加上当前画面（模型会看到它的截图）
+#[allow(clippy::too_many_arguments)]
+fn event_job(
+    kind: &str,
+    target_id: &str,
+    position: Option<(i32, i32)>,
+    form: Option<FormSubmission>,
+    seq: u64,
+    current: &RenderEnvelope,
+    dpi: Option<f32>,
+    history: &VecDeque<String>,
+) -> Result<LlmJob, Box<dyn Error>> {
+    let body = EventBody {
+        kind: kind.to_string(),
+        target_id: target_id.to_string(),
+        x: position.map(|p| p.0),
+        y: position.map(|p| p.1),
+        form,
+        gesture: None,
+        run: None,
     };
-    Ok(serde_json::to_string(&event)?)
+    body_job(body, seq, current, dpi, history)
+}
+
+/// 手势触发的生成任务：起点坐标之外带上方向与速度
+fn gesture_job(
+    gesture: &GestureEvent,
+    target_id: &str,
+    seq: u64,
+    current: &RenderEnvelope,
+    dpi: Option<f32>,
+    history: &VecDeque<String>,
+) -> Result<LlmJob, Box<dyn Error>> {
+    let body = EventBody {
+        kind: "gesture".to_string(),
+        target_id: target_id.to_string(),
+        x: Some(gesture.x),
+        y: Some(gesture.y),
+        form: None,
+        gesture: Some(GestureInfo {
+            direction: gesture.direction.name().to_string(),
+            velocity: gesture.velocity.round() as u32,
+            flick: gesture.is_flick(),
+        }),
+        run: None,
+    };
+    body_job(body, seq, current, dpi, history)
+}
+
+/// run 动作结束后的生成任务：点击位置之外带上命令的退出码与输出
+fn run_job(
+    output: RunOutput,
+    target_id: &str,
+    (x, y): (i32, i32),
+    seq: u64,
+    current: &RenderEnvelope,
+    dpi: Option<f32>,
+    history: &VecDeque<String>,
+) -> Result<LlmJob, Box<dyn Error>> {
+    let body = EventBody { kind: "run".to_string(), target_id: target_id.to_string(), x: Some(x), y: Some(y), form: None, gesture: None, run: Some(output) };
+    body_job(body, seq, current, dpi, history)
+}
+
+fn body_job(body: EventBody, seq: u64, current: &RenderEnvelope, dpi: Option<f32>, history: &VecDeque<String>) -> Result<LlmJob, Box<dyn Error>> {
+    let event = EventEnvelope { version: "AGD/0.2".to_string(), event_type: "event".to_string(), seq, event: body };
+    Ok(LlmJob {
+        event_json: Some(serde_json::to_string(&event)?),
+        user_text: None,
+        current: Some(current.clone()),
+        attachment: None,
+        companion: None,
+        vars: prompt_vars(Some(current), dpi, history),
+    })
+}
+
+/// 按当前界面同步定时器表，并取出一个到期的定时器。界面更新后仍存在的定时器（按 id）保持原有节奏；
+/// `ready` 为 false（等待模型、显示错误画面）时不触发，到期的定时器留到空闲后再发
+fn due_timer(due: &mut HashMap<String, Instant>, render: &RenderEnvelope, ready: bool) -> Option<String> {
+    let now = Instant::now();
+    due.retain(|id, _| render.timers.iter().any(|t| &t.id == id));
+    for timer in &render.timers {
+        due.entry(timer.id.clone()).or_insert(now + Duration::from_millis(timer.every_ms));
+    }
+    if !ready {
+        return None;
+    }
+    let timer = render.timers.iter().find(|t| due[&t.id] <= now)?;
+    due.insert(timer.id.clone(), now + Duration::from_millis(timer.every_ms));
+    Some(timer.id.clone())
+}
+
+/// 截图热键：Print、F12
+const SCREENSHOT_KEYS: &[u32] = &[0xFF61, 0xFFC9];
+
+/// 截图失败只记录日志，不影响会话
+fn take_screenshot(config: &Config, render: &RenderEnvelope) {
+    match recorder::save_screenshot(render, &config.screenshot_dir) {
+        Ok(path) => info!("Screenshot saved to {}", path.display()),
+        Err(e) => warn!("screenshot failed: {}", e),
+    }
+}
+
+/// 点击目标声明的本地动作
+fn rect_action<'a>(render: &'a RenderEnvelope, target_id: &str) -> Option<&'a str> {
+    render.commands.iter().find_map(|c| match c {
+        Command::Rect { id: Some(id), action: Some(action), .. } if id == target_id => Some(action.as_str()),
+        _ => None,
+    })
+}
+
+/// 点击目标的 link 地址；只返回通过校验的 http(s) / mailto 地址
+fn rect_href(render: &RenderEnvelope, target_id: &str) -> Option<String> {
+    render.commands.iter().find_map(|c| match c {
+        Command::Rect { id: Some(id), href: Some(href), .. } if id == target_id && validator::is_link(href) => Some(href.clone()),
+        _ => None,
+    })
+}
+
+/// 点击目标要执行的命令名；只返回白名单中的名称
+fn rect_run(render: &RenderEnvelope, target_id: &str) -> Option<String> {
+    render.commands.iter().find_map(|c| match c {
+        Command::Rect { id: Some(id), run: Some(name), .. } if id == target_id && runner::is_allowed(name) => Some(name.clone()),
+        _ => None,
+    })
+}
+
+/// 在后台执行 run 动作，避免长时间运行的命令阻塞事件循环
+fn start_run(name: String) -> mpsc::Receiver<RunOutput> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(runner::run(&name));
+    });
+    rx
+}
+
+/// 在后台用 xdg-open 交给桌面默认的浏览器 / 邮件程序打开，不等待它退出
+fn open_link(href: &str) {
+    let child = Process::new("xdg-open")
+        .arg(href)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            info!("Opening {}", href);
+            // 回收子进程，避免留下僵尸进程
+            thread::spawn(move || child.wait());
+        }
+        Err(e) => warn!("failed to open {} with xdg-open: {}", href, e),
+    }
 }
 
 fn build_hit_test(index: &mut HitTestIndex, render: &RenderEnvelope) {
     index.reset();
+    // 对话框打开时只有它的两个按钮可以点击，其下的 rect 与快捷键都不登记
+    if let Some(dialog) = dialog::active(render) {
+        for b in dialog::buttons(dialog) {
+            index.add(HitTarget { id: b.id, x: b.x, y: b.y, w: b.w, h: b.h, access_key: None });
+        }
+        return;
+    }
     for command in &render.commands {
-        if let Command::Rect { id, x, y, w, h, clickable, .. } = command {
+        if let Command::Rect { id, x, y, w, h, clickable, access_key, .. } = command {
             if *clickable {
                 if let Some(id) = id {
-                    index.add(HitTarget { id: id.clone(), x: *x, y: *y, w: *w, h: *h });
+                    let access_key = access_key.as_deref().and_then(validator::access_key_char);
+                    index.add(HitTarget { id: id.clone(), x: *x, y: *y, w: *w, h: *h, access_key });
                 }
             }
         }
     }
-}
-
-fn render_pressed_feedback(
-    x11: &backend::X11Backend,
-    render: &RenderEnvelope,
-    target: &HitTarget,
-) -> Result<(), Box<dyn Error>> {
-    renderer::render_frame_with_press(x11, render, target.x, target.y, target.w, target.h)?;
-    thread::sleep(Duration::from_millis(60));
-    renderer::render_frame(x11, render)?;
-    Ok(())
+    for shortcut in &render.shortcuts {
+        if let Some(chord) = validator::shortcut_chord(&shortcut.keys) {
+            index.add_shortcut(chord, shortcut.target.clone());
+        }
+    }
 }
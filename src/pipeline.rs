@@ -0,0 +1,709 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use base64::{Engine as _, engine::general_purpose};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::{ColorType, ImageEncoder};
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::capture;
+use crate::config::{Config, ModelScreenshotConfig};
+use crate::display;
+use crate::dsl::model::RenderEnvelope;
+use crate::dsl::validator::ValidationErrors;
+use crate::dsl::{diff, parser, semantics, validator};
+use crate::images;
+use crate::llm::prompts::PromptVars;
+use crate::llm::provider::{LLMMode, LLMProvider, RegionCrop};
+use crate::metrics;
+use crate::scripting::{Outcome, ScriptHost};
+use crate::x11::{backend, raster_cache};
+
+/// 交给 LLM 工作线程的一次生成任务
+#[derive(Clone)]
+pub struct LlmJob {
+    pub event_json: Option<String>,
+    pub user_text: Option<String>,
+    /// 当前屏幕上显示的界面；事件触发的生成会附带它的截图
+    pub current: Option<RenderEnvelope>,
+    /// 用户随文本附上的图片（base64，格式同模型截图）
+    pub attachment: Option<String>,
+    /// 跟随的其他程序窗口的截图，由工作线程在生成前截取
+    pub companion: Option<String>,
+    /// 提示词模板变量（窗口尺寸、DPI、状态快照、事件历史、可用字体等）
+    pub vars: PromptVars,
+}
+
+/// 工作线程的结果；`Box<dyn Error>` 不能跨线程，这里转成字符串
+pub type LlmResult = Result<RenderEnvelope, String>;
+
+/// Best-of-N 的一份候选：最终 DSL、解析结果与编码好的缩略图
+type Candidate = (String, RenderEnvelope, Vec<u8>);
+
+/// 任务取消令牌：有更新的任务提交后，旧任务在下一次调用模型前自行放弃
+#[derive(Clone)]
+pub struct CancelToken {
+    id: u64,
+    latest: Arc<AtomicU64>,
+}
+
+impl CancelToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.latest.load(Ordering::SeqCst) != self.id
+    }
+}
+
+/// LLM 任务调度：每个任务在独立线程中跑完整的 生成 -> 评估 -> 修正 流程。
+/// 新任务提交后旧任务被视为过期（superseded），其结果会被丢弃，不再阻塞新输入。
+pub struct LlmWorker {
+    llm: Arc<dyn LLMProvider>,
+    fonts: Arc<(Option<fontdue::Font>, Option<fontdue::Font>)>,
+    config: &'static Config,
+    is_debug: bool,
+    latest: Arc<AtomicU64>,
+    /// 累计提交的任务数，编排器据此判断一次输入有没有请求模型
+    submitted: AtomicU64,
+    /// `/follow` 选中的其他程序窗口，0 表示没有
+    companion: Arc<AtomicU32>,
+    /// 先于模型处理事件的本地脚本
+    scripts: Option<Arc<ScriptHost>>,
+    result_tx: mpsc::Sender<LlmResult>,
+}
+
+pub fn spawn_llm_worker(
+    llm: Box<dyn LLMProvider>,
+    config: &'static Config,
+    scripts: Option<Arc<ScriptHost>>,
+    is_debug: bool,
+) -> (LlmWorker, mpsc::Receiver<LlmResult>) {
+    let (result_tx, result_rx) = mpsc::channel::<LlmResult>();
+    let worker = LlmWorker {
+        llm: Arc::from(llm),
+        // 字体只加载一次，离屏渲染草稿时各任务线程共享
+        fonts: Arc::new(backend::load_fonts()),
+        config,
+        is_debug,
+        latest: Arc::new(AtomicU64::new(0)),
+        submitted: AtomicU64::new(0),
+        companion: Arc::new(AtomicU32::new(0)),
+        scripts,
+        result_tx,
+    };
+    (worker, result_rx)
+}
+
+impl LlmWorker {
+    /// 让所有在途任务过期（例如界面已在本地完成迁移）
+    pub fn cancel_pending(&self) {
+        self.latest.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// 换用新的模型驱动、配置与脚本（切换配置档案）；在途任务仍按旧配置跑完
+    pub fn reconfigure(&mut self, llm: Box<dyn LLMProvider>, config: &'static Config, scripts: Option<Arc<ScriptHost>>) {
+        self.llm = Arc::from(llm);
+        self.config = config;
+        self.scripts = scripts;
+    }
+
+    /// 之后每次生成都附上这个窗口的最新截图；None 停止跟随
+    pub fn follow_window(&self, window: Option<u32>) {
+        self.companion.store(window.unwrap_or(0), Ordering::SeqCst);
+    }
+
+    /// 累计提交的任务数
+    pub fn submitted(&self) -> u64 {
+        self.submitted.load(Ordering::SeqCst)
+    }
+
+    pub fn submit(&self, mut job: LlmJob) {
+        self.submitted.fetch_add(1, Ordering::SeqCst);
+        let id = self.latest.fetch_add(1, Ordering::SeqCst) + 1;
+        let token = CancelToken { id, latest: self.latest.clone() };
+        let llm = self.llm.clone();
+        let fonts = self.fonts.clone();
+        let config = self.config;
+        let is_debug = self.is_debug;
+        let result_tx = self.result_tx.clone();
+        let companion = self.companion.load(Ordering::SeqCst);
+        let scripts = self.scripts.clone();
+
+        thread::spawn(move || {
+            if let (Some(scripts), Some(event_json)) = (&scripts, &job.event_json) {
+                let outcome = scripts.handle(event_json, job.current.as_ref());
+                // 处理函数可能很慢（http_get、run）：期间有了更新的输入时，它的画面已经过期
+                if matches!(outcome, Outcome::Screen(_) | Outcome::Handled) && token.is_cancelled() {
+                    debug!("script result for request #{} superseded by newer input, discarded", id);
+                    return;
+                }
+                match outcome {
+                    Outcome::Forward => {}
+                    Outcome::Replace(event_json) => job.event_json = Some(event_json),
+                    Outcome::Screen(render) => {
+                        images::prefetch(&render);
+                        let _ = result_tx.send(Ok(*render));
+                        return;
+                    }
+                    Outcome::Handled => {
+                        let _ = result_tx.send(job.current.clone().ok_or_else(|| "no current screen".to_string()));
+                        return;
+                    }
+                }
+            }
+            if companion != 0 {
+                job.companion = capture_companion(companion, &config.model_screenshot);
+            }
+            let pipeline = Pipeline {
+                llm: &llm,
+                config,
+                primary: fonts.0.as_ref(),
+                emoji: fonts.1.as_ref(),
+                is_debug,
+                cancel: Some(&token),
+            };
+            let result = pipeline.generate_final(&job).map_err(|e| e.to_string());
+            // 上屏前在这里下载远程图片，界面线程绘制时不等网络
+            if let Ok(render) = &result {
+                images::prefetch(render);
+            }
+            if token.is_cancelled() {
+                debug!("request #{} superseded by newer input, result discarded", id);
+                return;
+            }
+            let _ = result_tx.send(result);
+        });
+    }
+}
+
+/// 视觉反馈环（Visual Feedback Loop）：生成草稿、离屏渲染、交给评估模型审阅并修正
+pub struct Pipeline<'a> {
+    pub llm: &'a Arc<dyn LLMProvider>,
+    pub config: &'a Config,
+    pub primary: Option<&'a fontdue::Font>,
+    pub emoji: Option<&'a fontdue::Font>,
+    pub is_debug: bool,
+    pub cancel: Option<&'a CancelToken>,
+}
+
+impl Pipeline<'_> {
+    /// 每次调用模型前检查任务是否已被更新的输入取代
+    fn check_cancelled(&self) -> Result<(), Box<dyn Error>> {
+        match self.cancel {
+            Some(token) if token.is_cancelled() => Err("superseded by newer input".into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn generate_final(&self, job: &LlmJob) -> Result<RenderEnvelope, Box<dyn Error>> {
+        let event_json = job.event_json.as_deref();
+        let user_text = job.user_text.as_deref();
+        if self.config.best_of > 1 {
+            let screenshot_base64 = self.screenshot_for(job)?;
+            return self.best_of_n(job, screenshot_base64, self.config.best_of);
+        }
+        let screenshot_base64 = self.screenshot_for(job)?;
+        self.check_cancelled()?;
+        let mode = LLMMode::Generate {
+            screenshot_base64,
+            attach_screenshot: self.attaches_screenshot(job),
+            attachment: job.attachment.clone(),
+            companion: job.companion.clone(),
+        };
+        let mut draft = self.llm.request_render(event_json, user_text, mode, &job.vars)?;
+        let policy = &self.config.evaluate;
+        if !policy.enabled || (policy.skip_for_events && event_json.is_some()) {
+            return self.parse_with_repair(&mut draft, job);
+        }
+        self.iterate_to_final(&draft, job)
+    }
+
+    /// 事件驱动的生成在提示词中附带当前画面截图
+    fn attaches_screenshot(&self, job: &LlmJob) -> bool {
+        self.config.attach_screenshot && job.event_json.is_some()
+    }
+
+    /// 当前画面截图（与评估草稿相同的离屏渲染与缩放）；开启工具调用时总是渲染，供模型按需查询
+    fn screenshot_for(&self, job: &LlmJob) -> Result<Option<String>, Box<dyn Error>> {
+        let Some(current) = job.current.as_ref() else {
+            return Ok(None);
+        };
+        if !self.attaches_screenshot(job) && !self.config.tools.enabled {
+            return Ok(None);
+        }
+        let (w, h, pixels) = raster_cache::render(current, self.primary, self.emoji)?;
+        let image_data = encode_model_screenshot(w, h, &pixels, &self.config.model_screenshot)?;
+        if self.is_debug {
+            let _ = std::fs::write(format!("debug_out/current_screen.{}", self.config.model_screenshot.extension()), &image_data);
+        }
+        Ok(Some(general_purpose::STANDARD.encode(&image_data)))
+    }
+
+    fn iterate_to_final(
+        &self,
+        initial_dsl: &str,
+        job: &LlmJob,
+    ) -> Result<RenderEnvelope, Box<dyn Error>> {
+        let (event_json, user_text) = (job.event_json.as_deref(), job.user_text.as_deref());
+        let is_debug = self.is_debug;
+        let mut current_dsl = initial_dsl.to_string();
+        let max_iterations = self.config.evaluate.max_iterations;
+        let mut previous: Option<RenderEnvelope> = None;
+        // 时间预算从第一份草稿生成后开始计；超时就不再等评估模型，避免用户干等多轮长请求
+        let budget = self.config.evaluate.budget_secs.map(Duration::from_secs);
+        let deadline = budget.map(|b| Instant::now() + b);
+        // 上一次在本地打回的问题；模型坚持原样（如有意超出窗口的装饰）时不再打回，交给评估模型判断
+        let mut last_rejected: Option<String> = None;
+        // 超时时显示的草稿：评估置信度最高的一份，其次是最近一份通过本地检查的
+        let mut best: Option<(f64, u32, RenderEnvelope)> = None;
+        let mut clean: Option<(u32, RenderEnvelope)> = None;
+
+        for i in 0..max_iterations {
+            let mut parsed = self.parse_with_repair(&mut current_dsl, job)?;
+            if self.config.evaluate.local_checks {
+                let fixed = validator::fix_contrast(&mut parsed);
+                if fixed > 0 {
+                    info!("Raised the contrast of {} text(s) in draft {} locally", fixed, i + 1);
+                }
+            }
+            // 把解析（修复、版本升级、YAML 转换）后的信封重新序列化，评估模型看到的是规范的 JSON
+            current_dsl = serde_json::to_string(&parsed)?;
+
+            // 确定性的本地检查先行：有问题时直接让模型按检查结果修改（纯文本请求），
+            // 检查通过后才交给视觉评估模型
+            let findings = layout_findings(&parsed);
+            let rejection = (self.config.evaluate.local_checks && !findings.is_empty() && i + 1 < max_iterations)
+                .then(|| ValidationErrors(findings).to_string())
+                .filter(|problems| last_rejected.as_ref() != Some(problems));
+            if let Some(problems) = rejection {
+                warn!("Local checks rejected draft {}: {}", i + 1, problems);
+                metrics::inc("agd_local_rejections_total", &[], 1.0);
+                if is_debug {
+                    let _ = std::fs::write(format!("debug_out/iter_{}_local_reject.txt", i), &problems);
+                }
+                self.check_cancelled()?;
+                let mode = LLMMode::Repair { raw_output: current_dsl.clone(), error: problems.clone() };
+                let Some(revised) = self.request_before(deadline, event_json, user_text, mode, &job.vars)? else {
+                    return Ok(self.over_budget(best, clean, (i, parsed), budget));
+                };
+                current_dsl = revised;
+                last_rejected = Some(problems);
+                previous = Some(parsed);
+                continue;
+            }
+
+            clean = Some((i, parsed.clone()));
+
+            // 本地检查（出界、对比度、语义大纲、草稿差异）与本轮草稿的离屏渲染、JPEG 编码并行。
+            // 下一份草稿就是评估模型的回复，无法在评估请求途中提前渲染；要与模型请求重叠渲染请用 best_of
+            let roi = self.region_of_interest(job, &parsed);
+            let (vars, images) = thread::scope(|scope| {
+                let checks = scope.spawn(|| self.evaluate_vars(job, &parsed, previous.as_ref(), i));
+                let images = raster_cache::render(&parsed, self.primary, self.emoji).and_then(|(w, h, pixels)| {
+                    let options = &self.config.model_screenshot;
+                    let thumbnail = encode_model_screenshot(w, h, &pixels, options)?;
+                    let crop = roi.map(|rect| encode_region(w, h, &pixels, rect, options)).transpose()?;
+                    Ok((thumbnail, crop))
+                });
+                (checks.join().unwrap_or_else(|_| job.vars.clone()), images)
+            });
+            let (image_data, crop_data) = images?;
+            let image_base64 = general_purpose::STANDARD.encode(&image_data);
+
+            if is_debug {
+                let extension = self.config.model_screenshot.extension();
+                let _ = std::fs::write(format!("debug_out/iter_{}_draft.json", i), &current_dsl);
+                let _ = std::fs::write(format!("debug_out/iter_{}_draft.{}", i, extension), &image_data);
+                if let Some(crop) = &crop_data {
+                    let _ = std::fs::write(format!("debug_out/iter_{}_region.{}", i, extension), crop);
+                }
+            }
+
+            self.check_cancelled()?;
+            info!("Iteration {}: evaluating UI quality...", i + 1);
+            let region = roi.zip(crop_data).map(|((x, y, w, h), crop)| RegionCrop {
+                x,
+                y,
+                w,
+                h,
+                image_base64: general_purpose::STANDARD.encode(&crop),
+            });
+            let mode = LLMMode::Evaluate { image_base64, region, dsl_code: current_dsl.clone() };
+            let Some(feedback_json) = self.request_before(deadline, event_json, user_text, mode, &vars)? else {
+                return Ok(self.over_budget(best, clean, (i, parsed), budget));
+            };
+
+            if is_debug {
+                let _ = std::fs::write(format!("debug_out/iter_{}_feedback.json", i), &feedback_json);
+            }
+
+            let v: Value = serde_json::from_str(&feedback_json)?;
+            let is_final = v["is_final"].as_bool().unwrap_or(false);
+            let reason = v["rejection_reason"].as_str().unwrap_or("No reason provided");
+            let render_val = v["render"].clone();
+
+            if let (Some(threshold), Some(confidence)) = (self.config.evaluate.confidence_threshold, v["confidence"].as_f64()) {
+                if !is_final && confidence as f32 >= threshold {
+                    info!("Draft accepted at confidence {:.2} after {} iterations.", confidence, i + 1);
+                    metrics::observe("agd_evaluate_iterations", &[], (i + 1) as f64);
+                    return Ok(parsed);
+                }
+            }
+            if let Some(confidence) = v["confidence"].as_f64() {
+                if best.as_ref().is_none_or(|(score, _, _)| confidence >= *score) {
+                    best = Some((confidence, i, parsed.clone()));
+                }
+            }
+
+            if is_final {
+                info!("UI finalized in {} iterations.", i + 1);
+                metrics::observe("agd_evaluate_iterations", &[], (i + 1) as f64);
+                current_dsl = serde_json::to_string(&render_val)?;
+                return self.parse_with_repair(&mut current_dsl, job);
+            } else {
+                info!("Draft rejected by evaluator. Reason: {}", reason);
+                if is_debug {
+                    let _ = std::fs::write(format!("debug_out/iter_{}_reason.txt", i), reason);
+                }
+                current_dsl = serde_json::to_string(&render_val)?;
+            }
+            previous = Some(parsed);
+        }
+
+        metrics::observe("agd_evaluate_iterations", &[], max_iterations as f64);
+        self.parse_with_repair(&mut current_dsl, job)
+    }
+
+    /// 事件只改动了一小块画面时，评估额外看这块区域的原尺寸截图，小部件的细节在缩略图里看不清。
+    /// 区域像素数不超过整帧缩略图，附带它大致不多花 token；窗口尺寸变化或大面积改动时不裁剪
+    fn region_of_interest(&self, job: &LlmJob, parsed: &RenderEnvelope) -> Option<(i32, i32, u32, u32)> {
+        let options = &self.config.model_screenshot;
+        if !options.region_crops || options.scale >= 1.0 {
+            return None;
+        }
+        let current = job.current.as_ref()?;
+        let (x, y, w, h) = diff::diff(current, parsed).dirty_rect(&parsed.window)?;
+        let window_area = parsed.window.width as f32 * parsed.window.height as f32;
+        (w as f32 * h as f32 <= window_area * options.scale * options.scale).then_some((x, y, w, h))
+    }
+
+    /// 评估循环超出时间预算：显示目前最好的草稿并加警告横幅。评估置信度最高的一份优先，
+    /// 其次是最近一份通过本地检查的，都没有时才用正在处理的草稿
+    fn over_budget(
+        &self,
+        best: Option<(f64, u32, RenderEnvelope)>,
+        clean: Option<(u32, RenderEnvelope)>,
+        (i, current): (u32, RenderEnvelope),
+        budget: Option<Duration>,
+    ) -> RenderEnvelope {
+        let budget = budget.unwrap_or_default();
+        let (shown, draft) = best.map(|(_, n, draft)| (n, draft)).or(clean).unwrap_or((i, current));
+        warn!("Evaluate loop exceeded its {}s budget after {} drafts; showing draft {} unreviewed", budget.as_secs(), i + 1, shown + 1);
+        metrics::inc("agd_evaluate_budget_exceeded_total", &[], 1.0);
+        metrics::observe("agd_evaluate_iterations", &[], (i + 1) as f64);
+        display::budget_banner(&draft, budget)
+    }
+
+    /// 评估提示词的变量：本地布局检查的发现交给评估模型，显示前就把出界、过宽描边等问题改掉
+    fn evaluate_vars(&self, job: &LlmJob, parsed: &RenderEnvelope, previous: Option<&RenderEnvelope>, i: u32) -> PromptVars {
+        let findings = layout_findings(parsed);
+        if !findings.is_empty() {
+            warn!("Layout check found {} problem(s) in draft {}", findings.len(), i + 1);
+        }
+        let mut vars = job.vars.clone();
+        vars.set("layout_warnings", describe_findings(&findings));
+        vars.set("accessibility_outline", semantics::describe(parsed));
+        // 从第二轮起告诉评估模型上一份草稿改了哪些地方，便于确认上次指出的问题是否已修正
+        if let Some(previous) = previous {
+            vars.set("draft_changes", diff::diff(previous, parsed).summary());
+        }
+        vars
+    }
+
+    /// 在截止时间前完成一次模型调用；超时返回 None。
+    /// 阻塞的 HTTP 请求无法中途取消，超时的调用留在后台线程跑完，结果被丢弃
+    fn request_before(
+        &self,
+        deadline: Option<Instant>,
+        event_json: Option<&str>,
+        user_text: Option<&str>,
+        mode: LLMMode,
+        vars: &PromptVars,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let Some(deadline) = deadline else {
+            return self.llm.request_render(event_json, user_text, mode, vars).map(Some);
+        };
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(None);
+        }
+        let (tx, rx) = mpsc::channel();
+        let llm = self.llm.clone();
+        let (event_json, user_text, vars) = (event_json.map(str::to_string), user_text.map(str::to_string), vars.clone());
+        thread::spawn(move || {
+            let result = llm.request_render(event_json.as_deref(), user_text.as_deref(), mode, &vars).map_err(|e| e.to_string());
+            let _ = tx.send(result);
+        });
+        match rx.recv_timeout(remaining) {
+            Ok(result) => result.map(Some).map_err(Into::into),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err("evaluate thread panicked".into()),
+        }
+    }
+
+    /// Best-of-N：并发生成 N 份草稿，各自渲染缩略图，再让评估模型一次性挑出最好的一份。
+    /// 每份草稿一返回就在自己的线程里解析、渲染、编码，与其余仍在途的草稿请求重叠
+    fn best_of_n(
+        &self,
+        job: &LlmJob,
+        screenshot_base64: Option<String>,
+        n: u32,
+    ) -> Result<RenderEnvelope, Box<dyn Error>> {
+        let (event_json, user_text) = (job.event_json.as_deref(), job.user_text.as_deref());
+        info!("Generating {} candidate drafts...", n);
+        let drafts: Vec<Result<Candidate, String>> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..n)
+                .map(|_| {
+                    let mode = LLMMode::Generate {
+                        screenshot_base64: screenshot_base64.clone(),
+                        attach_screenshot: self.attaches_screenshot(job),
+                        attachment: job.attachment.clone(),
+                        companion: job.companion.clone(),
+                    };
+                    scope.spawn(move || self.prepare_candidate(job, mode).map_err(|e| e.to_string()))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|_| Err("draft thread panicked".to_string())))
+                .collect()
+        });
+
+        let mut candidates: Vec<(String, RenderEnvelope)> = Vec::new();
+        let mut images = Vec::new();
+        let mut last_error = None;
+        for (i, draft) in drafts.into_iter().enumerate() {
+            match draft {
+                Ok((dsl, parsed, image_data)) => {
+                    if self.is_debug {
+                        let _ = std::fs::write(format!("debug_out/candidate_{}.json", candidates.len()), &dsl);
+                        let _ = std::fs::write(format!("debug_out/candidate_{}.{}", candidates.len(), self.config.model_screenshot.extension()), &image_data);
+                    }
+                    images.push(general_purpose::STANDARD.encode(&image_data));
+                    candidates.push((dsl, parsed));
+                }
+                Err(e) => {
+                    warn!("Candidate {} failed: {}", i + 1, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        if candidates.len() <= 1 {
+            return candidates
+                .pop()
+                .map(|(_, parsed)| parsed)
+                .ok_or_else(|| last_error.unwrap_or_else(|| "no candidate drafts".to_string()).into());
+        }
+
+        self.check_cancelled()?;
+        info!("Selecting best of {} candidates...", candidates.len());
+        let selection_json = self.llm.request_render(event_json, user_text, LLMMode::Select {
+            images_base64: images,
+            dsl_codes: candidates.iter().map(|(dsl, _)| dsl.clone()).collect(),
+        }, &job.vars)?;
+        let v: Value = serde_json::from_str(&selection_json)?;
+        let best = v["best_index"].as_u64().unwrap_or(0) as usize;
+        let best = best.min(candidates.len() - 1);
+        if let Some(reason) = v["reason"].as_str() {
+            info!("Picked candidate {}. Reason: {}", best + 1, reason);
+        }
+        Ok(candidates.swap_remove(best).1)
+    }
+
+    /// 生成一份候选草稿，解析（必要时修复）后渲染并编码缩略图
+    fn prepare_candidate(&self, job: &LlmJob, mode: LLMMode) -> Result<Candidate, Box<dyn Error>> {
+        let mut dsl = self.llm.request_render(job.event_json.as_deref(), job.user_text.as_deref(), mode, &job.vars)?;
+        let parsed = self.parse_with_repair(&mut dsl, job)?;
+        let (w, h, pixels) = raster_cache::render(&parsed, self.primary, self.emoji)?;
+        let image_data = encode_model_screenshot(w, h, &pixels, &self.config.model_screenshot)?;
+        Ok((dsl, parsed, image_data))
+    }
+
+    /// 解析并校验 DSL；失败时把错误信息和原始输出交回模型修复，最多 `max_repair_attempts` 次。
+    /// 成功后 `dsl` 会被替换为最终通过校验的版本。
+    fn parse_with_repair(
+        &self,
+        dsl: &mut String,
+        job: &LlmJob,
+    ) -> Result<RenderEnvelope, Box<dyn Error>> {
+        let (event_json, user_text) = (job.event_json.as_deref(), job.user_text.as_deref());
+        let max_repairs = self.config.max_repair_attempts;
+        let mut attempt = 0;
+        loop {
+            let error = match parser::parse_render(dsl) {
+                Ok(parsed) => match validator::validate_render(&parsed) {
+                    Ok(()) => return Ok(parsed),
+                    // 全部问题（路径 + 错误码）一起交给模型，一轮修复尽量改完
+                    Err(e) => e.into(),
+                },
+                Err(e) => e,
+            };
+            if attempt >= max_repairs {
+                return Err(error);
+            }
+            attempt += 1;
+            self.check_cancelled()?;
+            warn!("Invalid DSL ({}), asking model to repair (attempt {}/{})...", error, attempt, max_repairs);
+            if self.is_debug {
+                let _ = std::fs::write(format!("debug_out/repair_{}_input.json", attempt), dsl.as_str());
+                let _ = std::fs::write(format!("debug_out/repair_{}_error.txt", attempt), error.to_string());
+            }
+            *dsl = self.llm.request_render(event_json, user_text, LLMMode::Repair {
+                raw_output: dsl.clone(),
+                error: error.to_string(),
+            }, &job.vars)?;
+        }
+    }
+}
+
+/// 本地布局检查的全部发现：出界、描边过宽、文字溢出、可点击区域重叠、对比度不足
+fn layout_findings(parsed: &RenderEnvelope) -> Vec<validator::ValidationError> {
+    let mut findings = validator::check_bounds(parsed);
+    findings.extend(validator::check_overflow(parsed));
+    findings.extend(validator::check_clickables(parsed));
+    findings.extend(validator::check_contrast(parsed));
+    findings
+}
+
+fn describe_findings(findings: &[validator::ValidationError]) -> String {
+    if findings.is_empty() {
+        return "none".to_string();
+    }
+    findings.iter().map(|f| format!("- {}", f)).collect::<Vec<_>>().join("\n")
+}
+
+/// 截图缩小与编码的暂存缓冲区
+#[derive(Default)]
+struct Scratch {
+    rgb: Vec<u8>,
+    /// 每个目标列覆盖的源像素范围与一行目标像素的累加和
+    columns: Vec<(usize, usize)>,
+    sums: Vec<[u32; 3]>,
+    /// 上一次编码结果的大小，作为下一次输出的初始容量
+    encoded_len: usize,
+}
+
+/// 池中最多保留的暂存缓冲区：够评估循环与 Best-of-N 的并发编码使用
+const SCRATCH_POOL_SIZE: usize = 4;
+
+/// 暂存缓冲区池。每个任务都在新线程上运行，线程局部的缓冲区随线程结束丢弃，
+/// 所以放在进程级的池里：编码时取出一份，用完放回，之后的任务与评估轮次接着复用
+static SCREENSHOT_SCRATCH: Mutex<Vec<Scratch>> = Mutex::new(Vec::new());
+
+/// 把 BGRA 帧按 `model_screenshot` 配置缩小并编码（JPEG 或 PNG），作为发给模型的截图
+pub fn encode_model_screenshot(w: usize, h: usize, pixels: &[u8], options: &ModelScreenshotConfig) -> Result<Vec<u8>, Box<dyn Error>> {
+    let sw = ((w as f32 * options.scale) as usize).max(1);
+    let sh = ((h as f32 * options.scale) as usize).max(1);
+    if pixels.len() < w * h * 4 {
+        return Err("buffer size mismatch".into());
+    }
+    let mut scratch = SCREENSHOT_SCRATCH.lock().unwrap().pop().unwrap_or_default();
+    downscale_bgra(w, h, pixels, sw, sh, &mut scratch);
+    let mut encoded = Vec::with_capacity(scratch.encoded_len);
+    let result = if options.is_png() {
+        PngEncoder::new(&mut encoded).write_image(&scratch.rgb, sw as u32, sh as u32, ColorType::Rgb8)
+    } else {
+        JpegEncoder::new_with_quality(&mut encoded, options.quality).encode(&scratch.rgb, sw as u32, sh as u32, ColorType::Rgb8)
+    };
+    scratch.encoded_len = encoded.len();
+    let mut pool = SCREENSHOT_SCRATCH.lock().unwrap();
+    if pool.len() < SCRATCH_POOL_SIZE {
+        pool.push(scratch);
+    }
+    result?;
+    Ok(encoded)
+}
+
+/// 从 BGRA 帧中裁出一块区域（窗口坐标），按原分辨率编码
+pub fn encode_region(
+    w: usize,
+    h: usize,
+    pixels: &[u8],
+    (x, y, cw, ch): (i32, i32, u32, u32),
+    options: &ModelScreenshotConfig,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (x, y) = (x.max(0) as usize, y.max(0) as usize);
+    let cw = (cw as usize).min(w.saturating_sub(x));
+    let ch = (ch as usize).min(h.saturating_sub(y));
+    if cw == 0 || ch == 0 {
+        return Err("region is outside the frame".into());
+    }
+    let mut crop = Vec::with_capacity(cw * ch * 4);
+    for row in pixels.chunks_exact(w * 4).skip(y).take(ch) {
+        crop.extend_from_slice(&row[x * 4..(x + cw) * 4]);
+    }
+    encode_model_screenshot(cw, ch, &crop, &ModelScreenshotConfig { scale: 1.0, ..options.clone() })
+}
+
+/// 截取跟随的窗口并编码；窗口已关闭等失败只记录警告，这次生成不带截图
+fn capture_companion(window: u32, options: &ModelScreenshotConfig) -> Option<String> {
+    let captured = capture::grab_window(window).and_then(|c| encode_attachment(c.width, c.height, &c.pixels, options));
+    match captured {
+        Ok(image) => Some(image),
+        Err(e) => {
+            warn!("could not capture the followed window: {}", e);
+            None
+        }
+    }
+}
+
+/// 用户附图（粘贴、截取）缩放后的最长边：足够模型看清内容，又不会让请求过大
+const ATTACHMENT_MAX_SIDE: usize = 1024;
+
+/// 把用户附上的 BGRA 图片按模型截图的格式编码成 base64，长边超过 `ATTACHMENT_MAX_SIDE` 时等比缩小
+pub fn encode_attachment(w: usize, h: usize, pixels: &[u8], options: &ModelScreenshotConfig) -> Result<String, Box<dyn Error>> {
+    let scale = (ATTACHMENT_MAX_SIDE as f32 / w.max(h).max(1) as f32).min(1.0);
+    let encoded = encode_model_screenshot(w, h, pixels, &ModelScreenshotConfig { scale, ..options.clone() })?;
+    Ok(general_purpose::STANDARD.encode(&encoded))
+}
+
+/// 面积平均缩小：每个目标像素取其覆盖的源矩形的均值，同时把 BGRA 转成 RGB。
+/// 缩小倍数在 0.3 左右时与 Lanczos 观感相近，只需遍历一次源像素；`scale` 为 1 时原样转换。
+/// 结果写进 `scratch.rgb`，各缓冲区尺寸不变时不再分配
+fn downscale_bgra(w: usize, h: usize, pixels: &[u8], sw: usize, sh: usize, scratch: &mut Scratch) {
+    let span = |i: usize, src: usize, dst: usize| (i * src / dst, ((i + 1) * src / dst).max(i * src / dst + 1).min(src));
+    let Scratch { rgb: out, columns, sums, .. } = scratch;
+    columns.clear();
+    columns.extend((0..sw).map(|x| span(x, w, sw)));
+    out.clear();
+    out.resize(sw * sh * 3, 0);
+    sums.clear();
+    sums.resize(sw, [0; 3]);
+    // 空帧没有可取平均的源像素，输出保持全黑
+    if w == 0 || h == 0 {
+        return;
+    }
+    for y in 0..sh {
+        let (y0, y1) = span(y, h, sh);
+        sums.iter_mut().for_each(|s| *s = [0; 3]);
+        for row in pixels[y0 * w * 4..y1 * w * 4].chunks_exact(w * 4) {
+            for (sum, &(x0, x1)) in sums.iter_mut().zip(columns.iter()) {
+                for px in row[x0 * 4..x1 * 4].chunks_exact(4) {
+                    sum[0] += px[2] as u32;
+                    sum[1] += px[1] as u32;
+                    sum[2] += px[0] as u32;
+                }
+            }
+        }
+        let dst = &mut out[y * sw * 3..(y + 1) * sw * 3];
+        for ((rgb, sum), &(x0, x1)) in dst.chunks_exact_mut(3).zip(sums.iter()).zip(columns.iter()) {
+            let count = ((x1 - x0) * (y1 - y0)) as u32;
+            for c in 0..3 {
+                rgb[c] = ((sum[c] + count / 2) / count) as u8;
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command as Process, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, ImageBuffer, ImageFormat, Rgba, RgbaImage};
+use tracing::{info, warn};
+
+use crate::display::{Surface, UiEvent};
+use crate::dsl::model::RenderEnvelope;
+use crate::x11::buffer_pool::FrameBuffer;
+use crate::x11::{backend, renderer};
+
+/// WebM 交给 ffmpeg 编码时的固定帧率
+const WEBM_FPS: u32 = 10;
+/// 最后一帧没有后继帧，停留这么久
+const LAST_FRAME_HOLD: Duration = Duration::from_secs(1);
+
+/// 一帧截图：BGRA 缓冲区及其显示时刻
+struct Captured {
+    at: Instant,
+    width: usize,
+    height: usize,
+    bgra: FrameBuffer,
+}
+
+/// 会话录像：作为一个显示面接收每一帧（包括加载动画），在后台线程编码为 GIF 或 WebM，
+/// 方便分享演示或报告渲染问题。格式由文件扩展名决定，WebM 需要 PATH 中有 ffmpeg。
+pub struct Recorder {
+    fonts: (Option<fontdue::Font>, Option<fontdue::Font>),
+    frames: Option<Sender<Captured>>,
+    encoder: Option<JoinHandle<()>>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Result<Self, Box<dyn Error>> {
+        let webm = path.to_ascii_lowercase().ends_with(".webm");
+        if !webm && !path.to_ascii_lowercase().ends_with(".gif") {
+            return Err(format!("unsupported recording format (expected .gif or .webm): {}", path).into());
+        }
+        // 提前创建文件，路径无效时启动即报错
+        let file = File::create(path)?;
+        info!("Recording session to {}", path);
+        let (tx, rx) = mpsc::channel();
+        let path = path.to_string();
+        let encoder = thread::spawn(move || {
+            let result = if webm { encode_webm(&path, rx) } else { encode_gif(file, rx) };
+            match result {
+                Ok(count) => info!("Recorded {} frames to {}", count, path),
+                Err(e) => warn!("recording to {} failed: {}", path, e),
+            }
+        });
+        Ok(Self { fonts: backend::load_fonts(), frames: Some(tx), encoder: Some(encoder) })
+    }
+
+    fn capture(&self, render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
+        let at = Instant::now();
+        let (width, height, bgra) = renderer::render_to_buffer(render, self.fonts.0.as_ref(), self.fonts.1.as_ref())?;
+        if let Some(frames) = &self.frames {
+            let _ = frames.send(Captured { at, width, height, bgra });
+        }
+        Ok(())
+    }
+}
+
+impl Surface for Recorder {
+    fn present(&self, render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
+        self.capture(render)
+    }
+
+    fn present_transient(&self, render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
+        self.capture(render)
+    }
+
+    fn poll_event(&self) -> Result<Option<UiEvent>, Box<dyn Error>> {
+        Ok(None)
+    }
+}
+
+/// 会话结束时关闭 channel 并等待编码线程写完文件
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.frames.take();
+        if let Some(encoder) = self.encoder.take() {
+            let _ = encoder.join();
+        }
+    }
+}
+
+/// 按到达顺序产出 (帧, 显示时长)：一帧的时长要等下一帧到达才知道
+fn timed_frames(rx: Receiver<Captured>) -> impl Iterator<Item = (Captured, Duration)> {
+    let mut pending: Option<Captured> = None;
+    let mut rx = rx.into_iter();
+    std::iter::from_fn(move || loop {
+        match rx.next() {
+            Some(next) => {
+                if let Some(prev) = pending.replace(next) {
+                    let held = pending.as_ref().map(|n| n.at.duration_since(prev.at)).unwrap_or(LAST_FRAME_HOLD);
+                    return Some((prev, held));
+                }
+            }
+            None => return pending.take().map(|last| (last, LAST_FRAME_HOLD)),
+        }
+    })
+}
+
+/// 录像画布固定为第一帧的尺寸，之后尺寸不同的帧裁剪/补白后对齐到左上角
+fn to_rgba(frame: &Captured, width: usize, height: usize) -> FrameBuffer {
+    let mut rgba = FrameBuffer::take(width * height * 4, 255);
+    for y in 0..height.min(frame.height) {
+        for x in 0..width.min(frame.width) {
+            let src = (y * frame.width + x) * 4;
+            let dst = (y * width + x) * 4;
+            rgba[dst..dst + 3].copy_from_slice(&[frame.bgra[src + 2], frame.bgra[src + 1], frame.bgra[src]]);
+        }
+    }
+    rgba
+}
+
+fn encode_gif(file: File, rx: Receiver<Captured>) -> Result<usize, Box<dyn Error>> {
+    // speed 10：量化质量与编码耗时的折中
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let mut canvas = None;
+    let mut count = 0;
+    for (frame, held) in timed_frames(rx) {
+        let (width, height) = *canvas.get_or_insert((frame.width, frame.height));
+        let image = RgbaImage::from_raw(width as u32, height as u32, to_rgba(&frame, width, height).into_vec()).ok_or("frame buffer size mismatch")?;
+        encoder.encode_frame(Frame::from_parts(image, 0, 0, Delay::from_saturating_duration(held)))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn encode_webm(path: &str, rx: Receiver<Captured>) -> Result<usize, Box<dyn Error>> {
+    let mut ffmpeg: Option<Child> = None;
+    let mut canvas = None;
+    let mut count = 0;
+    for (frame, held) in timed_frames(rx) {
+        let (width, height) = *canvas.get_or_insert((frame.width, frame.height));
+        if ffmpeg.is_none() {
+            ffmpeg = Some(
+                Process::new("ffmpeg")
+                    .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+                    .args(["-s", &format!("{}x{}", width, height), "-r", &WEBM_FPS.to_string(), "-i", "-"])
+                    .args(["-c:v", "libvpx-vp9", "-pix_fmt", "yuv420p", path])
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .map_err(|e| format!("failed to start ffmpeg: {}", e))?,
+            );
+        }
+        let stdin = ffmpeg.as_mut().and_then(|c| c.stdin.as_mut()).ok_or("ffmpeg stdin unavailable")?;
+        // 固定帧率：按显示时长重复写入同一帧
+        let repeats = ((held.as_secs_f64() * WEBM_FPS as f64).round() as usize).max(1);
+        let rgba = to_rgba(&frame, width, height);
+        for _ in 0..repeats {
+            stdin.write_all(&rgba)?;
+        }
+        count += 1;
+    }
+    if let Some(mut child) = ffmpeg {
+        drop(child.stdin.take());
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(format!("ffmpeg exited with {}", status).into());
+        }
+    }
+    Ok(count)
+}
+
+/// 把当前画面保存为带时间戳的 PNG（`<dir>/agd-YYYYMMDD-HHMMSS.png`，UTC），复用离屏渲染结果，不依赖外部截图工具
+pub fn save_screenshot(render: &RenderEnvelope, dir: &str) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let path = Path::new(dir).join(format!("agd-{}.png", utc_timestamp()));
+    save_png(render, &path)?;
+    Ok(path)
+}
+
+/// 离屏渲染一帧并保存为 PNG
+pub fn save_png(render: &RenderEnvelope, path: &Path) -> Result<(), Box<dyn Error>> {
+    let (primary, emoji) = backend::load_fonts();
+    let (width, height, bgra) = renderer::render_to_buffer(render, primary.as_ref(), emoji.as_ref())?;
+    let frame = Captured { at: Instant::now(), width, height, bgra };
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(width as u32, height as u32, to_rgba(&frame, width, height)).ok_or("frame buffer size mismatch")?;
+    image.save_with_format(path, ImageFormat::Png)?;
+    Ok(())
+}
+
+/// `YYYYMMDD-HHMMSS`（UTC）
+fn utc_timestamp() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+    // 公历换算（Howard Hinnant 的 civil_from_days）
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, rem / 3600, rem / 60 % 60, rem % 60)
+}
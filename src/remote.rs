@@ -0,0 +1,165 @@
+use std::error::Error;
+use std::io::{Cursor, ErrorKind};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use image::{ImageBuffer, ImageFormat, Rgba};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{debug, info, warn};
+use tungstenite::{Message, WebSocket};
+
+use crate::display::{ClickEvent, GestureEvent, Surface, UiEvent};
+use crate::dsl::model::RenderEnvelope;
+use crate::dsl::theme;
+use crate::dsl::version;
+use crate::gesture::SwipeDirection;
+use crate::x11::buffer_pool::FrameBuffer;
+use crate::x11::{backend, renderer};
+
+/// 推送给客户端的帧格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// 二进制消息，内容为整帧 PNG
+    Png,
+    /// 文本消息，内容为 render 信封 JSON，由客户端自行绘制
+    Commands,
+}
+
+/// 客户端发来的输入消息
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Click { x: i32, y: i32 },
+    /// 客户端自己识别的滑动手势（触摸屏等），direction 为 left/right/up/down，velocity 为像素/秒
+    Gesture { x: i32, y: i32, direction: String, velocity: f32 },
+    Text { text: String },
+}
+
+/// 客户端读超时，超时后检查是否有待发送的帧
+const CLIENT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// WebSocket 远程显示面：把帧推给所有已连接的客户端（浏览器、手机等），并接收它们的点击与文本输入。
+/// 每个客户端一个线程，独占自己的 socket，通过 channel 收取要发送的帧。
+pub struct RemoteDisplay {
+    format: FrameFormat,
+    fonts: (Option<fontdue::Font>, Option<fontdue::Font>),
+    clients: Arc<Mutex<Vec<Sender<Message>>>>,
+    /// 最近一帧，新客户端连上后立即补发
+    last_frame: Arc<Mutex<Option<Message>>>,
+    events: Receiver<UiEvent>,
+}
+
+impl RemoteDisplay {
+    pub fn bind(addr: &str, format: FrameFormat) -> Result<Self, Box<dyn Error>> {
+        let listener = TcpListener::bind(addr)?;
+        info!("Remote display listening on ws://{}", listener.local_addr()?);
+
+        let clients: Arc<Mutex<Vec<Sender<Message>>>> = Arc::new(Mutex::new(Vec::new()));
+        let last_frame: Arc<Mutex<Option<Message>>> = Arc::new(Mutex::new(None));
+        let (event_tx, events) = mpsc::channel();
+
+        let accept_clients = clients.clone();
+        let accept_last_frame = last_frame.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+                let (frame_tx, frame_rx) = mpsc::channel();
+                if let Some(frame) = accept_last_frame.lock().ok().and_then(|f| f.clone()) {
+                    let _ = frame_tx.send(frame);
+                }
+                if let Ok(mut list) = accept_clients.lock() {
+                    list.push(frame_tx);
+                }
+                let event_tx = event_tx.clone();
+                thread::spawn(move || {
+                    info!("Remote client connected: {}", peer);
+                    if let Err(e) = serve_client(stream, frame_rx, event_tx) {
+                        debug!("remote client {} closed: {}", peer, e);
+                    }
+                    info!("Remote client disconnected: {}", peer);
+                });
+            }
+        });
+
+        Ok(Self { format, fonts: backend::load_fonts(), clients, last_frame, events })
+    }
+
+    fn encode(&self, render: &RenderEnvelope) -> Result<Message, Box<dyn Error>> {
+        match self.format {
+            FrameFormat::Commands => Ok(Message::Text(serde_json::to_string(&theme::resolve_render(render))?)),
+            FrameFormat::Png => {
+                let (w, h, pixels) = renderer::render_to_buffer(render, self.fonts.0.as_ref(), self.fonts.1.as_ref())?;
+                // 渲染缓冲区是 BGRA，PNG 需要 RGBA
+                let mut rgba = FrameBuffer::take(pixels.len(), 255);
+                for (dst, src) in rgba.chunks_exact_mut(4).zip(pixels.chunks_exact(4)) {
+                    dst[..3].copy_from_slice(&[src[2], src[1], src[0]]);
+                }
+                let img = ImageBuffer::<Rgba<u8>, _>::from_raw(w as u32, h as u32, rgba).ok_or("frame buffer size mismatch")?;
+                let mut png = Cursor::new(Vec::new());
+                img.write_to(&mut png, ImageFormat::Png)?;
+                Ok(Message::Binary(png.into_inner()))
+            }
+        }
+    }
+}
+
+impl Surface for RemoteDisplay {
+    fn present(&self, render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
+        let frame = self.encode(render)?;
+        if let Ok(mut last) = self.last_frame.lock() {
+            *last = Some(frame.clone());
+        }
+        if let Ok(mut clients) = self.clients.lock() {
+            // 发送失败说明客户端线程已退出
+            clients.retain(|tx| tx.send(frame.clone()).is_ok());
+        }
+        Ok(())
+    }
+
+    /// 只有整帧图片格式才推送临时帧；commands 格式的客户端只关心界面状态
+    fn present_transient(&self, render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
+        if self.format != FrameFormat::Png {
+            return Ok(());
+        }
+        let frame = self.encode(render)?;
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.retain(|tx| tx.send(frame.clone()).is_ok());
+        }
+        Ok(())
+    }
+
+    fn poll_event(&self) -> Result<Option<UiEvent>, Box<dyn Error>> {
+        Ok(self.events.try_recv().ok())
+    }
+}
+
+fn serve_client(stream: TcpStream, frames: Receiver<Message>, events: Sender<UiEvent>) -> Result<(), Box<dyn Error>> {
+    let mut ws: WebSocket<TcpStream> = tungstenite::accept(stream).map_err(|e| e.to_string())?;
+    // 握手完成后再设置读超时，否则握手本身会被超时打断
+    ws.get_ref().set_read_timeout(Some(CLIENT_POLL_INTERVAL))?;
+    ws.send(Message::Text(json!({ "type": "hello", "version": version::CURRENT_VERSION, "supported": version::SUPPORTED_VERSIONS }).to_string()))?;
+
+    loop {
+        while let Ok(frame) = frames.try_recv() {
+            ws.send(frame)?;
+        }
+        match ws.read() {
+            Ok(Message::Text(text)) => match serde_json::from_str::<ClientMessage>(&text) {
+                Ok(ClientMessage::Click { x, y }) => events.send(UiEvent::Click(ClickEvent { x, y }))?,
+                Ok(ClientMessage::Gesture { x, y, direction, velocity }) => match SwipeDirection::parse(&direction) {
+                    Some(direction) => events.send(UiEvent::Gesture(GestureEvent { x, y, direction, velocity }))?,
+                    None => warn!("ignoring gesture with unknown direction: {}", direction),
+                },
+                Ok(ClientMessage::Text { text }) => events.send(UiEvent::Text(text))?,
+                Err(e) => warn!("ignoring invalid remote message: {}", e),
+            },
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::config::ToneConfig;
+use crate::display::{ClickEvent, GestureEvent, SoundCue, Surface, UiEvent};
+use crate::dsl::model::RenderEnvelope;
+use crate::dsl::{semantics, theme};
+use crate::dsl::version;
+use crate::gesture::SwipeDirection;
+
+/// stdin 上的一行 JSON-RPC 2.0 请求或通知（没有 id 即为通知，不回复）
+#[derive(Deserialize)]
+struct RpcRequest {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// 机器模式：stdin/stdout 上逐行收发 JSON-RPC，供编辑器或其它 agent 以子进程方式嵌入。
+///
+/// 输入方法：`text {text}`、`click {x, y}`、`shutdown`；
+/// 输出通知：`ready`、每帧一条 `render`（参数为 render 信封），随后一条 `semantics`（该帧的无障碍树，
+/// 供嵌入方接入读屏等辅助技术）。
+pub struct RpcSurface {
+    events: Receiver<UiEvent>,
+}
+
+impl RpcSurface {
+    /// 启动 stdin 读取线程；stdin 关闭视为 shutdown
+    pub fn spawn() -> Self {
+        // ready 先于任何回复发出，嵌入方可以据此判断进程已就绪
+        notify("ready", json!({ "version": version::CURRENT_VERSION, "supported": version::SUPPORTED_VERSIONS }));
+        let (tx, events) = mpsc::channel();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                let Ok(line) = line else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if !handle_line(&line, &tx) {
+                    return;
+                }
+            }
+            let _ = tx.send(UiEvent::Quit);
+        });
+        Self { events }
+    }
+
+    /// 阻塞等待第一条文本输入，作为初始生成请求
+    pub fn wait_for_text(&self) -> Result<String, Box<dyn Error>> {
+        loop {
+            match self.events.recv()? {
+                UiEvent::Text(text) => return Ok(text),
+                UiEvent::Quit => return Err("shutdown before the first request".into()),
+                // 还没有画面，点击没有意义
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Surface for RpcSurface {
+    fn present(&self, render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
+        notify("render", serde_json::to_value(theme::resolve_render(render))?);
+        notify("semantics", json!({ "seq": render.seq, "nodes": semantics::outline(render) }));
+        Ok(())
+    }
+
+    fn play_sound(&self, cue: SoundCue, tone: ToneConfig) -> Result<(), Box<dyn Error>> {
+        notify(
+            "sound",
+            json!({ "cue": cue.name(), "volume": tone.volume, "pitch_hz": tone.pitch_hz, "duration_ms": tone.duration_ms }),
+        );
+        Ok(())
+    }
+
+    fn poll_event(&self) -> Result<Option<UiEvent>, Box<dyn Error>> {
+        Ok(self.events.try_recv().ok())
+    }
+}
+
+/// 处理一行输入；返回 false 表示收到 shutdown，读取线程应退出
+fn handle_line(line: &str, tx: &Sender<UiEvent>) -> bool {
+    let request: RpcRequest = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            reply(&Value::Null, Err((-32700, format!("parse error: {}", e))));
+            return true;
+        }
+    };
+    let event = match request.method.as_str() {
+        "text" => request.params["text"].as_str().map(|t| UiEvent::Text(t.to_string())),
+        "click" => match (request.params["x"].as_i64(), request.params["y"].as_i64()) {
+            (Some(x), Some(y)) => Some(UiEvent::Click(ClickEvent { x: x as i32, y: y as i32 })),
+            _ => None,
+        },
+        "gesture" => match (
+            request.params["x"].as_i64(),
+            request.params["y"].as_i64(),
+            request.params["direction"].as_str().and_then(SwipeDirection::parse),
+            request.params["velocity"].as_f64(),
+        ) {
+            (Some(x), Some(y), Some(direction), Some(velocity)) => {
+                Some(UiEvent::Gesture(GestureEvent { x: x as i32, y: y as i32, direction, velocity: velocity as f32 }))
+            }
+            _ => None,
+        },
+        "shutdown" => Some(UiEvent::Quit),
+        other => {
+            if let Some(id) = &request.id {
+                reply(id, Err((-32601, format!("method not found: {}", other))));
+            }
+            return true;
+        }
+    };
+    let Some(event) = event else {
+        if let Some(id) = &request.id {
+            reply(id, Err((-32602, format!("invalid params for {}", request.method))));
+        }
+        return true;
+    };
+    let quit = matches!(event, UiEvent::Quit);
+    if tx.send(event).is_err() {
+        return false;
+    }
+    if let Some(id) = &request.id {
+        // 只确认已受理；生成结果通过 render 通知异步送达
+        reply(id, Ok(json!({ "accepted": true })));
+    }
+    !quit
+}
+
+fn reply(id: &Value, result: Result<Value, (i64, String)>) {
+    let message = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+    };
+    write_line(&message);
+}
+
+pub fn notify(method: &str, params: Value) {
+    write_line(&json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+}
+
+fn write_line(message: &Value) {
+    let mut stdout = io::stdout().lock();
+    if writeln!(stdout, "{}", message).and_then(|_| stdout.flush()).is_err() {
+        warn!("failed to write JSON-RPC message to stdout");
+    }
+}
@@ -0,0 +1,135 @@
+//! `run` 动作：执行配置白名单中的本地命令，把退出码与输出作为 `run` 事件交给模型。
+//!
+//! 模型只能引用白名单里的名称，命令行完全来自配置、不经过 shell。子进程在清空的环境变量（只保留 PATH、HOME 与
+//! 语言设置）、空的标准输入和独立的进程组中运行，可选地包在 `run.sandbox` 给出的沙箱程序（bwrap、firejail 等）里；
+//! 超时后整个进程组被杀掉，输出超过上限的部分被丢弃。
+
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::config;
+use crate::dsl::model::RunOutput;
+
+/// 传给子进程的环境变量，其余一律清空
+const KEPT_ENV: &[&str] = &["PATH", "HOME", "LANG", "LC_ALL", "LC_CTYPE", "TZ"];
+/// 轮询子进程是否退出的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// 子进程退出后等待输出读完的时间（派生出的后台进程可能一直占着管道）
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 白名单中是否有这个名称
+pub fn is_allowed(name: &str) -> bool {
+    config::get().run.commands.contains_key(name)
+}
+
+/// 给生成提示词的可用命令：名称与命令行
+pub fn describe() -> String {
+    let commands = &config::get().run.commands;
+    if commands.is_empty() {
+        return "none (the run action is disabled)".to_string();
+    }
+    commands.iter().map(|(name, argv)| format!("{} ({})", name, argv.join(" "))).collect::<Vec<_>>().join(", ")
+}
+
+/// 执行白名单中的命令并等待结束（阻塞，由调用方放到后台线程）
+pub fn run(name: &str) -> RunOutput {
+    let settings = &config::get().run;
+    let mut output = RunOutput { name: name.to_string(), exit_code: None, stdout: String::new(), stderr: String::new(), timed_out: false, truncated: false };
+    let mut command = match command(name) {
+        Ok(command) => command,
+        Err(e) => {
+            output.stderr = e;
+            return output;
+        }
+    };
+    command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).process_group(0);
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let program = command.get_program().to_string_lossy();
+            warn!("run {}: failed to start {}: {}", name, program, e);
+            output.stderr = format!("failed to start {}: {}", program, e);
+            return output;
+        }
+    };
+    info!("Running {} (pid {})", name, child.id());
+    let limit = settings.max_output_bytes;
+    let stdout = child.stdout.take().map(|pipe| drain(pipe, limit));
+    let stderr = child.stderr.take().map(|pipe| drain(pipe, limit));
+
+    let deadline = Instant::now() + Duration::from_secs(settings.timeout_secs.max(1));
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() >= deadline => {
+                warn!("run {}: timed out after {}s, killing it", name, settings.timeout_secs);
+                output.timed_out = true;
+                // 负的 pid 表示整个进程组，连同命令派生的子进程一起结束
+                unsafe {
+                    libc::kill(-(child.id() as i32), libc::SIGKILL);
+                }
+                break child.wait().ok();
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                warn!("run {}: {}", name, e);
+                break None;
+            }
+        }
+    };
+    output.exit_code = status.and_then(|s| s.code());
+    for (rx, text) in [(stdout, &mut output.stdout), (stderr, &mut output.stderr)] {
+        if let Some((bytes, cut)) = rx.and_then(|rx| rx.recv_timeout(DRAIN_TIMEOUT).ok()) {
+            *text = String::from_utf8_lossy(&bytes).into_owned();
+            output.truncated |= cut;
+        }
+    }
+    output
+}
+
+/// 按白名单构造命令：套上沙箱程序、清空环境变量（只保留 `KEPT_ENV`）、切换工作目录；
+/// 标准输入输出由调用方决定（`run` 动作用管道，`terminal` 用伪终端）
+pub fn command(name: &str) -> Result<Command, String> {
+    let settings = &config::get().run;
+    let Some(argv) = settings.commands.get(name).filter(|argv| !argv.is_empty()) else {
+        return Err(format!("{} is not an allowed command", name));
+    };
+    let full: Vec<&String> = settings.sandbox.iter().chain(argv).collect();
+    let mut command = Command::new(full[0]);
+    command.args(&full[1..]).env_clear();
+    for key in KEPT_ENV {
+        if let Ok(value) = std::env::var(key) {
+            command.env(key, value);
+        }
+    }
+    if let Some(dir) = &settings.working_dir {
+        command.current_dir(dir);
+    }
+    Ok(command)
+}
+
+/// 在后台读完管道，只保留前 `limit` 个字节；返回内容与是否被截断
+fn drain(mut pipe: impl Read + Send + 'static, limit: usize) -> mpsc::Receiver<(Vec<u8>, bool)> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut cut = false;
+        let mut chunk = [0u8; 4096];
+        while let Ok(n) = pipe.read(&mut chunk) {
+            if n == 0 {
+                break;
+            }
+            let room = limit.saturating_sub(kept.len());
+            kept.extend_from_slice(&chunk[..n.min(room)]);
+            cut |= n > room;
+        }
+        let _ = tx.send((kept, cut));
+    });
+    rx
+}
@@ -0,0 +1,293 @@
+//! 本地事件脚本（Rhai，需要 `scripting` 特性）：脚本在模型之前处理匹配的事件，
+//! 用于校验表单、计算派生值、调用本地接口，必要时直接给出新界面而完全不请求模型。
+//!
+//! 脚本在加载时执行一次顶层语句，用 `on(kind, target, "函数名")` 注册处理函数（`target` 支持首尾 `*` 通配，
+//! 省略时匹配所有目标）。处理函数以 `(event, screen)` 调用，`event` 是事件信封中的 `event` 对象，
+//! `screen` 是当前界面（render 信封）。返回值决定事件的去向：
+//! - `()`：事件原样交给模型；
+//! - `#{ screen: ... }`：用这个界面替换当前界面，不请求模型；
+//! - `#{ event: ... }`：用改写后的事件（如补上派生字段）请求模型；
+//! - `#{ handled: true }`：事件已在本地处理，界面保持不变。
+//!
+//! 脚本出错或超出操作数上限时记录警告，事件照常交给模型。
+
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::config::ScriptsConfig;
+use crate::dsl::model::RenderEnvelope;
+
+/// 脚本对一个事件的处理结果
+#[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+pub enum Outcome {
+    /// 没有匹配的处理函数，或处理函数不接管：事件原样交给模型
+    Forward,
+    /// 改写后的事件信封（JSON），交给模型
+    Replace(String),
+    /// 脚本给出的新界面，不请求模型
+    Screen(Box<RenderEnvelope>),
+    /// 事件已在本地处理，界面保持不变
+    Handled,
+}
+
+/// 加载配置的脚本；没有配置脚本时返回 None
+#[cfg(feature = "scripting")]
+pub fn load(settings: &ScriptsConfig) -> Result<Option<Arc<ScriptHost>>, Box<dyn Error>> {
+    if settings.files.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Arc::new(ScriptHost::load(settings)?)))
+}
+
+#[cfg(not(feature = "scripting"))]
+pub fn load(settings: &ScriptsConfig) -> Result<Option<Arc<ScriptHost>>, Box<dyn Error>> {
+    if !settings.files.is_empty() {
+        tracing::warn!(files = settings.files.len(), "scripts need a build with --features scripting, events go straight to the model");
+    }
+    Ok(None)
+}
+
+/// 未开启 `scripting` 特性时的占位，`load` 不会构造它
+#[cfg(not(feature = "scripting"))]
+#[allow(dead_code)]
+pub struct ScriptHost;
+
+#[cfg(not(feature = "scripting"))]
+impl ScriptHost {
+    pub fn handle(&self, _event_json: &str, _current: Option<&RenderEnvelope>) -> Outcome {
+        Outcome::Forward
+    }
+}
+
+#[cfg(feature = "scripting")]
+pub use engine::ScriptHost;
+
+#[cfg(feature = "scripting")]
+mod engine {
+    use std::error::Error;
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use reqwest::blocking::Client;
+    use reqwest::redirect::Policy;
+    use reqwest::Url;
+    use rhai::serde::{from_dynamic, to_dynamic};
+    use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+    use serde_json::Value;
+    use tracing::{debug, info, warn};
+
+    use super::Outcome;
+    use crate::config::{self, ScriptsConfig};
+    use crate::dsl::{formatting, parser};
+    use crate::dsl::model::{Command, RenderEnvelope};
+    use crate::images;
+    use crate::metrics;
+    use crate::runner;
+    use crate::state::router;
+
+    /// `http_get` / `http_post` 读取的响应体上限
+    const MAX_RESPONSE_BYTES: u64 = 1 << 20;
+
+    type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+    /// `on(kind, target, function)` 注册的处理函数
+    #[derive(Debug, Clone)]
+    struct Handler {
+        kind: String,
+        target: String,
+        function: String,
+    }
+
+    /// 编译好的脚本与注册的处理函数；各 LLM 任务线程共享
+    pub struct ScriptHost {
+        engine: Engine,
+        ast: AST,
+        handlers: Vec<Handler>,
+    }
+
+    impl ScriptHost {
+        pub fn load(settings: &ScriptsConfig) -> Result<Self, Box<dyn Error>> {
+            let registered = Arc::new(Mutex::new(Vec::new()));
+            let engine = build_engine(settings, registered.clone());
+            let mut ast = AST::empty();
+            for file in &settings.files {
+                let compiled = engine.compile_file(file.into()).map_err(|e| format!("script {}: {}", file, e))?;
+                ast.combine(compiled);
+            }
+            // 顶层语句只在加载时执行这一次，其中的 on(...) 调用登记处理函数
+            engine.run_ast(&ast).map_err(|e| format!("scripts: {}", e))?;
+            let handlers: Vec<Handler> = registered.lock().unwrap().drain(..).collect();
+            for handler in &handlers {
+                if !ast.iter_functions().any(|f| f.name == handler.function && f.params.len() == 2) {
+                    return Err(format!("scripts: handler {} for {} events must be a function taking (event, screen)", handler.function, handler.kind).into());
+                }
+            }
+            info!(files = settings.files.len(), handlers = handlers.len(), "Scripts loaded");
+            Ok(Self { engine, ast, handlers })
+        }
+
+        /// 交给第一个匹配的处理函数；`event_json` 是完整的事件信封
+        pub fn handle(&self, event_json: &str, current: Option<&RenderEnvelope>) -> Outcome {
+            let Ok(mut envelope) = serde_json::from_str::<Value>(event_json) else {
+                return Outcome::Forward;
+            };
+            let kind = envelope["event"]["kind"].as_str().unwrap_or_default().to_string();
+            let target = envelope["event"]["target_id"].as_str().unwrap_or_default();
+            let Some(handler) = self.handlers.iter().find(|h| router::matches_pattern(&h.kind, &kind) && router::matches_pattern(&h.target, target)) else {
+                return Outcome::Forward;
+            };
+            match self.call(handler, &envelope["event"], current) {
+                Ok(result) => {
+                    let outcome = interpret(result, &mut envelope, current);
+                    let name = match &outcome {
+                        Outcome::Forward => "forward",
+                        Outcome::Replace(_) => "replace",
+                        Outcome::Screen(_) => "screen",
+                        Outcome::Handled => "handled",
+                    };
+                    debug!(function = %handler.function, outcome = name, "script handled {} event", kind);
+                    metrics::inc("agd_script_calls_total", &[("outcome", name)], 1.0);
+                    outcome
+                }
+                Err(e) => {
+                    warn!(function = %handler.function, "script failed, sending the event to the model: {}", e);
+                    metrics::inc("agd_script_calls_total", &[("outcome", "error")], 1.0);
+                    Outcome::Forward
+                }
+            }
+        }
+
+        fn call(&self, handler: &Handler, event: &Value, current: Option<&RenderEnvelope>) -> Result<Dynamic, Box<dyn Error>> {
+            let event = to_dynamic(event)?;
+            let screen = match current {
+                Some(render) => to_dynamic(render)?,
+                None => Dynamic::UNIT,
+            };
+            // 顶层语句已在加载时执行过，不能再跑一遍（否则会重复登记处理函数）
+            let options = CallFnOptions::new().eval_ast(false);
+            Ok(self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, &handler.function, (event, screen))?)
+        }
+    }
+
+    /// 把处理函数的返回值换成处理结果；无法识别的返回值按 `()` 处理
+    fn interpret(result: Dynamic, envelope: &mut Value, current: Option<&RenderEnvelope>) -> Outcome {
+        if result.is_unit() {
+            return Outcome::Forward;
+        }
+        let Some(map) = result.try_cast::<Map>() else {
+            warn!("script handler returned neither () nor a map, sending the event to the model");
+            return Outcome::Forward;
+        };
+        if let Some(screen) = map.get("screen") {
+            return match from_dynamic::<RenderEnvelope>(screen) {
+                Ok(mut render) => {
+                    parser::prepare(&mut render);
+                    Outcome::Screen(Box::new(render))
+                }
+                Err(e) => {
+                    warn!("script returned an invalid screen, sending the event to the model: {}", e);
+                    Outcome::Forward
+                }
+            };
+        }
+        if let Some(event) = map.get("event") {
+            return match from_dynamic::<Value>(event) {
+                Ok(event) => {
+                    envelope["event"] = event;
+                    Outcome::Replace(envelope.to_string())
+                }
+                Err(e) => {
+                    warn!("script returned an invalid event, sending the original: {}", e);
+                    Outcome::Forward
+                }
+            };
+        }
+        match map.get("handled").and_then(|h| h.as_bool().ok()) {
+            Some(true) if current.is_some() => Outcome::Handled,
+            _ => Outcome::Forward,
+        }
+    }
+
+    fn build_engine(settings: &ScriptsConfig, registered: Arc<Mutex<Vec<Handler>>>) -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(settings.max_operations);
+        engine.on_print(|text| info!("script: {}", text));
+        engine.on_debug(|text, source, pos| debug!(source = source.unwrap_or_default(), "script {}: {}", pos, text));
+
+        let all_targets = registered.clone();
+        engine.register_fn("on", move |kind: &str, target: &str, function: &str| {
+            registered.lock().unwrap().push(Handler { kind: kind.to_string(), target: target.to_string(), function: function.to_string() });
+        });
+        engine.register_fn("on", move |kind: &str, function: &str| {
+            all_targets.lock().unwrap().push(Handler { kind: kind.to_string(), target: "*".to_string(), function: function.to_string() });
+        });
+
+        engine.register_fn("run", |name: &str| -> ScriptResult<Dynamic> {
+            if !runner::is_allowed(name) {
+                return Err(format!("{} is not in run.commands", name).into());
+            }
+            to_dynamic(runner::run(name))
+        });
+        let hosts = settings.http_hosts.clone();
+        engine.register_fn("http_get", move |url: &str| -> ScriptResult<String> { http(&hosts, url, None) });
+        let hosts = settings.http_hosts.clone();
+        engine.register_fn("http_post", move |url: &str, body: &str| -> ScriptResult<String> { http(&hosts, url, Some(body)) });
+        engine.register_fn("json_parse", |text: &str| -> ScriptResult<Dynamic> {
+            let value: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+            to_dynamic(value)
+        });
+        engine.register_fn("json_string", |value: Dynamic| -> ScriptResult<String> { Ok(from_dynamic::<Value>(&value)?.to_string()) });
+        engine.register_fn("format_value", |value: Dynamic, format: &str| -> ScriptResult<String> {
+            Ok(formatting::format_value(&from_dynamic::<Value>(&value)?, format)?)
+        });
+        engine.register_fn("set_label", |screen: &mut Map, rect_id: &str, text: &str| -> ScriptResult<bool> { set_label(screen, rect_id, text) });
+        engine
+    }
+
+    /// 请求 `scripts.http_hosts` 中的主机，返回响应体；不跟随重定向
+    fn http(hosts: &[String], url: &str, body: Option<&str>) -> ScriptResult<String> {
+        let parsed = Url::parse(url).map_err(|e| format!("invalid URL {}: {}", url, e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("unsupported URL scheme {}", parsed.scheme()).into());
+        }
+        let host = parsed.host_str().unwrap_or_default().to_ascii_lowercase();
+        if !hosts.iter().any(|domain| images::domain_matches(&host, domain)) {
+            return Err(format!("host {} is not in scripts.http_hosts", host).into());
+        }
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config::get().http.timeout_secs))
+            .redirect(Policy::none())
+            .build()
+            .map_err(|e| e.to_string())?;
+        let request = match body {
+            Some(body) => client.post(parsed).header("Content-Type", "application/json").body(body.to_string()),
+            None => client.get(parsed),
+        };
+        let response = request.send().and_then(|r| r.error_for_status()).map_err(|e| e.to_string())?;
+        let mut text = String::new();
+        response.take(MAX_RESPONSE_BYTES).read_to_string(&mut text).map_err(|e| e.to_string())?;
+        Ok(text)
+    }
+
+    /// 把界面中 id 为 `rect_id` 的 rect 内的第一段文字换成 `text`；找不到时返回 false
+    fn set_label(screen: &mut Map, rect_id: &str, text: &str) -> ScriptResult<bool> {
+        let mut render: RenderEnvelope = from_dynamic(&Dynamic::from_map(screen.clone()))?;
+        let Some(bounds) = router::rect_bounds(&render, rect_id) else {
+            return Ok(false);
+        };
+        let label = render.commands.iter_mut().find_map(|c| match c {
+            Command::Text { x, y, text, value, format, .. } if router::inside(bounds, *x, *y) => Some((text, value, format)),
+            _ => None,
+        });
+        let Some((label, value, format)) = label else {
+            return Ok(false);
+        };
+        // 换成固定文字，原来按 value/format 格式化的内容不再适用
+        *label = text.to_string();
+        *value = None;
+        *format = None;
+        *screen = to_dynamic(&render)?.cast::<Map>();
+        Ok(true)
+    }
+}
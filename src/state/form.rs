@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use serde_json::Value;
+
+use crate::config;
+use crate::dsl::model::{Command, FormSubmission, RenderEnvelope};
+use crate::input::{self, Inputs};
+
+/// 表单区域：(x, y, w, h)
+fn region(render: &RenderEnvelope, form_id: &str) -> Option<(i32, i32, i32, i32)> {
+    render.commands.iter().find_map(|c| match c {
+        Command::Rect { id: Some(id), x, y, w, h, .. } if id == form_id => Some((*x, *y, *w as i32, *h as i32)),
+        _ => None,
+    })
+}
+
+fn contains((fx, fy, fw, fh): (i32, i32, i32, i32), x: i32, y: i32) -> bool {
+    x >= fx && y >= fy && x < fx + fw && y < fy + fh
+}
+
+/// 收集 `submitter`（action 为 submit 的按钮）所属表单区域内控件的状态，合成一次提交。
+/// 表单区域是 id 与按钮 `form` 相同的 rect，左上角落在区域内的可点击 rect 算作其中的控件。
+/// 本地维护状态的控件按 id 记入：勾选框 / 单选按钮（声明了 `checked` 的 rect）记为 true / false，
+/// 输入框记为输入的文字（`mask` 输入框默认记为 `<redacted>`），滑块记为数值，选择框记为选中的选项文字。
+/// 普通按钮不计入
+pub fn collect(render: &RenderEnvelope, submitter: &str, inputs: &Inputs) -> Option<FormSubmission> {
+    let form_id = render.commands.iter().find_map(|c| match c {
+        Command::Rect { id: Some(id), form: Some(form), .. } if id == submitter => Some(form.clone()),
+        _ => None,
+    })?;
+    let area = region(render, &form_id)?;
+    let mut fields = BTreeMap::new();
+    for command in &render.commands {
+        match command {
+            Command::Rect { id: Some(id), x, y, checked: Some(checked), .. } if id != submitter && contains(area, *x, *y) => {
+                fields.insert(id.clone(), Value::Bool(*checked));
+            }
+            Command::Input { id, x, y, mask, .. } if contains(area, *x, *y) => {
+                let value = if *mask && config::get().redact_masked_inputs { "<redacted>" } else { inputs.value(id) };
+                fields.insert(id.clone(), Value::String(value.to_string()));
+            }
+            Command::Slider { id, x, y, .. } if contains(area, *x, *y) => {
+                if let Some(value) = input::slider_value(command).and_then(serde_json::Number::from_f64) {
+                    fields.insert(id.clone(), Value::Number(value));
+                }
+            }
+            Command::Select { id, x, y, .. } if contains(area, *x, *y) => {
+                if let Some(option) = input::selected_option(command) {
+                    fields.insert(id.clone(), Value::String(option.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(FormSubmission { id: form_id, fields })
+}
+
+/// 位于 (x, y) 的控件所属表单的提交按钮中心：输入框里按 Enter 时等同于点击它
+pub fn submitter_at(render: &RenderEnvelope, x: i32, y: i32) -> Option<(i32, i32)> {
+    render.commands.iter().find_map(|c| match c {
+        Command::Rect { id: Some(_), x: bx, y: by, w, h, clickable: true, action: Some(action), form: Some(form), .. }
+            if action == "submit" && region(render, form).is_some_and(|area| contains(area, x, y)) =>
+        {
+            Some((bx + *w as i32 / 2, by + *h as i32 / 2))
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::parser;
+    use crate::state::router::IntentRouter;
+
+    #[test]
+    fn collects_checkboxes_sliders_and_selects_in_the_form() {
+        let render = parser::parse_render(
+            r##"{"version":"AGD/0.2","type":"render","seq":1,"window":{"width":400,"height":400,"title":"Prefs"},"commands":[
+                {"cmd":"rect","id":"prefs","x":0,"y":0,"w":400,"h":300,"fill":"#FFFFFF"},
+                {"cmd":"rect","id":"notify","x":10,"y":10,"w":40,"h":40,"fill":"#FFFFFF","clickable":true,"checked":false},
+                {"cmd":"text","x":14,"y":14,"text":"☐","color":"#000000"},
+                {"cmd":"rect","id":"chk_glyph_only","x":60,"y":10,"w":40,"h":40,"fill":"#FFFFFF","clickable":true},
+                {"cmd":"text","x":64,"y":14,"text":"☑","color":"#000000"},
+                {"cmd":"slider","id":"volume","x":10,"y":60,"w":201,"h":30,"min":0,"max":10,"step":1,"value":3},
+                {"cmd":"select","id":"theme","x":10,"y":100,"w":200,"h":40,"options":["light","dark"]},
+                {"cmd":"slider","id":"outside","x":10,"y":320,"w":200,"h":30},
+                {"cmd":"rect","id":"save","x":10,"y":200,"w":100,"h":40,"fill":"#0000FF","clickable":true,"action":"submit","form":"prefs"}]}"##,
+        )
+        .unwrap();
+        let mut router = IntentRouter::new(&crate::config::RouterConfig::default());
+        let mut render = router.route("notify", &render).unwrap();
+        let mut inputs = Inputs::new();
+        assert!(inputs.click_at(&render, 150, 75));
+        assert!(inputs.click_at(&render, 20, 110));
+        assert!(inputs.fill(&mut render));
+
+        let form = collect(&render, "save", &inputs).unwrap();
+        assert_eq!(form.id, "prefs");
+        assert_eq!(form.fields["notify"], Value::Bool(true));
+        // 只有字形、没有声明 checked 的按钮不算勾选框
+        assert!(!form.fields.contains_key("chk_glyph_only"));
+        assert_eq!(form.fields["volume"], 7.0);
+        assert_eq!(form.fields["theme"], "dark");
+        assert!(!form.fields.contains_key("outside"));
+        assert!(!form.fields.contains_key("save"));
+    }
+}
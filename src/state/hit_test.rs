@@ -1,6 +1,11 @@
+use crate::display::KeyEvent;
+use crate::dsl::validator::KeyChord;
+
 #[derive(Debug, Default)]
 pub struct HitTestIndex {
     items: Vec<HitTarget>,
+    /// 快捷键组合及其目标 id
+    shortcuts: Vec<(KeyChord, String)>,
 }
 
 #[derive(Debug, Clone)]
@@ -10,44 +15,46 @@ pub struct HitTarget {
     pub y: i32,
     pub w: u32,
     pub h: u32,
+    /// 小写的访问键
+    pub access_key: Option<char>,
 }
 
 impl HitTestIndex {
     pub fn new() -> Self {
-        Self { items: Vec::new() }
+        Self { items: Vec::new(), shortcuts: Vec::new() }
     }
 
     pub fn reset(&mut self) {
         self.items.clear();
+        self.shortcuts.clear();
     }
 
     pub fn add(&mut self, target: HitTarget) {
         self.items.push(target);
     }
 
-    pub fn hit(&self, x: i32, y: i32) -> Option<&str> {
-        for item in &self.items {
-            if x >= item.x
-                && y >= item.y
-                && x < item.x + item.w as i32
-                && y < item.y + item.h as i32
-            {
-                return Some(item.id.as_str());
-            }
-        }
-        None
+    pub fn add_shortcut(&mut self, chord: KeyChord, target: String) {
+        self.shortcuts.push((chord, target));
     }
 
     pub fn hit_target(&self, x: i32, y: i32) -> Option<&HitTarget> {
-        for item in &self.items {
-            if x >= item.x
+        self.items.iter().find(|item| {
+            x >= item.x
                 && y >= item.y
                 && x < item.x + item.w as i32
                 && y < item.y + item.h as i32
-            {
-                return Some(item);
-            }
-        }
-        None
+        })
+    }
+
+    /// 按键对应的访问键目标；Latin-1 范围内的 keysym 就是字符本身
+    pub fn access_key_target(&self, keysym: u32) -> Option<&HitTarget> {
+        let key = char::from_u32(keysym).filter(char::is_ascii_alphanumeric)?.to_ascii_lowercase();
+        self.items.iter().find(|item| item.access_key == Some(key))
+    }
+
+    /// 按下的组合键对应的快捷键目标
+    pub fn shortcut_target(&self, key: &KeyEvent) -> Option<&HitTarget> {
+        let (_, id) = self.shortcuts.iter().find(|(chord, _)| chord.matches(key.keysym, key.ctrl, key.alt, key.shift))?;
+        self.items.iter().find(|item| &item.id == id)
     }
 }
@@ -1 +1,4 @@
-pub mod hit_test;
\ No newline at end of file
+pub mod form;
+pub mod hit_test;
+pub mod navigation;
+pub mod router;
\ No newline at end of file
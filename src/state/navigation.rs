@@ -0,0 +1,47 @@
+use tracing::debug;
+
+use crate::dsl::model::RenderEnvelope;
+
+/// 导航栈深度上限，超出时丢弃最早的画面
+const MAX_DEPTH: usize = 16;
+
+/// 画面在导航栈与面包屑中的名字：模型给出的 `screen`，缺省时是窗口标题
+pub fn name(render: &RenderEnvelope) -> String {
+    render.screen.clone().filter(|s| !s.trim().is_empty()).unwrap_or_else(|| render.window.title.clone())
+}
+
+/// 编排器维护的导航栈：`navigate:push` 按钮把当前画面压栈后照常请求模型，
+/// `navigate:pop` 按钮在本地弹出上一个画面立即显示，不请求模型。
+/// 与意图路由按 id 猜测的返回栈不同，这里只记录模型明确声明的层级
+#[derive(Default)]
+pub struct Navigation {
+    stack: Vec<RenderEnvelope>,
+}
+
+impl Navigation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, screen: &RenderEnvelope) {
+        if self.stack.len() == MAX_DEPTH {
+            self.stack.remove(0);
+        }
+        debug!("navigation push: {}", name(screen));
+        let mut screen = screen.clone();
+        screen.navigation.clear();
+        self.stack.push(screen);
+    }
+
+    /// 弹出上一个画面；栈为空时返回 None，点击照常交给模型
+    pub fn pop(&mut self) -> Option<RenderEnvelope> {
+        let screen = self.stack.pop()?;
+        debug!("navigation pop: back to {}", name(&screen));
+        Some(screen)
+    }
+
+    /// 把栈中画面的名字（最早的在前）填进即将显示的画面，生成提示词时随界面状态交给模型
+    pub fn fill(&self, render: &mut RenderEnvelope) {
+        render.navigation = self.stack.iter().map(name).collect();
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+
+use crate::config::{RouteRule, RouterConfig};
+use crate::dsl::model::{Command, RenderEnvelope};
+
+/// 可以在本地完成、无需调用模型的交互意图
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Intent {
+    /// 关闭对话框 / 取消 / 返回：恢复上一屏
+    Back,
+    /// 勾选框：切换 rect 的 `checked` 与按钮内文本里的 ☐/☑ 等字形
+    Toggle,
+    /// 切换标签页：同一目标之前生成过的画面直接复用
+    Revisit,
+}
+
+/// 内置规则：按常见的 id 命名约定识别意图，用户规则优先
+const BUILTIN_RULES: &[(&str, Intent)] = &[
+    ("close", Intent::Back),
+    ("close_*", Intent::Back),
+    ("*_close", Intent::Back),
+    ("cancel", Intent::Back),
+    ("*_cancel", Intent::Back),
+    ("dismiss*", Intent::Back),
+    ("back", Intent::Back),
+    ("*_back", Intent::Back),
+    ("checkbox_*", Intent::Toggle),
+    ("*_checkbox", Intent::Toggle),
+    ("chk_*", Intent::Toggle),
+    ("toggle_*", Intent::Toggle),
+    ("tab_*", Intent::Revisit),
+    ("*_tab", Intent::Revisit),
+];
+
+/// 勾选状态成对的字形，未选中在前
+const TOGGLE_GLYPHS: &[(&str, &str)] = &[("☐", "☑"), ("[ ]", "[x]"), ("□", "■"), ("○", "●")];
+
+/// 返回栈深度上限
+const MAX_BACK_STACK: usize = 16;
+
+/// 本地意图路由：把简单的点击映射为本地状态迁移，只有真正新的请求才交给大模型
+pub struct IntentRouter {
+    enabled: bool,
+    rules: Vec<RouteRule>,
+    use_builtin_rules: bool,
+    /// 之前显示过的画面，`Back` 时弹出
+    back_stack: Vec<RenderEnvelope>,
+    /// `Revisit` 目标 -> 点击它后模型生成的画面
+    visited: HashMap<String, RenderEnvelope>,
+}
+
+impl IntentRouter {
+    pub fn new(config: &RouterConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            rules: config.rules.clone(),
+            use_builtin_rules: config.use_builtin_rules,
+            back_stack: Vec::new(),
+            visited: HashMap::new(),
+        }
+    }
+
+    pub fn classify(&self, target_id: &str) -> Option<Intent> {
+        if !self.enabled {
+            return None;
+        }
+        if let Some(rule) = self.rules.iter().find(|r| matches_pattern(&r.target, target_id)) {
+            return Some(rule.intent);
+        }
+        if !self.use_builtin_rules {
+            return None;
+        }
+        BUILTIN_RULES
+            .iter()
+            .find(|(pattern, _)| matches_pattern(pattern, target_id))
+            .map(|(_, intent)| *intent)
+    }
+
+    /// 尝试在本地处理一次点击；返回 None 表示需要交给模型
+    pub fn route(&mut self, target_id: &str, current: &RenderEnvelope) -> Option<RenderEnvelope> {
+        // 声明了 `checked` 的勾选框是本地控件，不看路由规则，总在本地切换
+        let intent = if is_checkbox(current, target_id) { Intent::Toggle } else { self.classify(target_id)? };
+        let next = match intent {
+            Intent::Back => return self.back_stack.pop(),
+            Intent::Toggle => toggle(target_id, current)?,
+            Intent::Revisit => self.visited.get(target_id)?.clone(),
+        };
+        self.push_back(current.clone());
+        Some(next)
+    }
+
+    /// 模型生成的新画面上屏后调用；`trigger` 为触发它的点击目标（文本输入时为 None）
+    pub fn record(&mut self, trigger: Option<&str>, previous: &RenderEnvelope, next: &RenderEnvelope) {
+        if !self.enabled {
+            return;
+        }
+        self.push_back(previous.clone());
+        match trigger {
+            Some(id) if self.classify(id) == Some(Intent::Revisit) => {
+                self.visited.insert(id.to_string(), next.clone());
+            }
+            Some(_) => {}
+            // 文本指令可能改变整个应用，之前缓存的标签页不再可信
+            None => self.visited.clear(),
+        }
+    }
+
+    fn push_back(&mut self, screen: RenderEnvelope) {
+        if self.back_stack.len() == MAX_BACK_STACK {
+            self.back_stack.remove(0);
+        }
+        self.back_stack.push(screen);
+    }
+}
+
+/// 支持首尾 `*` 通配的简单匹配
+pub fn matches_pattern(pattern: &str, id: &str) -> bool {
+    match (pattern.strip_prefix('*'), pattern.strip_suffix('*')) {
+        (Some(_), Some(_)) if pattern.len() >= 2 => id.contains(&pattern[1..pattern.len() - 1]),
+        (Some(suffix), _) => id.ends_with(suffix),
+        (_, Some(prefix)) => id.starts_with(prefix),
+        _ => pattern == id,
+    }
+}
+
+/// 目标是声明了 `checked` 的 rect
+fn is_checkbox(render: &RenderEnvelope, target_id: &str) -> bool {
+    render.commands.iter().any(|c| matches!(c, Command::Rect { id: Some(id), checked: Some(_), .. } if id == target_id))
+}
+
+/// 切换目标的 `checked` 与按钮内文本的勾选字形；两者都没有时返回 None
+fn toggle(target_id: &str, current: &RenderEnvelope) -> Option<RenderEnvelope> {
+    let bounds = rect_bounds(current, target_id)?;
+    let mut next = current.clone();
+    let mut toggled = false;
+    for command in next.commands.iter_mut() {
+        if let Command::Rect { id: Some(id), checked: Some(checked), .. } = command {
+            if id == target_id {
+                *checked = !*checked;
+                toggled = true;
+            }
+            continue;
+        }
+        let Command::Text { x, y, text, .. } = command else {
+            continue;
+        };
+        if !inside(bounds, *x, *y) {
+            continue;
+        }
+        for (off, on) in TOGGLE_GLYPHS {
+            if let Some(pos) = text.find(off) {
+                text.replace_range(pos..pos + off.len(), on);
+                toggled = true;
+                break;
+            } else if let Some(pos) = text.find(on) {
+                text.replace_range(pos..pos + on.len(), off);
+                toggled = true;
+                break;
+            }
+        }
+    }
+    if !toggled {
+        return None;
+    }
+    next.seq += 1;
+    Some(next)
+}
+
+pub fn rect_bounds(render: &RenderEnvelope, target_id: &str) -> Option<(i32, i32, i32, i32)> {
+    render.commands.iter().find_map(|c| match c {
+        Command::Rect { id: Some(id), x, y, w, h, .. } if id == target_id => Some((*x, *y, *w as i32, *h as i32)),
+        _ => None,
+    })
+}
+
+/// 文本坐标落在按钮内（留出一行字高的余量）
+pub fn inside((rx, ry, rw, rh): (i32, i32, i32, i32), x: i32, y: i32) -> bool {
+    x >= rx && x <= rx + rw && y >= ry && y <= ry + rh + 24
+}
@@ -0,0 +1,180 @@
+//! 启动时收集的系统信息（语言区域、时区、屏幕尺寸与 DPI、可用字体），压缩成一行写进系统提示词，
+//! 让生成的界面直接使用用户的语言、单位与尺寸。
+
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::info;
+
+use crate::x11::backend;
+
+/// 使用英制单位的地区
+const IMPERIAL_REGIONS: &[&str] = &["US", "LR", "MM"];
+/// 习惯 12 小时制的地区
+const TWELVE_HOUR_REGIONS: &[&str] = &["US", "CA", "AU", "NZ", "IN", "PH", "PK", "BD", "EG", "SA"];
+/// 日期按年-月-日书写的地区（美国另用月/日/年，其余按日/月/年）
+const YMD_REGIONS: &[&str] = &["CN", "TW", "HK", "JP", "KR", "HU", "LT", "SE"];
+
+/// 用户的语言区域，由 `LC_ALL`、`LC_MESSAGES`、`LANG` 依次决定
+#[derive(Debug, Clone)]
+pub struct Locale {
+    /// 语言代码，如 `zh`；未设置时为 `en`
+    pub language: String,
+    /// 地区代码，如 `CN`；未设置时为空
+    pub region: String,
+}
+
+impl Locale {
+    /// BCP 47 风格的标签，如 `zh-CN`
+    pub fn tag(&self) -> String {
+        if self.region.is_empty() {
+            self.language.clone()
+        } else {
+            format!("{}-{}", self.language, self.region)
+        }
+    }
+
+    pub fn metric(&self) -> bool {
+        !IMPERIAL_REGIONS.contains(&self.region.as_str())
+    }
+
+    pub fn twelve_hour(&self) -> bool {
+        TWELVE_HOUR_REGIONS.contains(&self.region.as_str())
+    }
+
+    /// 日期各部分的顺序：`Y-M-D`、`M/D/Y` 或 `D/M/Y`
+    pub fn date_order(&self) -> &'static str {
+        match self.region.as_str() {
+            "US" => "M/D/Y",
+            region if YMD_REGIONS.contains(&region) => "Y-M-D",
+            _ => "D/M/Y",
+        }
+    }
+}
+
+/// 启动时收集一次的系统信息
+#[derive(Debug, Clone)]
+pub struct SystemInfo {
+    pub locale: Locale,
+    /// IANA 时区名，如 `Asia/Shanghai`；无法确定时为 None
+    pub timezone: Option<String>,
+    /// 与 UTC 的偏移（秒）
+    pub utc_offset_secs: i64,
+    /// 根窗口（整块屏幕）的像素尺寸；没有 X11 显示面时为 None
+    pub screen: Option<(u32, u32)>,
+    pub dpi: Option<f32>,
+    pub fonts: Vec<String>,
+}
+
+static INFO: OnceLock<SystemInfo> = OnceLock::new();
+
+/// 打开显示面后调用一次，记下屏幕信息；之后的调用不再生效
+pub fn init(screen: Option<(u32, u32)>, dpi: Option<f32>) {
+    let info = INFO.get_or_init(|| gather(screen, dpi));
+    info!("system info: {}", summary(info));
+}
+
+/// 收集到的系统信息；未调用 `init` 时（preview、lint 等）不含屏幕信息
+pub fn get() -> &'static SystemInfo {
+    INFO.get_or_init(|| gather(None, None))
+}
+
+/// 给系统提示词的一行摘要
+pub fn describe() -> String {
+    summary(get())
+}
+
+fn summary(info: &SystemInfo) -> String {
+    let locale = &info.locale;
+    let mut parts = vec![format!(
+        "locale {} ({} units, {}-hour clock, dates {})",
+        locale.tag(),
+        if locale.metric() { "metric" } else { "imperial" },
+        if locale.twelve_hour() { 12 } else { 24 },
+        locale.date_order()
+    )];
+    let offset = format_offset(info.utc_offset_secs);
+    parts.push(match &info.timezone {
+        Some(zone) => format!("time zone {} ({})", zone, offset),
+        None => format!("time zone {}", offset),
+    });
+    parts.push(match (info.screen, info.dpi) {
+        (Some((w, h)), Some(dpi)) => format!("screen {}x{} px at {:.0} DPI", w, h, dpi),
+        (Some((w, h)), None) => format!("screen {}x{} px", w, h),
+        _ => "screen unknown".to_string(),
+    });
+    if info.fonts.is_empty() {
+        parts.push("fonts: built-in fallback only".to_string());
+    } else {
+        parts.push(format!("fonts: {}", info.fonts.join(", ")));
+    }
+    parts.join("; ")
+}
+
+fn gather(screen: Option<(u32, u32)>, dpi: Option<f32>) -> SystemInfo {
+    SystemInfo {
+        locale: detect_locale(),
+        timezone: detect_timezone(),
+        utc_offset_secs: utc_offset_at(now_secs()),
+        screen,
+        dpi,
+        fonts: backend::available_font_names(),
+    }
+}
+
+fn detect_locale() -> Locale {
+    let raw = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    parse_locale(&raw)
+}
+
+/// `zh_CN.UTF-8@latin` -> zh / CN；`C`、`POSIX` 与空值视为未设置
+fn parse_locale(raw: &str) -> Locale {
+    let base = raw.split(['.', '@']).next().unwrap_or("");
+    if base.is_empty() || base == "C" || base == "POSIX" {
+        return Locale { language: "en".to_string(), region: String::new() };
+    }
+    let mut parts = base.split(['_', '-']);
+    let language = parts.next().unwrap_or("en").to_ascii_lowercase();
+    let region = parts.next().unwrap_or("").to_ascii_uppercase();
+    Locale { language, region }
+}
+
+/// `TZ`，其次 `/etc/timezone`，最后 `/etc/localtime` 链接指向的 zoneinfo 文件
+fn detect_timezone() -> Option<String> {
+    if let Some(zone) = std::env::var("TZ").ok().map(|tz| tz.trim_start_matches(':').to_string()).filter(|tz| !tz.is_empty()) {
+        return Some(zone);
+    }
+    if let Some(zone) = std::fs::read_to_string("/etc/timezone").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()) {
+        return Some(zone);
+    }
+    let target = std::fs::read_link("/etc/localtime").ok()?;
+    let target = target.to_string_lossy();
+    target.split_once("zoneinfo/").map(|(_, zone)| zone.to_string())
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// 本地时区在某个 Unix 时间戳时与 UTC 的偏移（秒）
+pub fn utc_offset_at(timestamp: i64) -> i64 {
+    // localtime_r 按 TZ 与系统时区数据换算，tm_gmtoff 即该时刻（含夏令时）的偏移
+    unsafe {
+        let time = timestamp as libc::time_t;
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return 0;
+        }
+        tm.tm_gmtoff as i64
+    }
+}
+
+fn format_offset(secs: i64) -> String {
+    let sign = if secs < 0 { '-' } else { '+' };
+    let minutes = secs.abs() / 60;
+    format!("UTC{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
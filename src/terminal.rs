@@ -0,0 +1,616 @@
+//! `terminal` 命令：在伪终端里运行 `run.commands` 白名单中的命令，输出实时画进界面中的一块区域。
+//!
+//! 每个终端按 id 对应一个会话：界面更新后仍在的终端（id 与命令都不变）继续运行，消失的终端连同进程组一起结束。
+//! 输出经一个精简的 VT100 解析器写进字符网格（光标移动、擦除、回车换行与滚屏；颜色等属性被忽略），
+//! 编排器把网格内容填进 terminal 命令再绘制。点击终端让它获得键盘焦点，之后的按键（截图键除外）都写进伪终端，
+//! 点击别处取消焦点。
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use tracing::{debug, info, warn};
+
+use crate::display::KeyEvent;
+use crate::dsl::model::{Command, RenderEnvelope};
+use crate::runner;
+
+/// 未给出 `font_size` 时的字号
+pub const DEFAULT_FONT_SIZE: f32 = 14.0;
+pub const MIN_FONT_SIZE: f32 = 8.0;
+pub const MAX_FONT_SIZE: f32 = 48.0;
+/// 终端至少要放得下的列数与行数
+pub const MIN_COLS: u16 = 10;
+pub const MIN_ROWS: u16 = 2;
+/// 网格上限，防止超大区域分配过多格子
+const MAX_COLS: u16 = 400;
+const MAX_ROWS: u16 = 200;
+/// 网格与区域边缘的间距（像素）
+pub const PADDING: i32 = 4;
+/// 写给子进程的 `TERM`：解析器实现的是 VT100 的常用子集
+const TERM: &str = "vt100";
+
+/// 一个字符格的宽度与行高（像素）
+pub fn cell_size(font_size: Option<f32>) -> (f32, i32) {
+    let size = font_size.unwrap_or(DEFAULT_FONT_SIZE);
+    (size * 0.6, (size * 1.25).ceil() as i32)
+}
+
+/// 区域（去掉四周的间距）能放下的列数与行数
+pub fn grid_size(w: u32, h: u32, font_size: Option<f32>) -> (u16, u16) {
+    let (cell_w, line_h) = cell_size(font_size);
+    let (w, h) = ((w as i32 - 2 * PADDING).max(0), (h as i32 - 2 * PADDING).max(0));
+    let cols = (w as f32 / cell_w).floor().min(MAX_COLS as f32) as u16;
+    let rows = (h / line_h.max(1)).min(MAX_ROWS as i32) as u16;
+    (cols, rows)
+}
+
+/// 字符占的格数：CJK 与全角字符占两格
+pub fn char_cells(ch: char) -> usize {
+    match ch as u32 {
+        0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 => 2,
+        0x1F300..=0x1FAFF | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// 转义序列的解析状态
+enum Parse {
+    Ground,
+    Escape,
+    /// CSI（`ESC [`）的参数与中间字符
+    Csi(String),
+    /// OSC（`ESC ]`，设置标题等），直到 BEL 或 `ESC \`
+    Osc,
+    OscEscape,
+    /// `ESC (`、`ESC )` 等后面还跟一个字符
+    Charset,
+}
+
+/// 字符网格与光标
+struct Screen {
+    cols: usize,
+    rows: usize,
+    /// 全角字符后面的一格存 `'\0'` 占位
+    cells: Vec<Vec<char>>,
+    row: usize,
+    col: usize,
+    /// 写满最后一列后，下一个字符才换行（VT100 的延迟折行）
+    wrap_pending: bool,
+    saved: (usize, usize),
+    parse: Parse,
+    /// 跨两次读取被截断的 UTF-8 字节
+    partial: Vec<u8>,
+}
+
+impl Screen {
+    fn new(cols: u16, rows: u16) -> Self {
+        let (cols, rows) = (cols.max(1) as usize, rows.max(1) as usize);
+        Self {
+            cols,
+            rows,
+            cells: vec![vec![' '; cols]; rows],
+            row: 0,
+            col: 0,
+            wrap_pending: false,
+            saved: (0, 0),
+            parse: Parse::Ground,
+            partial: Vec::new(),
+        }
+    }
+
+    /// 改变网格大小：保留左上角的内容，光标收进新范围
+    fn resize(&mut self, cols: u16, rows: u16) {
+        let (cols, rows) = (cols.max(1) as usize, rows.max(1) as usize);
+        // 行数变少时丢掉顶部的行，保留靠近光标的最新输出
+        if self.row >= rows {
+            let drop = self.row + 1 - rows;
+            self.cells.drain(..drop);
+            self.row -= drop;
+            self.saved.0 = self.saved.0.saturating_sub(drop);
+        }
+        self.cells.resize(rows, vec![' '; cols]);
+        for line in &mut self.cells {
+            line.resize(cols, ' ');
+        }
+        self.cols = cols;
+        self.rows = rows;
+        self.col = self.col.min(cols - 1);
+        self.saved = (self.saved.0.min(rows - 1), self.saved.1.min(cols - 1));
+        self.wrap_pending = false;
+    }
+
+    /// 恢复保存的光标（`ESC 8`、`CSI u`），收进当前网格
+    fn restore_cursor(&mut self) {
+        self.row = self.saved.0.min(self.rows - 1);
+        self.col = self.saved.1.min(self.cols - 1);
+        self.wrap_pending = false;
+    }
+
+    fn feed(&mut self, bytes: &[u8]) {
+        let mut data = std::mem::take(&mut self.partial);
+        data.extend_from_slice(bytes);
+        let valid = match std::str::from_utf8(&data) {
+            Ok(text) => text.len(),
+            // 末尾是不完整的多字节字符时留到下一次，其余非法字节按替换字符处理
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => data.len(),
+        };
+        self.partial = data.split_off(valid);
+        for ch in String::from_utf8_lossy(&data).chars() {
+            self.step(ch);
+        }
+    }
+
+    fn step(&mut self, ch: char) {
+        match std::mem::replace(&mut self.parse, Parse::Ground) {
+            Parse::Ground => self.ground(ch),
+            Parse::Escape => match ch {
+                '[' => self.parse = Parse::Csi(String::new()),
+                ']' => self.parse = Parse::Osc,
+                '(' | ')' | '#' => self.parse = Parse::Charset,
+                '7' => self.saved = (self.row, self.col),
+                '8' => self.restore_cursor(),
+                'D' => self.line_feed(),
+                'E' => {
+                    self.col = 0;
+                    self.line_feed();
+                }
+                'M' => self.reverse_index(),
+                'c' => *self = Screen::new(self.cols as u16, self.rows as u16),
+                _ => {}
+            },
+            Parse::Csi(mut params) => {
+                if ('@'..='~').contains(&ch) {
+                    self.csi(&params, ch);
+                } else {
+                    params.push(ch);
+                    self.parse = Parse::Csi(params);
+                }
+            }
+            Parse::Osc => match ch {
+                '\u{7}' => {}
+                '\u{1b}' => self.parse = Parse::OscEscape,
+                _ => self.parse = Parse::Osc,
+            },
+            Parse::OscEscape => {
+                if ch != '\\' {
+                    self.parse = Parse::Osc;
+                }
+            }
+            Parse::Charset => {}
+        }
+    }
+
+    fn ground(&mut self, ch: char) {
+        match ch {
+            '\u{1b}' => self.parse = Parse::Escape,
+            '\r' => {
+                self.col = 0;
+                self.wrap_pending = false;
+            }
+            '\n' | '\u{b}' | '\u{c}' => self.line_feed(),
+            '\u{8}' => {
+                self.col = self.col.saturating_sub(1);
+                self.wrap_pending = false;
+            }
+            '\t' => {
+                self.col = ((self.col / 8 + 1) * 8).min(self.cols - 1);
+            }
+            ch if ch.is_control() => {}
+            ch => self.put(ch),
+        }
+    }
+
+    fn put(&mut self, ch: char) {
+        let width = char_cells(ch).min(self.cols);
+        if self.wrap_pending || self.col + width > self.cols {
+            self.col = 0;
+            self.line_feed();
+        }
+        let line = &mut self.cells[self.row];
+        line[self.col] = ch;
+        if width == 2 {
+            line[self.col + 1] = '\0';
+        }
+        if self.col + width >= self.cols {
+            self.col = self.cols - 1;
+            self.wrap_pending = true;
+        } else {
+            self.col += width;
+        }
+    }
+
+    fn line_feed(&mut self) {
+        self.wrap_pending = false;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+        } else {
+            self.cells.remove(0);
+            self.cells.push(vec![' '; self.cols]);
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        if self.row > 0 {
+            self.row -= 1;
+        } else {
+            self.cells.pop();
+            self.cells.insert(0, vec![' '; self.cols]);
+        }
+    }
+
+    fn csi(&mut self, params: &str, action: char) {
+        // `?` 开头的私有模式（备用屏幕、光标显隐等）不影响网格内容
+        if params.starts_with('?') {
+            return;
+        }
+        let args: Vec<usize> = params.split(';').map(|p| p.parse().unwrap_or(0)).collect();
+        let arg = |i: usize, default: usize| args.get(i).copied().filter(|&v| v != 0).unwrap_or(default);
+        self.wrap_pending = false;
+        match action {
+            'A' => self.row = self.row.saturating_sub(arg(0, 1)),
+            'B' => self.row = (self.row + arg(0, 1)).min(self.rows - 1),
+            'C' => self.col = (self.col + arg(0, 1)).min(self.cols - 1),
+            'D' => self.col = self.col.saturating_sub(arg(0, 1)),
+            'E' => {
+                self.row = (self.row + arg(0, 1)).min(self.rows - 1);
+                self.col = 0;
+            }
+            'F' => {
+                self.row = self.row.saturating_sub(arg(0, 1));
+                self.col = 0;
+            }
+            'G' | '`' => self.col = (arg(0, 1) - 1).min(self.cols - 1),
+            'd' => self.row = (arg(0, 1) - 1).min(self.rows - 1),
+            'H' | 'f' => {
+                self.row = (arg(0, 1) - 1).min(self.rows - 1);
+                self.col = (arg(1, 1) - 1).min(self.cols - 1);
+            }
+            'J' => {
+                let (row, col) = (self.row, self.col);
+                match args.first().copied().unwrap_or(0) {
+                    0 => {
+                        self.clear_line(row, col, self.cols);
+                        (row + 1..self.rows).for_each(|r| self.clear_line(r, 0, self.cols));
+                    }
+                    1 => {
+                        (0..row).for_each(|r| self.clear_line(r, 0, self.cols));
+                        self.clear_line(row, 0, col + 1);
+                    }
+                    _ => (0..self.rows).for_each(|r| self.clear_line(r, 0, self.cols)),
+                }
+            }
+            'K' => {
+                let (row, col) = (self.row, self.col);
+                match args.first().copied().unwrap_or(0) {
+                    0 => self.clear_line(row, col, self.cols),
+                    1 => self.clear_line(row, 0, col + 1),
+                    _ => self.clear_line(row, 0, self.cols),
+                }
+            }
+            'X' => self.clear_line(self.row, self.col, self.col + arg(0, 1)),
+            'P' => {
+                let (col, n) = (self.col, arg(0, 1).min(self.cols - self.col));
+                let line = &mut self.cells[self.row];
+                line.drain(col..col + n);
+                line.resize(self.cols, ' ');
+            }
+            '@' => {
+                let (col, n) = (self.col, arg(0, 1).min(self.cols - self.col));
+                let line = &mut self.cells[self.row];
+                line.splice(col..col, std::iter::repeat_n(' ', n));
+                line.truncate(self.cols);
+            }
+            'L' => {
+                for _ in 0..arg(0, 1).min(self.rows - self.row) {
+                    self.cells.pop();
+                    self.cells.insert(self.row, vec![' '; self.cols]);
+                }
+            }
+            'M' => {
+                for _ in 0..arg(0, 1).min(self.rows - self.row) {
+                    self.cells.remove(self.row);
+                    self.cells.push(vec![' '; self.cols]);
+                }
+            }
+            's' => self.saved = (self.row, self.col),
+            'u' => self.restore_cursor(),
+            // 颜色与字体属性（m）等其余序列忽略
+            _ => {}
+        }
+    }
+
+    fn clear_line(&mut self, row: usize, from: usize, to: usize) {
+        let line = &mut self.cells[row];
+        for cell in &mut line[from.min(self.cols)..to.min(self.cols)] {
+            *cell = ' ';
+        }
+    }
+
+    /// 每行一个字符串（去掉行尾空格与全角占位）；末尾的空行不保留
+    fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.cells.iter().map(|line| line.iter().filter(|&&c| c != '\0').collect::<String>().trim_end().to_string()).collect();
+        while lines.len() > self.row + 1 && lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        lines
+    }
+
+    /// 光标所在的（行, 列）；列按格数计，与绘制时的网格一致
+    fn cursor(&self) -> (u16, u16) {
+        (self.row as u16, self.col as u16)
+    }
+}
+
+/// 一个运行中的终端会话
+struct Session {
+    run: String,
+    size: (u16, u16),
+    master: File,
+    child: Child,
+    screen: Arc<Mutex<Screen>>,
+    dirty: Arc<AtomicBool>,
+}
+
+impl Session {
+    fn spawn(name: &str, (cols, rows): (u16, u16)) -> Result<Self, Box<dyn Error>> {
+        let mut command = runner::command(name)?;
+        let winsize = libc::winsize { ws_row: rows, ws_col: cols, ws_xpixel: 0, ws_ypixel: 0 };
+        let (mut master_fd, mut slave_fd) = (0, 0);
+        // SAFETY: openpty 只写入两个 fd；成功后两个 fd 都交给 File 管理
+        let (master, slave) = unsafe {
+            if libc::openpty(&mut master_fd, &mut slave_fd, std::ptr::null_mut(), std::ptr::null(), &winsize) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            libc::fcntl(master_fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            libc::fcntl(slave_fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            (File::from_raw_fd(master_fd), File::from_raw_fd(slave_fd))
+        };
+        command
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave))
+            .env("TERM", TERM)
+            .env("COLUMNS", cols.to_string())
+            .env("LINES", rows.to_string());
+        // 子进程自成一个会话并把伪终端设为控制终端，Ctrl+C 等信号由伪终端发给它的前台进程组
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = command.spawn()?;
+        // 命令里还持有伪终端从端的副本，释放后子进程退出时读端才会收到 EOF
+        drop(command);
+        info!("Terminal {} started (pid {}, {}x{})", name, child.id(), cols, rows);
+
+        let screen = Arc::new(Mutex::new(Screen::new(cols, rows)));
+        let dirty = Arc::new(AtomicBool::new(true));
+        let mut reader = master.try_clone()?;
+        let (shared, flag, label) = (screen.clone(), dirty.clone(), name.to_string());
+        thread::spawn(move || {
+            let mut chunk = [0u8; 4096];
+            // 子进程退出、从端全部关闭后读端返回 EIO
+            while let Ok(n) = reader.read(&mut chunk) {
+                if n == 0 {
+                    break;
+                }
+                shared.lock().unwrap().feed(&chunk[..n]);
+                flag.store(true, Ordering::SeqCst);
+            }
+            debug!("terminal {} output closed", label);
+            shared.lock().unwrap().feed(b"\r\n[process exited]");
+            flag.store(true, Ordering::SeqCst);
+        });
+        Ok(Self { run: name.to_string(), size: (cols, rows), master, child, screen, dirty })
+    }
+
+    fn resize(&mut self, (cols, rows): (u16, u16)) {
+        let winsize = libc::winsize { ws_row: rows, ws_col: cols, ws_xpixel: 0, ws_ypixel: 0 };
+        // 内核随后给前台进程组发 SIGWINCH
+        unsafe {
+            libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &winsize);
+        }
+        self.screen.lock().unwrap().resize(cols, rows);
+        self.size = (cols, rows);
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+}
+
+/// 界面移除终端或会话结束时结束整个进程组
+impl Drop for Session {
+    fn drop(&mut self) {
+        unsafe {
+            libc::kill(-(self.child.id() as i32), libc::SIGKILL);
+        }
+        let _ = self.child.wait();
+        debug!("terminal {} stopped", self.run);
+    }
+}
+
+/// 当前界面上的终端会话与键盘焦点
+#[derive(Default)]
+pub struct Terminals {
+    sessions: HashMap<String, Session>,
+    /// 启动失败的 (id, 命令)，界面不变时不再反复重试
+    failed: HashSet<(String, String)>,
+    focused: Option<String>,
+}
+
+impl Terminals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按界面中的 terminal 命令启动、调整或结束会话
+    pub fn sync(&mut self, render: &RenderEnvelope) {
+        let mut wanted = HashSet::new();
+        for command in &render.commands {
+            let Command::Terminal { id, w, h, run, font_size, .. } = command else {
+                continue;
+            };
+            wanted.insert(id.as_str());
+            let size = grid_size(*w, *h, *font_size);
+            match self.sessions.get_mut(id) {
+                Some(session) if session.run == *run => {
+                    if session.size != size {
+                        session.resize(size);
+                    }
+                    continue;
+                }
+                Some(_) => {
+                    self.sessions.remove(id);
+                }
+                None => {}
+            }
+            let key = (id.clone(), run.clone());
+            if self.failed.contains(&key) {
+                continue;
+            }
+            match Session::spawn(run, size) {
+                Ok(session) => {
+                    self.sessions.insert(id.clone(), session);
+                }
+                Err(e) => {
+                    warn!("terminal {}: failed to start {}: {}", id, run, e);
+                    self.failed.insert(key);
+                }
+            }
+        }
+        self.sessions.retain(|id, _| wanted.contains(id.as_str()));
+        self.failed.retain(|(id, _)| wanted.contains(id.as_str()));
+        if self.focused.as_deref().is_some_and(|id| !wanted.contains(id)) {
+            self.focused = None;
+        }
+    }
+
+    /// 有会话产生了新输出
+    pub fn has_output(&self) -> bool {
+        self.sessions.values().any(|s| s.dirty.load(Ordering::SeqCst))
+    }
+
+    /// 把各会话的屏幕内容与光标填进界面；内容有变化时返回 true
+    pub fn fill(&self, render: &mut RenderEnvelope) -> bool {
+        let mut changed = false;
+        for command in render.commands.iter_mut() {
+            let Command::Terminal { id, lines, cursor, .. } = command else {
+                continue;
+            };
+            let Some(session) = self.sessions.get(id) else {
+                continue;
+            };
+            session.dirty.store(false, Ordering::SeqCst);
+            let screen = session.screen.lock().unwrap();
+            let next_lines = screen.lines();
+            let next_cursor = (self.focused.as_deref() == Some(id.as_str())).then(|| screen.cursor());
+            if *lines != next_lines || *cursor != next_cursor {
+                *lines = next_lines;
+                *cursor = next_cursor;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// 点击落在终端上时让它获得焦点并返回 true；点在别处取消焦点
+    pub fn focus_at(&mut self, render: &RenderEnvelope, x: i32, y: i32) -> bool {
+        let hit = render.commands.iter().rev().find_map(|c| match c {
+            Command::Terminal { id, x: tx, y: ty, w, h, .. } if x >= *tx && y >= *ty && x < tx + *w as i32 && y < ty + *h as i32 => Some(id.clone()),
+            _ => None,
+        });
+        let hit = hit.filter(|id| self.sessions.contains_key(id));
+        self.focused = hit.clone();
+        hit.is_some()
+    }
+
+    /// 有焦点时把按键写进对应的伪终端；返回按键是否被终端接收
+    pub fn send_key(&mut self, key: &KeyEvent) -> bool {
+        let Some(session) = self.focused.as_ref().and_then(|id| self.sessions.get_mut(id)) else {
+            return false;
+        };
+        if let Some(bytes) = key_bytes(key) {
+            if let Err(e) = session.master.write_all(&bytes) {
+                warn!("terminal {}: {}", session.run, e);
+            }
+        }
+        true
+    }
+}
+
+/// 各终端当前的输出（去掉末尾空行），写进给模型的状态快照
+pub fn snapshot(render: &RenderEnvelope) -> HashMap<String, String> {
+    render
+        .commands
+        .iter()
+        .filter_map(|c| match c {
+            Command::Terminal { id, lines, .. } => Some((id.clone(), lines.join("\n").trim_end().to_string())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 按键对应写给终端的字节：可打印字符按 UTF-8，Ctrl+字母为控制字符，Alt 前置 ESC，方向键等用 VT100 序列
+fn key_bytes(key: &KeyEvent) -> Option<Vec<u8>> {
+    let named: Option<&[u8]> = match key.keysym {
+        0xFF0D | 0xFF8D => Some(b"\r"),
+        0xFF08 => Some(b"\x7f"),
+        0xFF09 => Some(b"\t"),
+        0xFF1B => Some(b"\x1b"),
+        0xFF51 => Some(b"\x1b[D"),
+        0xFF52 => Some(b"\x1b[A"),
+        0xFF53 => Some(b"\x1b[C"),
+        0xFF54 => Some(b"\x1b[B"),
+        0xFF50 => Some(b"\x1b[H"),
+        0xFF57 => Some(b"\x1b[F"),
+        0xFF55 => Some(b"\x1b[5~"),
+        0xFF56 => Some(b"\x1b[6~"),
+        0xFFFF => Some(b"\x1b[3~"),
+        _ => None,
+    };
+    let mut bytes = match named {
+        Some(bytes) => bytes.to_vec(),
+        None => {
+            // Latin-1 范围的 keysym 就是字符码，其余 Unicode 字符的 keysym 为 0x01000000 + 码位
+            let code = match key.keysym {
+                k @ 0x20..=0xFF => k,
+                k @ 0x0100_0100..=0x0110_FFFF => k - 0x0100_0000,
+                _ => return None,
+            };
+            let ch = char::from_u32(code)?;
+            if key.ctrl && ch.is_ascii_alphabetic() {
+                vec![ch.to_ascii_lowercase() as u8 & 0x1f]
+            } else {
+                ch.to_string().into_bytes()
+            }
+        }
+    };
+    if key.alt {
+        bytes.insert(0, 0x1b);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restored_cursor_stays_inside_shrunken_grid() {
+        let mut screen = Screen::new(20, 10);
+        screen.feed(b"\x1b[9;15H\x1b7");
+        screen.resize(6, 4);
+        screen.feed(b"\x1b8x\x1b[uy");
+        assert!(screen.row < 4 && screen.col < 6);
+    }
+}
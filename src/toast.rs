@@ -0,0 +1,162 @@
+//! `toast` 命令：画在窗口角落的提示条。窗口在本地计时，到 `duration_ms` 后把它从当前画面移除并重画，
+//! 模型不必为了撤掉一条“已保存”再生成一次界面。
+//!
+//! 同一角落的多条提示按命令顺序堆叠：第一条贴着角落，后面的依次向窗口内侧排开，前面的消失后其余的补位。
+//! 提示按 id（没有 id 时按文字）跨画面识别：模型在新画面里重复同一条提示不会重新计时，
+//! 已经消失或被点掉的提示也不会因此再出现。
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::dsl::builder::Render;
+use crate::dsl::model::{Command, RenderEnvelope};
+
+pub const KINDS: &[&str] = &["info", "success", "warning", "error"];
+pub const POSITIONS: &[&str] = &["top-right", "top-left", "bottom-right", "bottom-left"];
+pub const DEFAULT_DURATION_MS: u64 = 4000;
+pub const MIN_DURATION_MS: u64 = 1000;
+pub const MAX_DURATION_MS: u64 = 60_000;
+/// 提示条的宽度上限与高度；窗口较窄时宽度随窗口收缩
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 48;
+/// 与窗口边缘、相邻提示条之间的间距
+const MARGIN: i32 = 16;
+const GAP: i32 = 8;
+/// 左侧色条的宽度、色条与边框的间距，以及文字的左右内边距
+const ACCENT_WIDTH: u32 = 4;
+const ACCENT_INSET: i32 = 10;
+const PADDING: i32 = 12;
+
+/// 跨画面识别提示的键：id，没有 id 时是文字
+fn key(id: &Option<String>, text: &str) -> String {
+    id.clone().unwrap_or_else(|| format!("text:{}", text))
+}
+
+/// 按角落与堆叠次序给每条提示排好位置
+pub fn layout(render: &mut RenderEnvelope) {
+    let (width, height) = (render.window.width, render.window.height);
+    let w = WIDTH.min(width.saturating_sub(2 * MARGIN as u32)).max(1);
+    let mut stacked: HashMap<&'static str, i32> = HashMap::new();
+    for command in render.commands.iter_mut() {
+        let Command::Toast { position, frame, .. } = command else {
+            continue;
+        };
+        let corner = POSITIONS.iter().copied().find(|p| Some(*p) == position.as_deref()).unwrap_or("top-right");
+        let slot = stacked.entry(corner).or_insert(0);
+        let offset = MARGIN + *slot * (HEIGHT as i32 + GAP);
+        *slot += 1;
+        let x = if corner.ends_with("left") { MARGIN } else { width as i32 - MARGIN - w as i32 };
+        let y = if corner.starts_with("top") { offset } else { height as i32 - offset - HEIGHT as i32 };
+        *frame = Some((x, y, w, HEIGHT));
+    }
+}
+
+/// 把提示条换成圆角矩形、色条与文字交给光栅化器，画在其余命令之上；没有提示时原样借用
+pub fn expand(render: &RenderEnvelope) -> Cow<'_, RenderEnvelope> {
+    if !render.commands.iter().any(|c| matches!(c, Command::Toast { .. })) {
+        return Cow::Borrowed(render);
+    }
+    let (toasts, rest): (Vec<Command>, Vec<Command>) = render.commands.iter().cloned().partition(|c| matches!(c, Command::Toast { .. }));
+    let mut base = render.clone();
+    base.commands = rest;
+    let mut out = Render::from(base);
+    for toast in toasts {
+        let Command::Toast { text, kind, frame: Some((x, y, w, h)), .. } = toast else {
+            continue;
+        };
+        let accent = match kind.as_deref() {
+            Some("success") => "@success",
+            Some("warning") => "@accent",
+            Some("error") => "@danger",
+            _ => "@primary",
+        };
+        let text_x = x + ACCENT_INSET + ACCENT_WIDTH as i32 + PADDING;
+        let text_w = (w as i32 - (text_x - x) - PADDING).max(1) as u32;
+        out = out
+            .round_rect(x, y, w, h, 8, "@surface")
+            .stroke("@border", 1)
+            .rect(x + ACCENT_INSET, y + ACCENT_INSET, ACCENT_WIDTH, h.saturating_sub(2 * ACCENT_INSET as u32), accent)
+            .text(text_x, y + 10, text, "@text")
+            .fit(text_w, h.saturating_sub(12), "ellipsis");
+    }
+    Cow::Owned(out.build())
+}
+
+/// 编排器持有的提示计时：每条提示第一次出现的时刻，以及已经移除、不再显示的提示
+#[derive(Default)]
+pub struct Toasts {
+    shown: HashMap<String, (Instant, Duration)>,
+    gone: HashSet<String>,
+}
+
+impl Toasts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 新画面到达：开始给新出现的提示计时，去掉已经消失过的提示并重新排位
+    pub fn sync(&mut self, render: &mut RenderEnvelope) {
+        let mut present = HashSet::new();
+        for command in &render.commands {
+            if let Command::Toast { id, text, duration_ms, .. } = command {
+                let key = key(id, text);
+                if !self.gone.contains(&key) {
+                    let duration = Duration::from_millis(duration_ms.unwrap_or(DEFAULT_DURATION_MS));
+                    self.shown.entry(key.clone()).or_insert((Instant::now(), duration));
+                }
+                present.insert(key);
+            }
+        }
+        // 模型不再给出的提示忘掉，以后再给出时当作新提示
+        self.shown.retain(|key, _| present.contains(key));
+        self.gone.retain(|key| present.contains(key));
+        self.remove_gone(render);
+    }
+
+    /// 移除到期的提示；画面有变化时返回 true
+    pub fn expire(&mut self, render: &mut RenderEnvelope) -> bool {
+        let now = Instant::now();
+        let expired: Vec<String> = self.shown.iter().filter(|(_, (since, duration))| now >= *since + *duration).map(|(key, _)| key.clone()).collect();
+        if expired.is_empty() {
+            return false;
+        }
+        for key in expired {
+            debug!("toast {} expired", key);
+            self.shown.remove(&key);
+            self.gone.insert(key);
+        }
+        self.remove_gone(render)
+    }
+
+    /// 点击落在提示条上时关闭它并返回 true
+    pub fn dismiss_at(&mut self, render: &mut RenderEnvelope, x: i32, y: i32) -> bool {
+        let hit = render.commands.iter().rev().find_map(|c| match c {
+            Command::Toast { id, text, frame: Some((tx, ty, w, h)), .. } if x >= *tx && y >= *ty && x < tx + *w as i32 && y < ty + *h as i32 => {
+                Some(key(id, text))
+            }
+            _ => None,
+        });
+        let Some(key) = hit else {
+            return false;
+        };
+        debug!("toast {} dismissed", key);
+        self.shown.remove(&key);
+        self.gone.insert(key);
+        self.remove_gone(render)
+    }
+
+    /// 最早到期的提示的时刻，供事件循环安排等待
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.shown.values().map(|(since, duration)| *since + *duration).min()
+    }
+
+    fn remove_gone(&self, render: &mut RenderEnvelope) -> bool {
+        let before = render.commands.len();
+        render.commands.retain(|c| !matches!(c, Command::Toast { id, text, .. } if self.gone.contains(&key(id, text))));
+        layout(render);
+        render.commands.len() != before
+    }
+}
@@ -0,0 +1,266 @@
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+
+use crate::config;
+use crate::dsl::diff::{self, ChangeKind};
+use crate::dsl::model::{Command, RenderEnvelope};
+use crate::x11::backend::X11Backend;
+use crate::x11::buffer_pool::FrameBuffer;
+use crate::x11::renderer::{self, Region};
+
+/// 出现 / 消失动画的总时长与帧数
+const ANIMATION_DURATION: Duration = Duration::from_millis(220);
+const ANIMATION_STEPS: u32 = 12;
+/// pop 回弹的幅度（ease-out-back 的常数），缩放最大约到 1.1 倍
+const POP_OVERSHOOT: f32 = 1.70158;
+
+/// 动画预设，对应 `enter` / `exit` 的取值
+#[derive(Debug, Clone, Copy)]
+enum Preset {
+    Fade,
+    Pop,
+    /// 从窗口哪一边滑入：(-1, 0) 为左，(0, 1) 为下
+    Slide(i32, i32),
+}
+
+impl Preset {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "fade" => Some(Preset::Fade),
+            "pop" => Some(Preset::Pop),
+            "slide-from-left" => Some(Preset::Slide(-1, 0)),
+            "slide-from-right" => Some(Preset::Slide(1, 0)),
+            "slide-from-top" => Some(Preset::Slide(0, -1)),
+            "slide-from-bottom" => Some(Preset::Slide(0, 1)),
+            _ => None,
+        }
+    }
+}
+
+/// 一个出现或消失的元素。像素取自新帧（出现）或旧帧（消失），
+/// `mask` 标出与去掉这些命令后的画面不同的像素，即元素本身
+struct Layer {
+    preset: Preset,
+    entering: bool,
+    region: Region,
+    pixels: Vec<u8>,
+    mask: Vec<bool>,
+}
+
+struct Animation {
+    width: usize,
+    height: usize,
+    /// 动画期间的底图：新画面去掉正在出现的元素
+    base: FrameBuffer,
+    layers: Vec<Layer>,
+}
+
+/// 提交新的一帧。与窗口当前显示的画面相比，有声明 `enter` / `exit` 的元素出现或消失时，
+/// 先在本地播放动画，再照常提交新帧
+pub fn present(backend: &X11Backend, render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
+    if !config::get().theme.reduced_motion {
+        if let Some(animation) = prepare(backend, render)? {
+            play(backend, &animation)?;
+        }
+    }
+    renderer::render_frame(backend, render)
+}
+
+fn prepare(backend: &X11Backend, render: &RenderEnvelope) -> Result<Option<Animation>, Box<dyn Error>> {
+    let Some(previous) = backend.with_last_frame(|frame| frame.render.clone()) else {
+        return Ok(None);
+    };
+    let changes = diff::diff(&previous, render);
+    // 窗口尺寸变了时新旧两帧的像素对不上，直接切换
+    if changes.window_changed {
+        return Ok(None);
+    }
+    let pick = |kind: ChangeKind| -> Vec<&Command> {
+        changes
+            .changes
+            .iter()
+            .filter(|c| c.kind == kind)
+            .filter_map(|c| if kind == ChangeKind::Added { c.after.as_ref() } else { c.before.as_ref() })
+            .collect()
+    };
+    let enters = groups(&pick(ChangeKind::Added), |c| hints(c).0);
+    let exits = groups(&pick(ChangeKind::Removed), |c| hints(c).1);
+    if enters.is_empty() && exits.is_empty() {
+        return Ok(None);
+    }
+    let fonts = (backend.font_primary(), backend.font_emoji());
+    let mut layers = Vec::new();
+
+    let (width, height, pixels) = renderer::render_to_buffer(render, fonts.0, fonts.1)?;
+    let entering: Vec<&Command> = enters.iter().flat_map(|(_, members)| members.iter().copied()).collect();
+    let (_, _, base) = renderer::render_to_buffer(&without(render, &entering), fonts.0, fonts.1)?;
+    for (preset, members) in &enters {
+        layers.extend(layer(*preset, true, members, &pixels, &base, width, height));
+    }
+
+    if !exits.is_empty() {
+        let old = backend.with_last_frame(|frame| frame.pixels.clone()).unwrap_or_default();
+        let exiting: Vec<&Command> = exits.iter().flat_map(|(_, members)| members.iter().copied()).collect();
+        let (_, _, old_base) = renderer::render_to_buffer(&without(&previous, &exiting), fonts.0, fonts.1)?;
+        if old.len() == old_base.len() {
+            for (preset, members) in &exits {
+                layers.extend(layer(*preset, false, members, &old, &old_base, width, height));
+            }
+        }
+    }
+    Ok((!layers.is_empty()).then_some(Animation { width, height, base, layers }))
+}
+
+fn play(backend: &X11Backend, animation: &Animation) -> Result<(), Box<dyn Error>> {
+    let Some((x, y, w, h)) = animation
+        .layers
+        .iter()
+        .map(|layer| layer.sweep(animation.width, animation.height))
+        .reduce(renderer::union_rect)
+        .and_then(|(x, y, w, h)| renderer::clip_region(x, y, w, h, animation.width, animation.height))
+    else {
+        return Ok(());
+    };
+    let interval = ANIMATION_DURATION / ANIMATION_STEPS;
+    // 最后一步是元素的最终位置：出现的完整显示，消失的已不在，与新帧一致
+    for step in 1..=ANIMATION_STEPS {
+        let started = Instant::now();
+        let progress = step as f32 / ANIMATION_STEPS as f32;
+        let mut out = renderer::copy_region(&animation.base, animation.width, x, y, w, h);
+        for layer in &animation.layers {
+            layer.draw(&mut out, (x, y, w, h), progress, animation.width, animation.height);
+        }
+        renderer::put_pixels(backend, &out, x, y, w, h)?;
+        Connection::flush(backend.connection())?;
+        thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+    Ok(())
+}
+
+fn hints(command: &Command) -> (Option<&str>, Option<&str>) {
+    match command {
+        Command::Rect { enter, exit, .. }
+        | Command::RoundRect { enter, exit, .. }
+        | Command::Text { enter, exit, .. }
+        | Command::Image { enter, exit, .. } => (enter.as_deref(), exit.as_deref()),
+        _ => (None, None),
+    }
+}
+
+/// 声明了动画的命令各成一组；同时出现（或消失）、本身没有声明动画、且起点落在它范围内的命令
+/// （按钮上的标签、卡片里的图片）随它一起动
+fn groups<'a>(commands: &[&'a Command], hint: impl Fn(&Command) -> Option<&str>) -> Vec<(Preset, Vec<&'a Command>)> {
+    commands
+        .iter()
+        .filter_map(|root| {
+            let preset = Preset::parse(hint(root)?)?;
+            let (left, top, right, bottom) = diff::bounds(root)?;
+            let members = commands
+                .iter()
+                .filter(|c| {
+                    std::ptr::eq(**c, *root)
+                        || (hint(c).is_none()
+                            && diff::bounds(c).is_some_and(|(l, t, _, _)| l >= left && t >= top && l < right && t < bottom))
+                })
+                .copied()
+                .collect();
+            Some((preset, members))
+        })
+        .collect()
+}
+
+fn without(render: &RenderEnvelope, removed: &[&Command]) -> RenderEnvelope {
+    let mut render = render.clone();
+    render.commands.retain(|c| !removed.contains(&c));
+    render
+}
+
+fn layer(preset: Preset, entering: bool, members: &[&Command], frame: &[u8], base: &[u8], width: usize, height: usize) -> Option<Layer> {
+    let (l, t, r, b) = members.iter().filter_map(|c| diff::bounds(c)).reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))?;
+    let region = renderer::clip_region(l, t, (r - l) as u32, (b - t) as u32, width, height)?;
+    let (x, y, w, h) = region;
+    let pixels = renderer::copy_region(frame, width, x, y, w, h);
+    let under = renderer::copy_region(base, width, x, y, w, h);
+    let mask = pixels.chunks_exact(4).zip(under.chunks_exact(4)).map(|(a, b)| a != b).collect();
+    Some(Layer { preset, entering, region, pixels, mask })
+}
+
+fn ease_out_cubic(t: f32) -> f32 {
+    1.0 - (1.0 - t).powi(3)
+}
+
+fn ease_out_back(t: f32) -> f32 {
+    let c3 = POP_OVERSHOOT + 1.0;
+    1.0 + c3 * (t - 1.0).powi(3) + POP_OVERSHOOT * (t - 1.0).powi(2)
+}
+
+impl Layer {
+    /// 进度 `progress` 时的不透明度、缩放与位移。消失动画把出现动画倒过来播放
+    fn pose(&self, progress: f32, width: usize, height: usize) -> (f32, f32, f32, f32) {
+        let t = if self.entering { progress } else { 1.0 - progress }.clamp(0.0, 1.0);
+        let (x, y, w, h) = self.region;
+        match self.preset {
+            Preset::Fade => (ease_out_cubic(t), 1.0, 0.0, 0.0),
+            Preset::Pop => ((t * 2.0).min(1.0), ease_out_back(t), 0.0, 0.0),
+            Preset::Slide(sx, sy) => {
+                // 起点是元素刚好完全移出窗口的位置
+                let distance_x = if sx < 0 { x + w as i32 } else { width as i32 - x };
+                let distance_y = if sy < 0 { y + h as i32 } else { height as i32 - y };
+                let remaining = 1.0 - ease_out_cubic(t);
+                (1.0, 1.0, (sx * distance_x) as f32 * remaining, (sy * distance_y) as f32 * remaining)
+            }
+        }
+    }
+
+    /// 整个动画过程中元素可能经过的范围
+    fn sweep(&self, width: usize, height: usize) -> Region {
+        let (x, y, w, h) = self.region;
+        match self.preset {
+            Preset::Fade => self.region,
+            Preset::Pop => {
+                let (gx, gy) = (w as i32 / 8 + 1, h as i32 / 8 + 1);
+                (x - gx, y - gy, w + 2 * gx as u32, h + 2 * gy as u32)
+            }
+            Preset::Slide(-1, _) => (0, y, (x + w as i32).max(0) as u32, h),
+            Preset::Slide(1, _) => (x, y, (width as i32 - x).max(0) as u32, h),
+            Preset::Slide(_, -1) => (x, 0, w, (y + h as i32).max(0) as u32),
+            Preset::Slide(..) => (x, y, w, (height as i32 - y).max(0) as u32),
+        }
+    }
+
+    /// 把元素按当前姿态叠加到 `out`（窗口中 `area` 范围的像素）上
+    fn draw(&self, out: &mut [u8], area: Region, progress: f32, width: usize, height: usize) {
+        let (alpha, scale, dx, dy) = self.pose(progress, width, height);
+        if alpha <= 0.0 || scale < 0.01 {
+            return;
+        }
+        let (x, y, w, h) = self.region;
+        let (ax, ay, aw, ah) = area;
+        let (cx, cy) = (x as f32 + w as f32 / 2.0, y as f32 + h as f32 / 2.0);
+        // 只遍历元素当前覆盖的那部分
+        let left = ((cx + (x as f32 - cx) * scale + dx).floor() as i32).max(ax);
+        let top = ((cy + (y as f32 - cy) * scale + dy).floor() as i32).max(ay);
+        let right = ((cx + (x as f32 + w as f32 - cx) * scale + dx).ceil() as i32).min(ax + aw as i32);
+        let bottom = ((cy + (y as f32 + h as f32 - cy) * scale + dy).ceil() as i32).min(ay + ah as i32);
+        for py in top..bottom {
+            for px in left..right {
+                let sx = (cx + (px as f32 + 0.5 - dx - cx) / scale).floor() as i32 - x;
+                let sy = (cy + (py as f32 + 0.5 - dy - cy) / scale).floor() as i32 - y;
+                if sx < 0 || sy < 0 || sx >= w as i32 || sy >= h as i32 {
+                    continue;
+                }
+                let si = sy as usize * w as usize + sx as usize;
+                if !self.mask[si] {
+                    continue;
+                }
+                let di = ((py - ay) as usize * aw as usize + (px - ax) as usize) * 4;
+                for (channel, source) in out[di..di + 3].iter_mut().zip(&self.pixels[si * 4..si * 4 + 3]) {
+                    *channel = (*channel as f32 * (1.0 - alpha) + *source as f32 * alpha) as u8;
+                }
+            }
+        }
+    }
+}
@@ -1,28 +1,70 @@
 use std::error::Error;
+use std::sync::{Mutex, MutexGuard};
 
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::{
-    ChangeWindowAttributesAux, ConnectionExt, CreateGCAux, CreateWindowAux, EventMask,
-    WindowClass,
+    ChangeKeyboardControlAux, ChangeWindowAttributesAux, ConnectionExt, CreateGCAux, CreateWindowAux, EventMask,
+    ImageOrder, WindowClass,
 };
 use x11rb::rust_connection::RustConnection;
 use x11rb::wrapper::ConnectionExt as WrapperConnectionExt;
 use fontdue::Font;
 
+use crate::config;
+use crate::dsl::model::RenderEnvelope;
+use crate::x11::renderer::GlyphBox;
+use crate::gesture::GestureTracker;
+use crate::x11::buffer_pool::FrameBuffer;
+#[cfg(feature = "gl")]
+use crate::x11::gl::GlPresenter;
+use crate::x11::selection::{ClipboardAtoms, TextSelection};
+
 pub struct X11Backend {
     conn: RustConnection,
     window: u32,
     gc: u32,
     _cursor: u32,
+    #[allow(dead_code)]
     font: u32,
     depth: u8,
     bits_per_pixel: u8,
+    /// 服务器要求的扫描行对齐（位），每行字节数须是它的整数倍
+    scanline_pad: u8,
+    /// 服务器的像素字节序；经网络连接到大端机器上的服务器时为 MSBFirst
+    image_byte_order: ImageOrder,
+    /// GPU 呈现器（`presenter` 为 `gl` 且初始化成功时）
+    #[cfg(feature = "gl")]
+    gl: Option<GlPresenter>,
+    dpi: f32,
+    /// 根窗口（整块屏幕）的像素尺寸
+    screen_size: (u32, u32),
     font_primary: Option<Font>,
     font_emoji: Option<Font>,
+    /// 键盘映射：从 `min_keycode` 开始，每个 keycode 占 `keysyms_per_keycode` 个 keysym
+    min_keycode: u8,
+    keysyms_per_keycode: u8,
+    keysyms: Vec<u32>,
+    /// 窗口里当前显示的一帧：用来计算下一帧只需提交的变化区域，按下反馈也直接画在它的像素上
+    last_frame: Mutex<Option<PresentedFrame>>,
+    /// 文本拖选与复制出去的内容
+    selection: Mutex<TextSelection>,
+    clipboard_atoms: ClipboardAtoms,
+    /// 左键按下后的指针轨迹，松开时识别滑动手势
+    gesture: Mutex<GestureTracker>,
+}
+
+/// 已提交到窗口的一帧：信封、合成好的整帧像素（BGRA）与文字的字形盒子
+pub struct PresentedFrame {
+    pub render: RenderEnvelope,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: FrameBuffer,
+    pub glyphs: Vec<GlyphBox>,
 }
 
 impl X11Backend {
-    pub fn connect(width: u16, height: u16, title: &str) -> Result<Self, Box<dyn Error>> {
+    /// `background` 为窗口背景色（首帧之前与拉大窗口时露出的区域），None 时为白色
+    pub fn connect(width: u16, height: u16, title: &str, background: Option<(u8, u8, u8)>) -> Result<Self, Box<dyn Error>> {
         let (conn, screen_num) = x11rb::connect(None)?;
         let screen = &conn.setup().roots[screen_num];
 
@@ -30,8 +72,14 @@ impl X11Backend {
         let gc = conn.generate_id()?;
 
         let aux = CreateWindowAux::new()
-            .background_pixel(screen.white_pixel)
-            .event_mask(EventMask::EXPOSURE | EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE);
+            .background_pixel(background.and_then(|rgb| rgb_pixel(screen.root_depth, rgb)).unwrap_or(screen.white_pixel))
+            .event_mask(
+                EventMask::EXPOSURE
+                    | EventMask::BUTTON_PRESS
+                    | EventMask::BUTTON_RELEASE
+                    | EventMask::BUTTON1_MOTION
+                    | EventMask::KEY_PRESS,
+            );
 
         conn.create_window(
             screen.root_depth,
@@ -58,9 +106,29 @@ impl X11Backend {
         let cursor = create_default_cursor(&conn, window)?;
         let font = open_text_font(&conn)?;
         let (font_primary, font_emoji) = load_fonts();
-        let (depth, bits_per_pixel) = query_depth_and_bpp(&conn, screen.root_depth);
+        let (depth, bits_per_pixel, scanline_pad) = query_pixmap_format(&conn, screen.root_depth);
+        let image_byte_order = conn.setup().image_byte_order;
+        let root_visual = screen.root_visual;
+        let dpi = if screen.width_in_millimeters > 0 {
+            screen.width_in_pixels as f32 * 25.4 / screen.width_in_millimeters as f32
+        } else {
+            96.0
+        };
+        let screen_size = (screen.width_in_pixels as u32, screen.height_in_pixels as u32);
+        let min_keycode = conn.setup().min_keycode;
+        let max_keycode = conn.setup().max_keycode;
+        let mapping = conn.get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?.reply()?;
+        let clipboard_atoms = ClipboardAtoms::intern(&conn)?;
         conn.map_window(window)?;
         conn.flush()?;
+        // EGL 通过自己的连接引用这个窗口，须在上面 flush 之后创建
+        let wants_gl = config::get().presenter == "gl";
+        #[cfg(feature = "gl")]
+        let gl = wants_gl.then(|| GlPresenter::connect(window, root_visual)).flatten();
+        #[cfg(not(feature = "gl"))]
+        if wants_gl {
+            tracing::warn!(visual = root_visual, "presenter \"gl\" needs a build with --features gl, using the CPU presenter");
+        }
 
         Ok(Self {
             conn,
@@ -70,8 +138,21 @@ impl X11Backend {
             font,
             depth,
             bits_per_pixel,
+            scanline_pad,
+            image_byte_order,
+            dpi,
+            screen_size,
             font_primary,
             font_emoji,
+            min_keycode,
+            keysyms_per_keycode: mapping.keysyms_per_keycode,
+            keysyms: mapping.keysyms,
+            #[cfg(feature = "gl")]
+            gl,
+            last_frame: Mutex::new(None),
+            selection: Mutex::new(TextSelection::default()),
+            clipboard_atoms,
+            gesture: Mutex::new(GestureTracker::default()),
         })
     }
 
@@ -87,6 +168,7 @@ impl X11Backend {
         self.gc
     }
 
+    #[allow(dead_code)]
     pub fn font(&self) -> u32 {
         self.font
     }
@@ -99,6 +181,72 @@ impl X11Backend {
         self.bits_per_pixel
     }
 
+    pub fn scanline_pad(&self) -> u8 {
+        self.scanline_pad
+    }
+
+    pub fn image_byte_order(&self) -> ImageOrder {
+        self.image_byte_order
+    }
+
+    #[cfg(feature = "gl")]
+    pub fn gl_presenter(&self) -> Option<&GlPresenter> {
+        self.gl.as_ref()
+    }
+
+    pub fn dpi(&self) -> f32 {
+        self.dpi
+    }
+
+    pub fn screen_size(&self) -> (u32, u32) {
+        self.screen_size
+    }
+
+    /// 记录刚提交的帧，返回之前显示的那一帧
+    pub fn replace_last_frame(&self, frame: Option<PresentedFrame>) -> Option<PresentedFrame> {
+        std::mem::replace(&mut *self.last_frame.lock().unwrap(), frame)
+    }
+
+    pub fn with_last_frame<R>(&self, f: impl FnOnce(&PresentedFrame) -> R) -> Option<R> {
+        self.last_frame.lock().unwrap().as_ref().map(f)
+    }
+
+    pub fn selection(&self) -> MutexGuard<'_, TextSelection> {
+        self.selection.lock().unwrap()
+    }
+
+    pub fn clipboard_atoms(&self) -> &ClipboardAtoms {
+        &self.clipboard_atoms
+    }
+
+    pub fn gesture(&self) -> MutexGuard<'_, GestureTracker> {
+        self.gesture.lock().unwrap()
+    }
+
+    /// 以给定音高和时长响铃一次，之后恢复服务器原来的响铃设置
+    pub fn bell(&self, volume: u8, pitch_hz: u16, duration_ms: u16) -> Result<(), Box<dyn Error>> {
+        let previous = self.conn.get_keyboard_control()?.reply()?;
+        self.conn.change_keyboard_control(
+            &ChangeKeyboardControlAux::new().bell_pitch(pitch_hz as i32).bell_duration(duration_ms as i32),
+        )?;
+        self.conn.bell(volume.min(100) as i8)?;
+        self.conn.change_keyboard_control(
+            &ChangeKeyboardControlAux::new()
+                .bell_pitch(previous.bell_pitch as i32)
+                .bell_duration(previous.bell_duration as i32),
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// 更换窗口背景色；只影响服务器自行填充的区域，不重画当前内容
+    pub fn set_background(&self, rgb: (u8, u8, u8)) -> Result<(), Box<dyn Error>> {
+        if let Some(pixel) = rgb_pixel(self.depth, rgb) {
+            self.conn.change_window_attributes(self.window, &ChangeWindowAttributesAux::new().background_pixel(pixel))?;
+        }
+        Ok(())
+    }
+
     pub fn font_primary(&self) -> Option<&Font> {
         self.font_primary.as_ref()
     }
@@ -106,6 +254,27 @@ impl X11Backend {
     pub fn font_emoji(&self) -> Option<&Font> {
         self.font_emoji.as_ref()
     }
+
+    /// keycode 对应的 keysym；按住 Shift 时取第二列（没有则退回第一列）
+    pub fn keysym(&self, keycode: u8, shift: bool) -> u32 {
+        let per = self.keysyms_per_keycode as usize;
+        let Some(base) = keycode.checked_sub(self.min_keycode).map(|k| k as usize * per) else {
+            return 0;
+        };
+        let column = |i: usize| self.keysyms.get(base + i).copied().filter(|&k| k != 0 && i < per);
+        if shift { column(1).or_else(|| column(0)) } else { column(0) }.unwrap_or(0)
+    }
+}
+
+/// 会话结束（`/quit`、DSL exit 动作、守护进程关闭会话）时释放服务端资源并关闭窗口
+impl Drop for X11Backend {
+    fn drop(&mut self) {
+        let _ = self.conn.free_gc(self.gc);
+        let _ = self.conn.close_font(self.font);
+        let _ = self.conn.free_cursor(self._cursor);
+        let _ = self.conn.destroy_window(self.window);
+        let _ = self.conn.flush();
+    }
 }
 
 fn create_default_cursor(conn: &RustConnection, window: u32) -> Result<u32, Box<dyn Error>> {
@@ -139,30 +308,55 @@ fn open_text_font(conn: &RustConnection) -> Result<u32, Box<dyn Error>> {
     Ok(font)
 }
 
-fn query_depth_and_bpp(conn: &RustConnection, depth: u8) -> (u8, u8) {
-    let mut bpp = 32;
-    for fmt in &conn.setup().pixmap_formats {
-        if fmt.depth == depth {
-            bpp = fmt.bits_per_pixel;
-            break;
-        }
-    }
-    (depth, bpp)
+/// 根窗口深度对应的 ZPixmap 格式：(深度, 每像素位数, 扫描行对齐)；找不到时按紧密排列的 32 位处理
+fn query_pixmap_format(conn: &RustConnection, depth: u8) -> (u8, u8, u8) {
+    conn.setup()
+        .pixmap_formats
+        .iter()
+        .find(|fmt| fmt.depth == depth)
+        .map_or((depth, 32, 32), |fmt| (depth, fmt.bits_per_pixel, fmt.scanline_pad))
 }
 
-pub fn load_fonts() -> (Option<Font>, Option<Font>) {
-    let primary_candidates = vec![
+/// TrueColor 视觉（深度 24/32）下的像素值；其它深度没有固定的 RGB 排布，返回 None
+fn rgb_pixel(depth: u8, (r, g, b): (u8, u8, u8)) -> Option<u32> {
+    (depth >= 24).then_some((r as u32) << 16 | (g as u32) << 8 | b as u32)
+}
+
+fn primary_font_candidates() -> Vec<Option<String>> {
+    vec![
         std::env::var("X11_GUI_FONT").ok(),
         Some("C:\\Windows\\Fonts\\msyh.ttc".to_string()),
         Some("C:\\Windows\\Fonts\\simhei.ttf".to_string()),
         Some("C:\\Windows\\Fonts\\segoeui.ttf".to_string()),
         Some("C:\\Windows\\Fonts\\arial.ttf".to_string()),
-    ];
+    ]
+}
 
-    let emoji_candidates = vec![
+fn emoji_font_candidates() -> Vec<Option<String>> {
+    vec![
         std::env::var("X11_GUI_EMOJI_FONT").ok(),
         Some("C:\\Windows\\Fonts\\seguiemj.ttf".to_string()),
-    ];
+    ]
+}
+
+/// 本机存在的候选字体文件名（用于告诉模型能显示哪些字体）
+pub fn available_font_names() -> Vec<String> {
+    primary_font_candidates()
+        .into_iter()
+        .chain(emoji_font_candidates())
+        .flatten()
+        .filter(|path| std::path::Path::new(path).is_file())
+        .filter_map(|path| {
+            std::path::Path::new(&path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .collect()
+}
+
+pub fn load_fonts() -> (Option<Font>, Option<Font>) {
+    let primary_candidates = primary_font_candidates();
+    let emoji_candidates = emoji_font_candidates();
 
     let mut primary = None;
     for path in primary_candidates.into_iter().flatten() {
@@ -0,0 +1,71 @@
+//! 整帧像素缓冲区的复用池：每帧几 MB 的缓冲区用完后归还，下一帧（或评估循环的下一轮）直接取用，
+//! 避免反复向分配器申请、释放大块内存，在低内存设备上减少缺页与停顿。
+
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use crate::metrics;
+
+/// 池中最多保留的空闲缓冲区，超出的直接释放，限制常驻内存
+const MAX_IDLE: usize = 4;
+
+static IDLE: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+/// 从池中借出的像素缓冲区，drop 时归还
+#[derive(Default)]
+pub struct FrameBuffer(Vec<u8>);
+
+impl FrameBuffer {
+    /// 取一块长度为 `len`、全部填 `fill` 的缓冲区；优先复用容量足够的空闲缓冲区
+    pub fn take(len: usize, fill: u8) -> Self {
+        let reused = IDLE.lock().ok().and_then(|mut idle| {
+            let fit = idle.iter().position(|b| b.capacity() >= len).or_else(|| idle.len().checked_sub(1))?;
+            Some(idle.swap_remove(fit))
+        });
+        metrics::inc("agd_frame_buffers_total", &[("source", if reused.is_some() { "pool" } else { "alloc" })], 1.0);
+        let mut buffer = reused.unwrap_or_default();
+        buffer.clear();
+        buffer.resize(len, fill);
+        Self(buffer)
+    }
+
+    /// 交出底层 Vec（给需要拥有像素的编码器），它不再回到池中
+    pub fn into_vec(mut self) -> Vec<u8> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl Clone for FrameBuffer {
+    fn clone(&self) -> Self {
+        let mut copy = Self::take(0, 0);
+        copy.0.extend_from_slice(&self.0);
+        copy
+    }
+}
+
+impl Deref for FrameBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl DerefMut for FrameBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl Drop for FrameBuffer {
+    fn drop(&mut self) {
+        if self.0.capacity() == 0 {
+            return;
+        }
+        if let Ok(mut idle) = IDLE.lock() {
+            if idle.len() < MAX_IDLE {
+                idle.push(std::mem::take(&mut self.0));
+            }
+        }
+    }
+}
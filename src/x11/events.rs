@@ -1,24 +1,58 @@
 use std::error::Error;
 
 use x11rb::connection::Connection;
+use x11rb::protocol::xproto::KeyButMask;
 use x11rb::protocol::Event;
 
+use crate::display::{ClickEvent, KeyEvent, UiEvent};
 use crate::x11::backend::X11Backend;
+use crate::x11::selection;
 
-pub struct ClickEvent {
-    pub x: i32,
-    pub y: i32,
-}
+const LEFT_BUTTON: u8 = 1;
 
-pub fn poll_for_click(backend: &X11Backend) -> Result<Option<ClickEvent>, Box<dyn Error>> {
+pub fn poll_event(backend: &X11Backend) -> Result<Option<UiEvent>, Box<dyn Error>> {
     let conn = backend.connection();
-    if let Some(event) = conn.poll_for_event()? {
-        if let Event::ButtonRelease(ev) = event {
-            return Ok(Some(ClickEvent {
-                x: ev.event_x.into(),
-                y: ev.event_y.into(),
-            }));
+    match conn.poll_for_event()? {
+        // 左键按下、拖动、松开先交给文本选择，其次识别滑动手势；两者都不是的松开才是点击
+        Some(Event::ButtonPress(ev)) if ev.detail == LEFT_BUTTON => {
+            backend.gesture().press(ev.event_x.into(), ev.event_y.into(), ev.time);
+            selection::press(backend, ev.event_x.into(), ev.event_y.into())?;
+            Ok(None)
+        }
+        Some(Event::MotionNotify(ev)) => {
+            backend.gesture().motion(ev.event_x.into(), ev.event_y.into(), ev.time);
+            selection::drag(backend, ev.event_x.into(), ev.event_y.into())?;
+            Ok(None)
+        }
+        Some(Event::ButtonRelease(ev)) if ev.detail == LEFT_BUTTON => {
+            let gesture = backend.gesture().release(ev.event_x.into(), ev.event_y.into(), ev.time);
+            if selection::release(backend, ev.time)? {
+                return Ok(None);
+            }
+            Ok(Some(match gesture {
+                Some(gesture) => UiEvent::Gesture(gesture),
+                None => UiEvent::Click(ClickEvent { x: ev.event_x.into(), y: ev.event_y.into() }),
+            }))
+        }
+        Some(Event::ButtonRelease(ev)) => Ok(Some(UiEvent::Click(ClickEvent {
+            x: ev.event_x.into(),
+            y: ev.event_y.into(),
+        }))),
+        Some(Event::SelectionRequest(ev)) => {
+            selection::answer(backend, &ev)?;
+            Ok(None)
+        }
+        Some(Event::KeyPress(ev)) => {
+            let shift = ev.state.contains(KeyButMask::SHIFT);
+            Ok(Some(UiEvent::Key(KeyEvent {
+                keysym: backend.keysym(ev.detail, shift),
+                ctrl: ev.state.contains(KeyButMask::CONTROL),
+                alt: ev.state.contains(KeyButMask::MOD1),
+                shift,
+            })))
         }
+        // 只在最后一个 Expose 上重绘，避免一次遮挡触发多次全量绘制
+        Some(Event::Expose(ev)) if ev.count == 0 => Ok(Some(UiEvent::Expose)),
+        _ => Ok(None),
     }
-    Ok(None)
 }
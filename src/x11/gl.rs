@@ -0,0 +1,408 @@
+//! 可选的 GPU 呈现（`--features gl`，配置 `"presenter": "gl"`）：图元仍由 CPU 光栅化，
+//! 整帧作为纹理画到 EGL 窗口表面上，图片作为纹理上传、在 GPU 上缩放与混合。
+//! 合成结果读回内存，截图、按下反馈与选区高亮仍以它为准。
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Mutex;
+
+use glow::HasContext;
+use image::RgbaImage;
+use khronos_egl as egl;
+use sha2::{Digest, Sha256};
+
+use crate::dsl::{diff, validator};
+use crate::dsl::model::{Command, RenderEnvelope};
+use crate::images;
+use crate::dialog;
+use crate::input;
+use crate::toast;
+use crate::x11::buffer_pool::FrameBuffer;
+use crate::x11::renderer;
+
+/// EGL_KHR_platform_x11
+const PLATFORM_X11_KHR: egl::Enum = 0x31D5;
+/// 显存中最多保留的图片纹理，超出时淘汰最久未用的
+const MAX_TEXTURES: usize = 32;
+
+/// 读回的整帧：(宽, 高, BGRA 像素)
+type Frame = (usize, usize, FrameBuffer);
+
+/// 单位正方形的四个角由 gl_VertexID 生成，`u_rect` 给出像素坐标下的矩形（左上角原点）
+const VERTEX_SHADER: &str = r#"#version 300 es
+uniform vec2 u_viewport;
+uniform vec4 u_rect;
+out vec2 v_uv;
+void main() {
+    vec2 corner = vec2(float(gl_VertexID & 1), float(gl_VertexID >> 1));
+    vec2 pixel = u_rect.xy + corner * u_rect.zw;
+    v_uv = corner;
+    gl_Position = vec4(pixel.x / u_viewport.x * 2.0 - 1.0, 1.0 - pixel.y / u_viewport.y * 2.0, 0.0, 1.0);
+}
+"#;
+
+/// 整帧纹理按 RGBA 上传的 BGRA 像素，alpha 字节恒为 0，按不透明处理；图片纹理是带 alpha 的 RGBA
+const FRAGMENT_SHADER: &str = r#"#version 300 es
+precision highp float;
+uniform sampler2D u_texture;
+uniform bool u_frame;
+in vec2 v_uv;
+out vec4 color;
+void main() {
+    vec4 texel = texture(u_texture, v_uv);
+    color = u_frame ? vec4(texel.bgr, 1.0) : texel;
+}
+"#;
+
+/// 窗口的 GPU 呈现器；出错后内部状态被丢弃，之后的帧照常由 CPU 提交
+pub struct GlPresenter(Mutex<Option<Gpu>>);
+
+struct Gpu {
+    egl: egl::DynamicInstance<egl::EGL1_5>,
+    display: egl::Display,
+    surface: egl::Surface,
+    context: egl::Context,
+    gl: glow::Context,
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    frame: glow::Texture,
+    u_viewport: Option<glow::UniformLocation>,
+    u_rect: Option<glow::UniformLocation>,
+    u_frame: Option<glow::UniformLocation>,
+    max_texture_size: u32,
+    /// 图片来源的哈希 -> (纹理, 最近一次使用的帧号)
+    textures: HashMap<[u8; 32], (glow::Texture, u64)>,
+    frames: u64,
+}
+
+// SAFETY: EGL 句柄只是不透明指针；所有 GL 调用都在 Mutex 内进行，
+// 每帧先把上下文绑定到当前线程、画完再解绑，不会同时在两个线程上使用
+unsafe impl Send for Gpu {}
+
+struct Setup {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    frame: glow::Texture,
+    uniforms: [Option<glow::UniformLocation>; 3],
+    max_texture_size: u32,
+}
+
+/// 交给 GPU 合成的一张图片；纹理尚未缓存时附带解码好的像素
+struct ImageQuad {
+    key: [u8; 32],
+    rect: (i32, i32, u32, u32),
+    decoded: Option<RgbaImage>,
+}
+
+impl GlPresenter {
+    /// 在窗口上创建 EGL 表面与 OpenGL ES 3 上下文；失败时记录原因并返回 None（使用 CPU 呈现）
+    pub fn connect(window: u32, visual: u32) -> Option<Self> {
+        match Gpu::new(window, visual) {
+            Ok(gpu) => {
+                tracing::info!(max_texture_size = gpu.max_texture_size, "GPU presenter enabled");
+                Some(Self(Mutex::new(Some(gpu))))
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "GPU presenter unavailable, using the CPU presenter");
+                None
+            }
+        }
+    }
+
+    /// 合成并显示一帧，返回读回的整帧（BGRA）；GPU 已停用时返回 None，由调用方走 CPU 路径。
+    /// 图片加载失败等渲染错误照常返回 Err；GPU 自身出错时停用 GPU 呈现并返回 None
+    pub fn present(
+        &self,
+        render: &RenderEnvelope,
+        primary: Option<&fontdue::Font>,
+        emoji: Option<&fontdue::Font>,
+    ) -> Result<Option<Frame>, Box<dyn Error>> {
+        let mut guard = self.0.lock().unwrap();
+        let Some(gpu) = guard.as_mut() else {
+            return Ok(None);
+        };
+        // 输入框、对话框与提示条先换成基本图元，叠在它们下面的图片就不会被挪到 GPU 上画到它们上面
+        let with_inputs = input::expand(render);
+        let with_dialog = dialog::expand(&with_inputs);
+        let expanded = toast::expand(&with_dialog);
+        let render = expanded.as_ref();
+        let mut offloaded = Vec::new();
+        let mut quads = Vec::new();
+        for index in offloadable(render) {
+            let Command::Image { x: Some(x), y: Some(y), w: Some(w), h: Some(h), src_type: Some(src_type), src: Some(src), .. } =
+                &render.commands[index]
+            else {
+                continue;
+            };
+            let key: [u8; 32] = Sha256::new().chain_update(src_type).chain_update([0]).chain_update(src).finalize().into();
+            let decoded = match gpu.textures.contains_key(&key) {
+                true => None,
+                false => match images::load(src_type, src)? {
+                    Some(image) => Some(image.to_rgba8()),
+                    // 远程图片还没到：留给 CPU 画占位框
+                    None => continue,
+                },
+            };
+            offloaded.push(index);
+            quads.push(ImageQuad { key, rect: (*x, *y, *w, *h), decoded });
+        }
+        let mut rest = render.clone();
+        rest.commands = render.commands.iter().enumerate().filter(|(i, _)| !offloaded.contains(i)).map(|(_, c)| c.clone()).collect();
+        let (width, height, mut pixels) = renderer::render_to_buffer(&rest, primary, emoji)?;
+        match gpu.draw(&mut pixels, width, height, quads) {
+            Ok(()) => Ok(Some((width, height, pixels))),
+            Err(e) => {
+                tracing::warn!(error = %e, "GPU presentation failed, falling back to the CPU presenter");
+                *guard = None;
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Gpu {
+    fn new(window: u32, visual: u32) -> Result<Self, Box<dyn Error>> {
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+            .map_err(|e| format!("cannot load libEGL 1.5: {}", e))?;
+        // 默认显示：EGL 自己打开到 $DISPLAY 的连接；窗口 ID 在服务器上全局有效
+        let display = unsafe { egl.get_platform_display(PLATFORM_X11_KHR, egl::DEFAULT_DISPLAY, &[egl::ATTRIB_NONE]) }?;
+        egl.initialize(display)?;
+        let attributes = [
+            egl::SURFACE_TYPE,
+            egl::WINDOW_BIT,
+            egl::RENDERABLE_TYPE,
+            egl::OPENGL_ES3_BIT,
+            egl::RED_SIZE,
+            8,
+            egl::GREEN_SIZE,
+            8,
+            egl::BLUE_SIZE,
+            8,
+            egl::NONE,
+        ];
+        let mut configs = Vec::with_capacity(64);
+        egl.choose_config(display, &attributes, &mut configs)?;
+        let config = configs
+            .into_iter()
+            .find(|c| egl.get_config_attrib(display, *c, egl::NATIVE_VISUAL_ID).ok() == Some(visual as egl::Int))
+            .ok_or("no EGL config matches the window visual")?;
+        egl.bind_api(egl::OPENGL_ES_API)?;
+        let context = egl.create_context(display, config, None, &[egl::CONTEXT_MAJOR_VERSION, 3, egl::NONE])?;
+        // EGL_KHR_platform_x11 的窗口参数是指向 X11 Window 的指针
+        let mut xid = window as std::ffi::c_ulong;
+        let native = &mut xid as *mut std::ffi::c_ulong as egl::NativeWindowType;
+        let surface = unsafe { egl.create_platform_window_surface(display, config, native, &[egl::ATTRIB_NONE]) }?;
+        egl.make_current(display, Some(surface), Some(surface), Some(context))?;
+
+        let gl = unsafe {
+            glow::Context::from_loader_function(|name| egl.get_proc_address(name).map_or(std::ptr::null(), |f| f as *const _))
+        };
+        let setup = unsafe { Self::setup(&gl) };
+        egl.make_current(display, None, None, None)?;
+        let Setup { program, vertex_array, frame, uniforms: [u_viewport, u_rect, u_frame], max_texture_size } = setup?;
+        Ok(Self {
+            u_viewport,
+            u_rect,
+            u_frame,
+            egl,
+            display,
+            surface,
+            context,
+            gl,
+            program,
+            vertex_array,
+            frame,
+            max_texture_size,
+            textures: HashMap::new(),
+            frames: 0,
+        })
+    }
+
+    /// 编译着色器、创建顶点数组与整帧纹理；须在上下文绑定到当前线程时调用
+    unsafe fn setup(gl: &glow::Context) -> Result<Setup, Box<dyn Error>> {
+        let program = gl.create_program()?;
+        for (kind, source) in [(glow::VERTEX_SHADER, VERTEX_SHADER), (glow::FRAGMENT_SHADER, FRAGMENT_SHADER)] {
+            let shader = gl.create_shader(kind)?;
+            gl.shader_source(shader, source);
+            gl.compile_shader(shader);
+            if !gl.get_shader_compile_status(shader) {
+                return Err(format!("shader compile failed: {}", gl.get_shader_info_log(shader)).into());
+            }
+            gl.attach_shader(program, shader);
+            gl.delete_shader(shader);
+        }
+        gl.link_program(program);
+        if !gl.get_program_link_status(program) {
+            return Err(format!("shader link failed: {}", gl.get_program_info_log(program)).into());
+        }
+        // 顶点由 gl_VertexID 生成，不需要顶点缓冲，但绘制时仍要绑定一个顶点数组对象
+        let vertex_array = gl.create_vertex_array()?;
+        // 整帧纹理与窗口像素一一对应，不插值，读回的像素与 CPU 渲染一致
+        let frame = gl.create_texture()?;
+        gl.bind_texture(glow::TEXTURE_2D, Some(frame));
+        texture_parameters(gl, glow::NEAREST, glow::NEAREST);
+        gl.disable(glow::DITHER);
+        let uniforms = ["u_viewport", "u_rect", "u_frame"].map(|name| gl.get_uniform_location(program, name));
+        let max_texture_size = gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE).max(1) as u32;
+        Ok(Setup { program, vertex_array, frame, uniforms, max_texture_size })
+    }
+
+    fn draw(&mut self, pixels: &mut [u8], width: usize, height: usize, quads: Vec<ImageQuad>) -> Result<(), Box<dyn Error>> {
+        self.egl.make_current(self.display, Some(self.surface), Some(self.surface), Some(self.context))?;
+        let result = unsafe { self.draw_current(pixels, width, height, quads) };
+        self.egl.make_current(self.display, None, None, None)?;
+        result
+    }
+
+    unsafe fn draw_current(&mut self, pixels: &mut [u8], width: usize, height: usize, quads: Vec<ImageQuad>) -> Result<(), Box<dyn Error>> {
+        self.frames += 1;
+        let gl = &self.gl;
+        let (w, h) = (width as i32, height as i32);
+        gl.viewport(0, 0, w, h);
+        gl.use_program(Some(self.program));
+        gl.bind_vertex_array(Some(self.vertex_array));
+        gl.active_texture(glow::TEXTURE0);
+        gl.uniform_2_f32(self.u_viewport.as_ref(), width as f32, height as f32);
+
+        gl.bind_texture(glow::TEXTURE_2D, Some(self.frame));
+        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+        gl.tex_image_2d(glow::TEXTURE_2D, 0, glow::RGBA8 as i32, w, h, 0, glow::RGBA, glow::UNSIGNED_BYTE, glow::PixelUnpackData::Slice(Some(pixels)));
+        gl.uniform_1_i32(self.u_frame.as_ref(), 1);
+        gl.uniform_4_f32(self.u_rect.as_ref(), 0.0, 0.0, width as f32, height as f32);
+        gl.disable(glow::BLEND);
+        gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+
+        gl.enable(glow::BLEND);
+        gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+        gl.uniform_1_i32(self.u_frame.as_ref(), 0);
+        for quad in quads {
+            let texture = self.image_texture(quad.key, quad.decoded)?;
+            let gl = &self.gl;
+            let (x, y, qw, qh) = quad.rect;
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.uniform_4_f32(self.u_rect.as_ref(), x as f32, y as f32, qw as f32, qh as f32);
+            gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+        }
+
+        // 交换缓冲区之前读回：交换后后缓冲区的内容不确定
+        let gl = &self.gl;
+        let mut rgba = FrameBuffer::take(width * height * 4, 0);
+        gl.pixel_store_i32(glow::PACK_ALIGNMENT, 4);
+        gl.read_pixels(0, 0, w, h, glow::RGBA, glow::UNSIGNED_BYTE, glow::PixelPackData::Slice(Some(&mut rgba)));
+        let error = gl.get_error();
+        if error != glow::NO_ERROR {
+            return Err(format!("OpenGL error 0x{:04X}", error).into());
+        }
+        // GL 的第 0 行在底部：按行倒序转回自上而下的 BGRA
+        let row = width * 4;
+        for (dst, src) in pixels.chunks_exact_mut(row).zip(rgba.chunks_exact(row).rev()) {
+            for (d, s) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+                d.copy_from_slice(&[s[2], s[1], s[0], 0]);
+            }
+        }
+        self.egl.swap_buffers(self.display, self.surface)?;
+        self.evict();
+        Ok(())
+    }
+
+    /// 取缓存的图片纹理，没有时上传；超过 GPU 纹理尺寸上限的图片先在 CPU 上缩小
+    unsafe fn image_texture(&mut self, key: [u8; 32], decoded: Option<RgbaImage>) -> Result<glow::Texture, Box<dyn Error>> {
+        if let Some((texture, used)) = self.textures.get_mut(&key) {
+            *used = self.frames;
+            return Ok(*texture);
+        }
+        let mut image = decoded.ok_or("image texture was evicted before drawing")?;
+        let limit = self.max_texture_size;
+        if image.width() > limit || image.height() > limit {
+            let scale = limit as f32 / image.width().max(image.height()) as f32;
+            let (w, h) = (((image.width() as f32 * scale) as u32).max(1), ((image.height() as f32 * scale) as u32).max(1));
+            image = image::imageops::resize(&image, w, h, image::imageops::FilterType::Triangle);
+        }
+        let gl = &self.gl;
+        let texture = gl.create_texture()?;
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RGBA8 as i32,
+            image.width() as i32,
+            image.height() as i32,
+            0,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            glow::PixelUnpackData::Slice(Some(image.as_raw())),
+        );
+        // 缩小时用 mipmap 三线性过滤，避免大图缩成缩略图时的锯齿与闪烁
+        gl.generate_mipmap(glow::TEXTURE_2D);
+        texture_parameters(gl, glow::LINEAR_MIPMAP_LINEAR, glow::LINEAR);
+        self.textures.insert(key, (texture, self.frames));
+        Ok(texture)
+    }
+
+    /// 纹理数超过上限时释放本帧没有用到、最久未用的那些
+    unsafe fn evict(&mut self) {
+        while self.textures.len() > MAX_TEXTURES {
+            let Some((&key, &(texture, used))) = self.textures.iter().min_by_key(|(_, (_, used))| *used) else {
+                break;
+            };
+            if used == self.frames {
+                break;
+            }
+            self.gl.delete_texture(texture);
+            self.textures.remove(&key);
+        }
+    }
+}
+
+impl Drop for Gpu {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_surface(self.display, self.surface);
+        let _ = self.egl.destroy_context(self.display, self.context);
+        let _ = self.egl.terminate(self.display);
+    }
+}
+
+unsafe fn texture_parameters(gl: &glow::Context, min_filter: u32, mag_filter: u32) {
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, min_filter as i32);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, mag_filter as i32);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
+}
+
+/// 可以交给 GPU 的图片命令的下标：GPU 在 CPU 画好的整帧之上合成图片，
+/// 所以只取之后没有任何命令与之重叠的图片，被后续命令覆盖的图片仍由 CPU 按顺序绘制
+fn offloadable(render: &RenderEnvelope) -> Vec<usize> {
+    let window_right = render.window.width as i32;
+    render
+        .commands
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| matches!(c, Command::Image { x: Some(_), y: Some(_), w: Some(_), h: Some(_), src_type: Some(_), src: Some(_), .. }))
+        .filter(|(i, image)| {
+            let Some(area) = diff::bounds(image) else {
+                return false;
+            };
+            render.commands[i + 1..].iter().all(|later| !overlaps(area, later, window_right))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn overlaps((l, t, r, b): (i32, i32, i32, i32), later: &Command, window_right: i32) -> bool {
+    let margin = renderer::font_size_px() as i32;
+    let later = match later {
+        // 竖排文本按估算的外接框再留一个字号的余量
+        Command::Text { orientation, .. } if validator::is_vertical(orientation) => {
+            diff::bounds(later).map(|(l, t, r, b)| (l - margin, t - margin, r + margin, b + margin))
+        }
+        // 文本的外接框只是估算：横向按一直延伸到窗口右边缘、纵向按每行两个字号处理
+        Command::Text { x, y, text, .. } => {
+            let rows = text.lines().count().max(1) as i32;
+            Some((x - margin, y - margin, window_right, y + rows * margin * 2))
+        }
+        // clear 等没有外接框的命令覆盖整个窗口
+        _ => diff::bounds(later),
+    };
+    later.is_none_or(|(ll, lt, lr, lb)| ll < r && lt < b && lr > l && lb > t)
+}
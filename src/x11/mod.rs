@@ -1,3 +1,11 @@
+pub mod animation;
 pub mod backend;
+pub mod buffer_pool;
 pub mod events;
-pub mod renderer;
\ No newline at end of file
+#[cfg(feature = "gl")]
+pub mod gl;
+pub mod raster_cache;
+pub mod renderer;
+pub mod selection;
+#[cfg(feature = "skia")]
+pub mod skia;
\ No newline at end of file
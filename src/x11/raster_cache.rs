@@ -0,0 +1,155 @@
+//! 离屏渲染的缓存：评估循环中相邻草稿（以及事件前的当前画面）大多数命令相同，
+//! 从最接近的已缓存帧出发，只重新光栅化变化的区域。
+
+use std::cell::RefCell;
+use std::error::Error;
+
+use sha2::{Digest, Sha256};
+
+use crate::dsl::diff;
+use crate::dsl::model::{Command, Point, RenderEnvelope};
+use crate::dsl::theme;
+use crate::images;
+use crate::metrics;
+use crate::x11::buffer_pool::FrameBuffer;
+use crate::x11::renderer::{self, Region};
+
+/// 每个线程保留的最近几帧
+const CACHE_ENTRIES: usize = 4;
+
+struct CachedFrame {
+    hash: [u8; 32],
+    render: RenderEnvelope,
+    width: usize,
+    height: usize,
+    pixels: FrameBuffer,
+}
+
+thread_local! {
+    /// 生成任务在各自的线程上跑完整个评估循环，缓存按线程保存，不需要加锁
+    static FRAMES: RefCell<Vec<CachedFrame>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 与 `renderer::render_to_buffer` 结果相同，但复用本线程最近渲染过的帧：
+/// 内容完全相同时直接返回缓存的像素，否则只重画与最接近的缓存帧相比变化的区域
+pub fn render(
+    render: &RenderEnvelope,
+    primary: Option<&fontdue::Font>,
+    emoji: Option<&fontdue::Font>,
+) -> Result<(usize, usize, FrameBuffer), Box<dyn Error>> {
+    // 离屏渲染都在工作线程上，远程图片先下载好，评估模型看到的是真实图片而不是占位框
+    images::prefetch(render);
+    let hash: [u8; 32] = Sha256::digest(serde_json::to_vec(render)?).into();
+    let reused = FRAMES.with_borrow(|frames| {
+        if let Some(hit) = frames.iter().find(|f| f.hash == hash) {
+            return Some((hit.width, hit.height, hit.pixels.clone(), None));
+        }
+        // 高对比度模式按文字下方的实际像素调整颜色，区域边缘的文字会与整帧渲染不同
+        if theme::high_contrast() {
+            return None;
+        }
+        frames
+            .iter()
+            .filter(|f| f.render.window == render.window)
+            .filter_map(|f| Some((f, diff::diff(&f.render, render).dirty_rect(&render.window)?)))
+            .min_by_key(|(_, (_, _, w, h))| *w as u64 * *h as u64)
+            .map(|(f, dirty)| (f.width, f.height, f.pixels.clone(), Some(dirty)))
+    });
+    let (width, height, pixels) = match reused {
+        Some((width, height, pixels, None)) => {
+            metrics::inc("agd_raster_cache_total", &[("result", "hit")], 1.0);
+            return Ok((width, height, pixels));
+        }
+        Some((width, height, mut pixels, Some(dirty))) if (dirty.2 as usize) * (dirty.3 as usize) < width * height => {
+            metrics::inc("agd_raster_cache_total", &[("result", "partial")], 1.0);
+            redraw_region(render, dirty, &mut pixels, width, primary, emoji)?;
+            (width, height, pixels)
+        }
+        _ => {
+            metrics::inc("agd_raster_cache_total", &[("result", "miss")], 1.0);
+            renderer::render_to_buffer(render, primary, emoji)?
+        }
+    };
+    FRAMES.with_borrow_mut(|frames| {
+        if frames.len() >= CACHE_ENTRIES {
+            frames.remove(0);
+        }
+        frames.push(CachedFrame { hash, render: render.clone(), width, height, pixels: pixels.clone() });
+    });
+    Ok((width, height, pixels))
+}
+
+/// 把整个信封平移到区域原点、以区域大小为窗口渲染，再贴回整帧：
+/// 区域内与整帧渲染逐像素一致，区域外的命令被绘制函数自然裁掉
+fn redraw_region(
+    render: &RenderEnvelope,
+    (x, y, w, h): Region,
+    pixels: &mut [u8],
+    width: usize,
+    primary: Option<&fontdue::Font>,
+    emoji: Option<&fontdue::Font>,
+) -> Result<(), Box<dyn Error>> {
+    let mut local = render.clone();
+    local.window.width = w;
+    local.window.height = h;
+    for command in &mut local.commands {
+        translate(command, -x, -y);
+    }
+    let (_, _, patch) = renderer::render_to_buffer(&local, primary, emoji)?;
+    let row = w as usize * 4;
+    for (i, line) in patch.chunks_exact(row).enumerate() {
+        let start = ((y as usize + i) * width + x as usize) * 4;
+        pixels[start..start + row].copy_from_slice(line);
+    }
+    Ok(())
+}
+
+fn translate(command: &mut Command, dx: i32, dy: i32) {
+    let shift = |v: &mut Option<i32>, d: i32| {
+        if let Some(v) = v {
+            *v += d;
+        }
+    };
+    let shift_points = |points: &mut Option<Vec<Point>>| {
+        for p in points.iter_mut().flatten() {
+            p.x += dx;
+            p.y += dy;
+        }
+    };
+    match command {
+        Command::Clear { .. } => {}
+        Command::Rect { x, y, .. } | Command::Text { x, y, .. } | Command::Terminal { x, y, .. } | Command::Input { x, y, .. }
+        | Command::Slider { x, y, .. }
+        | Command::Select { x, y, .. } => {
+            *x += dx;
+            *y += dy;
+        }
+        Command::Toast { frame, .. } | Command::Dialog { frame, .. } => {
+            if let Some((x, y, _, _)) = frame {
+                *x += dx;
+                *y += dy;
+            }
+        }
+        Command::Line { x1, y1, x2, y2, .. } => {
+            *x1 += dx;
+            *y1 += dy;
+            *x2 += dx;
+            *y2 += dy;
+        }
+        Command::Circle { cx, cy, .. } | Command::Ellipse { cx, cy, .. } | Command::Arc { cx, cy, .. } => {
+            shift(cx, dx);
+            shift(cy, dy);
+        }
+        Command::RoundRect { x, y, .. } | Command::Image { x, y, .. } => {
+            shift(x, dx);
+            shift(y, dy);
+        }
+        Command::Polyline { points, .. } | Command::Polygon { points, .. } => shift_points(points),
+        Command::Path { segments, .. } => {
+            for segment in segments.iter_mut().flatten() {
+                shift(&mut segment.x, dx);
+                shift(&mut segment.y, dy);
+            }
+        }
+    }
+}
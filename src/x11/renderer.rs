@@ -1,38 +1,124 @@
+use std::borrow::Cow;
 use std::error::Error;
-use base64::{Engine as _, engine::general_purpose};
+use std::sync::OnceLock;
 use x11rb::connection::Connection;
-use x11rb::protocol::xproto::{
-    Char2b, ConnectionExt, ImageFormat,
-};
+use x11rb::protocol::xproto::{ConnectionExt, ImageFormat, ImageOrder};
+use crate::dsl::diff;
 use crate::dsl::model::{Command, Point, PathSegment, RenderEnvelope};
-use crate::x11::backend::X11Backend;
+use crate::config;
+use crate::datasource;
+use crate::images;
+use crate::terminal;
+use crate::dialog;
+use crate::input;
+use crate::toast;
+use crate::dsl::{theme, validator};
+use crate::x11::backend::{self, PresentedFrame, X11Backend};
+use crate::x11::buffer_pool::FrameBuffer;
+#[cfg(feature = "skia")]
+use crate::x11::skia;
 
 /// 渲染一帧到 X11 窗口
 pub fn render_frame(backend: &X11Backend, render: &RenderEnvelope) -> Result<(), Box<dyn Error>> {
-    let conn = backend.connection();
-    let window = backend.window();
-    let gc = backend.gc();
-
+    // GPU 呈现时整帧已经上屏，这里拿到的是读回的合成结果
+    #[cfg(feature = "gl")]
+    let presented = match backend.gl_presenter() {
+        Some(gl) => gl.present(render, backend.font_primary(), backend.font_emoji())?,
+        None => None,
+    };
+    #[cfg(not(feature = "gl"))]
+    let presented: Option<(usize, usize, FrameBuffer)> = None;
+    let on_screen = presented.is_some();
     // 我们先在内存中生成完整的位图，然后一次性发给 X11，这样可以保持显示和“草稿截图”完全一致
-    let (width, height, pixels) = render_to_buffer(render, backend.font_primary(), backend.font_emoji())?;
+    let (width, height, pixels) = match presented {
+        Some(frame) => frame,
+        None => render_to_buffer(render, backend.font_primary(), backend.font_emoji())?,
+    };
 
-    conn.put_image(
-        ImageFormat::Z_PIXMAP,
-        window,
-        gc,
-        width as u16,
-        height as u16,
-        0,
-        0,
-        0,
-        backend.depth(),
-        &pixels,
-    )?;
+    // 与上一帧比较，只提交变化的区域；没有上一帧或内容相同（重绘请求）时提交整帧
+    // 新的一帧让旧选区失效；屏幕上残留的高亮并入本次提交的区域
+    let stale_selection = backend.selection().forget();
+    let previous = backend.replace_last_frame(None);
+    let background = window_background(render);
+    if let Some(rgb) = background.filter(|_| previous.as_ref().map(|last| window_background(&last.render)) != Some(background)) {
+        backend.set_background(rgb)?;
+    }
+    let region = previous
+        .and_then(|last| diff::diff(&last.render, render).dirty_rect(&render.window))
+        .map(|dirty| stale_selection.map_or(dirty, |stale| union_rect(dirty, stale)));
+    let (x, y, w, h) = region.unwrap_or((0, 0, width as u32, height as u32));
+    if on_screen {
+        // 已由 GPU 整帧交换上屏
+    } else if (x, y, w as usize, h as usize) == (0, 0, width, height) {
+        put_pixels(backend, &pixels, 0, 0, w, h)?;
+    } else {
+        put_pixels(backend, &copy_region(&pixels, width, x, y, w, h), x, y, w, h)?;
+    }
+    let glyphs = layout_glyphs(render, backend.font_primary(), backend.font_emoji());
+    backend.replace_last_frame(Some(PresentedFrame { render: render.clone(), width, height, pixels, glyphs }));
 
-    Connection::flush(conn)?;
+    Connection::flush(backend.connection())?;
     Ok(())
 }
 
+/// 窗口背景色：`window.background`，未设置时取第一条 clear 的颜色，使首帧之前和拉大窗口时不露出白底
+pub fn window_background(render: &RenderEnvelope) -> Option<(u8, u8, u8)> {
+    let color = render.window.background.as_deref().or_else(|| {
+        render.commands.iter().find_map(|c| match c {
+            Command::Clear { color } => Some(color.as_str()),
+            _ => None,
+        })
+    })?;
+    theme::parse_rgb(color)
+}
+
+/// 默认按下反馈的描边颜色与粗细
+const PRESS_COLOR: (u8, u8, u8) = (32, 32, 32);
+const PRESS_THICKNESS: u32 = 2;
+/// ripple 叠加色的不透明度
+const RIPPLE_ALPHA: f32 = 0.35;
+
+/// 可点击 rect 的按下反馈，对应 rect 的 `press_style` / `press_color`
+#[derive(Debug, Clone, Copy)]
+pub enum PressStyle {
+    Outline((u8, u8, u8)),
+    Invert,
+    Darken,
+    /// 从中心扩散的半透明圆，分几帧播放
+    Ripple((u8, u8, u8)),
+}
+
+impl PressStyle {
+    /// 读取目标 rect 声明的样式；未声明时为深灰描边
+    pub fn for_target(render: &RenderEnvelope, target_id: &str) -> Self {
+        let (style, color) = render
+            .commands
+            .iter()
+            .find_map(|c| match c {
+                Command::Rect { id: Some(id), press_style, press_color, .. } if id == target_id => {
+                    Some((press_style.as_deref(), press_color.as_deref()))
+                }
+                _ => None,
+            })
+            .unwrap_or((None, None));
+        let color = color.and_then(theme::parse_rgb).unwrap_or(PRESS_COLOR);
+        match style {
+            Some("invert") => PressStyle::Invert,
+            Some("darken") => PressStyle::Darken,
+            Some("ripple") => PressStyle::Ripple(color),
+            _ => PressStyle::Outline(color),
+        }
+    }
+
+    pub fn is_animated(self) -> bool {
+        matches!(self, PressStyle::Ripple(_))
+    }
+}
+
+/// 按下反馈：在缓存的当前帧像素上只重画目标所在的一小块，不重新光栅化整个画面。
+/// 窗口显示的不是这份 render（或还没有缓存）时先整帧渲染一次。
+/// `progress`（0..=1）是动画样式的播放进度
+#[allow(clippy::too_many_arguments)]
 pub fn render_frame_with_press(
     backend: &X11Backend,
     render: &RenderEnvelope,
@@ -40,57 +126,435 @@ pub fn render_frame_with_press(
     y: i32,
     w: u32,
     h: u32,
+    style: PressStyle,
+    progress: f32,
 ) -> Result<(), Box<dyn Error>> {
-    let conn = backend.connection();
-    let window = backend.window();
-    let gc = backend.gc();
-    let (width, height, mut pixels) = render_to_buffer(render, backend.font_primary(), backend.font_emoji())?;
+    let showing = backend.with_last_frame(|frame| diff::diff(&frame.render, render).is_empty()).unwrap_or(false);
+    if !showing {
+        render_frame(backend, render)?;
+    }
+    let patch = backend
+        .with_last_frame(|frame| {
+            // 描边画到 rect 边缘外 1px
+            let (rx, ry, rw, rh) = clip_region(x - 1, y - 1, w + 2, h + 2, frame.width, frame.height)?;
+            let mut patch = copy_region(&frame.pixels, frame.width, rx, ry, rw, rh);
+            apply_press(&mut patch, rw as usize, rh as usize, x - rx, y - ry, w, h, style, progress);
+            Some((rx, ry, rw, rh, patch))
+        })
+        .flatten();
+    if let Some((rx, ry, rw, rh, patch)) = patch {
+        put_pixels(backend, &patch, rx, ry, rw, rh)?;
+        Connection::flush(backend.connection())?;
+    }
+    Ok(())
+}
+
+/// 在像素块上画按下效果；(x, y, w, h) 是目标 rect 在块内的位置
+#[allow(clippy::too_many_arguments)]
+fn apply_press(p: &mut [u8], pw: usize, ph: usize, x: i32, y: i32, w: u32, h: u32, style: PressStyle, progress: f32) {
+    let (cx, cy) = (x as f32 + w as f32 / 2.0, y as f32 + h as f32 / 2.0);
+    let radius = progress.clamp(0.0, 1.0) * (w as f32).hypot(h as f32) / 2.0;
+    for py in y.max(0)..(y + h as i32).min(ph as i32) {
+        for px in x.max(0)..(x + w as i32).min(pw as i32) {
+            let idx = (py as usize * pw + px as usize) * 4;
+            match style {
+                PressStyle::Outline(_) => return,
+                PressStyle::Invert => {
+                    for channel in &mut p[idx..idx + 3] {
+                        *channel = 255 - *channel;
+                    }
+                }
+                PressStyle::Darken => {
+                    for channel in &mut p[idx..idx + 3] {
+                        *channel = (*channel as u16 * 3 / 4) as u8;
+                    }
+                }
+                PressStyle::Ripple((r, g, b)) => {
+                    if (px as f32 + 0.5 - cx).hypot(py as f32 + 0.5 - cy) <= radius {
+                        for (channel, target) in p[idx..idx + 3].iter_mut().zip([b, g, r]) {
+                            *channel = (*channel as f32 * (1.0 - RIPPLE_ALPHA) + target as f32 * RIPPLE_ALPHA) as u8;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if let PressStyle::Outline(color) = style {
+        draw_rect_outline(p, pw, ph, x, y, w, h, color, PRESS_THICKNESS);
+    }
+}
+
+/// 撤掉按下反馈：把目标区域恢复成缓存的当前帧像素
+pub fn release_press(backend: &X11Backend, x: i32, y: i32, w: u32, h: u32) -> Result<(), Box<dyn Error>> {
+    let patch = backend
+        .with_last_frame(|frame| {
+            let (rx, ry, rw, rh) = clip_region(x - 1, y - 1, w + 2, h + 2, frame.width, frame.height)?;
+            Some((rx, ry, rw, rh, copy_region(&frame.pixels, frame.width, rx, ry, rw, rh)))
+        })
+        .flatten();
+    if let Some((rx, ry, rw, rh, patch)) = patch {
+        put_pixels(backend, &patch, rx, ry, rw, rh)?;
+        Connection::flush(backend.connection())?;
+    }
+    Ok(())
+}
+
+/// 窗口内的矩形区域 (x, y, w, h)
+pub type Region = (i32, i32, u32, u32);
+
+/// 选区高亮的颜色与不透明度
+const SELECTION_COLOR: (u8, u8, u8) = (59, 130, 246);
+const SELECTION_ALPHA: f32 = 0.4;
+
+/// 文本选区高亮：在缓存的当前帧像素上给选中的字形盒子叠加半透明底色。
+/// 只提交新选区与 `stale`（上次高亮的范围）的并集，`selected` 为空时就是撤掉旧高亮；
+/// 返回这次高亮覆盖的范围，供下次更新时恢复
+pub fn render_selection(
+    backend: &X11Backend,
+    selected: &[GlyphBox],
+    stale: Option<Region>,
+) -> Result<Option<Region>, Box<dyn Error>> {
+    let covered = selected.iter().map(|g| (g.x, g.y, g.w, g.h)).reduce(union_rect);
+    let Some(region) = [covered, stale].into_iter().flatten().reduce(union_rect) else {
+        return Ok(None);
+    };
+    let patch = backend
+        .with_last_frame(|frame| {
+            let (rx, ry, rw, rh) = clip_region(region.0, region.1, region.2, region.3, frame.width, frame.height)?;
+            let mut patch = copy_region(&frame.pixels, frame.width, rx, ry, rw, rh);
+            for glyph in selected {
+                tint_rect(&mut patch, rw as usize, rh as usize, glyph.x - rx, glyph.y - ry, glyph.w, glyph.h, SELECTION_COLOR, SELECTION_ALPHA);
+            }
+            Some((rx, ry, rw, rh, patch))
+        })
+        .flatten();
+    if let Some((rx, ry, rw, rh, patch)) = patch {
+        put_pixels(backend, &patch, rx, ry, rw, rh)?;
+        Connection::flush(backend.connection())?;
+    }
+    Ok(covered)
+}
+
+/// 一个已排布的字符：第 `command` 条命令（text）第 `line` 行的 `ch`，
+/// 盒子宽为前进宽度、高为行高，与 draw_text 的排布一致
+#[derive(Debug, Clone, Copy)]
+pub struct GlyphBox {
+    pub command: usize,
+    pub line: usize,
+    pub ch: char,
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+}
+
+/// 按阅读顺序（命令、行、字符）排布全部 text 命令的字形盒子，用于拖选文本；没有字体时为空
+pub fn layout_glyphs(render: &RenderEnvelope, primary: Option<&fontdue::Font>, emoji: Option<&fontdue::Font>) -> Vec<GlyphBox> {
+    let Some(primary) = primary else {
+        return Vec::new();
+    };
+    let mut glyphs = Vec::new();
+    for (command, c) in render.commands.iter().enumerate() {
+        let Command::Text { x, y, text, w, h, fit, orientation, rotate, .. } = c else { continue };
+        if validator::is_vertical(orientation) {
+            let cells = vertical_cells(text, *x, *y, font_size_px(), rotate.unwrap_or(0), primary, emoji);
+            glyphs.extend(cells.into_iter().map(|c| GlyphBox { command, line: c.line, ch: c.ch, x: c.x, y: c.y, w: c.w, h: c.h }));
+            continue;
+        }
+        let fitted = fit_text(text, *x, *y, *w, *h, fit.as_deref(), Some(primary), emoji);
+        let size = fitted.size;
+        let line_height = line_height_px(primary, size);
+        for (line, content) in fitted.text.lines().enumerate() {
+            if content.trim().is_empty() {
+                continue;
+            }
+            let top = y + line as i32 * line_height;
+            let mut cursor_x = *x as f32;
+            for ch in content.chars() {
+                let font = if primary.lookup_glyph_index(ch) != 0 { primary } else { emoji.unwrap_or(primary) };
+                let advance = font.metrics(ch, size).advance_width;
+                let left = cursor_x as i32;
+                let w = ((cursor_x + advance) as i32 - left).max(1) as u32;
+                glyphs.push(GlyphBox { command, line, ch, x: left, y: top, w, h: line_height.max(1) as u32 });
+                cursor_x += advance;
+            }
+        }
+    }
+    glyphs
+}
+
+/// `measure_text` 的结果：按最大宽度折行后的各行、最宽一行的宽度、行高与总高度（像素）
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMetrics {
+    pub lines: Vec<String>,
+    pub width: i32,
+    pub line_height: i32,
+    pub height: i32,
+}
+
+/// 进程内共享的字体，供不持有 backend 的测量调用方（校验、布局检查）使用；只加载一次
+fn shared_fonts() -> &'static (Option<fontdue::Font>, Option<fontdue::Font>) {
+    static FONTS: OnceLock<(Option<fontdue::Font>, Option<fontdue::Font>)> = OnceLock::new();
+    FONTS.get_or_init(backend::load_fonts)
+}
+
+/// 按与 draw_text 相同的字形排布测量文本。给出 `max_width` 时每行在空格处或 CJK 字符前后折行，
+/// 单个词比 `max_width` 还宽时按字符断开；没有可用字体时半角字符按 0.5em、全角按 1em 估算
+pub fn measure_text(text: &str, size: f32, max_width: Option<f32>) -> TextMetrics {
+    let (primary, emoji) = shared_fonts();
+    measure_with(primary.as_ref(), emoji.as_ref(), text, size, max_width)
+}
 
-    // Local-only pressed feedback: emphasize the clicked rect with a bold outline.
-    let press_color = (32u8, 32u8, 32u8);
-    let press_thickness = 2u32;
-    draw_rect_outline(&mut pixels, width, height, x, y, w, h, press_color, press_thickness);
+fn measure_with(primary: Option<&fontdue::Font>, emoji: Option<&fontdue::Font>, text: &str, size: f32, max_width: Option<f32>) -> TextMetrics {
+    let advance = |ch: char| match primary {
+        Some(primary) => {
+            let font = if primary.lookup_glyph_index(ch) != 0 { primary } else { emoji.unwrap_or(primary) };
+            font.metrics(ch, size).advance_width
+        }
+        None if is_wide(ch) => size,
+        None => size * 0.5,
+    };
+    let line_height = primary.map(|font| line_height_px(font, size)).unwrap_or((size * 1.5) as i32);
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        match max_width {
+            Some(max_width) => wrap_line(line, max_width, &advance, &mut lines),
+            None => lines.push(line.to_string()),
+        }
+    }
+    let width = lines.iter().map(|line| line.chars().map(&advance).sum::<f32>().ceil() as i32).max().unwrap_or(0);
+    let height = line_height * lines.len() as i32;
+    TextMetrics { lines, width, line_height, height }
+}
+
+/// `shrink` 最多缩到的字号
+const MIN_FIT_SIZE: f32 = 8.0;
+/// `ellipsis` 截断时追加的省略号
+const ELLIPSIS: char = '…';
+
+/// 按 `fit` 排好的文本：实际绘制的内容、字号，以及 `clip` / `shrink` 时的裁剪框
+#[derive(Debug, Clone)]
+pub struct FittedText<'a> {
+    pub text: Cow<'a, str>,
+    pub size: f32,
+    pub clip: Option<(i32, i32, u32, u32)>,
+}
+
+/// 让 text 命令适应它的 `w` / `h` 文字框：`shrink` 逐步缩小字号直到放下（最小 8px，仍放不下时裁掉），
+/// `ellipsis` 截断过宽的行并丢弃放不下的行，末行以省略号结尾，`clip` 原样绘制但只保留框内部分。
+/// 没有 `fit` 时与原来一样按全局字号绘制；绘制、拖选与溢出检查共用这一排布
+#[allow(clippy::too_many_arguments)]
+pub fn fit_text<'a>(
+    text: &'a str, x: i32, y: i32,
+    w: Option<u32>, h: Option<u32>, fit: Option<&str>,
+    primary: Option<&fontdue::Font>, emoji: Option<&fontdue::Font>
+) -> FittedText<'a> {
+    let size = font_size_px();
+    let unchanged = FittedText { text: Cow::Borrowed(text), size, clip: None };
+    let Some(fit) = fit.filter(|_| w.is_some() || h.is_some()) else {
+        return unchanged;
+    };
+    let (max_w, max_h) = (w.map_or(f32::INFINITY, |w| w as f32), h.map_or(i32::MAX, |h| h as i32));
+    let clip = Some((x, y, w.unwrap_or(u32::MAX / 2), h.unwrap_or(u32::MAX / 2)));
+    let fits = |m: &TextMetrics| m.width as f32 <= max_w && m.height <= max_h;
+    match fit {
+        "shrink" => {
+            let measured = measure_with(primary, emoji, text, size, None);
+            if fits(&measured) {
+                return unchanged;
+            }
+            let scale = (max_w / measured.width.max(1) as f32).min(max_h as f32 / measured.height.max(1) as f32);
+            let mut size = (size * scale).floor().clamp(MIN_FIT_SIZE, size);
+            while size > MIN_FIT_SIZE && !fits(&measure_with(primary, emoji, text, size, None)) {
+                size -= 1.0;
+            }
+            FittedText { text: Cow::Borrowed(text), size, clip }
+        }
+        "ellipsis" => {
+            let measured = measure_with(primary, emoji, text, size, None);
+            if fits(&measured) {
+                return unchanged;
+            }
+            let width_of = |line: &str| measure_with(primary, emoji, line, size, None).width as f32;
+            let rows = (max_h / measured.line_height.max(1)).max(1) as usize;
+            let total = measured.lines.len();
+            let lines: Vec<String> = measured
+                .lines
+                .into_iter()
+                .take(rows)
+                .enumerate()
+                .map(|(i, line)| {
+                    let cut_below = i + 1 == rows && total > rows;
+                    if !cut_below && width_of(&line) <= max_w {
+                        return line;
+                    }
+                    let mut kept: Vec<char> = line.trim_end().chars().collect();
+                    loop {
+                        let candidate: String = kept.iter().chain(std::iter::once(&ELLIPSIS)).collect();
+                        if kept.is_empty() || width_of(&candidate) <= max_w {
+                            return candidate;
+                        }
+                        kept.pop();
+                        while kept.last().is_some_and(|c| c.is_whitespace()) {
+                            kept.pop();
+                        }
+                    }
+                })
+                .collect();
+            FittedText { text: Cow::Owned(lines.join("\n")), size, clip: None }
+        }
+        "clip" => FittedText { text: Cow::Borrowed(text), size, clip },
+        _ => unchanged,
+    }
+}
+
+/// CJK 等全角字符：前后都可以折行
+fn is_wide(ch: char) -> bool {
+    (ch as u32) >= 0x2E80
+}
+
+/// 贪心折行：放不下下一个字符时退回到最近的折行点（空格之后、全角字符前后），没有折行点就在当前字符前断开
+fn wrap_line(line: &str, max_width: f32, advance: &impl Fn(char) -> f32, lines: &mut Vec<String>) {
+    let mut current = String::new();
+    let mut width = 0.0;
+    let mut break_at: Option<usize> = None;
+    for ch in line.chars() {
+        let w = advance(ch);
+        if !ch.is_whitespace() && width + w > max_width && !current.trim().is_empty() {
+            let split = break_at.filter(|i| *i > 0).unwrap_or(current.len());
+            let rest = current.split_off(split);
+            lines.push(current.trim_end().to_string());
+            current = rest.trim_start().to_string();
+            width = current.chars().map(advance).sum();
+            break_at = None;
+        }
+        if is_wide(ch) && !current.is_empty() {
+            break_at = Some(current.len());
+        }
+        current.push(ch);
+        width += w;
+        if ch.is_whitespace() || is_wide(ch) {
+            break_at = Some(current.len());
+        }
+    }
+    lines.push(current.trim_end().to_string());
+}
+
+/// 两个区域的外接矩形
+pub fn union_rect(a: Region, b: Region) -> Region {
+    let (left, top) = (a.0.min(b.0), a.1.min(b.1));
+    let right = (a.0 + a.2 as i32).max(b.0 + b.2 as i32);
+    let bottom = (a.1 + a.3 as i32).max(b.1 + b.3 as i32);
+    (left, top, (right - left) as u32, (bottom - top) as u32)
+}
+
+/// 裁剪到帧内的区域；完全在帧外时返回 None
+pub fn clip_region(x: i32, y: i32, w: u32, h: u32, width: usize, height: usize) -> Option<(i32, i32, u32, u32)> {
+    let (left, top) = (x.max(0), y.max(0));
+    let (right, bottom) = ((x + w as i32).min(width as i32), (y + h as i32).min(height as i32));
+    (right > left && bottom > top).then(|| (left, top, (right - left) as u32, (bottom - top) as u32))
+}
+
+/// 从整帧缓冲中复制出一个矩形区域（区域须在帧内）
+pub fn copy_region(pixels: &[u8], stride_width: usize, x: i32, y: i32, w: u32, h: u32) -> Vec<u8> {
+    let (x, w) = (x as usize, w as usize);
+    let mut region = Vec::with_capacity(w * h as usize * 4);
+    for row in y as usize..y as usize + h as usize {
+        let start = (row * stride_width + x) * 4;
+        region.extend_from_slice(&pixels[start..start + w * 4]);
+    }
+    region
+}
 
-    conn.put_image(
+/// 把一块紧密排列的 BGRA 像素提交到窗口的 (x, y)，按服务器的像素格式重新排列
+pub fn put_pixels(backend: &X11Backend, data: &[u8], x: i32, y: i32, w: u32, h: u32) -> Result<(), Box<dyn Error>> {
+    let msb_first = backend.image_byte_order() == ImageOrder::MSB_FIRST;
+    let data = encode_rows(data, w as usize, h as usize, backend.bits_per_pixel(), backend.scanline_pad(), msb_first);
+    backend.connection().put_image(
         ImageFormat::Z_PIXMAP,
-        window,
-        gc,
-        width as u16,
-        height as u16,
-        0,
-        0,
+        backend.window(),
+        backend.gc(),
+        w as u16,
+        h as u16,
+        x as i16,
+        y as i16,
         0,
         backend.depth(),
-        &pixels,
+        &data,
     )?;
-
-    Connection::flush(conn)?;
     Ok(())
 }
 
+/// ZPixmap 的行布局：每像素占 `bits_per_pixel / 8` 字节（取像素值 B | G << 8 | R << 16 的低位字节），
+/// `msb_first` 时按大端顺序写出，每行补齐到 `scanline_pad` 位。小端 32 位、无需补齐时（最常见）原样返回
+fn encode_rows(data: &[u8], w: usize, h: usize, bits_per_pixel: u8, scanline_pad: u8, msb_first: bool) -> Cow<'_, [u8]> {
+    let bytes = (bits_per_pixel as usize / 8).clamp(1, 4);
+    let pad = (scanline_pad as usize / 8).max(1);
+    let stride = (w * bytes).div_ceil(pad) * pad;
+    if w == 0 || (bytes == 4 && stride == w * 4 && !msb_first) {
+        return Cow::Borrowed(data);
+    }
+    let mut out = vec![0; stride * h];
+    for (src, dst) in data.chunks_exact(w * 4).zip(out.chunks_exact_mut(stride)) {
+        for (px, slot) in src.chunks_exact(4).zip(dst.chunks_exact_mut(bytes)) {
+            slot.copy_from_slice(&px[..bytes]);
+            if msb_first {
+                slot.reverse();
+            }
+        }
+    }
+    Cow::Owned(out)
+}
+
 /// 核心逻辑：将所有指令渲染到一个像素缓冲区 (RGBA/BGRA)
 pub fn render_to_buffer(
     render: &RenderEnvelope,
     primary: Option<&fontdue::Font>,
     emoji: Option<&fontdue::Font>,
-) -> Result<(usize, usize, Vec<u8>), Box<dyn Error>> {
+) -> Result<(usize, usize, FrameBuffer), Box<dyn Error>> {
+    let with_inputs = input::expand(render);
+    let with_dialog = dialog::expand(&with_inputs);
+    let expanded = toast::expand(&with_dialog);
+    let render = expanded.as_ref();
     let width = render.window.width as usize;
     let height = render.window.height as usize;
-    let mut pixels = vec![0u8; width * height * 4];
+    let mut pixels = FrameBuffer::take(width * height * 4, 0);
 
     // 默认背景色（通常第一个指令是 Clear，但这里做个兜底）
     fill_rect(&mut pixels, width, height, 0, 0, width as u32, height as u32, (255, 255, 255));
 
-    for command in &render.commands {
+    // 带访问键的可点击 rect：落在其中的标签文本给对应字符加下划线
+    let access_keys: Vec<(i32, i32, u32, u32, char)> = render
+        .commands
+        .iter()
+        .filter_map(|c| match c {
+            Command::Rect { x, y, w, h, clickable: true, access_key: Some(key), .. } => {
+                Some((*x, *y, *w, *h, validator::access_key_char(key)?))
+            }
+            _ => None,
+        })
+        .collect();
+
+    // 高对比度模式：文字颜色与实际背景的对比度不足时改成黑或白
+    let min_contrast = theme::high_contrast().then(|| config::get().theme.min_contrast);
+
+    // 局部重绘时信封已平移到脏区域原点、窗口即脏区域，区域外的命令同样在这里被剔除
+    #[cfg(feature = "skia")]
+    let use_skia = config::get().rasterizer == "skia";
+    for command in render.commands.iter().filter(|c| visible(c, width, height)) {
+        #[cfg(feature = "skia")]
+        if use_skia && skia::draw(&mut pixels, width, height, command)? {
+            continue;
+        }
         match command {
             Command::Clear { color } => {
                 let rgb = parse_rgb(color)?;
                 fill_rect(&mut pixels, width, height, 0, 0, width as u32, height as u32, rgb_tuple(rgb));
             }
-            Command::Rect { x, y, w, h, fill, stroke, stroke_width, .. } => {
+            Command::Rect { x, y, w, h, fill, stroke, stroke_width, bind, progress, .. } => {
                 if let Some(fill_color) = fill {
                     let rgb = parse_rgb(fill_color)?;
-                    fill_rect(&mut pixels, width, height, *x, *y, *w, *h, rgb_tuple(rgb));
+                    fill_rect(&mut pixels, width, height, *x, *y, datasource::bar_width(*w, bind, *progress), *h, rgb_tuple(rgb));
                 }
                 if let Some(stroke_color) = stroke {
                     let rgb = parse_rgb(stroke_color)?;
@@ -98,15 +562,44 @@ pub fn render_to_buffer(
                     draw_rect_outline(&mut pixels, width, height, *x, *y, *w, *h, rgb_tuple(rgb), thickness);
                 }
             }
-            Command::Text { x, y, text, color, bg } => {
+            Command::Text { x, y, text, color, bg, w, h, fit, orientation, rotate, .. } => {
                 if let Some(font) = primary {
-                    let fg_rgb = rgb_tuple(parse_rgb(color.as_deref().unwrap_or("#000000"))?);
+                    let mut fg_rgb = rgb_tuple(parse_rgb(color.as_deref().unwrap_or("#000000"))?);
                     let bg_rgb = if let Some(bg_str) = bg {
                         Some(rgb_tuple(parse_rgb(bg_str)?))
                     } else {
                         None
                     };
-                    draw_text(&mut pixels, width, height, *x, *y, text, fg_rgb, bg_rgb, font, emoji);
+                    if validator::is_vertical(orientation) {
+                        let (size, rotate) = (font_size_px(), rotate.unwrap_or(0));
+                        let cells = vertical_cells(text, *x, *y, size, rotate, font, emoji);
+                        if let Some(min) = min_contrast {
+                            let area = cells.iter().fold(None, |area, c| {
+                                let cell = (c.x, c.y, c.x + c.w as i32, c.y + c.h as i32);
+                                Some(area.map_or(cell, |(l, t, r, b): (i32, i32, i32, i32)| (l.min(cell.0), t.min(cell.1), r.max(cell.2), b.max(cell.3))))
+                            });
+                            let (l, t, r, b) = area.unwrap_or((*x, *y, *x + 1, *y + 1));
+                            let effective_bg = bg_rgb.unwrap_or_else(|| average_under_text(&pixels, width, height, (l, t, r - l, b - t)));
+                            fg_rgb = theme::ensure_contrast(fg_rgb, effective_bg, min);
+                        }
+                        draw_vertical_text(&mut pixels, width, height, &cells, size, rotate, fg_rgb, bg_rgb, font, emoji);
+                        continue;
+                    }
+                    let fitted = fit_text(text, *x, *y, *w, *h, fit.as_deref(), Some(font), emoji);
+                    let text = fitted.text.as_ref();
+                    if let Some(min) = min_contrast {
+                        let measured = measure_with(Some(font), None, text, fitted.size, None);
+                        let area = (*x, *y, measured.width.max(1), measured.height.max(measured.line_height));
+                        let effective_bg = bg_rgb.unwrap_or_else(|| average_under_text(&pixels, width, height, area));
+                        fg_rgb = theme::ensure_contrast(fg_rgb, effective_bg, min);
+                    }
+                    draw_text(&mut pixels, width, height, *x, *y, text, fitted.size, fitted.clip, fg_rgb, bg_rgb, font, emoji);
+                    let inside = |&&(rx, ry, rw, rh, _): &&(i32, i32, u32, u32, char)| {
+                        *x >= rx && *y >= ry && *x < rx + rw as i32 && *y < ry + rh as i32
+                    };
+                    if let Some(&(.., key)) = access_keys.iter().rev().find(inside) {
+                        underline_mnemonic(&mut pixels, width, height, *x, *y, text, fitted.size, key, fg_rgb, font, emoji);
+                    }
                 }
             }
             Command::Line { x1, y1, x2, y2, color, width: line_width } => {
@@ -140,7 +633,7 @@ pub fn render_to_buffer(
                     }
                 }
             }
-            Command::RoundRect { x, y, w, h, r, fill, stroke, stroke_width } => {
+            Command::RoundRect { x, y, w, h, r, fill, stroke, stroke_width, .. } => {
                 if let (Some(x), Some(y), Some(w), Some(h), Some(r)) = (x, y, w, h, r) {
                     if let Some(fill_color) = fill {
                         let rgb = rgb_tuple(parse_rgb(fill_color)?);
@@ -153,11 +646,27 @@ pub fn render_to_buffer(
                     }
                 }
             }
-            Command::Arc { cx, cy, r, start_angle, end_angle, color, width: line_width } => {
+            Command::Arc { cx, cy, r, start_angle, end_angle, color, width: line_width, fill, pie } => {
                 if let (Some(cx), Some(cy), Some(r), Some(start), Some(end)) = (cx, cy, r, start_angle, end_angle) {
-                    let rgb = rgb_tuple(parse_rgb(color.as_deref().unwrap_or("#000000"))?);
+                    let r = *r as i32;
                     let thickness = line_width.unwrap_or(1);
-                    draw_arc(&mut pixels, width, height, *cx, *cy, *r as i32, *start, *end, rgb, thickness);
+                    // 扇形从圆心填起；环形段的内半径为 r - width
+                    let inner = if *pie { 0 } else { (r - thickness as i32).max(0) };
+                    if let Some(fill_color) = fill {
+                        let rgb = rgb_tuple(parse_rgb(fill_color)?);
+                        fill_sector(&mut pixels, width, height, *cx, *cy, inner, r, *start, *end, rgb);
+                    }
+                    // 有填充时只在显式给出 color 时描边；没有填充时沿用默认黑色描边
+                    if let Some(stroke_color) = color.as_deref().or(fill.is_none().then_some("#000000")) {
+                        let rgb = rgb_tuple(parse_rgb(stroke_color)?);
+                        if fill.is_some() || *pie {
+                            // 环形段的 width 是环宽，轮廓用 1px
+                            let outline = if *pie { thickness } else { 1 };
+                            draw_sector_outline(&mut pixels, width, height, *cx, *cy, inner, r, *start, *end, rgb, outline);
+                        } else {
+                            draw_arc(&mut pixels, width, height, *cx, *cy, r, *start, *end, rgb, thickness);
+                        }
+                    }
                 }
             }
             Command::Polyline { points, color, width: line_width } => {
@@ -180,7 +689,7 @@ pub fn render_to_buffer(
                     }
                 }
             }
-            Command::Image { x, y, w, h, src_type, src } => {
+            Command::Image { x, y, w, h, src_type, src, .. } => {
                 if let (Some(x), Some(y), Some(w), Some(h), Some(src_type), Some(src)) = (x, y, w, h, src_type, src) {
                     draw_image(&mut pixels, width, height, *x, *y, *w, *h, src_type, src)?;
                 }
@@ -207,28 +716,88 @@ pub fn render_to_buffer(
                     }
                 }
             }
+            Command::Terminal { x, y, w, h, font_size, color, bg, lines, cursor, .. } => {
+                let fg_rgb = rgb_tuple(parse_rgb(color.as_deref().unwrap_or("#D0D0D0"))?);
+                let bg_rgb = rgb_tuple(parse_rgb(bg.as_deref().unwrap_or("#101010"))?);
+                fill_rect(&mut pixels, width, height, *x, *y, *w, *h, bg_rgb);
+                if let Some(font) = primary {
+                    draw_terminal(&mut pixels, width, height, (*x, *y, *w, *h), lines, *cursor, *font_size, (fg_rgb, bg_rgb), font, emoji);
+                }
+                // 有键盘焦点时描出边框
+                if cursor.is_some() {
+                    draw_rect_outline(&mut pixels, width, height, *x, *y, *w, *h, fg_rgb, 1);
+                }
+            }
+            // 提示条与输入框、滑块、选择框在循环之前已由 toast::expand / input::expand 换成基本图元
+            Command::Toast { .. } | Command::Input { .. } | Command::Slider { .. } | Command::Select { .. } => {}
+            // 对话框的卡片与按钮已由 dialog::expand 插在其后，这里只压暗其下的画面
+            Command::Dialog { .. } => tint_rect(&mut pixels, width, height, 0, 0, width as u32, height as u32, (0, 0, 0), dialog::DIM_ALPHA),
         }
     }
 
     Ok((width, height, pixels))
 }
 
+/// 外接框（含描边）与画布不相交的命令整条跳过。文本的外接框只是估算，由 draw_text 逐行、逐字裁剪
+fn visible(command: &Command, width: usize, height: usize) -> bool {
+    match command {
+        Command::Text { .. } => true,
+        _ => diff::bounds(command).is_none_or(|(l, t, r, b)| r >= 0 && b >= 0 && l < width as i32 && t < height as i32),
+    }
+}
+
 // --- 基础绘图辅助函数 ---
 
+/// BGRA 像素打包成一个 u32（内存中的字节顺序为 B、G、R、A），整行填充时一次写一个像素
+fn pack_bgra((r, g, b): (u8, u8, u8)) -> u32 {
+    u32::from_ne_bytes([b, g, r, 0])
+}
+
+/// 先把矩形裁到画布内，再按行整段填充；矩形横跨整个画布宽度时各行连续，一次填完
+#[allow(clippy::too_many_arguments)]
 fn fill_rect(p: &mut [u8], pw: usize, ph: usize, x: i32, y: i32, w: u32, h: u32, rgb: (u8, u8, u8)) {
-    for iy in y..(y + h as i32) {
-        for ix in x..(x + w as i32) {
-            if ix >= 0 && ix < pw as i32 && iy >= 0 && iy < ph as i32 {
-                let idx = (iy as usize * pw + ix as usize) * 4;
-                p[idx] = rgb.2;     // B
-                p[idx + 1] = rgb.1; // G
-                p[idx + 2] = rgb.0; // R
-                p[idx + 3] = 0;     // Alpha
+    let left = (x as i64).max(0) as usize;
+    let right = (x as i64 + w as i64).clamp(0, pw as i64) as usize;
+    let top = (y as i64).max(0) as usize;
+    let bottom = (y as i64 + h as i64).clamp(0, ph as i64) as usize;
+    if left >= right || top >= bottom {
+        return;
+    }
+    let packed = pack_bgra(rgb);
+    if left == 0 && right == pw {
+        fill_words(&mut p[top * pw * 4..bottom * pw * 4], packed);
+        return;
+    }
+    for row in top..bottom {
+        fill_words(&mut p[(row * pw + left) * 4..(row * pw + right) * 4], packed);
+    }
+}
+
+fn fill_words(span: &mut [u8], packed: u32) {
+    match bytemuck::try_cast_slice_mut::<u8, u32>(span) {
+        Ok(words) => words.fill(packed),
+        // 缓冲区起点没有按 4 字节对齐（分配器通常会对齐）时逐像素复制
+        Err(_) => {
+            let bytes = packed.to_ne_bytes();
+            span.chunks_exact_mut(4).for_each(|px| px.copy_from_slice(&bytes));
+        }
+    }
+}
+
+/// 在矩形内按 `alpha` 叠加一层颜色（文本选区等半透明高亮）
+#[allow(clippy::too_many_arguments)]
+fn tint_rect(p: &mut [u8], pw: usize, ph: usize, x: i32, y: i32, w: u32, h: u32, (r, g, b): (u8, u8, u8), alpha: f32) {
+    for py in y.max(0)..(y + h as i32).min(ph as i32) {
+        for px in x.max(0)..(x + w as i32).min(pw as i32) {
+            let idx = (py as usize * pw + px as usize) * 4;
+            for (channel, target) in p[idx..idx + 3].iter_mut().zip([b, g, r]) {
+                *channel = (*channel as f32 * (1.0 - alpha) + target as f32 * alpha) as u8;
             }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_rect_outline(p: &mut [u8], pw: usize, ph: usize, x: i32, y: i32, w: u32, h: u32, rgb: (u8, u8, u8), t: u32) {
     for i in 0..t as i32 {
         draw_line(p, pw, ph, x, y + i, x + w as i32, y + i, rgb, 1); // Top
@@ -238,9 +807,13 @@ fn draw_rect_outline(p: &mut [u8], pw: usize, ph: usize, x: i32, y: i32, w: u32,
     }
 }
 
+/// 1px 线用 Bresenham；更粗的线按胶囊形整体填充，避免逐点印方块造成的锯齿边
+#[allow(clippy::too_many_arguments)]
 fn draw_line(p: &mut [u8], pw: usize, ph: usize, x1: i32, y1: i32, x2: i32, y2: i32, rgb: (u8, u8, u8), t: u32) {
-    let thickness = t.max(1) as i32;
-    let half = thickness / 2;
+    if t > 1 {
+        fill_capsule(p, pw, ph, x1, y1, x2, y2, rgb, t);
+        return;
+    }
     let mut x = x1;
     let mut y = y1;
     let dx = (x2 - x1).abs();
@@ -249,7 +822,7 @@ fn draw_line(p: &mut [u8], pw: usize, ph: usize, x1: i32, y1: i32, x2: i32, y2:
     let sy = if y1 < y2 { 1 } else { -1 };
     let mut err = dx + dy;
     loop {
-        fill_rect(p, pw, ph, x - half, y - half, thickness as u32, thickness as u32, rgb);
+        fill_rect(p, pw, ph, x, y, 1, 1, rgb);
         if x == x2 && y == y2 {
             break;
         }
@@ -265,6 +838,58 @@ fn draw_line(p: &mut [u8], pw: usize, ph: usize, x1: i32, y1: i32, x2: i32, y2:
     }
 }
 
+/// 填充到线段距离不超过半宽的像素（两端为半圆，折线的相邻段自然圆角相接）。
+/// 偶数宽度时带状区域向左上偏半个像素，与水平/竖直线的整数像素行列对齐
+#[allow(clippy::too_many_arguments)]
+fn fill_capsule(p: &mut [u8], pw: usize, ph: usize, x1: i32, y1: i32, x2: i32, y2: i32, rgb: (u8, u8, u8), t: u32) {
+    let half = t as f32 / 2.0;
+    let shift = if t.is_multiple_of(2) { -0.5 } else { 0.0 };
+    let (ax, ay) = (x1 as f32 + shift, y1 as f32 + shift);
+    let (bx, by) = (x2 as f32 + shift, y2 as f32 + shift);
+    let len = (bx - ax).hypot(by - ay);
+    // 线段两侧平移半宽得到的四边形，加上两端的圆
+    let quad = if len > 0.0 {
+        let (nx, ny) = (-(by - ay) / len * half, (bx - ax) / len * half);
+        Some([(ax + nx, ay + ny), (bx + nx, by + ny), (bx - nx, by - ny), (ax - nx, ay - ny)])
+    } else {
+        None
+    };
+    let min_y = ((ay.min(by) - half).floor() as i32).max(0);
+    let max_y = ((ay.max(by) + half).ceil() as i32).min(ph as i32 - 1);
+    for y in min_y..=max_y {
+        let row = y as f32;
+        // 胶囊是凸形，每行覆盖的是一段连续区间：取端点圆与四边形在该行上区间的并
+        let (mut lo, mut hi) = (f32::INFINITY, f32::NEG_INFINITY);
+        for (cx, cy) in [(ax, ay), (bx, by)] {
+            let d = row - cy;
+            if d.abs() <= half {
+                let w = (half * half - d * d).sqrt();
+                lo = lo.min(cx - w);
+                hi = hi.max(cx + w);
+            }
+        }
+        if let Some(quad) = &quad {
+            for i in 0..4 {
+                let ((qx, qy), (rx, ry)) = (quad[i], quad[(i + 1) % 4]);
+                if (qy <= row && row <= ry) || (ry <= row && row <= qy) {
+                    let (x, x_end) = if ry == qy {
+                        (qx.min(rx), qx.max(rx))
+                    } else {
+                        let x = qx + (row - qy) / (ry - qy) * (rx - qx);
+                        (x, x)
+                    };
+                    lo = lo.min(x);
+                    hi = hi.max(x_end);
+                }
+            }
+        }
+        let (start, end) = (lo.ceil() as i32, hi.floor() as i32);
+        if end >= start {
+            fill_rect(p, pw, ph, start, y, (end - start + 1) as u32, 1, rgb);
+        }
+    }
+}
+
 fn draw_polyline(p: &mut [u8], pw: usize, ph: usize, points: &[Point], rgb: (u8, u8, u8), t: u32) {
     for pair in points.windows(2) {
         draw_line(p, pw, ph, pair[0].x, pair[0].y, pair[1].x, pair[1].y, rgb, t);
@@ -285,8 +910,8 @@ fn fill_polygon(p: &mut [u8], pw: usize, ph: usize, points: &[Point], rgb: (u8,
     if points.len() < 3 {
         return;
     }
-    let min_y = points.iter().map(|pt| pt.y).min().unwrap_or(0);
-    let max_y = points.iter().map(|pt| pt.y).max().unwrap_or(0);
+    let min_y = points.iter().map(|pt| pt.y).min().unwrap_or(0).max(0);
+    let max_y = points.iter().map(|pt| pt.y).max().unwrap_or(0).min(ph as i32 - 1);
     for y in min_y..=max_y {
         let mut intersections = Vec::new();
         for i in 0..points.len() {
@@ -315,51 +940,112 @@ fn fill_polygon(p: &mut [u8], pw: usize, ph: usize, points: &[Point], rgb: (u8,
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_circle_outline(p: &mut [u8], pw: usize, ph: usize, cx: i32, cy: i32, r: i32, rgb: (u8, u8, u8), t: u32) {
-    draw_arc(p, pw, ph, cx, cy, r, 0.0, 360.0, rgb, t);
+    let outer = r + t as i32 / 2;
+    fill_ring(p, pw, ph, cx, cy, &circle_extents(outer), &circle_extents(outer - t as i32), rgb);
 }
 
 fn fill_circle(p: &mut [u8], pw: usize, ph: usize, cx: i32, cy: i32, r: i32, rgb: (u8, u8, u8)) {
-    let r2 = (r * r) as f32;
-    for dy in -r..=r {
-        let y = cy + dy;
-        let dx = (r2 - (dy * dy) as f32).sqrt() as i32;
-        fill_rect(p, pw, ph, cx - dx, y, (dx * 2 + 1) as u32, 1, rgb);
-    }
+    fill_ring(p, pw, ph, cx, cy, &circle_extents(r), &[], rgb);
 }
 
+/// 描边沿轮廓居中：外缘为半径加半个线宽，内缘再向内一个线宽
+#[allow(clippy::too_many_arguments)]
 fn draw_ellipse_outline(p: &mut [u8], pw: usize, ph: usize, cx: i32, cy: i32, rx: i32, ry: i32, rgb: (u8, u8, u8), t: u32) {
-    let mut angle: f32 = 0.0;
-    let step = 2.0_f32;
-    let mut prev = None;
-    while angle <= 360.0 {
-        let rad = angle.to_radians();
-        let x = cx + (rx as f32 * rad.cos()).round() as i32;
-        let y = cy + (ry as f32 * rad.sin()).round() as i32;
-        if let Some((px, py)) = prev {
-            draw_line(p, pw, ph, px, py, x, y, rgb, t);
+    let (outer_x, outer_y) = (rx + t as i32 / 2, ry + t as i32 / 2);
+    let inner = ellipse_extents(outer_x - t as i32, outer_y - t as i32);
+    fill_ring(p, pw, ph, cx, cy, &ellipse_extents(outer_x, outer_y), &inner, rgb);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn fill_ellipse(p: &mut [u8], pw: usize, ph: usize, cx: i32, cy: i32, rx: i32, ry: i32, rgb: (u8, u8, u8)) {
+    fill_ring(p, pw, ph, cx, cy, &ellipse_extents(rx, ry), &[], rgb);
+}
+
+/// 中点画圆法：`extents[dy]` 是轮廓在离圆心 dy 行处的最大 |dx|；半径为负时为空
+fn circle_extents(r: i32) -> Vec<i32> {
+    if r < 0 {
+        return Vec::new();
+    }
+    let mut extents = vec![0; r as usize + 1];
+    let (mut x, mut y, mut d) = (r, 0, 1 - r);
+    while x >= y {
+        // 每步得到八分圆上的一点，按对称性同时记录 (x, y) 与 (y, x)
+        extents[y as usize] = extents[y as usize].max(x);
+        extents[x as usize] = extents[x as usize].max(y);
+        y += 1;
+        if d < 0 {
+            d += 2 * y + 1;
+        } else {
+            x -= 1;
+            d += 2 * (y - x) + 1;
         }
-        prev = Some((x, y));
-        angle += step;
     }
+    extents
 }
 
-fn fill_ellipse(p: &mut [u8], pw: usize, ph: usize, cx: i32, cy: i32, rx: i32, ry: i32, rgb: (u8, u8, u8)) {
-    let rx2 = (rx * rx) as f32;
-    let ry2 = (ry * ry) as f32;
-    for dy in -ry..=ry {
-        let y = cy + dy;
-        let term = 1.0 - (dy * dy) as f32 / ry2;
-        if term < 0.0 {
-            continue;
+/// 中点椭圆法，含义同 `circle_extents`：先走斜率绝对值小于 1 的上半段，再走陡峭的侧边段
+fn ellipse_extents(rx: i32, ry: i32) -> Vec<i32> {
+    if rx < 0 || ry < 0 {
+        return Vec::new();
+    }
+    let mut extents = vec![0; ry as usize + 1];
+    let (rx2, ry2) = ((rx as i64 * rx as i64) as f64, (ry as i64 * ry as i64) as f64);
+    let (mut x, mut y) = (0i32, ry);
+    let (mut dx, mut dy) = (0.0, 2.0 * rx2 * y as f64);
+    let mut d = ry2 - rx2 * ry as f64 + rx2 / 4.0;
+    while dx < dy {
+        extents[y as usize] = extents[y as usize].max(x);
+        x += 1;
+        dx += 2.0 * ry2;
+        if d < 0.0 {
+            d += dx + ry2;
+        } else {
+            y -= 1;
+            dy -= 2.0 * rx2;
+            d += dx - dy + ry2;
+        }
+    }
+    d = ry2 * (x as f64 + 0.5).powi(2) + rx2 * (y as f64 - 1.0).powi(2) - rx2 * ry2;
+    while y >= 0 {
+        extents[y as usize] = extents[y as usize].max(x);
+        y -= 1;
+        dy -= 2.0 * rx2;
+        if d > 0.0 {
+            d += rx2 - dy;
+        } else {
+            x += 1;
+            dx += 2.0 * ry2;
+            d += dx - dy + rx2;
+        }
+    }
+    // 很扁的椭圆在上半段就会走到 y = 0，中线一行补到最宽处
+    extents[0] = rx;
+    extents
+}
+
+/// 按行填充外轮廓与内轮廓之间的像素（内轮廓为空时整块填充）。按行取区间，描边再粗也没有缝隙
+#[allow(clippy::too_many_arguments)]
+fn fill_ring(p: &mut [u8], pw: usize, ph: usize, cx: i32, cy: i32, outer: &[i32], inner: &[i32], rgb: (u8, u8, u8)) {
+    let rows = outer.len() as i32 - 1;
+    for dy in (-rows).max(-cy)..=rows.min(ph as i32 - 1 - cy) {
+        let out = outer[dy.unsigned_abs() as usize];
+        match inner.get(dy.unsigned_abs() as usize) {
+            Some(&ins) => {
+                // 内外轮廓在同一行重合时至少保留外缘一个像素
+                let ins = ins.min(out - 1);
+                fill_rect(p, pw, ph, cx - out, cy + dy, (out - ins) as u32, 1, rgb);
+                fill_rect(p, pw, ph, cx + ins + 1, cy + dy, (out - ins) as u32, 1, rgb);
+            }
+            None => fill_rect(p, pw, ph, cx - out, cy + dy, (out * 2 + 1) as u32, 1, rgb),
         }
-        let dx = (rx2 * term).sqrt() as i32;
-        fill_rect(p, pw, ph, cx - dx, y, (dx * 2 + 1) as u32, 1, rgb);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn fill_round_rect(p: &mut [u8], pw: usize, ph: usize, x: i32, y: i32, w: u32, h: u32, r: u32, rgb: (u8, u8, u8)) {
-    let r = r.min((w.min(h) / 2) as u32) as i32;
+    let r = r.min(w.min(h) / 2) as i32;
     let w_i = w as i32;
     let h_i = h as i32;
     fill_rect(p, pw, ph, x + r, y, (w_i - 2 * r).max(0) as u32, h, rgb);
@@ -371,8 +1057,9 @@ fn fill_round_rect(p: &mut [u8], pw: usize, ph: usize, x: i32, y: i32, w: u32, h
     fill_circle_quadrant(p, pw, ph, x + w_i - r - 1, y + h_i - r - 1, r, rgb, 1, 1);
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_round_rect_outline(p: &mut [u8], pw: usize, ph: usize, x: i32, y: i32, w: u32, h: u32, r: u32, rgb: (u8, u8, u8), t: u32) {
-    let r = r.min((w.min(h) / 2) as u32) as i32;
+    let r = r.min(w.min(h) / 2) as i32;
     let w_i = w as i32;
     let h_i = h as i32;
     draw_line(p, pw, ph, x + r, y, x + w_i - r - 1, y, rgb, t);
@@ -385,6 +1072,7 @@ fn draw_round_rect_outline(p: &mut [u8], pw: usize, ph: usize, x: i32, y: i32, w
     draw_arc(p, pw, ph, x + r, y + h_i - r - 1, r, 90.0, 180.0, rgb, t);
 }
 
+#[allow(clippy::too_many_arguments)]
 fn fill_circle_quadrant(
     p: &mut [u8],
     pw: usize,
@@ -398,14 +1086,18 @@ fn fill_circle_quadrant(
 ) {
     let r2 = (r * r) as f32;
     for dy in 0..=r {
-        let dx = (r2 - (dy * dy) as f32).sqrt() as i32;
         let y = cy + sy * dy;
+        if y < 0 || y >= ph as i32 {
+            continue;
+        }
+        let dx = (r2 - (dy * dy) as f32).sqrt() as i32;
         let x_start = if sx < 0 { cx - dx } else { cx };
         let width = dx + 1;
         fill_rect(p, pw, ph, x_start, y, width as u32, 1, rgb);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_arc(p: &mut [u8], pw: usize, ph: usize, cx: i32, cy: i32, r: i32, start_deg: f32, end_deg: f32, rgb: (u8, u8, u8), t: u32) {
     let mut angle = start_deg;
     let step = if end_deg >= start_deg { 1.0 } else { -1.0 };
@@ -422,6 +1114,46 @@ fn draw_arc(p: &mut [u8], pw: usize, ph: usize, cx: i32, cy: i32, r: i32, start_
     }
 }
 
+/// 填充扇形或环形段：圆心距在 [inner, r] 内、角度落在起止角之间的像素（按像素中心采样）
+#[allow(clippy::too_many_arguments)]
+fn fill_sector(p: &mut [u8], pw: usize, ph: usize, cx: i32, cy: i32, inner: i32, r: i32, start_deg: f32, end_deg: f32, rgb: (u8, u8, u8)) {
+    let (from, sweep) = if end_deg >= start_deg { (start_deg, end_deg - start_deg) } else { (end_deg, start_deg - end_deg) };
+    let (outer2, inner2) = ((r as f32 + 0.5).powi(2), (inner as f32 - 0.5).max(0.0).powi(2));
+    for dy in (-r).max(-cy)..=r.min(ph as i32 - 1 - cy) {
+        let y = cy + dy;
+        for dx in (-r).max(-cx)..=r.min(pw as i32 - 1 - cx) {
+            let x = cx + dx;
+            let d2 = (dx * dx + dy * dy) as f32;
+            if d2 > outer2 || (inner > 0 && d2 < inner2) {
+                continue;
+            }
+            // 与 draw_arc 一致：角度从 x 轴正方向起，y 向下，即屏幕上顺时针
+            let angle = (dy as f32).atan2(dx as f32).to_degrees();
+            if sweep >= 360.0 || (angle - from).rem_euclid(360.0) <= sweep {
+                fill_rect(p, pw, ph, x, y, 1, 1, rgb);
+            }
+        }
+    }
+}
+
+/// 扇形 / 环形段的轮廓：外弧、内弧（扇形时为圆心）以及两条端边
+#[allow(clippy::too_many_arguments)]
+fn draw_sector_outline(p: &mut [u8], pw: usize, ph: usize, cx: i32, cy: i32, inner: i32, r: i32, start_deg: f32, end_deg: f32, rgb: (u8, u8, u8), t: u32) {
+    draw_arc(p, pw, ph, cx, cy, r, start_deg, end_deg, rgb, t);
+    if inner > 0 {
+        draw_arc(p, pw, ph, cx, cy, inner, start_deg, end_deg, rgb, t);
+    }
+    if (end_deg - start_deg).abs() >= 360.0 {
+        return;
+    }
+    for angle in [start_deg, end_deg] {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let point = |radius: i32| (cx + (radius as f32 * cos).round() as i32, cy + (radius as f32 * sin).round() as i32);
+        let ((x1, y1), (x2, y2)) = (point(inner), point(r));
+        draw_line(p, pw, ph, x1, y1, x2, y2, rgb, t);
+    }
+}
+
 fn segments_to_subpaths(segments: &[PathSegment]) -> Vec<Vec<Point>> {
     let mut paths = Vec::new();
     let mut current: Vec<Point> = Vec::new();
@@ -460,6 +1192,11 @@ fn segments_to_subpaths(segments: &[PathSegment]) -> Vec<Vec<Point>> {
     paths
 }
 
+/// 远程图片到达前的占位框颜色
+const PLACEHOLDER_FILL: (u8, u8, u8) = (0xE5, 0xE7, 0xEB);
+const PLACEHOLDER_BORDER: (u8, u8, u8) = (0xD1, 0xD5, 0xDB);
+
+#[allow(clippy::too_many_arguments)]
 fn draw_image(
     p: &mut [u8],
     pw: usize,
@@ -471,23 +1208,22 @@ fn draw_image(
     src_type: &str,
     src: &str,
 ) -> Result<(), Box<dyn Error>> {
-    let img = match src_type {
-        "path" => image::open(src)?,
-        "base64" => {
-            let bytes = general_purpose::STANDARD.decode(src.as_bytes())?;
-            image::load_from_memory(&bytes)?
-        }
-        _ => return Err("unsupported image src_type".into()),
+    let Some(img) = images::load(src_type, src)? else {
+        // 远程图片还在下载（或下载失败）：画浅灰占位框
+        fill_rect(p, pw, ph, x, y, w, h, PLACEHOLDER_FILL);
+        draw_rect_outline(p, pw, ph, x, y, w, h, PLACEHOLDER_BORDER, 1);
+        return Ok(());
     };
     let resized = image::imageops::resize(&img, w, h, image::imageops::FilterType::Lanczos3);
     let (iw, ih) = resized.dimensions();
-    for iy in 0..ih {
-        for ix in 0..iw {
+    // 只遍历图片与画布相交的部分
+    let (ix0, iy0) = ((-x).max(0) as u32, (-y).max(0) as u32);
+    let ix1 = (pw as i64 - x as i64).clamp(0, iw as i64) as u32;
+    let iy1 = (ph as i64 - y as i64).clamp(0, ih as i64) as u32;
+    for iy in iy0..iy1 {
+        for ix in ix0..ix1 {
             let px = x + ix as i32;
             let py = y + iy as i32;
-            if px < 0 || py < 0 || px >= pw as i32 || py >= ph as i32 {
-                continue;
-            }
             let rgba = resized.get_pixel(ix, iy).0;
             let alpha = rgba[3] as u16;
             let idx = (py as usize * pw + px as usize) * 4;
@@ -508,26 +1244,40 @@ fn draw_image(
     Ok(())
 }
 
+/// `clip` 给出时只画落在该区域内的像素（text 的 `fit: clip` / `shrink`）
+#[allow(clippy::too_many_arguments)]
 fn draw_text(
     p: &mut [u8], pw: usize, ph: usize,
-    x: i32, y: i32, text: &str,
+    x: i32, y: i32, text: &str, size: f32,
+    clip: Option<(i32, i32, u32, u32)>,
     fg: (u8, u8, u8), bg: Option<(u8, u8, u8)>,
     primary: &fontdue::Font,
     emoji: Option<&fontdue::Font>
 ) {
-    let size = font_size_px();
     let line_height = line_height_px(primary, size);
+    let (left, top, right, bottom) = match clip {
+        Some((cx, cy, cw, ch)) => (cx.max(0), cy.max(0), (cx as i64 + cw as i64).min(pw as i64) as i32, (cy as i64 + ch as i64).min(ph as i64) as i32),
+        None => (0, 0, pw as i32, ph as i32),
+    };
     
     for (line_index, line) in text.lines().enumerate() {
         if line.trim().is_empty() { continue; }
         
         let cursor_y = y + line_index as i32 * line_height;
+        // 整行在画布上方或下方（留一行高的余量给超出行框的字形）时不栅格化
+        if cursor_y + 2 * line_height < 0 || cursor_y - line_height >= ph as i32 {
+            continue;
+        }
         let mut cursor_x = x as f32;
         
         let metrics = primary.horizontal_line_metrics(size).unwrap_or(fontdue::LineMetrics { ascent: size, descent: 0.0, line_gap: 0.0, new_line_size: size * 1.2 });
         let baseline_y = cursor_y as f32 + metrics.ascent;
 
         for ch in line.chars() {
+            // 文字只向右排，光标越过右边缘（留一个字号的余量）后的字都不可见
+            if cursor_x >= pw as f32 + size {
+                break;
+            }
             let font = if primary.lookup_glyph_index(ch) != 0 { primary } else { emoji.unwrap_or(primary) };
             let (g_metrics, bitmap) = font.rasterize(ch, size);
             
@@ -542,7 +1292,7 @@ fn draw_text(
                     let px = gx + bx as i32;
                     let py = gy + by as i32;
                     
-                    if px >= 0 && px < pw as i32 && py >= 0 && py < ph as i32 {
+                    if px >= left && px < right && py >= top && py < bottom {
                         let idx = (py as usize * pw + px as usize) * 4;
                         let real_bg = bg.unwrap_or_else(|| (p[idx+2], p[idx+1], p[idx]));
                         
@@ -561,18 +1311,186 @@ fn draw_text(
     }
 }
 
+/// 按固定格宽逐字画终端内容（全角字符占两格），光标画成反色块
+#[allow(clippy::too_many_arguments)]
+fn draw_terminal(
+    p: &mut [u8], pw: usize, ph: usize,
+    area: (i32, i32, u32, u32), lines: &[String], cursor: Option<(u16, u16)>, font_size: Option<f32>,
+    (fg, bg): ((u8, u8, u8), (u8, u8, u8)),
+    primary: &fontdue::Font,
+    emoji: Option<&fontdue::Font>
+) {
+    let (x, y) = (area.0 + terminal::PADDING, area.1 + terminal::PADDING);
+    let size = font_size.unwrap_or(terminal::DEFAULT_FONT_SIZE);
+    let (cell_w, line_h) = terminal::cell_size(font_size);
+    if let Some((row, col)) = cursor {
+        let cx = x + (col as f32 * cell_w) as i32;
+        fill_rect(p, pw, ph, cx, y + row as i32 * line_h, cell_w.ceil() as u32, line_h as u32, fg);
+    }
+    let mut glyph = [0u8; 4];
+    for (row, line) in lines.iter().enumerate() {
+        let cy = y + row as i32 * line_h;
+        let mut col = 0usize;
+        for ch in line.chars() {
+            let cx = x + (col as f32 * cell_w) as i32;
+            if ch != ' ' {
+                // 光标所在格的字反色，才能在光标块上看清
+                let on_cursor = cursor == Some((row as u16, col as u16));
+                let color = if on_cursor { bg } else { fg };
+                draw_text(p, pw, ph, cx, cy, ch.encode_utf8(&mut glyph), size, Some(area), color, None, primary, emoji);
+            }
+            col += terminal::char_cells(ch);
+        }
+    }
+}
+
+/// 竖排文本中一个字占的格子：所在列（原文的行）、字符与画面上的位置
+struct VerticalCell {
+    line: usize,
+    ch: char,
+    x: i32,
+    y: i32,
+    w: u32,
+    h: u32,
+}
+
+/// 竖排文本的排布，(x, y) 是整块文字的左上角，原文每行成一列、列宽为一个行高。
+/// `rotate` 为 0 时字形直立、每字占 1em，列从右向左排（CJK 竖排）；为 90 时字形顺时针转、按字宽向下排，
+/// 列同样从右向左；为 -90 时字形逆时针转、从下往上读，各列底端对齐，列从左向右（纵轴标签）
+fn vertical_cells(text: &str, x: i32, y: i32, size: f32, rotate: i32, primary: &fontdue::Font, emoji: Option<&fontdue::Font>) -> Vec<VerticalCell> {
+    let column = line_height_px(primary, size);
+    let advance = |ch: char| {
+        let font = if primary.lookup_glyph_index(ch) != 0 { primary } else { emoji.unwrap_or(primary) };
+        font.metrics(ch, size).advance_width
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    let longest = lines.iter().map(|line| line.chars().map(advance).sum::<f32>()).fold(0.0, f32::max);
+    let mut cells = Vec::new();
+    for (line, content) in lines.iter().enumerate() {
+        let column_x = if rotate < 0 { x + line as i32 * column } else { x + (lines.len() - 1 - line) as i32 * column };
+        let mut cursor = if rotate < 0 { y as f32 + longest } else { y as f32 };
+        for ch in content.chars() {
+            let step = if rotate == 0 { size } else { advance(ch) };
+            let top = if rotate < 0 { cursor - step } else { cursor };
+            let (start, end) = (top as i32, (top + step) as i32);
+            cells.push(VerticalCell { line, ch, x: column_x, y: start, w: column.max(1) as u32, h: (end - start).max(1) as u32 });
+            cursor = if rotate < 0 { top } else { top + step };
+        }
+    }
+    cells
+}
+
+/// 按 `vertical_cells` 的排布画竖排文本；字形像素先按横排算出沿书写方向的 u 与从行顶向下的 v，再转到格子里
+#[allow(clippy::too_many_arguments)]
+fn draw_vertical_text(
+    p: &mut [u8], pw: usize, ph: usize,
+    cells: &[VerticalCell], size: f32, rotate: i32,
+    fg: (u8, u8, u8), bg: Option<(u8, u8, u8)>,
+    primary: &fontdue::Font,
+    emoji: Option<&fontdue::Font>
+) {
+    let metrics = primary.horizontal_line_metrics(size).unwrap_or(fontdue::LineMetrics { ascent: size, descent: 0.0, line_gap: 0.0, new_line_size: size * 1.2 });
+    // 直立字形的基线：把 ascent 到 descent 按比例放进 1em 高的格子
+    let upright_baseline = (size * metrics.ascent / (metrics.ascent - metrics.descent).max(1.0)) as i32;
+    for cell in cells {
+        if cell.x >= pw as i32 || cell.y >= ph as i32 || cell.x + (cell.w as i32) < 0 || cell.y + (cell.h as i32) < 0 {
+            continue;
+        }
+        let font = if primary.lookup_glyph_index(cell.ch) != 0 { primary } else { emoji.unwrap_or(primary) };
+        let (g, bitmap) = font.rasterize(cell.ch, size);
+        let glyph_top = g.ymin + g.height as i32;
+        for by in 0..g.height {
+            for bx in 0..g.width {
+                let alpha = bitmap[by * g.width + bx];
+                if alpha == 0 && bg.is_none() {
+                    continue;
+                }
+                let u = g.xmin + bx as i32;
+                let v = metrics.ascent as i32 - glyph_top + by as i32;
+                let (px, py) = match rotate {
+                    0 => (cell.x + (cell.w as i32 - g.advance_width as i32) / 2 + u, cell.y + upright_baseline - glyph_top + by as i32),
+                    r if r > 0 => (cell.x + cell.w as i32 - 1 - v, cell.y + u),
+                    _ => (cell.x + v, cell.y + cell.h as i32 - 1 - u),
+                };
+                blend_pixel(p, pw, ph, px, py, alpha, fg, bg);
+            }
+        }
+    }
+}
+
+/// 按覆盖率 alpha 把前景色混合到 (px, py)：给出 bg 时以它为底色，否则以已绘制的像素为底
+#[allow(clippy::too_many_arguments)]
+fn blend_pixel(p: &mut [u8], pw: usize, ph: usize, px: i32, py: i32, alpha: u8, fg: (u8, u8, u8), bg: Option<(u8, u8, u8)>) {
+    if px < 0 || py < 0 || px >= pw as i32 || py >= ph as i32 {
+        return;
+    }
+    let idx = (py as usize * pw + px as usize) * 4;
+    let real_bg = bg.unwrap_or((p[idx + 2], p[idx + 1], p[idx]));
+    let a = alpha as u16;
+    let inv = 255 - a;
+    p[idx] = ((fg.2 as u16 * a + real_bg.2 as u16 * inv) / 255) as u8;
+    p[idx + 1] = ((fg.1 as u16 * a + real_bg.1 as u16 * inv) / 255) as u8;
+    p[idx + 2] = ((fg.0 as u16 * a + real_bg.0 as u16 * inv) / 255) as u8;
+    p[idx + 3] = 0;
+}
+
+/// 区域内已绘制像素的平均色，作为没有 bg 的文字的实际背景
+fn average_under_text(p: &[u8], pw: usize, ph: usize, (x, y, w, h): (i32, i32, i32, i32)) -> (u8, u8, u8) {
+    let (mut sum, mut count) = ([0u64; 3], 0u64);
+    for py in y.max(0)..(y + h).min(ph as i32) {
+        for px in x.max(0)..(x + w).min(pw as i32) {
+            let idx = (py as usize * pw + px as usize) * 4;
+            sum[0] += p[idx + 2] as u64;
+            sum[1] += p[idx + 1] as u64;
+            sum[2] += p[idx] as u64;
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return (255, 255, 255);
+    }
+    ((sum[0] / count) as u8, (sum[1] / count) as u8, (sum[2] / count) as u8)
+}
+
+/// 给文本中第一个与访问键相同（不分大小写）的字符画下划线，字形排布与 draw_text 一致
+#[allow(clippy::too_many_arguments)]
+fn underline_mnemonic(
+    p: &mut [u8], pw: usize, ph: usize,
+    x: i32, y: i32, text: &str, size: f32, key: char,
+    fg: (u8, u8, u8),
+    primary: &fontdue::Font,
+    emoji: Option<&fontdue::Font>
+) {
+    let line_height = line_height_px(primary, size);
+    let ascent = primary.horizontal_line_metrics(size).map(|m| m.ascent).unwrap_or(size);
+    for (line_index, line) in text.lines().enumerate() {
+        let mut cursor_x = x as f32;
+        for ch in line.chars() {
+            let font = if primary.lookup_glyph_index(ch) != 0 { primary } else { emoji.unwrap_or(primary) };
+            let advance = font.metrics(ch, size).advance_width;
+            if ch.to_ascii_lowercase() == key {
+                let underline_y = y + line_index as i32 * line_height + ascent as i32 + 2;
+                fill_rect(p, pw, ph, cursor_x as i32, underline_y, advance.max(1.0) as u32, 2, fg);
+                return;
+            }
+            cursor_x += advance;
+        }
+    }
+}
+
 // --- 现有的辅助函数迁移 ---
 
-fn parse_rgb(value: &str) -> Result<u32, Box<dyn Error>> {
+pub fn parse_rgb(value: &str) -> Result<u32, Box<dyn Error>> {
+    let value = theme::resolve(value).ok_or_else(|| format!("unknown theme token: {}", value))?;
     let value = value.strip_prefix('#').ok_or("color must start with #")?;
     Ok(u32::from_str_radix(value, 16)?)
 }
 
-fn rgb_tuple(pixel: u32) -> (u8, u8, u8) {
+pub fn rgb_tuple(pixel: u32) -> (u8, u8, u8) {
     (((pixel >> 16) & 0xff) as u8, ((pixel >> 8) & 0xff) as u8, (pixel & 0xff) as u8)
 }
 
-fn font_size_px() -> f32 {
+pub fn font_size_px() -> f32 {
     std::env::var("X11_GUI_FONT_SIZE").ok().and_then(|v| v.parse().ok()).unwrap_or(24.0)
 }
 
@@ -583,7 +1501,3 @@ fn line_height_px(font: &fontdue::Font, size: f32) -> i32 {
         (size * 1.5) as i32
     }
 }
-
-fn utf8_to_char2b(text: &str) -> Vec<Char2b> {
-    text.encode_utf16().map(|c| Char2b { byte1: (c >> 8) as u8, byte2: (c & 0xff) as u8 }).collect()
-}
@@ -0,0 +1,195 @@
+use std::error::Error;
+
+use tracing::{debug, info};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt, EventMask, PropMode, SelectionNotifyEvent, SelectionRequestEvent, SELECTION_NOTIFY_EVENT,
+};
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as WrapperConnectionExt;
+
+use crate::x11::backend::X11Backend;
+use crate::x11::renderer::{self, GlyphBox, Region};
+
+/// 按下后移动超过这个距离（像素）才算拖选，否则松开仍是一次点击
+const DRAG_THRESHOLD: i32 = 4;
+
+/// 剪贴板相关的 atom，连接时一次性取得
+pub struct ClipboardAtoms {
+    clipboard: Atom,
+    utf8_string: Atom,
+    targets: Atom,
+}
+
+impl ClipboardAtoms {
+    pub fn intern(conn: &RustConnection) -> Result<Self, Box<dyn Error>> {
+        let clipboard = conn.intern_atom(false, b"CLIPBOARD")?;
+        let utf8_string = conn.intern_atom(false, b"UTF8_STRING")?;
+        let targets = conn.intern_atom(false, b"TARGETS")?;
+        Ok(Self { clipboard: clipboard.reply()?.atom, utf8_string: utf8_string.reply()?.atom, targets: targets.reply()?.atom })
+    }
+}
+
+/// 窗口里的文本拖选：起点与终点是当前帧字形列表的下标
+#[derive(Debug, Default)]
+pub struct TextSelection {
+    /// 左键按下的位置，松开前有效
+    press: Option<(i32, i32)>,
+    anchor: Option<usize>,
+    focus: Option<usize>,
+    /// 屏幕上高亮覆盖的范围
+    highlight: Option<Region>,
+    /// 最近一次复制的文本，窗口持有 CLIPBOARD / PRIMARY 期间用它应答粘贴请求
+    copied: Option<String>,
+}
+
+impl TextSelection {
+    /// 丢弃选区（换帧时字形下标失效），返回需要恢复的高亮范围；已复制的文本保留
+    pub fn forget(&mut self) -> Option<Region> {
+        self.press = None;
+        self.anchor = None;
+        self.focus = None;
+        self.highlight.take()
+    }
+
+    fn range(&self) -> Option<(usize, usize)> {
+        let (anchor, focus) = (self.anchor?, self.focus?);
+        Some((anchor.min(focus), anchor.max(focus)))
+    }
+}
+
+/// 左键按下：撤掉上一次的高亮，记下起点
+pub fn press(backend: &X11Backend, x: i32, y: i32) -> Result<(), Box<dyn Error>> {
+    let mut selection = backend.selection();
+    let stale = selection.forget();
+    selection.press = Some((x, y));
+    if stale.is_some() {
+        renderer::render_selection(backend, &[], stale)?;
+    }
+    Ok(())
+}
+
+/// 按住左键移动：起点落在文字上且移动超过阈值后开始选择，终点取离指针最近的字形
+pub fn drag(backend: &X11Backend, x: i32, y: i32) -> Result<(), Box<dyn Error>> {
+    let mut selection = backend.selection();
+    let Some((px, py)) = selection.press else {
+        return Ok(());
+    };
+    if selection.anchor.is_none() {
+        if (x - px).abs().max((y - py).abs()) < DRAG_THRESHOLD {
+            return Ok(());
+        }
+        match backend.with_last_frame(|frame| glyph_at(&frame.glyphs, px, py)).flatten() {
+            Some(anchor) => selection.anchor = Some(anchor),
+            // 从文字以外开始拖动：不选择，松开时照常算点击
+            None => {
+                selection.press = None;
+                return Ok(());
+            }
+        }
+    }
+    let focus = backend.with_last_frame(|frame| nearest_glyph(&frame.glyphs, x, y)).flatten();
+    if focus.is_none() || focus == selection.focus {
+        return Ok(());
+    }
+    selection.focus = focus;
+    let selected = selected_glyphs(backend, &selection);
+    selection.highlight = renderer::render_selection(backend, &selected, selection.highlight)?;
+    Ok(())
+}
+
+/// 左键松开：有选区时把文字复制到 CLIPBOARD 与 PRIMARY 并返回 true（这次松开不算点击）
+pub fn release(backend: &X11Backend, time: u32) -> Result<bool, Box<dyn Error>> {
+    let mut selection = backend.selection();
+    selection.press = None;
+    let selected = selected_glyphs(backend, &selection);
+    if selected.is_empty() {
+        selection.anchor = None;
+        return Ok(false);
+    }
+    let text = selected_text(&selected);
+    let conn = backend.connection();
+    let atoms = backend.clipboard_atoms();
+    conn.set_selection_owner(backend.window(), atoms.clipboard, time)?;
+    conn.set_selection_owner(backend.window(), AtomEnum::PRIMARY.into(), time)?;
+    conn.flush()?;
+    info!("Copied {} characters to the clipboard.", text.chars().count());
+    selection.copied = Some(text);
+    Ok(true)
+}
+
+/// 应答其他程序的粘贴请求：支持 TARGETS、UTF8_STRING 与 STRING，其余拒绝
+pub fn answer(backend: &X11Backend, request: &SelectionRequestEvent) -> Result<(), Box<dyn Error>> {
+    let conn = backend.connection();
+    let atoms = backend.clipboard_atoms();
+    // 旧式客户端不给 property 时按约定写到与 target 同名的属性上
+    let property = if request.property == u32::from(AtomEnum::NONE) { request.target } else { request.property };
+    let copied = backend.selection().copied.clone();
+    let answered = match copied {
+        Some(_) if request.target == atoms.targets => {
+            let supported = [atoms.targets, atoms.utf8_string, AtomEnum::STRING.into()];
+            conn.change_property32(PropMode::REPLACE, request.requestor, property, AtomEnum::ATOM, &supported)?;
+            true
+        }
+        Some(text) if request.target == atoms.utf8_string || request.target == u32::from(AtomEnum::STRING) => {
+            conn.change_property8(PropMode::REPLACE, request.requestor, property, request.target, text.as_bytes())?;
+            true
+        }
+        _ => false,
+    };
+    debug!("Selection request for target {} {}", request.target, if answered { "answered" } else { "refused" });
+    let notify = SelectionNotifyEvent {
+        response_type: SELECTION_NOTIFY_EVENT,
+        sequence: 0,
+        time: request.time,
+        requestor: request.requestor,
+        selection: request.selection,
+        target: request.target,
+        property: if answered { property } else { AtomEnum::NONE.into() },
+    };
+    conn.send_event(false, request.requestor, EventMask::NO_EVENT, notify)?;
+    conn.flush()?;
+    Ok(())
+}
+
+/// 本窗口仍持有 CLIPBOARD 时最近一次复制的文字
+pub fn copied_text(backend: &X11Backend) -> Option<String> {
+    let owner = backend.connection().get_selection_owner(backend.clipboard_atoms().clipboard).ok()?.reply().ok()?.owner;
+    if owner != backend.window() {
+        return None;
+    }
+    backend.selection().copied.clone()
+}
+
+fn selected_glyphs(backend: &X11Backend, selection: &TextSelection) -> Vec<GlyphBox> {
+    let Some((start, end)) = selection.range() else {
+        return Vec::new();
+    };
+    backend.with_last_frame(|frame| frame.glyphs.get(start..=end).map(<[GlyphBox]>::to_vec)).flatten().unwrap_or_default()
+}
+
+/// 选中字形拼成的文本：跨行、跨 text 命令处换行
+fn selected_text(glyphs: &[GlyphBox]) -> String {
+    let mut text = String::new();
+    for (i, glyph) in glyphs.iter().enumerate() {
+        if i > 0 && (glyphs[i - 1].command, glyphs[i - 1].line) != (glyph.command, glyph.line) {
+            text.push('\n');
+        }
+        text.push(glyph.ch);
+    }
+    text
+}
+
+fn glyph_at(glyphs: &[GlyphBox], x: i32, y: i32) -> Option<usize> {
+    glyphs.iter().position(|g| x >= g.x && y >= g.y && x < g.x + g.w as i32 && y < g.y + g.h as i32)
+}
+
+/// 离 (x, y) 最近的字形（点在盒子内时距离为 0）
+fn nearest_glyph(glyphs: &[GlyphBox], x: i32, y: i32) -> Option<usize> {
+    let distance = |g: &GlyphBox| {
+        let dx = (g.x - x).max(x - (g.x + g.w as i32 - 1)).max(0) as i64;
+        let dy = (g.y - y).max(y - (g.y + g.h as i32 - 1)).max(0) as i64;
+        dx * dx + dy * dy
+    };
+    glyphs.iter().enumerate().min_by_key(|(_, g)| distance(g)).map(|(i, _)| i)
+}
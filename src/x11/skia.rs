@@ -0,0 +1,269 @@
+//! 可选的 tiny-skia 光栅化（`--features skia`）：几何图元转成 tiny-skia 路径，抗锯齿填充并按规范描边
+//! （斜线、圆弧、折线拐角与线帽不再有阶梯与缝隙）。clear、文字与图片仍走内置的光栅化器。
+//!
+//! 几何约定与内置光栅化器一致：矩形的描边画在矩形内侧，圆与椭圆的描边沿轮廓居中，
+//! 线段、折线与多边形的顶点是像素中心，线帽与拐角为圆形。
+
+use std::error::Error;
+
+use tiny_skia::{FillRule, LineCap, LineJoin, Paint, Path, PathBuilder, PixmapMut, Rect, Stroke, Transform};
+
+use crate::datasource;
+use crate::dsl::model::{Command, PathSegment, Point};
+use crate::x11::renderer::{parse_rgb, rgb_tuple};
+
+/// 贝塞尔曲线近似圆弧时每段的最大角度
+const ARC_SEGMENT_DEG: f32 = 90.0;
+
+/// 用 tiny-skia 画一条几何命令；返回 false 表示这条命令不归它画（clear、文字、图片），由调用方处理
+pub fn draw(pixels: &mut [u8], width: usize, height: usize, command: &Command) -> Result<bool, Box<dyn Error>> {
+    let Some(mut pixmap) = PixmapMut::from_bytes(pixels, width as u32, height as u32) else {
+        return Ok(true);
+    };
+    let canvas = &mut pixmap;
+    match command {
+        Command::Clear { .. } | Command::Text { .. } | Command::Image { .. } => return Ok(false),
+        Command::Rect { x, y, w, h, fill, stroke, stroke_width, bind, progress, .. } => {
+            let fill_w = datasource::bar_width(*w, bind, *progress) as f32;
+            let (x, y, w, h) = (*x as f32, *y as f32, *w as f32, *h as f32);
+            if let Some(color) = fill {
+                fill_path(canvas, rounded_rect(x, y, fill_w, h, 0.0), color, FillRule::Winding)?;
+            }
+            if let Some(color) = stroke {
+                stroke_inside(canvas, x, y, w, h, 0.0, stroke_width.unwrap_or(1), color)?;
+            }
+        }
+        Command::RoundRect { x: Some(x), y: Some(y), w: Some(w), h: Some(h), r: Some(r), fill, stroke, stroke_width, .. } => {
+            let (x, y, w, h) = (*x as f32, *y as f32, *w as f32, *h as f32);
+            if let Some(color) = fill {
+                fill_path(canvas, rounded_rect(x, y, w, h, *r as f32), color, FillRule::Winding)?;
+            }
+            if let Some(color) = stroke {
+                stroke_inside(canvas, x, y, w, h, *r as f32, stroke_width.unwrap_or(1), color)?;
+            }
+        }
+        Command::Circle { cx: Some(cx), cy: Some(cy), r: Some(r), fill, stroke, stroke_width } => {
+            let (rx, ry) = (*r as f32, *r as f32);
+            draw_oval(canvas, *cx, *cy, rx, ry, fill.as_deref(), stroke.as_deref(), stroke_width.unwrap_or(1))?;
+        }
+        Command::Ellipse { cx: Some(cx), cy: Some(cy), rx: Some(rx), ry: Some(ry), fill, stroke, stroke_width } => {
+            draw_oval(canvas, *cx, *cy, *rx as f32, *ry as f32, fill.as_deref(), stroke.as_deref(), stroke_width.unwrap_or(1))?;
+        }
+        Command::Line { x1, y1, x2, y2, color, width: line_width } => {
+            let path = polyline(&[Point { x: *x1, y: *y1 }, Point { x: *x2, y: *y2 }], false);
+            stroke_round(canvas, path, line_width.unwrap_or(1), color.as_deref().unwrap_or("#000000"))?;
+        }
+        Command::Polyline { points: Some(points), color, width: line_width } => {
+            stroke_round(canvas, polyline(points, false), line_width.unwrap_or(1), color.as_deref().unwrap_or("#000000"))?;
+        }
+        Command::Polygon { points: Some(points), fill, stroke, stroke_width } => {
+            if let Some(color) = fill {
+                // 与内置扫描线填充一致：自相交的部分按奇偶规则留空
+                fill_path(canvas, polyline(points, true), color, FillRule::EvenOdd)?;
+            }
+            if let Some(color) = stroke {
+                stroke_round(canvas, polyline(points, true), stroke_width.unwrap_or(1), color)?;
+            }
+        }
+        Command::Path { segments: Some(segments), fill, stroke, stroke_width } => {
+            if let Some(color) = fill {
+                fill_path(canvas, segments_path(segments, true), color, FillRule::Winding)?;
+            }
+            if let Some(color) = stroke {
+                stroke_round(canvas, segments_path(segments, false), stroke_width.unwrap_or(1), color)?;
+            }
+        }
+        Command::Arc { cx: Some(cx), cy: Some(cy), r: Some(r), start_angle: Some(start), end_angle: Some(end), color, width: line_width, fill, pie } => {
+            let (cx, cy, r) = (*cx as f32 + 0.5, *cy as f32 + 0.5, *r as f32);
+            let thickness = line_width.unwrap_or(1);
+            // 扇形从圆心填起；环形段的内半径为 r - width
+            let inner = if *pie { 0.0 } else { (r - thickness as f32).max(0.0) };
+            if let Some(color) = fill {
+                // 与内置填充一致：像素中心到圆心的距离在 [inner - 0.5, r + 0.5] 内
+                let hole = if inner > 0.0 { inner - 0.5 } else { 0.0 };
+                fill_path(canvas, sector(cx, cy, hole, r + 0.5, *start, *end), color, FillRule::EvenOdd)?;
+            }
+            // 有填充时只在显式给出 color 时描边；没有填充时沿用默认黑色描边
+            if let Some(color) = color.as_deref().or(fill.is_none().then_some("#000000")) {
+                if fill.is_some() || *pie {
+                    // 环形段的 width 是环宽，轮廓用 1px
+                    let outline = if *pie { thickness } else { 1 };
+                    stroke_round(canvas, sector(cx, cy, inner, r, *start, *end), outline, color)?;
+                } else {
+                    let mut builder = PathBuilder::new();
+                    arc_to(&mut builder, cx, cy, r, *start, *end, true);
+                    stroke_round(canvas, builder.finish(), thickness, color)?;
+                }
+            }
+        }
+        // 缺少必填字段的命令什么也不画，与内置光栅化器一致
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// BGRA 缓冲区当作 RGBA 交给 tiny-skia，所以红蓝通道对调
+fn paint(color: &str) -> Result<Paint<'static>, Box<dyn Error>> {
+    let (r, g, b) = rgb_tuple(parse_rgb(color)?);
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(b, g, r, 255);
+    Ok(paint)
+}
+
+fn fill_path(canvas: &mut PixmapMut, path: Option<Path>, color: &str, rule: FillRule) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = path {
+        canvas.fill_path(&path, &paint(color)?, rule, Transform::identity(), None);
+    }
+    Ok(())
+}
+
+fn stroke_round(canvas: &mut PixmapMut, path: Option<Path>, width: u32, color: &str) -> Result<(), Box<dyn Error>> {
+    let stroke = Stroke { width: width as f32, line_cap: LineCap::Round, line_join: LineJoin::Round, ..Stroke::default() };
+    if let Some(path) = path {
+        canvas.stroke_path(&path, &paint(color)?, &stroke, Transform::identity(), None);
+    }
+    Ok(())
+}
+
+/// 描边画在矩形内侧：沿内缩半个线宽的轮廓描边；线宽盖满整个矩形时直接填充
+#[allow(clippy::too_many_arguments)]
+fn stroke_inside(canvas: &mut PixmapMut, x: f32, y: f32, w: f32, h: f32, r: f32, width: u32, color: &str) -> Result<(), Box<dyn Error>> {
+    let t = width as f32;
+    if 2.0 * t >= w.min(h) {
+        return fill_path(canvas, rounded_rect(x, y, w, h, r), color, FillRule::Winding);
+    }
+    let half = t / 2.0;
+    let path = rounded_rect(x + half, y + half, w - t, h - t, (r - half).max(0.0));
+    let stroke = Stroke { width: t, line_join: LineJoin::Miter, ..Stroke::default() };
+    if let Some(path) = path {
+        canvas.stroke_path(&path, &paint(color)?, &stroke, Transform::identity(), None);
+    }
+    Ok(())
+}
+
+/// 圆与椭圆以圆心像素的中心为圆心；填充覆盖到离圆心 r 个像素（含）为止，描边沿半径 r 居中
+#[allow(clippy::too_many_arguments)]
+fn draw_oval(canvas: &mut PixmapMut, cx: i32, cy: i32, rx: f32, ry: f32, fill: Option<&str>, stroke: Option<&str>, width: u32) -> Result<(), Box<dyn Error>> {
+    let (cx, cy) = (cx as f32 + 0.5, cy as f32 + 0.5);
+    let oval = |rx: f32, ry: f32| Rect::from_ltrb(cx - rx, cy - ry, cx + rx, cy + ry).and_then(PathBuilder::from_oval);
+    if let Some(color) = fill {
+        fill_path(canvas, oval(rx + 0.5, ry + 0.5), color, FillRule::Winding)?;
+    }
+    if let Some(color) = stroke {
+        stroke_round(canvas, oval(rx, ry), width, color)?;
+    }
+    Ok(())
+}
+
+/// 圆角半径不超过短边的一半；四角用三次贝塞尔近似四分之一圆
+fn rounded_rect(x: f32, y: f32, w: f32, h: f32, r: f32) -> Option<Path> {
+    let r = r.min(w.min(h) / 2.0).max(0.0);
+    if r == 0.0 {
+        return Rect::from_xywh(x, y, w, h).map(PathBuilder::from_rect);
+    }
+    // 四分之一圆的贝塞尔控制点距离
+    let k = r * 0.552_284_8;
+    let (right, bottom) = (x + w, y + h);
+    let mut b = PathBuilder::new();
+    b.move_to(x + r, y);
+    b.line_to(right - r, y);
+    b.cubic_to(right - r + k, y, right, y + r - k, right, y + r);
+    b.line_to(right, bottom - r);
+    b.cubic_to(right, bottom - r + k, right - r + k, bottom, right - r, bottom);
+    b.line_to(x + r, bottom);
+    b.cubic_to(x + r - k, bottom, x, bottom - r + k, x, bottom - r);
+    b.line_to(x, y + r);
+    b.cubic_to(x, y + r - k, x + r - k, y, x + r, y);
+    b.close();
+    b.finish()
+}
+
+fn polyline(points: &[Point], closed: bool) -> Option<Path> {
+    let (first, rest) = points.split_first()?;
+    let mut b = PathBuilder::new();
+    b.move_to(first.x as f32 + 0.5, first.y as f32 + 0.5);
+    for p in rest {
+        b.line_to(p.x as f32 + 0.5, p.y as f32 + 0.5);
+    }
+    if closed {
+        b.close();
+    }
+    b.finish()
+}
+
+/// M / L / Z 段落转成路径；填充时每个子路径都隐式闭合
+fn segments_path(segments: &[PathSegment], fill: bool) -> Option<Path> {
+    let mut b = PathBuilder::new();
+    let mut open = false;
+    for seg in segments {
+        let point = seg.x.zip(seg.y).map(|(x, y)| (x as f32 + 0.5, y as f32 + 0.5));
+        match (seg.cmd.as_str(), point) {
+            ("M", Some((x, y))) => {
+                if open && fill {
+                    b.close();
+                }
+                b.move_to(x, y);
+                open = true;
+            }
+            ("L", Some((x, y))) if open => b.line_to(x, y),
+            ("Z", _) if open => {
+                b.close();
+                open = false;
+            }
+            _ => {}
+        }
+    }
+    if open && fill {
+        b.close();
+    }
+    b.finish()
+}
+
+/// 扇形（inner 为 0）或环形段的轮廓；转满一圈时是整圆或圆环
+fn sector(cx: f32, cy: f32, inner: f32, outer: f32, start: f32, end: f32) -> Option<Path> {
+    let mut b = PathBuilder::new();
+    if (end - start).abs() >= 360.0 {
+        b.push_circle(cx, cy, outer);
+        if inner > 0.0 {
+            b.push_circle(cx, cy, inner);
+        }
+        return b.finish();
+    }
+    arc_to(&mut b, cx, cy, outer, start, end, true);
+    if inner > 0.0 {
+        arc_to(&mut b, cx, cy, inner, end, start, false);
+    } else {
+        b.line_to(cx, cy);
+    }
+    b.close();
+    b.finish()
+}
+
+/// 从 start 到 end 度（x 轴正方向起，y 向下即屏幕上顺时针）的圆弧，按不超过 90° 一段用三次贝塞尔近似。
+/// `move_first` 为 true 时先移到起点，否则从当前点连线过去
+fn arc_to(b: &mut PathBuilder, cx: f32, cy: f32, r: f32, start: f32, end: f32, move_first: bool) {
+    let sweep = (end - start).clamp(-360.0, 360.0);
+    let segments = (sweep.abs() / ARC_SEGMENT_DEG).ceil().max(1.0) as usize;
+    let step = (sweep / segments as f32).to_radians();
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    let mut angle = start.to_radians();
+    let (sin, cos) = angle.sin_cos();
+    if move_first {
+        b.move_to(cx + r * cos, cy + r * sin);
+    } else {
+        b.line_to(cx + r * cos, cy + r * sin);
+    }
+    for _ in 0..segments {
+        let (s0, c0) = angle.sin_cos();
+        let (s1, c1) = (angle + step).sin_cos();
+        b.cubic_to(
+            cx + r * (c0 - k * s0),
+            cy + r * (s0 + k * c0),
+            cx + r * (c1 + k * s1),
+            cy + r * (s1 - k * c1),
+            cx + r * c1,
+            cy + r * s1,
+        );
+        angle += step;
+    }
+}